@@ -0,0 +1,319 @@
+//! Learning analytics
+//!
+//! Events are recorded only when the `analytics` feature is on and the user
+//! has explicitly opted in. They're buffered in memory and flushed to the
+//! local database in batches. Nothing leaves the machine unless telemetry is
+//! separately turned on; every upload sent is kept in a local log.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use crate::cloud::CloudClient;
+use crate::db::Database;
+use crate::error::{Result, ShellError};
+
+/// Settings key the opt-in is stored under, apart from the importable settings
+pub const OPT_IN_KEY: &str = "analytics_opt_in";
+
+/// Settings key of the telemetry switch, off unless set to `true`
+pub const TELEMETRY_KEY: &str = "telemetry";
+
+/// Buffered events that trigger a flush
+const BUFFER_SIZE: usize = 20;
+
+/// Most events sent in one upload request
+const UPLOAD_BATCH: usize = 100;
+
+/// Something a student did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnalyticsEvent {
+    LessonStarted { lesson_id: String },
+    RunExecuted { language: String, exit_code: i64, duration_ms: u64, timed_out: bool },
+    TestPassed { lesson_id: String, test_id: String },
+    TestFailed { lesson_id: String, test_id: String },
+    HintRevealed { lesson_id: String, index: usize },
+}
+
+impl AnalyticsEvent {
+    /// Name the event is counted under
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AnalyticsEvent::LessonStarted { .. } => "lesson_started",
+            AnalyticsEvent::RunExecuted { .. } => "run_executed",
+            AnalyticsEvent::TestPassed { .. } => "test_passed",
+            AnalyticsEvent::TestFailed { .. } => "test_failed",
+            AnalyticsEvent::HintRevealed { .. } => "hint_revealed",
+        }
+    }
+}
+
+/// An event and when it happened
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// RFC 3339 timestamp
+    pub recorded_at: String,
+    pub event: AnalyticsEvent,
+}
+
+/// Time window of a query; open ends are unbounded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalyticsRange {
+    /// Inclusive RFC 3339 start
+    #[serde(default)]
+    pub since: Option<String>,
+    /// Exclusive RFC 3339 end
+    #[serde(default)]
+    pub until: Option<String>,
+}
+
+/// Locally recorded events for the dashboard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalAnalytics {
+    /// Oldest first
+    pub events: Vec<RecordedEvent>,
+    /// Events per kind
+    pub counts: BTreeMap<String, u32>,
+}
+
+/// An upload that was sent, exactly as sent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryLogEntry {
+    /// RFC 3339 timestamp
+    pub sent_at: String,
+    pub url: String,
+    /// Request body
+    pub payload: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    /// Where uploads would go, if configured
+    pub endpoint: Option<String>,
+    /// Recorded events not uploaded yet
+    pub pending_events: usize,
+    pub sent_uploads: usize,
+    pub last_sent_at: Option<String>,
+}
+
+/// Proof that telemetry is on, required to send anything. Only
+/// `telemetry_consent` makes one.
+pub struct TelemetryConsent(());
+
+/// Consent to send, if the user turned telemetry on
+pub fn telemetry_consent(db: &Database) -> Option<TelemetryConsent> {
+    matches!(db.get_setting(TELEMETRY_KEY), Ok(Some(value)) if value == "true").then_some(TelemetryConsent(()))
+}
+
+pub fn telemetry_status(db: &Database, endpoint: Option<String>) -> Result<TelemetryStatus> {
+    let log = db.list_telemetry_log()?;
+    Ok(TelemetryStatus {
+        enabled: telemetry_consent(db).is_some(),
+        endpoint,
+        pending_events: db.count_pending_analytics_events()?,
+        sent_uploads: log.len(),
+        last_sent_at: log.last().map(|entry| entry.sent_at.clone()),
+    })
+}
+
+/// Whether events should be recorded: the feature is on and the user opted in
+pub fn enabled(analytics_flag: bool, db: &Database) -> bool {
+    analytics_flag && opted_in(db)
+}
+
+pub fn opted_in(db: &Database) -> bool {
+    matches!(db.get_setting(OPT_IN_KEY), Ok(Some(value)) if value == "true")
+}
+
+/// In-memory buffer in front of the local event table
+#[derive(Default)]
+pub struct AnalyticsSink {
+    buffer: Mutex<Vec<RecordedEvent>>,
+}
+
+impl AnalyticsSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffer `event` if recording is `enabled`, flushing once the buffer
+    /// is full. Returns whether a flush happened.
+    pub fn record(&self, db: &Database, enabled: bool, event: AnalyticsEvent) -> Result<bool> {
+        if !enabled {
+            return Ok(false);
+        }
+        let full = {
+            let mut buffer = self.lock()?;
+            buffer.push(RecordedEvent { recorded_at: chrono::Utc::now().to_rfc3339(), event });
+            buffer.len() >= BUFFER_SIZE
+        };
+        if full {
+            self.flush(db)?;
+        }
+        Ok(full)
+    }
+
+    /// Write buffered events to the database; on failure they stay buffered
+    pub fn flush(&self, db: &Database) -> Result<usize> {
+        let events = std::mem::take(&mut *self.lock()?);
+        if events.is_empty() {
+            return Ok(0);
+        }
+        if let Err(e) = db.insert_analytics_events(&events) {
+            let mut buffer = self.lock()?;
+            let newer = std::mem::replace(&mut *buffer, events);
+            buffer.extend(newer);
+            return Err(e);
+        }
+        Ok(events.len())
+    }
+
+    /// Drop buffered events without storing them
+    pub fn discard(&self) -> Result<()> {
+        self.lock()?.clear();
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Vec<RecordedEvent>>> {
+        self.buffer.lock().map_err(|_| ShellError::Service("Analytics buffer poisoned".into()))
+    }
+}
+
+/// Stored events in `range` with per-kind counts
+pub fn local_analytics(db: &Database, range: &AnalyticsRange) -> Result<LocalAnalytics> {
+    let events = db.list_analytics_events(range.since.as_deref(), range.until.as_deref())?;
+    let mut counts = BTreeMap::new();
+    for recorded in &events {
+        *counts.entry(recorded.event.kind().to_string()).or_insert(0) += 1;
+    }
+    Ok(LocalAnalytics { events, counts })
+}
+
+/// Send stored events that haven't been uploaded yet, in batches, and log
+/// each upload. The only path to the network: it sends nothing unless
+/// telemetry is on. Returns how many events were sent.
+pub async fn upload_pending(db: &Database, client: &CloudClient) -> Result<usize> {
+    let Some(consent) = telemetry_consent(db) else { return Ok(0) };
+    let mut sent = 0;
+    loop {
+        let pending = db.pending_analytics_events(UPLOAD_BATCH)?;
+        if pending.is_empty() {
+            return Ok(sent);
+        }
+        let (ids, events): (Vec<i64>, Vec<RecordedEvent>) = pending.into_iter().unzip();
+        let entry = client.send_analytics(&consent, &events).await?;
+        db.insert_telemetry_log(&entry)?;
+        db.mark_analytics_uploaded(&ids)?;
+        sent += ids.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hint(index: usize) -> AnalyticsEvent {
+        AnalyticsEvent::HintRevealed { lesson_id: "python-loops".into(), index }
+    }
+
+    #[test]
+    fn test_events_buffer_until_full() {
+        let db = Database::in_memory().unwrap();
+        let sink = AnalyticsSink::new();
+        for i in 0..BUFFER_SIZE - 1 {
+            assert!(!sink.record(&db, true, hint(i)).unwrap());
+        }
+        assert!(local_analytics(&db, &AnalyticsRange::default()).unwrap().events.is_empty());
+
+        assert!(sink.record(&db, true, hint(BUFFER_SIZE)).unwrap());
+        let stored = local_analytics(&db, &AnalyticsRange::default()).unwrap();
+        assert_eq!(stored.events.len(), BUFFER_SIZE);
+        assert_eq!(stored.events[0].event, hint(0));
+        assert_eq!(stored.counts["hint_revealed"], BUFFER_SIZE as u32);
+    }
+
+    #[test]
+    fn test_flush_stores_and_purge_removes() {
+        let db = Database::in_memory().unwrap();
+        let sink = AnalyticsSink::new();
+        sink.record(&db, true, AnalyticsEvent::LessonStarted { lesson_id: "python-loops".into() }).unwrap();
+        sink.record(&db, true, AnalyticsEvent::TestFailed { lesson_id: "python-loops".into(), test_id: "t1".into() }).unwrap();
+        assert_eq!(sink.flush(&db).unwrap(), 2);
+        assert_eq!(sink.flush(&db).unwrap(), 0);
+
+        let stored = local_analytics(&db, &AnalyticsRange::default()).unwrap();
+        assert_eq!(stored.counts.get("lesson_started"), Some(&1));
+        assert_eq!(stored.counts.get("test_failed"), Some(&1));
+        let future = AnalyticsRange { since: Some("2999-01-01T00:00:00Z".into()), until: None };
+        assert!(local_analytics(&db, &future).unwrap().events.is_empty());
+        assert_eq!(db.pending_analytics_events(10).unwrap().len(), 2);
+
+        db.purge_analytics_events().unwrap();
+        assert!(local_analytics(&db, &AnalyticsRange::default()).unwrap().events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nothing_sent_with_telemetry_off() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/api/v1/analytics/events").with_status(200).expect(0).create_async().await;
+        let client = CloudClient::new(&server.url(), "secret-token").unwrap();
+        let db = Database::in_memory().unwrap();
+        let sink = AnalyticsSink::new();
+
+        // Out of the box nothing is recorded or sent
+        sink.record(&db, enabled(true, &db), hint(0)).unwrap();
+        assert_eq!(sink.flush(&db).unwrap(), 0);
+        assert_eq!(upload_pending(&db, &client).await.unwrap(), 0);
+
+        // Local recording alone still keeps everything local
+        db.set_setting(OPT_IN_KEY, "true").unwrap();
+        sink.record(&db, enabled(true, &db), hint(1)).unwrap();
+        assert_eq!(sink.flush(&db).unwrap(), 1);
+        assert_eq!(upload_pending(&db, &client).await.unwrap(), 0);
+        mock.assert_async().await;
+
+        let status = telemetry_status(&db, None).unwrap();
+        assert!(!status.enabled);
+        assert_eq!((status.pending_events, status.sent_uploads), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_sent_uploads_are_logged_verbatim() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/api/v1/analytics/events").with_status(200).expect(1).create_async().await;
+        let client = CloudClient::new(&server.url(), "secret-token").unwrap();
+        let db = Database::in_memory().unwrap();
+        db.set_setting(OPT_IN_KEY, "true").unwrap();
+        db.set_setting(TELEMETRY_KEY, "true").unwrap();
+        let sink = AnalyticsSink::new();
+        sink.record(&db, true, hint(2)).unwrap();
+        sink.flush(&db).unwrap();
+
+        assert_eq!(upload_pending(&db, &client).await.unwrap(), 1);
+        mock.assert_async().await;
+        let log = db.list_telemetry_log().unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].url, format!("{}/api/v1/analytics/events", server.url()));
+        let payload: serde_json::Value = serde_json::from_str(&log[0].payload).unwrap();
+        assert_eq!(payload["events"][0]["event"]["type"], "hint_revealed");
+        assert_eq!(telemetry_status(&db, None).unwrap().pending_events, 0);
+    }
+
+    #[test]
+    fn test_nothing_recorded_without_opt_in() {
+        let db = Database::in_memory().unwrap();
+        let sink = AnalyticsSink::new();
+        assert!(!enabled(true, &db));
+        db.set_setting(OPT_IN_KEY, "true").unwrap();
+        assert!(enabled(true, &db));
+        assert!(!enabled(false, &db));
+        db.set_setting(OPT_IN_KEY, "false").unwrap();
+
+        for i in 0..BUFFER_SIZE * 2 {
+            sink.record(&db, enabled(true, &db), hint(i)).unwrap();
+        }
+        assert_eq!(sink.flush(&db).unwrap(), 0);
+        assert!(local_analytics(&db, &AnalyticsRange::default()).unwrap().events.is_empty());
+    }
+}
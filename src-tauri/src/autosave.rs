@@ -0,0 +1,86 @@
+//! Debounced auto-save
+//!
+//! The editor schedules a save on every change. Only the last schedule for
+//! a path within the auto-save delay is written, so a burst of keystrokes
+//! is one write, and the write happens here rather than in a frontend timer
+//! a reload or crash would lose.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Pending saves, by path, each identified by the schedule that queued it
+#[derive(Default)]
+pub struct AutoSaver {
+    pending: Mutex<HashMap<PathBuf, u64>>,
+    next_schedule: AtomicU64,
+}
+
+impl AutoSaver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `save` for `path` once `delay` passes with no later schedule for
+    /// the same path; a later schedule supersedes this one
+    pub fn schedule<F>(self: &Arc<Self>, path: PathBuf, delay: Duration, save: F)
+    where
+        F: FnOnce(&Path) + Send + 'static,
+    {
+        let schedule = self.next_schedule.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(path.clone(), schedule);
+        let saver = Arc::clone(self);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            {
+                let mut pending = saver.lock();
+                if pending.get(&path) != Some(&schedule) {
+                    return;
+                }
+                pending.remove(&path);
+            }
+            let _ = tokio::task::spawn_blocking(move || save(&path)).await;
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, u64>> {
+        self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rapid_schedules_coalesce_into_one_write() {
+        let saver = Arc::new(AutoSaver::new());
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let delay = Duration::from_millis(50);
+
+        for content in ["p", "pr", "pri", "print(1)"] {
+            let writes = Arc::clone(&writes);
+            saver.schedule(PathBuf::from("/project/main.py"), delay, move |path| {
+                writes.lock().unwrap().push((path.to_path_buf(), content));
+            });
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        // Another file's save is independent
+        let other = Arc::clone(&writes);
+        saver.schedule(PathBuf::from("/project/util.py"), delay, move |path| {
+            other.lock().unwrap().push((path.to_path_buf(), "x = 1"));
+        });
+        assert_eq!(saver.lock().len(), 2);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let mut writes = writes.lock().unwrap().clone();
+        writes.sort();
+        assert_eq!(writes, vec![
+            (PathBuf::from("/project/main.py"), "print(1)"),
+            (PathBuf::from("/project/util.py"), "x = 1"),
+        ]);
+        assert!(saver.lock().is_empty());
+    }
+}
@@ -0,0 +1,322 @@
+//! Lesson bundles for Shell IDE
+//!
+//! A `.shelllesson` bundle is a zip archive holding a lesson file, its
+//! `assets/` and `starter/` directories and a `manifest.json` describing them.
+
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+use crate::commands::lessons::Lesson;
+use crate::error::{Result, ShellError};
+use crate::security::PathSanitizer;
+
+/// File extension for lesson bundles
+pub const BUNDLE_EXTENSION: &str = "shelllesson";
+
+/// Name of the manifest entry inside a bundle
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// Bundle layout version written into the manifest
+pub const BUNDLE_FORMAT: u32 = 1;
+
+/// Directories next to the lesson file that are packed into a bundle
+const BUNDLED_DIRS: &[&str] = &["assets", "starter"];
+
+/// Largest total uncompressed size accepted when importing a bundle
+pub const MAX_BUNDLE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Largest manifest accepted, read before anything else is trusted
+const MAX_MANIFEST_SIZE: u64 = 1024 * 1024;
+
+/// Bundle contents description
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format: u32,
+    pub lesson_id: String,
+    pub lesson_version: String,
+    pub title: String,
+    /// Lesson file name at the bundle root
+    pub lesson_file: String,
+    /// Every other file in the bundle, as `/`-separated relative paths
+    pub files: Vec<String>,
+    pub created_at: String,
+}
+
+/// Pack a lesson and its assets into a bundle at `out`
+pub fn export(lesson_path: &Path, lesson: &Lesson, out: &Path) -> Result<BundleManifest> {
+    let lesson_dir = lesson_path.parent()
+        .ok_or_else(|| ShellError::Lesson("Lesson file has no parent directory".into()))?;
+    let lesson_file = lesson_path.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| ShellError::Lesson("Invalid lesson file name".into()))?
+        .to_string();
+
+    let mut files = Vec::new();
+    for dir in BUNDLED_DIRS {
+        collect_files(lesson_dir, &lesson_dir.join(dir), &mut files)?;
+    }
+    files.sort();
+
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        lesson_id: lesson.id.clone(),
+        lesson_version: lesson.version.clone(),
+        title: lesson.title.clone(),
+        lesson_file: lesson_file.clone(),
+        files,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(out)?);
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    zip.start_file(MANIFEST_NAME, options).map_err(zip_error)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+
+    for name in std::iter::once(&lesson_file).chain(&manifest.files) {
+        zip.start_file(name.as_str(), options).map_err(zip_error)?;
+        zip.write_all(&std::fs::read(lesson_dir.join(name))?)?;
+    }
+    zip.finish().map_err(zip_error)?;
+
+    Ok(manifest)
+}
+
+/// Collect regular files under `dir` as sanitized paths relative to `root`.
+/// Symlinks are skipped so a bundle can't pick up files outside the lesson.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(root)
+                .map_err(|_| ShellError::Security("File outside lesson directory".into()))?;
+            files.push(bundle_path(relative)?);
+        }
+    }
+    Ok(())
+}
+
+/// Portable `/`-separated form of a relative path
+fn bundle_path(relative: &Path) -> Result<String> {
+    let parts: Vec<_> = relative.components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<_>>()
+        .ok_or_else(|| ShellError::Lesson(format!("Non UTF-8 file name: {}", relative.display())))?;
+    let joined = parts.join("/");
+
+    PathSanitizer::relative(&joined)?;
+    Ok(joined)
+}
+
+/// Extract a bundle into `dest`, which should be an empty staging directory.
+///
+/// Entries must be listed in the manifest and stay inside `dest`, and the
+/// total uncompressed size may not exceed `max_size`. The declared sizes are
+/// not trusted: reads stop once the limit is reached.
+pub fn extract(bundle_path: &Path, dest: &Path, max_size: u64) -> Result<BundleManifest> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(bundle_path)?).map_err(zip_error)?;
+
+    let manifest: BundleManifest = {
+        let entry = archive.by_name(MANIFEST_NAME)
+            .map_err(|_| ShellError::Lesson("Bundle has no manifest".into()))?;
+        let mut data = Vec::new();
+        entry.take(MAX_MANIFEST_SIZE).read_to_end(&mut data)?;
+        serde_json::from_slice(&data)
+            .map_err(|e| ShellError::Lesson(format!("Invalid bundle manifest: {}", e)))?
+    };
+    if manifest.format > BUNDLE_FORMAT {
+        return Err(ShellError::Lesson(format!(
+            "Bundle format {} is newer than this version of Shell supports", manifest.format
+        )));
+    }
+    if Path::new(&manifest.lesson_file).components().count() != 1 {
+        return Err(ShellError::Security(format!("Invalid lesson file name: {}", manifest.lesson_file)));
+    }
+
+    let declared = (0..archive.len())
+        .map(|i| archive.by_index_raw(i).map(|e| e.size()).unwrap_or(0))
+        .try_fold(0u64, u64::checked_add)
+        .ok_or_else(|| ShellError::Security("Bundle declares an impossible extracted size".into()))?;
+    if declared > max_size {
+        return Err(ShellError::Security(format!(
+            "Bundle is too large when extracted ({} bytes, max {})", declared, max_size
+        )));
+    }
+
+    let mut remaining = max_size;
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(zip_error)?;
+        let name = entry.name().to_string();
+        if name == MANIFEST_NAME || entry.is_dir() {
+            continue;
+        }
+
+        // Zip-slip: never trust entry names to stay inside the destination
+        let relative = entry.enclosed_name()
+            .and_then(|p| p.to_str())
+            .and_then(|p| PathSanitizer::relative(p).ok())
+            .ok_or_else(|| ShellError::Security(format!("Bundle entry escapes destination: {}", name)))?;
+        if name != manifest.lesson_file && !manifest.files.contains(&name) {
+            return Err(ShellError::Lesson(format!("Bundle entry not listed in manifest: {}", name)));
+        }
+
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::File::create(&target)?;
+        let written = std::io::copy(&mut entry.take(remaining + 1), &mut file)?;
+        if written > remaining {
+            return Err(ShellError::Security(format!("Bundle is too large when extracted (max {} bytes)", max_size)));
+        }
+        remaining -= written;
+    }
+
+    if !dest.join(&manifest.lesson_file).is_file() {
+        return Err(ShellError::Lesson(format!("Bundle is missing its lesson file: {}", manifest.lesson_file)));
+    }
+
+    Ok(manifest)
+}
+
+fn zip_error(e: zip::result::ZipError) -> ShellError {
+    ShellError::Lesson(format!("Bundle error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const LESSON: &str = r#"
+id: bundled
+version: "1.0.0"
+title: Bundled
+description: ""
+language: python
+difficulty: beginner
+tags: []
+prerequisites: []
+content:
+  explanation: Uses an image
+  hints: []
+grading:
+  local_tests:
+    - { id: t1, name: T1, input: "", expected_output: "hi", points: 1, hidden: false }
+"#;
+
+    /// Lesson directory with assets and starter files in a fresh temp dir
+    fn lesson_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shell-bundle-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("assets/img")).unwrap();
+        std::fs::create_dir_all(dir.join("starter")).unwrap();
+        std::fs::write(dir.join("lesson.yaml"), LESSON).unwrap();
+        std::fs::write(dir.join("assets/img/diagram.svg"), "<svg/>").unwrap();
+        std::fs::write(dir.join("starter/main.py"), "# start here\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not bundled").unwrap();
+        dir
+    }
+
+    fn read_entry(archive: &mut zip::ZipArchive<std::fs::File>, name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        archive.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_export_bundle() {
+        let dir = lesson_dir();
+        let lesson: Lesson = serde_yaml::from_str(LESSON).unwrap();
+        let out = dir.join(format!("bundled.{}", BUNDLE_EXTENSION));
+
+        let manifest = export(&dir.join("lesson.yaml"), &lesson, &out).unwrap();
+        assert_eq!(manifest.files, vec!["assets/img/diagram.svg", "starter/main.py"]);
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&out).unwrap()).unwrap();
+        let mut names: Vec<_> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, vec!["assets/img/diagram.svg", "lesson.yaml", "manifest.json", "starter/main.py"]);
+
+        let stored: BundleManifest = serde_json::from_slice(&read_entry(&mut archive, MANIFEST_NAME)).unwrap();
+        assert_eq!(stored.lesson_id, "bundled");
+        assert_eq!(read_entry(&mut archive, "assets/img/diagram.svg"), b"<svg/>");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// Write a bundle with the given entries next to a valid manifest
+    fn craft_bundle(dir: &Path, entries: &[(&str, &[u8])]) -> PathBuf {
+        let manifest = BundleManifest {
+            format: BUNDLE_FORMAT,
+            lesson_id: "bundled".into(),
+            lesson_version: "1.0.0".into(),
+            title: "Bundled".into(),
+            lesson_file: "lesson.yaml".into(),
+            files: entries.iter().map(|(name, _)| name.to_string()).filter(|n| n != "lesson.yaml").collect(),
+            created_at: String::new(),
+        };
+        let path = dir.join("crafted.shelllesson");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default();
+        zip.start_file(MANIFEST_NAME, options).unwrap();
+        zip.write_all(&serde_json::to_vec(&manifest).unwrap()).unwrap();
+        for (name, data) in entries {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_import_exported_bundle() {
+        let dir = lesson_dir();
+        let lesson: Lesson = serde_yaml::from_str(LESSON).unwrap();
+        let out = dir.join("bundled.shelllesson");
+        export(&dir.join("lesson.yaml"), &lesson, &out).unwrap();
+
+        let dest = dir.join("imported");
+        let manifest = extract(&out, &dest, MAX_BUNDLE_SIZE).unwrap();
+
+        assert_eq!(manifest.lesson_id, "bundled");
+        assert_eq!(std::fs::read_to_string(dest.join("lesson.yaml")).unwrap(), LESSON);
+        assert_eq!(std::fs::read_to_string(dest.join("starter/main.py")).unwrap(), "# start here\n");
+        assert!(!dest.join("notes.txt").exists());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_import_rejects_zip_slip() {
+        let dir = lesson_dir();
+        let bundle = craft_bundle(&dir, &[("lesson.yaml", LESSON.as_bytes()), ("../escaped.txt", b"gotcha")]);
+
+        let err = extract(&bundle, &dir.join("imported"), MAX_BUNDLE_SIZE).unwrap_err();
+
+        assert!(matches!(err, ShellError::Security(ref m) if m.contains("escapes destination")), "{}", err);
+        assert!(!dir.join("escaped.txt").exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_import_rejects_oversized_bundle() {
+        let dir = lesson_dir();
+        let zeros = vec![0u8; 64 * 1024];
+        let bundle = craft_bundle(&dir, &[("lesson.yaml", LESSON.as_bytes()), ("assets/zeros.bin", &zeros)]);
+
+        let err = extract(&bundle, &dir.join("imported"), 16 * 1024).unwrap_err();
+
+        assert!(matches!(err, ShellError::Security(ref m) if m.contains("too large")), "{}", err);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,210 @@
+//! Classrooms
+//!
+//! A teacher's classes, their rosters, and which student made each stored
+//! submission, so a lesson's progress can be viewed per class.
+
+use serde::{Deserialize, Serialize};
+use crate::db::Database;
+use crate::error::{Result, ShellError};
+
+/// Longest classroom or student name
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Classroom {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+/// A student on a classroom's roster
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Student {
+    pub id: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub added_at: String,
+}
+
+/// A student to add to a roster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewStudent {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+/// One student's submissions for a lesson
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StudentProgress {
+    pub student: Student,
+    pub submissions: u32,
+    pub best_score: Option<f64>,
+    /// Status of the latest submission, e.g. `graded`
+    pub last_status: Option<String>,
+    pub last_submitted_at: Option<String>,
+}
+
+fn checked_name(kind: &str, name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(ShellError::Configuration(format!(
+            "{} name must be 1 to {} characters", kind, MAX_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_string())
+}
+
+fn require_classroom(db: &Database, classroom_id: &str) -> Result<Classroom> {
+    db.get_classroom(classroom_id)?
+        .ok_or_else(|| ShellError::Configuration(format!("Unknown classroom: {}", classroom_id)))
+}
+
+/// Create an empty classroom
+pub fn create(db: &Database, name: &str) -> Result<Classroom> {
+    let classroom = Classroom {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: checked_name("Classroom", name)?,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    db.create_classroom(&classroom)?;
+    Ok(classroom)
+}
+
+/// Add a student to a classroom. Emails, when given, are unique per roster.
+pub fn add_student(db: &Database, classroom_id: &str, student: NewStudent) -> Result<Student> {
+    require_classroom(db, classroom_id)?;
+    let email = student.email
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty());
+    if let Some(email) = &email {
+        if !email.contains('@') {
+            return Err(ShellError::Configuration(format!("Invalid email: {}", email)));
+        }
+        if db.list_classroom_students(classroom_id)?.iter().any(|s| s.email.as_ref() == Some(email)) {
+            return Err(ShellError::Configuration(format!("{} is already in this classroom", email)));
+        }
+    }
+
+    let student = Student {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: checked_name("Student", &student.name)?,
+        email,
+        added_at: chrono::Utc::now().to_rfc3339(),
+    };
+    db.add_classroom_student(classroom_id, &student)?;
+    Ok(student)
+}
+
+/// A classroom's roster
+pub fn students(db: &Database, classroom_id: &str) -> Result<Vec<Student>> {
+    require_classroom(db, classroom_id)?;
+    db.list_classroom_students(classroom_id)
+}
+
+/// Take a student off a classroom's roster
+pub fn remove_student(db: &Database, classroom_id: &str, student_id: &str) -> Result<()> {
+    if !db.remove_classroom_student(classroom_id, student_id)? {
+        return Err(ShellError::Configuration(format!("Student {} is not in classroom {}", student_id, classroom_id)));
+    }
+    Ok(())
+}
+
+/// Attribute a stored submission to a student in a classroom
+pub fn assign_submission(db: &Database, submission_id: &str, classroom_id: &str, student_id: &str) -> Result<()> {
+    if !students(db, classroom_id)?.iter().any(|s| s.id == student_id) {
+        return Err(ShellError::Configuration(format!("Student {} is not in classroom {}", student_id, classroom_id)));
+    }
+    if db.get_submission(submission_id)?.is_none() {
+        return Err(ShellError::Lesson(format!("Submission not found: {}", submission_id)));
+    }
+    db.link_classroom_submission(submission_id, classroom_id, student_id)
+}
+
+/// Every student in a classroom with their submissions for `lesson_id`
+pub fn progress(db: &Database, classroom_id: &str, lesson_id: &str) -> Result<Vec<StudentProgress>> {
+    require_classroom(db, classroom_id)?;
+    db.classroom_progress(classroom_id, lesson_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SubmissionRecord;
+
+    fn new_student(name: &str, email: Option<&str>) -> NewStudent {
+        NewStudent { name: name.into(), email: email.map(str::to_string) }
+    }
+
+    fn submission(id: &str, lesson_id: &str, submitted_at: &str, score: f64) -> SubmissionRecord {
+        SubmissionRecord {
+            id: id.into(),
+            lesson_id: Some(lesson_id.into()),
+            project_id: None,
+            submitted_at: submitted_at.into(),
+            status: "graded".into(),
+            score: Some(score),
+            feedback: None,
+            result: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_roster_crud() {
+        let db = Database::in_memory().unwrap();
+        let period_two = create(&db, "  Period 2 ").unwrap();
+        let period_one = create(&db, "Period 1").unwrap();
+        assert_eq!(period_two.name, "Period 2");
+        assert!(create(&db, " ").is_err());
+        let names: Vec<_> = db.list_classrooms().unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["Period 1", "Period 2"]);
+
+        let grace = add_student(&db, &period_one.id, new_student("Grace", Some("Grace@School.edu"))).unwrap();
+        add_student(&db, &period_one.id, new_student("Alan", None)).unwrap();
+        assert_eq!(grace.email.as_deref(), Some("grace@school.edu"));
+        assert!(add_student(&db, &period_one.id, new_student("Grace H", Some("grace@school.edu"))).is_err());
+        assert!(add_student(&db, &period_one.id, new_student("Ada", Some("not-an-email"))).is_err());
+        assert!(add_student(&db, "missing", new_student("Ada", None)).is_err());
+        // The same email may be in another class
+        add_student(&db, &period_two.id, new_student("Grace", Some("grace@school.edu"))).unwrap();
+
+        let roster: Vec<_> = students(&db, &period_one.id).unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(roster, vec!["Alan", "Grace"]);
+
+        remove_student(&db, &period_one.id, &grace.id).unwrap();
+        assert!(remove_student(&db, &period_one.id, &grace.id).is_err());
+        assert_eq!(students(&db, &period_one.id).unwrap().len(), 1);
+        assert_eq!(students(&db, &period_two.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_progress_per_student() {
+        let db = Database::in_memory().unwrap();
+        let class = create(&db, "Period 1").unwrap();
+        let grace = add_student(&db, &class.id, new_student("Grace", None)).unwrap();
+        let alan = add_student(&db, &class.id, new_student("Alan", None)).unwrap();
+        for lesson in ["loops", "functions"] {
+            db.upsert_lesson(lesson, lesson, "", &format!("/missing/{}/lesson.yaml", lesson), "1.0.0").unwrap();
+        }
+        db.save_submission(&submission("s1", "loops", "2026-01-13T10:00:00Z", 90.0)).unwrap();
+        db.save_submission(&submission("s2", "loops", "2026-01-14T10:00:00Z", 60.0)).unwrap();
+        db.save_submission(&submission("s3", "functions", "2026-01-15T10:00:00Z", 100.0)).unwrap();
+        for id in ["s1", "s2", "s3"] {
+            assign_submission(&db, id, &class.id, &grace.id).unwrap();
+        }
+        assert!(assign_submission(&db, "missing", &class.id, &grace.id).is_err());
+        assert!(assign_submission(&db, "s1", &class.id, "stranger").is_err());
+
+        // Re-grading a linked submission keeps its link
+        db.save_submission(&submission("s2", "loops", "2026-01-14T10:00:00Z", 95.0)).unwrap();
+
+        let progress = progress(&db, &class.id, "loops").unwrap();
+        assert_eq!(progress.len(), 2);
+        assert_eq!(progress[0].student, alan);
+        assert_eq!((progress[0].submissions, progress[0].best_score), (0, None));
+        assert_eq!((progress[1].submissions, progress[1].best_score), (2, Some(95.0)));
+        assert_eq!(progress[1].last_submitted_at.as_deref(), Some("2026-01-14T10:00:00Z"));
+        assert_eq!(progress[1].last_status.as_deref(), Some("graded"));
+    }
+}
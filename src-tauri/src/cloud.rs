@@ -0,0 +1,283 @@
+//! Cloud grading client for Shell IDE
+//!
+//! Cloud grading is optional. When the service can't be reached the caller
+//! falls back to local grading, so nothing here may block learning.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use crate::analytics::{RecordedEvent, TelemetryConsent, TelemetryLogEntry};
+use crate::commands::grading::{CloudGradingRequest, GradingResult};
+use crate::error::{Result, ShellError};
+
+/// Per-request timeout for the grading service
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Total attempts for transient failures (connection errors, timeouts, 5xx)
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry, doubled for each further retry
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+pub struct CloudClient {
+    http: reqwest::Client,
+    endpoint: String,
+    token: String,
+}
+
+#[derive(Serialize)]
+struct SeatRequest<'a> {
+    install_id: &'a str,
+}
+
+#[derive(Deserialize)]
+struct SeatResponse {
+    /// Installs holding a seat, including the one just registered
+    installs: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct AnalyticsBatch<'a> {
+    events: &'a [RecordedEvent],
+}
+
+/// Outcome of a single failed attempt
+struct AttemptError {
+    error: ShellError,
+    retry: bool,
+}
+
+impl CloudClient {
+    pub fn new(endpoint: &str, token: &str) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| ShellError::Configuration(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            http,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        })
+    }
+
+    /// Submit code for grading, retrying transient failures with backoff.
+    ///
+    /// Returns `ShellError::Network` when the service is unreachable, so the
+    /// caller can fall back to local grading.
+    pub async fn submit(&self, request: &CloudGradingRequest) -> Result<GradingResult> {
+        let url = format!("{}/api/v1/submissions", self.endpoint);
+        let mut delay = RETRY_BASE_DELAY;
+        let mut attempt = 1;
+
+        loop {
+            match self.submit_once(&url, request).await {
+                Ok(result) => return Ok(result),
+                Err(failure) if failure.retry && attempt < MAX_ATTEMPTS => {
+                    tracing::warn!("Cloud grading attempt {} failed: {}", attempt, failure.error);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(failure) => return Err(failure.error),
+            }
+        }
+    }
+
+    /// Register an install against a license seat, returning the seat list
+    pub async fn claim_seat(&self, license_id: &str, install_id: &str) -> Result<Vec<String>> {
+        let url = format!("{}/api/v1/licenses/{}/seats", self.endpoint, license_id);
+        let response = self.http.post(&url)
+            .bearer_auth(&self.token)
+            .json(&SeatRequest { install_id })
+            .send()
+            .await
+            .map_err(|e| license_server_error(&e))?;
+
+        match response.status() {
+            status if status.is_success() => Ok(response.json::<SeatResponse>().await
+                .map_err(|e| ShellError::Service(format!("Invalid response from license server: {}", e)))?
+                .installs),
+            reqwest::StatusCode::CONFLICT => Err(ShellError::FeatureNotAvailable(
+                "All seats on this license are in use. Release a seat on another install first.".into()
+            )),
+            status => Err(ShellError::Service(format!("License server returned {}", status))),
+        }
+    }
+
+    /// Free an install's seat
+    pub async fn release_seat(&self, license_id: &str, install_id: &str) -> Result<()> {
+        let url = format!("{}/api/v1/licenses/{}/seats/{}", self.endpoint, license_id, install_id);
+        let response = self.http.delete(&url)
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| license_server_error(&e))?;
+
+        let status = response.status();
+        if status.is_success() || status == reqwest::StatusCode::NOT_FOUND {
+            Ok(())
+        } else {
+            Err(ShellError::Service(format!("License server returned {}", status)))
+        }
+    }
+
+    /// Send a batch of analytics events, returning exactly what was sent
+    pub async fn send_analytics(&self, _consent: &TelemetryConsent, events: &[RecordedEvent]) -> Result<TelemetryLogEntry> {
+        let url = format!("{}/api/v1/analytics/events", self.endpoint);
+        let payload = serde_json::to_string(&AnalyticsBatch { events })?;
+        let response = self.http.post(&url)
+            .bearer_auth(&self.token)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.clone())
+            .send()
+            .await
+            .map_err(|e| if e.is_connect() || e.is_timeout() {
+                ShellError::Network(format!("Analytics service unreachable: {}", e))
+            } else {
+                ShellError::Service(format!("Analytics upload failed: {}", e))
+            })?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(TelemetryLogEntry { sent_at: chrono::Utc::now().to_rfc3339(), url, payload })
+        } else {
+            Err(ShellError::Service(format!("Analytics service returned {}", status)))
+        }
+    }
+
+    async fn submit_once(&self, url: &str, request: &CloudGradingRequest) -> std::result::Result<GradingResult, AttemptError> {
+        let response = self.http.post(url)
+            .bearer_auth(&self.token)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| {
+                let error = if e.is_connect() || e.is_timeout() {
+                    ShellError::Network(format!("Grading service unreachable: {}", e))
+                } else {
+                    ShellError::Service(format!("Grading request failed: {}", e))
+                };
+                AttemptError { retry: error.is_retriable(), error }
+            })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return response.json::<GradingResult>().await.map_err(|e| AttemptError {
+                retry: false,
+                error: ShellError::Service(format!("Invalid response from grading service: {}", e)),
+            });
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        Err(match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => AttemptError {
+                retry: false,
+                error: ShellError::CloudRequired(
+                    "The grading service rejected your license. Please re-activate it.".into()
+                ),
+            },
+            _ => AttemptError {
+                retry: status.is_server_error(),
+                error: ShellError::Service(format!("Grading service returned {}: {}", status, body.trim())),
+            },
+        })
+    }
+}
+
+fn license_server_error(e: &reqwest::Error) -> ShellError {
+    if e.is_connect() || e.is_timeout() {
+        ShellError::Network(format!("License server unreachable: {}", e))
+    } else {
+        ShellError::Service(format!("License request failed: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> CloudGradingRequest {
+        CloudGradingRequest {
+            lesson_id: "python-hello-world".into(),
+            code: "print('Hello, World!')".into(),
+            language: "python".into(),
+            project_files: vec![],
+            lesson_path: None,
+        }
+    }
+
+    const GRADED: &str = r#"{
+        "submission_id": "sub_abc123",
+        "lesson_id": "python-hello-world",
+        "total_points": 100,
+        "max_points": 100,
+        "percentage": 100.0,
+        "test_results": [],
+        "feedback": "Perfect score! Excellent work!",
+        "graded_at": "2026-01-13T10:30:00Z",
+        "graded_by": "Cloud"
+    }"#;
+
+    #[tokio::test]
+    async fn test_submit_success() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/api/v1/submissions")
+            .match_header("authorization", "Bearer secret-token")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(GRADED)
+            .create_async()
+            .await;
+
+        let client = CloudClient::new(&server.url(), "secret-token").unwrap();
+        let result = client.submit(&request()).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(result.submission_id, "sub_abc123");
+        assert_eq!(result.percentage, 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_submit_unauthorized_is_not_retried() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/api/v1/submissions")
+            .with_status(401)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = CloudClient::new(&server.url(), "expired").unwrap();
+        let err = client.submit(&request()).await.unwrap_err();
+
+        mock.assert_async().await;
+        assert!(matches!(err, ShellError::CloudRequired(_)));
+    }
+
+    #[tokio::test]
+    async fn test_submit_server_error_is_retried() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/api/v1/submissions")
+            .with_status(500)
+            .with_body("worker crashed")
+            .expect(MAX_ATTEMPTS as usize)
+            .create_async()
+            .await;
+
+        let client = CloudClient::new(&server.url(), "secret-token").unwrap();
+        let err = client.submit(&request()).await.unwrap_err();
+
+        mock.assert_async().await;
+        assert!(matches!(err, ShellError::Service(ref msg) if msg.contains("worker crashed")));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_service_is_network_error() {
+        // Bind then drop a listener to get a port nothing is listening on
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let client = CloudClient::new(&format!("http://127.0.0.1:{}", port), "secret-token").unwrap();
+        let err = client.submit(&request()).await.unwrap_err();
+
+        assert!(matches!(err, ShellError::Network(_)));
+    }
+}
@@ -0,0 +1,155 @@
+//! Coalescing of identical runs
+//!
+//! Auto-run can fire the same `run_code` request several times before the
+//! first finishes. Callers that opt in with a `dedupe_key` share the run
+//! already in flight under that key instead of starting another container.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard};
+use tokio::sync::watch;
+use crate::docker::ExecutionResult;
+use crate::error::{Result, ShellError};
+
+/// Outcome of a run as every waiter sees it. Errors are shared as messages,
+/// since `ShellError` can't be cloned.
+type SharedOutcome = std::result::Result<ExecutionResult, String>;
+
+/// Runs in flight by `dedupe_key`
+#[derive(Default)]
+pub struct RunCoalescer {
+    in_flight: Mutex<HashMap<String, watch::Receiver<Option<SharedOutcome>>>>,
+}
+
+impl RunCoalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `run`, unless a run under the same `key` is in flight, in which
+    /// case wait for its result instead; the result then carries that run's
+    /// id. Without a key, `run` always runs.
+    pub async fn run<F>(&self, key: Option<&str>, run: F) -> Result<ExecutionResult>
+    where
+        F: Future<Output = Result<ExecutionResult>>,
+    {
+        let Some(key) = key else { return run.await };
+
+        let (sender, receiver) = {
+            let mut in_flight = self.lock();
+            match in_flight.get(key) {
+                Some(receiver) => (None, receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    in_flight.insert(key.to_string(), receiver.clone());
+                    (Some(sender), receiver)
+                }
+            }
+        };
+        match sender {
+            Some(sender) => self.lead(key, sender, run).await,
+            None => follow(receiver).await,
+        }
+    }
+
+    async fn lead<F>(&self, key: &str, sender: watch::Sender<Option<SharedOutcome>>, run: F) -> Result<ExecutionResult>
+    where
+        F: Future<Output = Result<ExecutionResult>>,
+    {
+        // Unregistered even if the caller drops this future mid-run
+        let _registered = Registered { coalescer: self, key };
+        let outcome = run.await;
+        let shared = match &outcome {
+            Ok(result) => Ok(result.clone()),
+            Err(e) => Err(e.to_string()),
+        };
+        let _ = sender.send(Some(shared));
+        outcome
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, watch::Receiver<Option<SharedOutcome>>>> {
+        self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Wait for the leading run's outcome
+async fn follow(mut receiver: watch::Receiver<Option<SharedOutcome>>) -> Result<ExecutionResult> {
+    loop {
+        if let Some(outcome) = receiver.borrow_and_update().clone() {
+            return outcome.map_err(ShellError::Execution);
+        }
+        if receiver.changed().await.is_err() {
+            return Err(ShellError::Execution("The identical run this one joined was cancelled".into()));
+        }
+    }
+}
+
+/// A leading run's entry, removed on drop
+struct Registered<'a> {
+    coalescer: &'a RunCoalescer,
+    key: &'a str,
+}
+
+impl Drop for Registered<'_> {
+    fn drop(&mut self) {
+        self.coalescer.lock().remove(self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::docker::ExecutionPhase;
+
+    fn result(id: &str) -> ExecutionResult {
+        ExecutionResult {
+            id: id.into(),
+            exit_code: 0,
+            stdout: "done\n".into(),
+            stderr: String::new(),
+            duration_ms: 1,
+            timed_out: false,
+            trace: None,
+            phase: ExecutionPhase::Run,
+            compile_output: None,
+            tests_passed: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            log_collection_timed_out: false,
+            output_truncated: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_identical_keyed_runs_share_one_container() {
+        let coalescer = RunCoalescer::new();
+        let containers = AtomicUsize::new(0);
+        let run = |id: &'static str| {
+            let containers = &containers;
+            async move {
+                containers.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                Ok(result(id))
+            }
+        };
+
+        let (first, second) = tokio::join!(
+            coalescer.run(Some("project:main.py:abc"), run("first")),
+            coalescer.run(Some("project:main.py:abc"), run("second")),
+        );
+        assert_eq!(containers.load(Ordering::SeqCst), 1);
+        assert_eq!(first.unwrap().id, "first");
+        assert_eq!(second.unwrap().id, "first");
+        assert!(coalescer.lock().is_empty());
+
+        // Different keys and unkeyed runs stay independent
+        let (a, b, c) = tokio::join!(
+            coalescer.run(Some("a"), run("a")),
+            coalescer.run(Some("b"), run("b")),
+            coalescer.run(None, run("c")),
+        );
+        assert_eq!((a.unwrap().id, b.unwrap().id, c.unwrap().id), ("a".into(), "b".into(), "c".into()));
+        assert_eq!(containers.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,112 @@
+//! Analytics IPC commands
+
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tauri::{AppHandle, Manager, State};
+use crate::analytics::{self, AnalyticsEvent, AnalyticsRange, AnalyticsSink, LocalAnalytics, TelemetryStatus};
+use crate::cloud::CloudClient;
+use crate::commands::settings::load_settings;
+use crate::db::Database;
+use crate::error::Result;
+use crate::features::FeatureFlags;
+use crate::fs::FileSystem;
+use crate::security::SecurityPolicy;
+
+/// Record `event` for a command. Does nothing unless the feature is on and
+/// the user opted in; failures are logged, never returned to the command.
+pub fn record_event(app: &AppHandle, event: AnalyticsEvent) {
+    let db = app.state::<Database>();
+    let flag = app.state::<RwLock<FeatureFlags>>().read().map(|f| f.analytics).unwrap_or(false);
+    match app.state::<AnalyticsSink>().record(&db, analytics::enabled(flag, &db), event) {
+        Ok(true) => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move { upload(&app).await });
+        }
+        Ok(false) => {}
+        Err(e) => tracing::warn!("Failed to record analytics event: {}", e),
+    }
+}
+
+/// Send stored events when telemetry is on and an endpoint and a license
+/// token are configured
+async fn upload(app: &AppHandle) {
+    let db = app.state::<Database>();
+    if analytics::telemetry_consent(&db).is_none() {
+        return;
+    }
+    let Some(endpoint) = load_settings(&db).analytics_url else { return };
+    let token = app.state::<RwLock<FeatureFlags>>().read().ok()
+        .and_then(|f| f.license.as_ref().and_then(|l| l.token.clone()));
+    let Some(token) = token else { return };
+
+    let sent = match CloudClient::new(&endpoint, &token) {
+        Ok(client) => analytics::upload_pending(&db, &client).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = sent {
+        tracing::warn!("Analytics upload failed: {}", e);
+    }
+}
+
+/// Recorded events in `range`, for the dashboard
+#[tauri::command]
+pub async fn get_local_analytics(
+    range: Option<AnalyticsRange>,
+    db: State<'_, Database>,
+    sink: State<'_, AnalyticsSink>,
+) -> Result<LocalAnalytics> {
+    sink.flush(&db)?;
+    analytics::local_analytics(&db, &range.unwrap_or_default())
+}
+
+/// Whether the user agreed to analytics recording
+#[tauri::command]
+pub async fn get_analytics_opt_in(db: State<'_, Database>) -> Result<bool> {
+    Ok(analytics::opted_in(&db))
+}
+
+/// Opt in to or out of analytics. Opting out drops unsaved events.
+#[tauri::command]
+pub async fn set_analytics_opt_in(
+    enabled: bool,
+    db: State<'_, Database>,
+    sink: State<'_, AnalyticsSink>,
+) -> Result<()> {
+    if !enabled {
+        sink.discard()?;
+    }
+    db.set_setting(analytics::OPT_IN_KEY, if enabled { "true" } else { "false" })
+}
+
+/// Delete every locally recorded event
+#[tauri::command]
+pub async fn purge_analytics(
+    db: State<'_, Database>,
+    sink: State<'_, AnalyticsSink>,
+) -> Result<()> {
+    sink.discard()?;
+    db.purge_analytics_events()
+}
+
+/// Whether telemetry is on and what it has sent
+#[tauri::command]
+pub async fn get_telemetry_status(db: State<'_, Database>) -> Result<TelemetryStatus> {
+    analytics::telemetry_status(&db, load_settings(&db).analytics_url)
+}
+
+/// Turn uploading of recorded events on or off
+#[tauri::command]
+pub async fn set_telemetry_enabled(enabled: bool, db: State<'_, Database>) -> Result<()> {
+    db.set_setting(analytics::TELEMETRY_KEY, if enabled { "true" } else { "false" })
+}
+
+/// Write every upload telemetry has sent, verbatim, to a JSON file
+#[tauri::command]
+pub async fn export_telemetry_log(
+    path: String,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<()> {
+    let log = db.list_telemetry_log()?;
+    FileSystem::new(Arc::clone(&policy)).write_file(Path::new(&path), &serde_json::to_string_pretty(&log)?)
+}
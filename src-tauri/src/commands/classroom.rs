@@ -0,0 +1,96 @@
+//! Classroom IPC commands
+//!
+//! All of them require the `classrooms` feature.
+
+use std::sync::RwLock;
+use tauri::State;
+use crate::classroom::{self, Classroom, NewStudent, Student, StudentProgress};
+use crate::db::Database;
+use crate::error::{Result, ShellError};
+use crate::features::FeatureFlags;
+
+#[tauri::command]
+pub async fn create_classroom(
+    name: String,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<Classroom> {
+    require_classrooms(&features)?;
+    classroom::create(&db, &name)
+}
+
+#[tauri::command]
+pub async fn list_classrooms(
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<Vec<Classroom>> {
+    require_classrooms(&features)?;
+    db.list_classrooms()
+}
+
+#[tauri::command]
+pub async fn add_student(
+    classroom_id: String,
+    student: NewStudent,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<Student> {
+    require_classrooms(&features)?;
+    classroom::add_student(&db, &classroom_id, student)
+}
+
+#[tauri::command]
+pub async fn remove_student(
+    classroom_id: String,
+    student_id: String,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<()> {
+    require_classrooms(&features)?;
+    classroom::remove_student(&db, &classroom_id, &student_id)
+}
+
+#[tauri::command]
+pub async fn list_students(
+    classroom_id: String,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<Vec<Student>> {
+    require_classrooms(&features)?;
+    classroom::students(&db, &classroom_id)
+}
+
+/// Attribute a stored submission to a student in a classroom
+#[tauri::command]
+pub async fn assign_submission(
+    submission_id: String,
+    classroom_id: String,
+    student_id: String,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<()> {
+    require_classrooms(&features)?;
+    classroom::assign_submission(&db, &submission_id, &classroom_id, &student_id)
+}
+
+/// Each student's submissions for a lesson, including students with none
+#[tauri::command]
+pub async fn classroom_progress(
+    classroom_id: String,
+    lesson_id: String,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<Vec<StudentProgress>> {
+    require_classrooms(&features)?;
+    classroom::progress(&db, &classroom_id, &lesson_id)
+}
+
+fn require_classrooms(features: &RwLock<FeatureFlags>) -> Result<()> {
+    let enabled = features.read()
+        .map_err(|_| ShellError::Security("Failed to read feature flags".into()))?
+        .classrooms;
+    if !enabled {
+        return Err(ShellError::FeatureNotAvailable("Classrooms require a teacher license.".into()));
+    }
+    Ok(())
+}
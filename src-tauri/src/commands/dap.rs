@@ -0,0 +1,70 @@
+//! Debugger IPC commands
+//!
+//! Adapter messages are emitted as `dap://{session_id}/message` events,
+//! and `dap://{session_id}/exit` once the adapter stops.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+use crate::dap::{adapter_for, launch_arguments, DapEvent, DapSessions};
+use crate::error::Result;
+use crate::security::SecurityPolicy;
+use crate::services::ServiceManager;
+
+/// A started debug session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DapSession {
+    pub session_id: String,
+    /// Arguments for the UI's `launch` request
+    pub launch: Value,
+}
+
+/// Start a debug adapter for `program`, run from `project_path`
+#[tauri::command]
+pub async fn dap_start(
+    language: String,
+    program: String,
+    project_path: String,
+    app: AppHandle,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    services: State<'_, ServiceManager>,
+    sessions: State<'_, DapSessions>,
+) -> Result<DapSession> {
+    policy.check_host_execution()?;
+    let program = PathBuf::from(program);
+    let cwd = PathBuf::from(project_path);
+    policy.validate_path(&program)?;
+    policy.validate_path(&cwd)?;
+    let adapter = adapter_for(&language)?;
+
+    let listener = Arc::new(move |session_id: &str, event: DapEvent| {
+        let _ = match event {
+            DapEvent::Message(message) => app.emit(&format!("dap://{}/message", session_id), message),
+            DapEvent::Closed => app.emit(&format!("dap://{}/exit", session_id), ()),
+        };
+    });
+    let session_id = sessions.start(&services, &language, adapter, &cwd, listener).await?;
+    Ok(DapSession { session_id, launch: launch_arguments(&language, &program, &cwd) })
+}
+
+/// Send a DAP message to a session's adapter
+#[tauri::command]
+pub async fn dap_send(
+    session_id: String,
+    message: Value,
+    sessions: State<'_, DapSessions>,
+) -> Result<()> {
+    sessions.send(&session_id, &message).await
+}
+
+/// Stop a session's adapter
+#[tauri::command]
+pub async fn dap_stop(
+    session_id: String,
+    services: State<'_, ServiceManager>,
+    sessions: State<'_, DapSessions>,
+) -> Result<()> {
+    sessions.stop(&services, &session_id).await
+}
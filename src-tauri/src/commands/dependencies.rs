@@ -0,0 +1,44 @@
+//! Lesson dependency IPC commands
+
+use std::path::Path;
+use std::sync::Arc;
+use tauri::State;
+use crate::commands::execution::{get_language_image, project_lesson};
+use crate::db::Database;
+use crate::dependencies::{self, InstallResult};
+use crate::docker::DockerManager;
+use crate::error::{Result, ShellError};
+use crate::security::SecurityPolicy;
+
+/// Install packages from the project lesson's allowlist into an image that
+/// the project's later runs use
+#[tauri::command]
+pub async fn install_dependencies(
+    language: String,
+    project_path: String,
+    packages: Vec<String>,
+    docker: State<'_, DockerManager>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<InstallResult> {
+    // Installs need the network, which an exam rules out
+    if policy.exam().is_some() {
+        return Err(ShellError::Security("Packages can't be installed during an exam".into()));
+    }
+    let project = Path::new(&project_path);
+    // The project is later mounted into the image built here
+    policy.validate_mount(project)?;
+    let project = project.canonicalize()?;
+
+    let allowlist: Vec<String> = project_lesson(&project)
+        .and_then(|doc| serde_json::from_value(doc.get("dependencies")?.clone()).ok())
+        .unwrap_or_default();
+    let packages = dependencies::allowed_packages(&packages, &allowlist)?;
+
+    if !docker.is_available().await {
+        return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
+    }
+    let result = dependencies::install(&docker, &language, &packages).await?;
+    db.set_dependency_image(&project.to_string_lossy(), &get_language_image(&language)?, &result.image, &result.packages)?;
+    Ok(result)
+}
@@ -0,0 +1,80 @@
+//! Exam mode IPC commands
+//!
+//! The session is persisted so restarting the app doesn't end an exam.
+
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tauri::State;
+use crate::db::Database;
+use crate::error::{Result, ShellError};
+use crate::features::FeatureFlags;
+use crate::security::{ExamSession, SecurityPolicy};
+
+/// Settings key the active session is stored under
+pub const EXAM_SESSION_KEY: &str = "exam_session";
+
+/// Lock the IDE down to one project for an exam
+#[tauri::command]
+pub async fn enter_exam_mode(
+    project_path: String,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<ExamSession> {
+    let enabled = features.read()
+        .map_err(|_| ShellError::Security("Failed to read feature flags".into()))?
+        .exam_mode;
+    if !enabled {
+        return Err(ShellError::FeatureNotAvailable("Exam mode requires an institution license.".into()));
+    }
+
+    let session = policy.enter_exam(Path::new(&project_path))?;
+    if let Err(e) = db.set_setting(EXAM_SESSION_KEY, &serde_json::to_string(&session)?) {
+        policy.exit_exam();
+        return Err(e);
+    }
+    Ok(session)
+}
+
+/// End the exam; requires the teacher's license token
+#[tauri::command]
+pub async fn exit_exam_mode(
+    token: String,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<()> {
+    let expected = features.read()
+        .map_err(|_| ShellError::Security("Failed to read feature flags".into()))?
+        .license.as_ref()
+        .and_then(|l| l.token.clone());
+    if !expected.is_some_and(|expected| tokens_match(&expected, &token)) {
+        return Err(ShellError::Security("Only a teacher can end the exam".into()));
+    }
+
+    db.delete_setting(EXAM_SESSION_KEY)?;
+    policy.exit_exam();
+    Ok(())
+}
+
+/// The exam in progress, if any
+#[tauri::command]
+pub async fn get_exam_session(
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<Option<ExamSession>> {
+    Ok(policy.exam())
+}
+
+/// Resume a persisted exam at startup
+pub fn resume(db: &Database, policy: &SecurityPolicy) -> Result<()> {
+    if let Some(stored) = db.get_setting(EXAM_SESSION_KEY)? {
+        policy.resume_exam(serde_json::from_str(&stored)?);
+    }
+    Ok(())
+}
+
+/// Compare without short-circuiting on the first differing byte
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected.bytes().zip(given.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
@@ -2,13 +2,27 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tauri::State;
-use crate::docker::{DockerManager, ExecutionRequest, ExecutionResult, ContainerInfo};
-use crate::error::{Result, ShellError};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+use crate::docker::{DockerInfo, DockerManager, ExecutionBackend, ExecutionOutput, ExecutionRequest, ExecutionResult, ContainerInfo, ResourceLimits};
+use crate::analytics::AnalyticsEvent;
+use crate::coalesce::RunCoalescer;
+use crate::commands::analytics::record_event;
+use crate::commands::lessons::RunOptions;
+use crate::commands::settings::{load_settings, Settings};
+use crate::coverage::CoverageReport;
+use crate::db::Database;
+use crate::languages::{self, LanguageInfo, LanguageSpec, COMPILED_BINARY, LANGUAGES};
+use crate::error::{ErrorContext, Result, ShellError};
+use crate::security::SecurityPolicy;
 
 /// Request to run code
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunCodeRequest {
+    /// Id for the run's lifecycle events; generated when absent
+    #[serde(default)]
+    pub execution_id: Option<String>,
     /// Language for execution
     pub language: String,
     /// Source code to run
@@ -17,6 +31,15 @@ pub struct RunCodeRequest {
     pub project_path: String,
     /// Entry point file
     pub entry_point: Option<String>,
+    /// Run the entry point or the project's test suite
+    #[serde(default)]
+    pub run_mode: RunMode,
+    /// Extra compiler flags, checked against the security policy
+    #[serde(default)]
+    pub compile_flags: Option<Vec<String>>,
+    /// Arguments passed to the program
+    #[serde(default)]
+    pub run_args: Option<Vec<String>>,
     /// Standard input to provide
     pub stdin: Option<String>,
     /// Environment variables
@@ -27,6 +50,35 @@ pub struct RunCodeRequest {
     pub trace_io: Option<bool>,
     /// Timeout in seconds
     pub timeout: Option<u64>,
+    /// Also return base64 of output streams that aren't valid UTF-8
+    #[serde(default)]
+    pub raw_output: Option<bool>,
+    /// Let the program write into the project directory, for lessons that
+    /// edit in place
+    #[serde(default)]
+    pub workspace_writable: Option<bool>,
+    /// Opt-in: while a run with the same key is in flight, return its
+    /// result instead of starting another container (e.g. project + file hash)
+    #[serde(default)]
+    pub dedupe_key: Option<String>,
+    /// Image with the project's installed packages, looked up by the
+    /// command rather than trusted from the caller
+    #[serde(skip)]
+    pub image: Option<String>,
+    /// Limits from settings for whatever the request leaves unset
+    #[serde(skip)]
+    pub limits: Option<ResourceLimits>,
+}
+
+/// What a run executes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunMode {
+    /// The program's entry point
+    #[default]
+    Main,
+    /// The project's tests, through the language's test framework
+    Tests,
 }
 
 /// Execution status response
@@ -36,71 +88,685 @@ pub struct ExecutionStatus {
     pub containers: Vec<ContainerInfo>,
 }
 
+/// Something that would stop a run request from running
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RunProblem {
+    /// No image or run command for the language
+    UnsupportedLanguage { language: String },
+    /// The given entry point is not a file in the project, or none was given
+    /// and no conventional entry file exists
+    MissingEntryPoint { entry_point: Option<String>, candidates: Vec<String> },
+    /// Flags, arguments or run mode the run would reject
+    InvalidOptions { message: String },
+    /// The security policy rejects the project, image, compiler flags or
+    /// environment
+    Denied { message: String },
+    /// Docker is not installed or not running
+    DockerUnavailable,
+    /// The image lacks a tool the run command needs
+    MissingToolchain { tool: String, guidance: String },
+}
+
+/// Whether a language's image has the tool its run command needs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolchainStatus {
+    pub language: String,
+    pub image: String,
+    pub tool: String,
+    pub available: bool,
+    /// First line the version probe printed
+    pub version: Option<String>,
+    /// How to get the tool, when it's missing
+    pub guidance: Option<String>,
+}
+
+/// How long a toolchain version probe may take
+const TOOLCHAIN_PROBE_TIMEOUT_SECS: u64 = 30;
+
+/// Outcome of checking a run request without running it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunValidation {
+    pub valid: bool,
+    pub image: Option<String>,
+    pub entry_point: Option<String>,
+    /// What the container would execute
+    pub command: Option<Vec<String>>,
+    pub problems: Vec<RunProblem>,
+}
+
+/// Most timed runs a benchmark may request
+const MAX_BENCHMARK_RUNS: u32 = 50;
+/// Most warmup runs a benchmark may request
+const MAX_WARMUP_RUNS: u32 = 10;
+/// Warmup runs when the caller doesn't say
+const DEFAULT_WARMUP_RUNS: u32 = 1;
+
+/// Summary of a benchmark's timed runs, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkStats {
+    pub min_ms: u64,
+    pub median_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: u64,
+    /// Population standard deviation
+    pub stddev_ms: f64,
+}
+
+/// Outcome of running a program repeatedly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub runs: u32,
+    /// Runs discarded before timing started
+    pub warmup: u32,
+    /// Duration of each timed run, in order
+    pub durations_ms: Vec<u64>,
+    pub stats: BenchmarkStats,
+    /// The final run, for its output
+    pub last: ExecutionResult,
+}
+
+/// A language whose image is ready locally
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreparedLanguage {
+    pub language: String,
+    pub image: String,
+    /// False when the image was already present
+    pub pulled: bool,
+}
+
 /// Map language to Docker image
 pub fn get_language_image(language: &str) -> Result<String> {
-    let image = match language.to_lowercase().as_str() {
-        "python" | "py" => "python:3.12-slim",
-        "javascript" | "js" | "node" => "node:20-slim",
-        "typescript" | "ts" => "node:20-slim",
-        "rust" | "rs" => "rust:1.75-slim",
-        "go" | "golang" => "golang:1.21-alpine",
-        "java" => "eclipse-temurin:21-jdk",
-        "c" | "cpp" | "c++" => "gcc:13",
-        "ruby" | "rb" => "ruby:3.3-slim",
-        _ => return Err(ShellError::Execution(format!("Unsupported language: {}", language))),
-    };
-    Ok(image.to_string())
-}
-
-/// Get command to run for a language
-fn get_run_command(language: &str, entry_point: &str) -> Result<Vec<String>> {
-    let cmd = match language.to_lowercase().as_str() {
-        "python" | "py" => vec!["python".to_string(), entry_point.to_string()],
-        "javascript" | "js" | "node" => vec!["node".to_string(), entry_point.to_string()],
-        "typescript" | "ts" => vec!["npx".to_string(), "tsx".to_string(), entry_point.to_string()],
-        "rust" | "rs" => vec!["cargo".to_string(), "run".to_string()],
-        "go" | "golang" => vec!["go".to_string(), "run".to_string(), entry_point.to_string()],
-        "java" => vec!["java".to_string(), entry_point.to_string()],
-        "c" => vec!["sh".to_string(), "-c".to_string(), 
-                    format!("gcc {} -o /tmp/a.out && /tmp/a.out", entry_point)],
-        "cpp" | "c++" => vec!["sh".to_string(), "-c".to_string(), 
-                              format!("g++ {} -o /tmp/a.out && /tmp/a.out", entry_point)],
-        "ruby" | "rb" => vec!["ruby".to_string(), entry_point.to_string()],
-        _ => return Err(ShellError::Execution(format!("Unsupported language: {}", language))),
+    Ok(languages::supported(language)?.image.to_string())
+}
+
+/// Build step for compiled languages, run before `get_run_command`.
+/// Flags must already be validated; they are rejected for languages
+/// without a separate build step.
+pub fn get_compile_command(language: &str, entry_point: &str, flags: &[String]) -> Result<Option<Vec<String>>> {
+    let compiler = match languages::language(language).and_then(|spec| spec.compiler) {
+        Some(compiler) => compiler,
+        None if flags.is_empty() => return Ok(None),
+        None => return Err(ShellError::Execution(format!("Compiler flags are not supported for {}", language))),
     };
+
+    let mut command = vec![compiler.to_string()];
+    command.extend(flags.iter().cloned());
+    command.extend([entry_point.to_string(), "-o".to_string(), COMPILED_BINARY.to_string()]);
+    Ok(Some(command))
+}
+
+/// Get command to run for a language, with arguments for the program
+pub fn get_run_command(language: &str, entry_point: &str, args: &[String]) -> Result<Vec<String>> {
+    let mut cmd = languages::supported(language)?.run_command(entry_point);
+    if !args.is_empty() {
+        if cmd.first().map(String::as_str) == Some("cargo") {
+            cmd.push("--".to_string());
+        }
+        cmd.extend(args.iter().cloned());
+    }
+    Ok(cmd)
+}
+
+/// Test framework command for a language, with arguments for the framework.
+/// The framework's exit code decides pass/fail.
+pub fn get_test_command(language: &str, args: &[String]) -> Result<Vec<String>> {
+    let mut cmd: Vec<String> = languages::language(language)
+        .and_then(|spec| spec.test_command)
+        .ok_or_else(|| ShellError::Execution(format!("Running tests is not supported for {}", language)))?
+        .iter().map(|part| part.to_string()).collect();
+    if !args.is_empty() {
+        if cmd.first().map(String::as_str) == Some("npm") {
+            cmd.push("--".to_string());
+        }
+        cmd.extend(args.iter().cloned());
+    }
     Ok(cmd)
 }
 
+/// Probe `image` for the tool `language` runs with, in an empty workspace
+pub async fn probe_toolchain(language: &str, image: &str, backend: &dyn ExecutionBackend) -> Result<ToolchainStatus> {
+    let spec = languages::supported(language)?;
+    let probe = run_probe(image, spec.toolchain_probe, backend).await?;
+    Ok(toolchain_status(language, image, spec.toolchain, spec.toolchain_guidance, &probe))
+}
+
+/// Probe `image` for the framework `language`'s tests run with; `None` when
+/// the toolchain is all they need
+pub async fn probe_test_framework(language: &str, image: &str, backend: &dyn ExecutionBackend) -> Result<Option<ToolchainStatus>> {
+    let spec = languages::supported(language)?;
+    let Some(framework) = spec.test_framework else {
+        return Ok(None);
+    };
+    let probe = run_probe(image, spec.test_framework_probe, backend).await?;
+    Ok(Some(toolchain_status(language, image, framework, spec.test_framework_guidance, &probe)))
+}
+
+/// Without its framework a test run would fail with the interpreter's error
+/// rather than saying what's missing
+async fn require_test_framework(language: &str, image: &str, backend: &dyn ExecutionBackend) -> Result<()> {
+    match probe_test_framework(language, image, backend).await? {
+        Some(status) if !status.available => Err(ShellError::Execution(format!(
+            "{} is not installed in {}. {}", status.tool, status.image, status.guidance.unwrap_or_default()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Run a probe `command` in `image`, in an empty workspace
+async fn run_probe(image: &str, command: &[&str], backend: &dyn ExecutionBackend) -> Result<ExecutionResult> {
+    let workspace = std::env::temp_dir().join(format!("shell-probe-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&workspace)?;
+    let request = ExecutionRequest {
+        id: uuid::Uuid::new_v4().to_string(),
+        image: image.to_string(),
+        command: command.iter().map(|arg| arg.to_string()).collect(),
+        working_dir: "/workspace".to_string(),
+        source_path: workspace.to_string_lossy().into_owned(),
+        env: HashMap::new(),
+        memory_limit: None,
+        cpu_quota: None,
+        timeout: Some(TOOLCHAIN_PROBE_TIMEOUT_SECS),
+        step_mode: false,
+        trace_io: false,
+        stdin: None,
+        compile_command: None,
+        raw_output: false,
+        workspace_writable: false,
+        scratch_workspace: true,
+    };
+    let result = backend.execute(request).await;
+    let _ = std::fs::remove_dir_all(&workspace);
+    result
+}
+
+/// Read a finished version probe. Some tools (`java -version`) print to stderr.
+fn toolchain_status(language: &str, image: &str, tool: &str, guidance: &str, probe: &ExecutionResult) -> ToolchainStatus {
+    let available = probe.exit_code == 0 && !probe.timed_out;
+    let version = probe.stdout.lines().chain(probe.stderr.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .filter(|_| available)
+        .map(str::to_string);
+    ToolchainStatus {
+        language: language.to_string(),
+        image: image.to_string(),
+        tool: tool.to_string(),
+        available,
+        version,
+        guidance: (!available).then(|| guidance.to_string()),
+    }
+}
+
+/// Every supported language and how Shell runs it
+#[tauri::command]
+pub async fn list_supported_languages() -> Result<Vec<LanguageSpec>> {
+    Ok(LANGUAGES.to_vec())
+}
+
+/// Supported languages for the "new file" picker, marking those whose
+/// images still need pulling
+#[tauri::command]
+pub async fn get_supported_languages(docker: State<'_, DockerManager>) -> Result<Vec<LanguageInfo>> {
+    let info = docker_info(docker).await?;
+    Ok(languages::language_infos(&info.images))
+}
+
+/// Check that a language's image has the tool its runs need
+#[tauri::command]
+pub async fn check_toolchain(language: String, docker: State<'_, DockerManager>) -> Result<ToolchainStatus> {
+    if !docker.is_available().await {
+        return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
+    }
+    let image = get_language_image(&language)?;
+    probe_toolchain(&language, &image, docker.inner()).await
+}
+
+/// Conventional entry files for a language, in order of preference
+pub fn entry_point_candidates(language: &str) -> &'static [&'static str] {
+    languages::language(language).map_or(&[], |spec| spec.entry_points)
+}
+
+/// Find the file to run: the project lesson's `entry_point` if it exists,
+/// otherwise the first conventional entry file present
+pub fn detect_entry_point(project_path: &Path, language: &str) -> Option<String> {
+    let from_lesson = project_lesson(project_path)
+        .and_then(|doc| doc.get("entry_point")?.as_str().map(str::to_string));
+
+    from_lesson.into_iter()
+        .chain(entry_point_candidates(language).iter().map(|c| c.to_string()))
+        .find(|candidate| project_path.join(candidate).is_file())
+}
+
+/// The lesson document at the project root, if any
+pub fn project_lesson(project_path: &Path) -> Option<serde_json::Value> {
+    let path = ["lesson.yaml", "lesson.yml", "lesson.json", "lesson.toml"].iter()
+        .map(|name| project_path.join(name))
+        .find(|path| path.is_file())?;
+    let content = std::fs::read_to_string(&path).ok()?;
+    crate::lessons::parse_document(&path, &content).ok()
+}
+
+/// Flags and arguments from the request, falling back to the project lesson's
+fn resolve_run_options(request: &RunCodeRequest) -> RunOptions {
+    let lesson = project_lesson(Path::new(&request.project_path))
+        .and_then(|doc| serde_json::from_value::<RunOptions>(doc.get("run")?.clone()).ok())
+        .unwrap_or_default();
+
+    RunOptions {
+        compile_flags: request.compile_flags.clone().unwrap_or(lesson.compile_flags),
+        run_args: request.run_args.clone().unwrap_or(lesson.run_args),
+    }
+}
+
+fn resolve_entry_point(request: &RunCodeRequest) -> Result<String> {
+    if let Some(entry_point) = &request.entry_point {
+        return Ok(entry_point.clone());
+    }
+    detect_entry_point(Path::new(&request.project_path), &request.language).ok_or_else(|| {
+        let candidates = entry_point_candidates(&request.language);
+        ShellError::Execution(if candidates.is_empty() {
+            format!("No entry point given and none is known for {}", request.language)
+        } else {
+            format!("No entry point found for {}; expected one of: {}", request.language, candidates.join(", "))
+        })
+    })
+}
+
+/// Default resource limits configured in `settings`
+pub fn resource_limits(settings: &Settings) -> ResourceLimits {
+    ResourceLimits::new(settings.execution_memory_mb, settings.execution_cpu_percent, settings.execution_timeout)
+}
+
+/// Image from `install_dependencies` for the request's project and language
+fn dependency_image(db: &Database, request: &RunCodeRequest) -> Result<Option<String>> {
+    let base_image = get_language_image(&request.language)?;
+    match Path::new(&request.project_path).canonicalize() {
+        Ok(project) => db.get_dependency_image(&project.to_string_lossy(), &base_image),
+        Err(_) => Ok(None),
+    }
+}
+
 /// Run code in a container
 #[tauri::command]
 pub async fn run_code(
-    request: RunCodeRequest,
+    mut request: RunCodeRequest,
+    app: AppHandle,
     docker: State<'_, DockerManager>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    coalescer: State<'_, RunCoalescer>,
 ) -> Result<ExecutionResult> {
     // Ensure Docker is available
     if !docker.is_available().await {
         return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
     }
 
-    let image = get_language_image(&request.language)?;
-    let entry_point = request.entry_point.as_deref().unwrap_or("main");
-    let command = get_run_command(&request.language, entry_point)?;
+    let context = ErrorContext {
+        language: Some(request.language.clone()),
+        file: request.entry_point.clone(),
+        container_id: None,
+    };
+    request.image = dependency_image(&db, &request).map_err(|e| e.with_context(context.clone()))?;
+    request.limits = Some(resource_limits(&load_settings(&db)));
+    let language = request.language.clone();
+    let dedupe_key = request.dedupe_key.clone();
+    let result = coalescer.run(dedupe_key.as_deref(), run_request(request, docker.inner(), &policy)).await
+        .map_err(|e| e.with_context(context))?;
+    record_event(&app, AnalyticsEvent::RunExecuted {
+        language,
+        exit_code: result.exit_code,
+        duration_ms: result.duration_ms,
+        timed_out: result.timed_out,
+    });
+    Ok(result)
+}
+
+async fn run_request(request: RunCodeRequest, backend: &dyn ExecutionBackend, policy: &SecurityPolicy) -> Result<ExecutionResult> {
+    let run_mode = request.run_mode;
+    let language = request.language.clone();
+    let (exec_request, entry_point) = prepare_run(request, policy)?;
+    if run_mode == RunMode::Tests {
+        require_test_framework(&language, &exec_request.image, backend).await?;
+    }
+    let mut result = backend.execute(exec_request).await
+        .map_err(|e| e.with_context(ErrorContext { file: entry_point, ..Default::default() }))?;
+    if run_mode == RunMode::Tests {
+        result.tests_passed = Some(result.exit_code == 0 && !result.timed_out);
+    }
+    Ok(result)
+}
 
+/// Container request for a run, with the entry point it resolved
+fn prepare_run(request: RunCodeRequest, policy: &SecurityPolicy) -> Result<(ExecutionRequest, Option<String>)> {
+    let id = match &request.execution_id {
+        Some(id) => uuid::Uuid::parse_str(id)
+            .map_err(|_| ShellError::Execution(format!("Execution id '{}' is not a UUID", id)))?
+            .to_string(),
+        None => uuid::Uuid::new_v4().to_string(),
+    };
+    let image = match &request.image {
+        Some(image) => image.clone(),
+        None => get_language_image(&request.language)?,
+    };
+    let options = resolve_run_options(&request);
+    if let Some(e) = policy_errors(&request, Some(&image), &options, policy).into_iter().next() {
+        return Err(e);
+    }
+    let (entry_point, compile_command, command) = match request.run_mode {
+        RunMode::Main => {
+            let entry_point = resolve_entry_point(&request)?;
+            let compile_command = get_compile_command(&request.language, &entry_point, &options.compile_flags)?;
+            let command = get_run_command(&request.language, &entry_point, &options.run_args)?;
+            (Some(entry_point), compile_command, command)
+        }
+        RunMode::Tests => {
+            if !options.compile_flags.is_empty() {
+                return Err(ShellError::Execution("Compiler flags are not supported when running tests".into()));
+            }
+            (None, None, get_test_command(&request.language, &options.run_args)?)
+        }
+    };
+    let mut env = policy.sanitize_env(request.env.unwrap_or_default())?;
+    if request.run_mode == RunMode::Tests {
+        // `cargo test` builds into the project, which is mounted read-only
+        env.insert("CARGO_TARGET_DIR".to_string(), "/tmp/target".to_string());
+    }
     let exec_request = ExecutionRequest {
-        id: uuid::Uuid::new_v4().to_string(),
+        id,
         image,
         command,
         working_dir: "/workspace".to_string(),
         source_path: request.project_path,
-        env: request.env.unwrap_or_default(),
-        memory_limit: None,
-        cpu_quota: None,
-        timeout: request.timeout,
+        env,
+        memory_limit: request.limits.map(|l| l.memory_limit),
+        cpu_quota: request.limits.map(|l| l.cpu_quota),
+        timeout: request.timeout.map(ResourceLimits::clamp_timeout).or(request.limits.map(|l| l.timeout_seconds)),
         step_mode: request.step_mode.unwrap_or(false),
         trace_io: request.trace_io.unwrap_or(true),
+        stdin: request.stdin,
+        compile_command,
+        raw_output: request.raw_output.unwrap_or(false),
+        workspace_writable: request.workspace_writable.unwrap_or(false),
+        scratch_workspace: false,
     };
+    Ok((exec_request, entry_point))
+}
+
+/// Policy checks shared by `prepare_run` and the dry run, so the dry run
+/// can't pass a request the run rejects. The project is mounted into the
+/// container, so it must be one the policy allows (only the exam project
+/// during an exam), and never the app's own data.
+fn policy_errors(request: &RunCodeRequest, image: Option<&str>, options: &RunOptions, policy: &SecurityPolicy) -> Vec<ShellError> {
+    [
+        policy.validate_mount(Path::new(&request.project_path)),
+        image.map_or(Ok(()), |image| policy.validate_image(image)),
+        policy.validate_compile_flags(&options.compile_flags),
+        policy.sanitize_env(request.env.clone().unwrap_or_default()).map(drop),
+    ].into_iter().filter_map(Result::err).collect()
+}
+
+/// Check that a run request could run, without running the program: the
+/// language is supported, its options are allowed, the entry point exists,
+/// Docker is up and the image has the language's toolchain. For lesson
+/// authoring preflights.
+#[tauri::command]
+pub async fn validate_run_request(
+    mut request: RunCodeRequest,
+    docker: State<'_, DockerManager>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<RunValidation> {
+    // An unsupported language is reported as a problem, not an error
+    request.image = dependency_image(&db, &request).unwrap_or(None);
+    let docker_available = docker.is_available().await;
+    let mut validation = check_run_request(&request, &policy, docker_available);
+
+    // Only worth a container once the request is otherwise runnable
+    if let (true, Some(image)) = (validation.valid, &validation.image) {
+        match probe_toolchain(&request.language, image, docker.inner()).await {
+            Ok(status) if !status.available => {
+                validation.problems.push(RunProblem::MissingToolchain {
+                    tool: status.tool,
+                    guidance: status.guidance.unwrap_or_default(),
+                });
+                validation.valid = false;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Toolchain probe for {} failed: {}", request.language, e),
+        }
+    }
+    if let (true, Some(image), RunMode::Tests) = (validation.valid, &validation.image, request.run_mode) {
+        match probe_test_framework(&request.language, image, docker.inner()).await {
+            Ok(Some(status)) if !status.available => {
+                validation.problems.push(RunProblem::MissingToolchain {
+                    tool: status.tool,
+                    guidance: status.guidance.unwrap_or_default(),
+                });
+                validation.valid = false;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Test framework probe for {} failed: {}", request.language, e),
+        }
+    }
+    Ok(validation)
+}
+
+/// Every problem `prepare_run` and Docker would hit for `request`
+fn check_run_request(request: &RunCodeRequest, policy: &SecurityPolicy, docker_available: bool) -> RunValidation {
+    let mut problems = Vec::new();
+    let (mut entry_point, mut command) = (None, None);
+
+    let image = request.image.clone().or_else(|| get_language_image(&request.language).ok());
+    if image.is_none() {
+        problems.push(RunProblem::UnsupportedLanguage { language: request.language.clone() });
+    }
+
+    let options = resolve_run_options(request);
+    for e in policy_errors(request, image.as_deref(), &options, policy) {
+        problems.push(RunProblem::Denied { message: e.to_string() });
+    }
 
-    docker.run(exec_request).await
+    if image.is_some() {
+        let project_path = Path::new(&request.project_path);
+        match request.run_mode {
+            RunMode::Main => {
+                let found = match &request.entry_point {
+                    Some(given) => Some(given.clone()).filter(|given| project_path.join(given).is_file()),
+                    None => detect_entry_point(project_path, &request.language),
+                };
+                match found {
+                    Some(found) => {
+                        let built = get_compile_command(&request.language, &found, &options.compile_flags)
+                            .and_then(|_| get_run_command(&request.language, &found, &options.run_args));
+                        match built {
+                            Ok(built) => command = Some(built),
+                            Err(e) => problems.push(RunProblem::InvalidOptions { message: e.to_string() }),
+                        }
+                        entry_point = Some(found);
+                    }
+                    None => problems.push(RunProblem::MissingEntryPoint {
+                        entry_point: request.entry_point.clone(),
+                        candidates: entry_point_candidates(&request.language).iter().map(|c| c.to_string()).collect(),
+                    }),
+                }
+            }
+            RunMode::Tests if !options.compile_flags.is_empty() => problems.push(RunProblem::InvalidOptions {
+                message: "Compiler flags are not supported when running tests".into(),
+            }),
+            RunMode::Tests => match get_test_command(&request.language, &options.run_args) {
+                Ok(built) => command = Some(built),
+                Err(e) => problems.push(RunProblem::InvalidOptions { message: e.to_string() }),
+            },
+        }
+    }
+
+    if !docker_available {
+        problems.push(RunProblem::DockerUnavailable);
+    }
+    RunValidation { valid: problems.is_empty(), image, entry_point, command, problems }
+}
+
+/// Run code under its language's coverage tool and report which lines ran
+#[tauri::command]
+pub async fn run_with_coverage(
+    mut request: RunCodeRequest,
+    docker: State<'_, DockerManager>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<CoverageReport> {
+    if !docker.is_available().await {
+        return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
+    }
+    request.image = dependency_image(&db, &request)?;
+    request.limits = Some(resource_limits(&load_settings(&db)));
+    coverage_request(request, docker.inner(), &policy).await
+}
+
+async fn coverage_request(request: RunCodeRequest, backend: &dyn ExecutionBackend, policy: &SecurityPolicy) -> Result<CoverageReport> {
+    let language = request.language.clone();
+    let project_path = request.project_path.clone();
+    let run_mode = request.run_mode;
+    let (mut exec_request, entry_point) = prepare_run(request, policy)?;
+    if run_mode == RunMode::Tests {
+        require_test_framework(&language, &exec_request.image, backend).await?;
+    }
+    let marker = format!("__shell_coverage_{}__", exec_request.id.replace('-', ""));
+    exec_request.command = crate::coverage::coverage_command(&language, &exec_request.command, &marker)?;
+    // The trace would carry the report as program output
+    exec_request.trace_io = false;
+
+    let mut result = backend.execute(exec_request).await
+        .map_err(|e| e.with_context(ErrorContext { file: entry_point, ..Default::default() }))?;
+    if run_mode == RunMode::Tests {
+        result.tests_passed = Some(result.exit_code == 0 && !result.timed_out);
+    }
+    crate::coverage::report(&language, Path::new(&project_path), result, &marker)
+}
+
+/// Min, median, mean, max and standard deviation of `durations`
+pub fn benchmark_stats(durations: &[u64]) -> Option<BenchmarkStats> {
+    let mut sorted = durations.to_vec();
+    sorted.sort_unstable();
+    let (&min_ms, &max_ms) = (sorted.first()?, sorted.last()?);
+    let n = sorted.len();
+    let median_ms = if n % 2 == 0 {
+        (sorted[n / 2 - 1] + sorted[n / 2]) as f64 / 2.0
+    } else {
+        sorted[n / 2] as f64
+    };
+    let mean_ms = sorted.iter().sum::<u64>() as f64 / n as f64;
+    let variance = sorted.iter().map(|&d| (d as f64 - mean_ms).powi(2)).sum::<f64>() / n as f64;
+    Some(BenchmarkStats { min_ms, median_ms, mean_ms, max_ms, stddev_ms: variance.sqrt() })
+}
+
+/// Run a program `runs` times after `warmup` discarded runs and report
+/// timing statistics. There's no compile cache, so compiled languages
+/// rebuild on every run and their timings include the compile.
+#[tauri::command]
+pub async fn benchmark_code(
+    mut request: RunCodeRequest,
+    runs: u32,
+    warmup: Option<u32>,
+    docker: State<'_, DockerManager>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<BenchmarkResult> {
+    if !docker.is_available().await {
+        return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
+    }
+    request.image = dependency_image(&db, &request)?;
+    request.limits = Some(resource_limits(&load_settings(&db)));
+    benchmark_request(request, runs, warmup.unwrap_or(DEFAULT_WARMUP_RUNS), docker.inner(), &policy).await
+}
+
+async fn benchmark_request(
+    request: RunCodeRequest,
+    runs: u32,
+    warmup: u32,
+    backend: &dyn ExecutionBackend,
+    policy: &SecurityPolicy,
+) -> Result<BenchmarkResult> {
+    if runs == 0 || runs > MAX_BENCHMARK_RUNS {
+        return Err(ShellError::Execution(format!("Benchmark runs must be between 1 and {}", MAX_BENCHMARK_RUNS)));
+    }
+    if warmup > MAX_WARMUP_RUNS {
+        return Err(ShellError::Execution(format!("At most {} warmup runs are allowed", MAX_WARMUP_RUNS)));
+    }
+
+    let total = warmup + runs;
+    let mut durations_ms = Vec::with_capacity(runs as usize);
+    let mut last = None;
+    for iteration in 1..=total {
+        // Each run gets its own lifecycle id
+        let request = RunCodeRequest { execution_id: None, ..request.clone() };
+        let failed = |reason: String| {
+            ShellError::Execution(format!("Benchmark run {} of {} failed: {}", iteration, total, reason))
+        };
+        let result = run_request(request, backend, policy).await.map_err(|e| failed(e.to_string()))?;
+        if result.timed_out {
+            return Err(failed("timed out".into()));
+        }
+        if result.exit_code != 0 {
+            return Err(failed(format!("exit code {}", result.exit_code)));
+        }
+        if iteration > warmup {
+            durations_ms.push(result.duration_ms);
+        }
+        last = Some(result);
+    }
+
+    let stats = benchmark_stats(&durations_ms).expect("at least one timed run");
+    Ok(BenchmarkResult { runs, warmup, durations_ms, stats, last: last.expect("at least one run") })
+}
+
+/// Event carrying pull progress of `image`, e.g. `image://python:3_12-slim/progress`.
+/// Characters event names can't contain become `_`.
+pub fn pull_event_name(image: &str) -> String {
+    let image: String = image.chars()
+        .map(|c| if c.is_alphanumeric() || "-/:_".contains(c) { c } else { '_' })
+        .collect();
+    format!("image://{}/progress", image)
+}
+
+async fn prepare(app: &AppHandle, docker: &DockerManager, language: &str) -> Result<PreparedLanguage> {
+    let image = get_language_image(language)?;
+    let event = pull_event_name(&image);
+    let pulled = docker.pull_image(&image, |progress| {
+        let _ = app.emit(&event, progress);
+    }).await?;
+    Ok(PreparedLanguage { language: language.to_string(), image, pulled })
+}
+
+/// Pull a language's image ahead of its first run, emitting progress events
+#[tauri::command]
+pub async fn prepare_language(
+    language: String,
+    app: AppHandle,
+    docker: State<'_, DockerManager>,
+) -> Result<PreparedLanguage> {
+    if !docker.is_available().await {
+        return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
+    }
+    prepare(&app, &docker, &language).await
+}
+
+/// Pull the images of several languages, one after another, for a setup flow
+#[tauri::command]
+pub async fn prepare_languages(
+    languages: Option<Vec<String>>,
+    app: AppHandle,
+    docker: State<'_, DockerManager>,
+) -> Result<Vec<PreparedLanguage>> {
+    if !docker.is_available().await {
+        return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
+    }
+    let languages = languages.unwrap_or_else(|| LANGUAGES.iter().map(|spec| spec.id.to_string()).collect());
+    let mut prepared = Vec::with_capacity(languages.len());
+    for language in &languages {
+        prepared.push(prepare(&app, &docker, language).await?);
+    }
+    Ok(prepared)
 }
 
 /// Stop a running execution
@@ -112,6 +778,16 @@ pub async fn stop_execution(
     docker.stop(&execution_id).await
 }
 
+/// Output a running or just-finished execution has printed so far, so a
+/// reconnecting UI can catch up before following the live stream
+#[tauri::command]
+pub async fn get_execution_output(
+    execution_id: String,
+    docker: State<'_, DockerManager>,
+) -> Result<ExecutionOutput> {
+    docker.execution_output(&execution_id)
+}
+
 /// Get current execution status
 #[tauri::command]
 pub async fn get_execution_status(
@@ -123,3 +799,445 @@ pub async fn get_execution_status(
         containers,
     })
 }
+
+/// What Docker offers and which language images are pulled. Never fails:
+/// when Docker is missing the result says why.
+#[tauri::command]
+pub async fn docker_info(docker: State<'_, DockerManager>) -> Result<DockerInfo> {
+    let languages: Vec<(String, String)> = LANGUAGES.iter()
+        .map(|spec| (spec.id.to_string(), spec.image.to_string()))
+        .collect();
+    Ok(docker.info(&languages).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::ExecutionPhase;
+
+    fn request(project_path: &str, stdin: &str) -> RunCodeRequest {
+        RunCodeRequest {
+            execution_id: None,
+            language: "python".into(),
+            code: String::new(),
+            project_path: project_path.into(),
+            entry_point: Some("main.py".into()),
+            run_mode: RunMode::Main,
+            compile_flags: None,
+            run_args: None,
+            stdin: Some(stdin.into()),
+            env: None,
+            step_mode: None,
+            trace_io: Some(false),
+            timeout: Some(30),
+            raw_output: None,
+            workspace_writable: None,
+            dedupe_key: None,
+            image: None,
+            limits: None,
+        }
+    }
+
+    /// Test projects live under /tmp and the crate, outside the default roots
+    fn test_policy() -> SecurityPolicy {
+        let mut policy = SecurityPolicy::default();
+        policy.allowed_paths = vec!["/".into()];
+        policy
+    }
+
+    #[tokio::test]
+    async fn test_project_outside_allowed_roots_is_rejected() {
+        let mut policy = SecurityPolicy::default();
+        policy.allowed_paths = vec![fixture("python")];
+        assert!(prepare_run(request(&fixture("python").to_string_lossy(), ""), &policy).is_ok());
+
+        let err = run_request(request(&fixture("go").to_string_lossy(), ""), &EchoBackend, &policy).await.unwrap_err();
+        assert!(err.to_string().starts_with("Security error: Access denied"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_app_data_as_project_is_denied() {
+        let app_data = std::env::temp_dir().join(format!("shell-app-data-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&app_data).unwrap();
+        std::fs::write(app_data.join("shell.db"), "").unwrap();
+        std::fs::write(app_data.join("main.py"), "").unwrap();
+
+        let err = run_request(request(&app_data.to_string_lossy(), ""), &EchoBackend, &test_policy()).await.unwrap_err();
+        assert!(matches!(err, ShellError::Security(_)), "{}", err);
+
+        // Protected by the policy even before the database is created
+        std::fs::remove_file(app_data.join("shell.db")).unwrap();
+        let mut policy = test_policy();
+        policy.protected_paths = vec![app_data.clone()];
+        let err = run_request(request(&app_data.to_string_lossy(), ""), &EchoBackend, &policy).await.unwrap_err();
+        assert!(matches!(err, ShellError::Security(_)), "{}", err);
+        std::fs::remove_dir_all(&app_data).unwrap();
+    }
+
+    /// Echoes stdin back the way `print("Hello, " + input())` would
+    struct EchoBackend;
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for EchoBackend {
+        async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+            Ok(ExecutionResult {
+                id: request.id,
+                exit_code: 0,
+                stdout: format!("Hello, {}\n", request.stdin.unwrap_or_default().trim_end()),
+                stderr: String::new(),
+                duration_ms: 1,
+                timed_out: false,
+                trace: None,
+                phase: ExecutionPhase::Run,
+                compile_output: None,
+                tests_passed: None,
+                stdout_base64: None,
+                stderr_base64: None,
+                log_collection_timed_out: false,
+                output_truncated: false,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stdin_reaches_backend() {
+        let result = run_request(request("/tmp", "Ada\n"), &EchoBackend, &test_policy()).await.unwrap();
+        assert_eq!(result.stdout, "Hello, Ada\n");
+    }
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/projects").join(name)
+    }
+
+    #[test]
+    fn test_resource_settings_shape_host_config() {
+        let settings = Settings { execution_memory_mb: 512, execution_cpu_percent: 150, execution_timeout: 90, ..Settings::default() };
+        let limits = Some(resource_limits(&settings));
+        let (exec, _) = prepare_run(RunCodeRequest { timeout: None, limits, ..request("/tmp", "") }, &test_policy()).unwrap();
+        let host = DockerManager::host_config(exec.memory_limit, exec.cpu_quota);
+        assert_eq!(host.memory, Some(512 * 1024 * 1024));
+        assert_eq!(host.cpu_quota, Some(150_000));
+        assert_eq!(exec.timeout, Some(90));
+
+        // Defaults match the built-in limits; extremes are clamped
+        assert_eq!(resource_limits(&Settings::default()), ResourceLimits::default());
+        let extreme = Settings { execution_memory_mb: 1, execution_cpu_percent: 10_000, execution_timeout: 0, ..Settings::default() };
+        assert_eq!(resource_limits(&extreme), ResourceLimits { memory_limit: 64 * 1024 * 1024, cpu_quota: 400_000, timeout_seconds: 1 });
+
+        // A timeout on the request itself wins
+        let (exec, _) = prepare_run(RunCodeRequest { timeout: Some(5), limits, ..request("/tmp", "") }, &test_policy()).unwrap();
+        assert_eq!(exec.timeout, Some(5));
+    }
+
+    #[test]
+    fn test_registry_reproduces_language_mappings() {
+        let cases: &[(&str, &str, &[&str])] = &[
+            ("python", "python:3.12-slim", &["python", "main"]),
+            ("py", "python:3.12-slim", &["python", "main"]),
+            ("node", "node:20-slim", &["node", "main"]),
+            ("TS", "node:20-slim", &["npx", "tsx", "main"]),
+            ("rs", "rust:1.75-slim", &["cargo", "run"]),
+            ("golang", "golang:1.21-alpine", &["go", "run", "main"]),
+            ("java", "eclipse-temurin:21-jdk", &["java", "main"]),
+            ("c", "gcc:13", &["/tmp/a.out"]),
+            ("c++", "gcc:13", &["/tmp/a.out"]),
+            ("rb", "ruby:3.3-slim", &["ruby", "main"]),
+        ];
+        for (language, image, command) in cases {
+            assert_eq!(get_language_image(language).unwrap(), *image, "{}", language);
+            assert_eq!(get_run_command(language, "main", &[]).unwrap(), *command, "{}", language);
+        }
+        assert!(get_language_image("cobol").is_err());
+        assert_eq!(get_test_command("js", &[]).unwrap(), vec!["npm", "test"]);
+        assert_eq!(get_test_command("go", &[]).unwrap(), vec!["go", "test", "./..."]);
+        assert!(get_test_command("java", &[]).is_err());
+        assert_eq!(entry_point_candidates("Python"), ["main.py", "app.py", "__main__.py"]);
+        assert!(entry_point_candidates("cobol").is_empty());
+
+        // Formatter names match the formatters that actually run
+        for spec in LANGUAGES {
+            let formatter = crate::format::formatter_for(spec.id).ok().map(|f| f.name);
+            assert_eq!(formatter.as_deref(), spec.formatter, "{}", spec.id);
+        }
+        assert_eq!(pull_event_name("python:3.12-slim"), "image://python:3_12-slim/progress");
+    }
+
+    #[test]
+    fn test_detect_entry_point_per_language() {
+        let cases = [
+            ("python", "python", "app.py"),
+            ("javascript", "javascript", "index.js"),
+            ("rust", "rust", "src/main.rs"),
+            ("go", "go", "main.go"),
+            ("java", "java", "Main.java"),
+            ("lesson", "python", "solution.py"),
+        ];
+        for (dir, language, expected) in cases {
+            assert_eq!(detect_entry_point(&fixture(dir), language).as_deref(), Some(expected), "{}", dir);
+        }
+        assert_eq!(detect_entry_point(&fixture("go"), "python"), None);
+    }
+
+    #[tokio::test]
+    async fn test_missing_entry_point_lists_candidates() {
+        let mut request = request(&fixture("go").to_string_lossy(), "");
+        request.entry_point = None;
+
+        let err = run_request(request, &EchoBackend, &test_policy()).await.unwrap_err();
+        assert_eq!(err.to_string(), "Execution error: No entry point found for python; expected one of: main.py, app.py, __main__.py");
+    }
+
+    #[test]
+    fn test_toolchain_status_from_probe() {
+        let probe = |exit_code: i64, stdout: &str, stderr: &str| ExecutionResult {
+            id: String::new(),
+            exit_code,
+            stdout: stdout.into(),
+            stderr: stderr.into(),
+            duration_ms: 1,
+            timed_out: false,
+            trace: None,
+            phase: ExecutionPhase::Run,
+            compile_output: None,
+            tests_passed: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            log_collection_timed_out: false,
+            output_truncated: false,
+        };
+        let status = |language: &str, probe: &ExecutionResult| {
+            let spec = languages::supported(language).unwrap();
+            toolchain_status(language, "image:tag", spec.toolchain, spec.toolchain_guidance, probe)
+        };
+
+        let rust = status("rust", &probe(0, "cargo 1.75.0 (1d8b05cdd 2023-11-20)\n", ""));
+        assert!(rust.available);
+        assert_eq!(rust.version.as_deref(), Some("cargo 1.75.0 (1d8b05cdd 2023-11-20)"));
+        assert_eq!(rust.guidance, None);
+
+        let java = status("java", &probe(0, "", "openjdk version \"21.0.2\" 2024-01-16\nOpenJDK Runtime\n"));
+        assert_eq!(java.version.as_deref(), Some("openjdk version \"21.0.2\" 2024-01-16"));
+
+        let typescript = status("typescript", &probe(1, "", "npm ERR! canceled\n"));
+        assert!(!typescript.available);
+        assert_eq!(typescript.tool, "tsx");
+        assert_eq!(typescript.version, None);
+        assert!(typescript.guidance.unwrap().contains("npm install -g tsx"));
+    }
+
+    #[test]
+    fn test_dry_run_reports_problems_without_running() {
+        let policy = test_policy();
+        let mut valid = request(&fixture("python").to_string_lossy(), "");
+        valid.entry_point = None;
+        let validation = check_run_request(&valid, &policy, true);
+        assert!(validation.valid, "{:?}", validation.problems);
+        assert_eq!(validation.image.as_deref(), Some("python:3.12-slim"));
+        assert_eq!(validation.entry_point.as_deref(), Some("app.py"));
+        assert_eq!(validation.command, Some(vec!["python".to_string(), "app.py".to_string()]));
+
+        let unsupported = RunCodeRequest { language: "cobol".into(), ..valid.clone() };
+        let validation = check_run_request(&unsupported, &policy, false);
+        assert!(!validation.valid);
+        assert_eq!(validation.problems, vec![
+            RunProblem::UnsupportedLanguage { language: "cobol".into() },
+            RunProblem::DockerUnavailable,
+        ]);
+
+        let missing = RunCodeRequest { entry_point: Some("solution.py".into()), ..valid };
+        let validation = check_run_request(&missing, &policy, true);
+        assert_eq!(validation.problems, vec![RunProblem::MissingEntryPoint {
+            entry_point: Some("solution.py".into()),
+            candidates: vec!["main.py".into(), "app.py".into(), "__main__.py".into()],
+        }]);
+        assert_eq!(validation.command, None);
+    }
+
+    #[test]
+    fn test_dry_run_applies_run_policy() {
+        let project = fixture("python");
+        let mut policy = test_policy();
+        policy.protected_paths = vec![project.clone()];
+        let mut request = request(&project.to_string_lossy(), "");
+        request.env = Some(HashMap::from([("NOT-A-NAME".to_string(), "1".to_string())]));
+
+        // Both problems are reported, as `prepare_run` would fail on either
+        let validation = check_run_request(&request, &policy, true);
+        assert!(!validation.valid);
+        let denied: Vec<_> = validation.problems.iter()
+            .filter(|problem| matches!(problem, RunProblem::Denied { .. }))
+            .collect();
+        assert_eq!(denied.len(), 2, "{:?}", validation.problems);
+        assert!(prepare_run(request, &policy).is_err());
+    }
+
+    #[test]
+    fn test_flags_and_args_are_injected() {
+        let flags = vec!["-std=c++20".to_string(), "-O2".to_string()];
+        assert_eq!(
+            get_compile_command("cpp", "main.cpp", &flags).unwrap().unwrap(),
+            vec!["g++", "-std=c++20", "-O2", "main.cpp", "-o", "/tmp/a.out"],
+        );
+
+        let args = vec!["--verbose".to_string()];
+        assert_eq!(get_run_command("cpp", "main.cpp", &args).unwrap(), vec!["/tmp/a.out", "--verbose"]);
+        assert_eq!(get_run_command("rust", "src/main.rs", &args).unwrap(), vec!["cargo", "run", "--", "--verbose"]);
+        assert!(get_compile_command("python", "main.py", &flags).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_denied_compile_flag_is_rejected() {
+        let mut request = request("/tmp", "");
+        request.language = "c".into();
+        request.entry_point = Some("main.c".into());
+        request.compile_flags = Some(vec!["-o".into(), "/workspace/evil".into()]);
+
+        let err = run_request(request, &EchoBackend, &test_policy()).await.unwrap_err();
+        assert_eq!(err.to_string(), "Security error: Compiler flag '-o' is not allowed");
+    }
+
+    /// Fails when the test command mentions `failing`, like a red test suite
+    struct TestRunner;
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for TestRunner {
+        async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+            let command = request.command.join(" ");
+            let exit_code = if command.contains("failing") { 1 } else { 0 };
+            Ok(ExecutionResult { exit_code, stdout: command, ..EchoBackend.execute(request).await? })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tests_mode_uses_framework_exit_code() {
+        let cases = [
+            ("python", vec![], "python -m pytest", true),
+            ("python", vec!["-k".to_string(), "failing".to_string()], "python -m pytest -k failing", false),
+            ("rust", vec![], "cargo test", true),
+            ("rust", vec!["failing".to_string()], "cargo test failing", false),
+        ];
+        for (language, args, command, passed) in cases {
+            let mut request = request("/tmp", "");
+            request.language = language.into();
+            request.entry_point = None;
+            request.run_mode = RunMode::Tests;
+            request.run_args = Some(args);
+
+            let result = run_request(request, &TestRunner, &test_policy()).await.unwrap();
+            assert_eq!(result.stdout, command);
+            assert_eq!(result.tests_passed, Some(passed), "{}", command);
+        }
+    }
+
+    /// An image with Python but not pytest
+    struct NoPytest;
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for NoPytest {
+        async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+            if request.command.join(" ") == "python -m pytest --version" {
+                let stderr = "/usr/local/bin/python: No module named pytest\n".to_string();
+                return Ok(ExecutionResult { exit_code: 1, stderr, ..EchoBackend.execute(request).await? });
+            }
+            EchoBackend.execute(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_test_framework_is_reported() {
+        let mut request = request("/tmp", "");
+        request.entry_point = None;
+        request.run_mode = RunMode::Tests;
+
+        let err = run_request(request.clone(), &NoPytest, &test_policy()).await.unwrap_err();
+        assert!(err.to_string().contains("pytest is not installed in python:3.12-slim"), "{}", err);
+        // Main runs don't need the framework
+        request.run_mode = RunMode::Main;
+        request.entry_point = Some("main.py".into());
+        assert!(run_request(request, &NoPytest, &test_policy()).await.is_ok());
+    }
+
+    /// Reports the environment the container would get
+    struct EnvBackend;
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for EnvBackend {
+        async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+            let mut keys: Vec<_> = request.env.keys().cloned().collect();
+            keys.sort();
+            Ok(ExecutionResult { stdout: keys.join(","), ..EchoBackend.execute(request).await? })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_denied_env_does_not_reach_container() {
+        let mut request = request("/tmp", "");
+        request.env = Some([("LD_PRELOAD", "/tmp/hook.so"), ("PYTHONSTARTUP", "hook.py"), ("PATH", "/tmp"), ("GREETING", "hi")]
+            .into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+
+        let result = run_request(request, &EnvBackend, &test_policy()).await.unwrap();
+        assert_eq!(result.stdout, "GREETING");
+    }
+
+    #[tokio::test]
+    async fn test_cargo_tests_build_outside_the_project() {
+        let mut request = request("/tmp", "");
+        request.language = "rust".into();
+        request.entry_point = None;
+        request.run_mode = RunMode::Tests;
+
+        let result = run_request(request, &EnvBackend, &test_policy()).await.unwrap();
+        assert_eq!(result.stdout, "CARGO_TARGET_DIR");
+    }
+
+    /// Replays canned durations, failing with exit code 1 when they run out
+    struct TimedBackend(std::sync::Mutex<Vec<u64>>);
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for TimedBackend {
+        async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+            let duration_ms = self.0.lock().unwrap().pop();
+            Ok(ExecutionResult {
+                exit_code: if duration_ms.is_some() { 0 } else { 1 },
+                duration_ms: duration_ms.unwrap_or(0),
+                ..EchoBackend.execute(request).await?
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_stats_skip_warmup() {
+        // Popped from the end: the 900ms warmup comes first
+        let backend = TimedBackend(std::sync::Mutex::new(vec![9, 2, 4, 4, 4, 5, 5, 7, 900]));
+        let result = benchmark_request(request("/tmp", ""), 8, 1, &backend, &test_policy()).await.unwrap();
+
+        assert_eq!(result.durations_ms, vec![7, 5, 5, 4, 4, 4, 2, 9]);
+        assert_eq!(result.stats, BenchmarkStats { min_ms: 2, median_ms: 4.5, mean_ms: 5.0, max_ms: 9, stddev_ms: 2.0 });
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_reports_failed_iteration() {
+        let policy = test_policy();
+        let backend = TimedBackend(std::sync::Mutex::new(vec![3, 3]));
+        let err = benchmark_request(request("/tmp", ""), 4, 0, &backend, &policy).await.unwrap_err();
+        assert_eq!(err.to_string(), "Execution error: Benchmark run 3 of 4 failed: exit code 1");
+
+        assert!(benchmark_request(request("/tmp", ""), 0, 0, &backend, &policy).await.is_err());
+        assert!(benchmark_request(request("/tmp", ""), MAX_BENCHMARK_RUNS + 1, 0, &backend, &policy).await.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a running Docker daemon"]
+    async fn test_python_input_in_docker() {
+        let dir = std::env::temp_dir().join(format!("shell-stdin-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.py"), "name = input()\nprint('Hello, ' + name)\n").unwrap();
+
+        let docker = DockerManager::new();
+        assert!(docker.is_available().await);
+        let result = run_request(request(&dir.to_string_lossy(), "Ada\n"), &docker, &test_policy()).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result.unwrap().stdout.trim(), "Hello, Ada");
+    }
+}
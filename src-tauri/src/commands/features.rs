@@ -1,9 +1,27 @@
 //! Feature flags IPC commands
 
-use tauri::State;
+use chrono::Utc;
+use tauri::{AppHandle, Manager, State};
+use std::path::{Path, PathBuf};
 use std::sync::RwLock;
-use crate::features::FeatureFlags;
-use crate::error::Result;
+use crate::commands::settings::load_settings;
+use crate::db::Database;
+use crate::features::{FeatureFlags, License};
+use crate::error::{Result, ShellError};
+use crate::seats::{self, SeatLedger};
+
+/// Held across each license change that awaits the network or the seat
+/// ledger. Changes read the flags, await, then store what they read, so
+/// without it a concurrent change (such as the hourly seat refresh) could be
+/// overwritten with a stale snapshot.
+#[derive(Default)]
+pub struct LicenseLock(tokio::sync::Mutex<()>);
+
+impl LicenseLock {
+    pub async fn lock(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.0.lock().await
+    }
+}
 
 /// Get current feature flags
 #[tauri::command]
@@ -24,3 +42,223 @@ pub async fn is_teacher_mode(
         .map_err(|_| crate::error::ShellError::Security("Failed to read feature flags".into()))?;
     Ok(flags.teacher_mode)
 }
+
+/// Verify a signed license, register a seat for it, unlock its features
+/// and persist them
+#[tauri::command]
+pub async fn activate_license(
+    license_blob: String,
+    app: AppHandle,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+    license_lock: State<'_, LicenseLock>,
+) -> Result<FeatureFlags> {
+    let _guard = license_lock.lock().await;
+    let endpoint = load_settings(&db).cloud_grading_url;
+    activate(&license_blob, &app_data_dir(&app)?, endpoint.as_deref(), &features).await
+}
+
+/// Return to student mode, giving up this install's seat
+#[tauri::command]
+pub async fn deactivate_license(
+    app: AppHandle,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+    license_lock: State<'_, LicenseLock>,
+) -> Result<FeatureFlags> {
+    let _guard = license_lock.lock().await;
+    let endpoint = load_settings(&db).cloud_grading_url;
+    deactivate(&app_data_dir(&app)?, endpoint.as_deref(), &features).await
+}
+
+async fn activate(
+    license_blob: &str,
+    app_data: &Path,
+    endpoint: Option<&str>,
+    features: &RwLock<FeatureFlags>,
+) -> Result<FeatureFlags> {
+    let license = License::from_blob(license_blob)?;
+
+    // Start from student defaults so a smaller license can't inherit flags
+    let mut updated = read_flags(features)?;
+    updated.deactivate();
+    updated.verify_and_activate(license.clone())?;
+
+    let mut ledger = SeatLedger::load(app_data);
+    seats::claim(&mut ledger, &license, endpoint, Utc::now()).await?;
+    ledger.save(app_data)?;
+
+    updated.save(app_data)?;
+    store_flags(features, updated)
+}
+
+async fn deactivate(app_data: &Path, endpoint: Option<&str>, features: &RwLock<FeatureFlags>) -> Result<FeatureFlags> {
+    let mut flags = read_flags(features)?;
+
+    if let Some(license) = flags.license.as_ref().filter(|l| l.seats.is_some()) {
+        let mut ledger = SeatLedger::load(app_data);
+        let install_id = ledger.install_id.clone();
+        if let Err(e) = seats::release(&mut ledger, license, &install_id, endpoint).await {
+            // The seat is freed on the server once its claim goes stale
+            tracing::warn!("Could not release license seat: {}", e);
+            ledger.release(&license.id(), &install_id);
+        }
+        ledger.save(app_data)?;
+    }
+
+    flags.deactivate();
+    flags.save(app_data)?;
+    store_flags(features, flags)
+}
+
+/// Release a license seat so another install can take it.
+/// Defaults to this install, which then reverts to student features.
+#[tauri::command]
+pub async fn release_seat(
+    install_id: Option<String>,
+    app: AppHandle,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+    license_lock: State<'_, LicenseLock>,
+) -> Result<FeatureFlags> {
+    let _guard = license_lock.lock().await;
+    let app_data = app_data_dir(&app)?;
+    let endpoint = load_settings(&db).cloud_grading_url;
+
+    let mut flags = read_flags(&features)?;
+    let license = flags.license.clone()
+        .filter(|l| l.seats.is_some())
+        .ok_or_else(|| ShellError::FeatureNotAvailable("The current license has no seats".into()))?;
+
+    let mut ledger = SeatLedger::load(&app_data);
+    let install_id = install_id.unwrap_or_else(|| ledger.install_id.clone());
+    seats::release(&mut ledger, &license, &install_id, endpoint.as_deref()).await?;
+    ledger.save(&app_data)?;
+
+    flags.enforce_seat_at(&ledger, Utc::now());
+    flags.save(&app_data)?;
+    store_flags(&features, flags)
+}
+
+/// Re-confirm this install's seat with the license server, dropping
+/// teacher features once the cached seat runs out and restoring them when
+/// a lapsed seat can be claimed again
+pub async fn refresh_seat(app: &AppHandle) -> Result<()> {
+    let license_lock = app.state::<LicenseLock>();
+    let _guard = license_lock.lock().await;
+    let app_data = app_data_dir(app)?;
+    let endpoint = load_settings(&app.state::<Database>()).cloud_grading_url;
+    refresh(&app_data, endpoint.as_deref(), &app.state::<RwLock<FeatureFlags>>()).await.map(|_| ())
+}
+
+async fn refresh(app_data: &Path, endpoint: Option<&str>, features: &RwLock<FeatureFlags>) -> Result<FeatureFlags> {
+    let mut flags = read_flags(features)?;
+    let Some(license) = flags.license.clone().filter(|l| l.seats.is_some()) else {
+        return Ok(flags);
+    };
+
+    let now = Utc::now();
+    let mut ledger = SeatLedger::load(app_data);
+    match seats::claim(&mut ledger, &license, endpoint, now).await {
+        Ok(()) => {
+            // Rebuild from the license, as activation does, so a seat that
+            // lapsed while offline unlocks its features again
+            let mut rebuilt = flags.clone();
+            rebuilt.deactivate();
+            match rebuilt.verify_and_activate(license) {
+                Ok(()) => flags = rebuilt,
+                Err(e) => tracing::warn!("Could not restore license features: {}", e),
+            }
+        }
+        Err(e) => tracing::warn!("Could not confirm license seat: {}", e),
+    }
+    ledger.save(app_data)?;
+
+    flags.enforce_seat_at(&ledger, now);
+    flags.save(app_data)?;
+    store_flags(features, flags)
+}
+
+pub fn app_data_dir(app: &AppHandle) -> Result<PathBuf> {
+    app.path().app_data_dir().map_err(|e| ShellError::Configuration(e.to_string()))
+}
+
+fn read_flags(features: &RwLock<FeatureFlags>) -> Result<FeatureFlags> {
+    features.read()
+        .map(|flags| flags.clone())
+        .map_err(|_| ShellError::Security("Failed to read feature flags".into()))
+}
+
+fn store_flags(features: &RwLock<FeatureFlags>, flags: FeatureFlags) -> Result<FeatureFlags> {
+    let mut current = features.write()
+        .map_err(|_| ShellError::Security("Failed to update feature flags".into()))?;
+    *current = flags;
+    Ok(current.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::LicenseType;
+
+    // Relies on the development-build bypass for unsigned licenses
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    async fn test_activate_then_deactivate() {
+        let dir = std::env::temp_dir().join(format!("shell-license-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let features = RwLock::new(FeatureFlags::default());
+        let blob = r#"{"license_type":"TeacherInstitution","valid_until":null,"seats":3}"#;
+
+        let flags = activate(blob, &dir, None, &features).await.unwrap();
+        assert!(flags.teacher_mode && flags.exam_mode);
+        assert!(read_flags(&features).unwrap().teacher_mode);
+        let license_id = flags.license.as_ref().unwrap().id();
+        assert_eq!(SeatLedger::load(&dir).installs(&license_id).len(), 1);
+
+        let flags = deactivate(&dir, None, &features).await.unwrap();
+        assert!(!flags.teacher_mode && !flags.exam_mode);
+        assert_eq!(flags.license.unwrap().license_type, LicenseType::Student);
+        assert!(!read_flags(&features).unwrap().teacher_mode);
+        assert!(SeatLedger::load(&dir).installs(&license_id).is_empty());
+        assert!(!FeatureFlags::load(&dir).teacher_mode);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    async fn test_refresh_restores_a_lapsed_seat() {
+        let dir = std::env::temp_dir().join(format!("shell-license-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let features = RwLock::new(FeatureFlags::default());
+        let blob = r#"{"license_type":"TeacherIndividual","valid_until":null,"seats":1}"#;
+        let flags = activate(blob, &dir, None, &features).await.unwrap();
+        let license_id = flags.license.as_ref().unwrap().id();
+
+        // The seat lapses while offline and teacher features are dropped
+        let mut ledger = SeatLedger::load(&dir);
+        let install_id = ledger.install_id.clone();
+        ledger.release(&license_id, &install_id);
+        ledger.save(&dir).unwrap();
+        let mut lapsed = read_flags(&features).unwrap();
+        lapsed.enforce_seat_at(&ledger, Utc::now());
+        assert!(!lapsed.teacher_mode);
+        store_flags(&features, lapsed).unwrap();
+
+        let flags = refresh(&dir, None, &features).await.unwrap();
+        assert!(flags.teacher_mode && flags.cloud_sync);
+        assert!(read_flags(&features).unwrap().teacher_mode);
+        assert_eq!(SeatLedger::load(&dir).installs(&license_id).len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_activate_rejects_garbage() {
+        let features = RwLock::new(FeatureFlags::default());
+        let dir = std::env::temp_dir();
+        assert!(activate("not a license", &dir, None, &features).await.is_err());
+        assert!(!read_flags(&features).unwrap().teacher_mode);
+    }
+}
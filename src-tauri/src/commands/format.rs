@@ -0,0 +1,10 @@
+//! Formatting IPC commands
+
+use crate::error::Result;
+
+/// Format source with the language's formatter. Fails, leaving the
+/// caller's source as it was, when the formatter is missing or rejects it.
+#[tauri::command]
+pub async fn format_code(language: String, code: String) -> Result<String> {
+    crate::format::format(&language, &code).await
+}
@@ -2,46 +2,154 @@
 //! Optimized to use shared security policy for better performance
 
 use std::sync::Arc;
-use tauri::State;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+use crate::autosave::AutoSaver;
+use crate::commands::settings::load_settings;
+use crate::db::{Database, Project};
 use crate::error::Result;
-use crate::fs::{FileSystem, DirectoryContents, ProjectInfo};
+use crate::fs::{FileCache, FileContents, FileInfo, FileSystem, DirectoryContents, ProjectInfo, WatchedDirectory};
+use crate::projects;
 use crate::security::SecurityPolicy;
 use std::path::Path;
 
-/// Read a project directory and return its structure
+/// Read a project directory and return its structure. Opening a project
+/// registers it.
 #[tauri::command]
-pub async fn read_project(path: String) -> Result<ProjectInfo> {
+pub async fn read_project(path: String, db: State<'_, Database>) -> Result<ProjectInfo> {
     // Use spawn_blocking for file I/O to not block async runtime
+    let info = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || {
+            let path = Path::new(&path);
+            ProjectInfo::detect(path)
+        }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))??
+    };
+    if let Err(e) = projects::register(&db, Path::new(&path)) {
+        tracing::warn!("Failed to register project {}: {}", path, e);
+    }
+    Ok(info)
+}
+
+/// Register a project directory, returning its stored record
+#[tauri::command]
+pub async fn register_project(path: String, db: State<'_, Database>) -> Result<Project> {
+    projects::register(&db, Path::new(&path))
+}
+
+/// Registered projects, most recently opened first
+#[tauri::command]
+pub async fn list_projects(db: State<'_, Database>) -> Result<Vec<Project>> {
+    db.list_projects()
+}
+
+/// Read a file's contents
+#[tauri::command]
+pub async fn read_file(
+    path: String,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
+) -> Result<FileContents> {
+    let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
+    tokio::task::spawn_blocking(move || {
+        let fs = FileSystem::with_cache(policy, cache);
+        fs.read_file(Path::new(&path))
+    }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
+}
+
+/// Read a byte window of a file, for paging through files too large to open
+#[tauri::command]
+pub async fn read_file_range(
+    path: String,
+    start: u64,
+    len: u64,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
+) -> Result<FileContents> {
+    let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
     tokio::task::spawn_blocking(move || {
-        let path = Path::new(&path);
-        ProjectInfo::detect(path)
+        let fs = FileSystem::with_cache(policy, cache);
+        fs.read_range(Path::new(&path), start, len)
     }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
 }
 
-/// Write content to a file
+/// Write content to a file, in `encoding` when given (defaults to UTF-8)
 #[tauri::command]
 pub async fn write_file(
     path: String, 
     content: String,
+    encoding: Option<String>,
     policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
 ) -> Result<()> {
     let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
     tokio::task::spawn_blocking(move || {
-        let fs = FileSystem::new(policy);
-        fs.write_file(Path::new(&path), &content)
+        let fs = FileSystem::with_cache(policy, cache);
+        fs.write_file_as(Path::new(&path), &content, encoding.as_deref().unwrap_or("utf-8"))
     }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
 }
 
+/// Outcome of an auto-save, sent as `fs://saved` or `fs://save-failed`
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoSaveEvent {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Save `content` to `path` once edits to it pause for the auto-save delay.
+/// Only the last content scheduled within the delay is written, atomically.
+/// Returns false, scheduling nothing, when auto-save is turned off.
+#[tauri::command]
+pub async fn schedule_autosave(
+    path: String,
+    content: String,
+    encoding: Option<String>,
+    app: AppHandle,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
+    saver: State<'_, Arc<AutoSaver>>,
+) -> Result<bool> {
+    let settings = load_settings(&db);
+    if !settings.auto_save {
+        return Ok(false);
+    }
+    // Refuse paths outside the project up front rather than in a failed save
+    policy.validate_path(Path::new(&path))?;
+
+    let fs = FileSystem::with_cache(Arc::clone(&policy), Arc::clone(&cache));
+    let delay = std::time::Duration::from_millis(settings.auto_save_delay_ms.into());
+    saver.schedule(path.into(), delay, move |path| {
+        let saved = fs.write_file_atomic(path, &content, encoding.as_deref().unwrap_or("utf-8"));
+        let path = path.to_string_lossy().into_owned();
+        let (name, error) = match saved {
+            Ok(()) => ("fs://saved", None),
+            Err(e) => {
+                tracing::warn!("Auto-save of {} failed: {}", path, e);
+                ("fs://save-failed", Some(e.to_string()))
+            }
+        };
+        let _ = app.emit(name, AutoSaveEvent { path, error });
+    });
+    Ok(true)
+}
+
 /// Create a new file
 #[tauri::command]
 pub async fn create_file(
     path: String, 
     content: Option<String>,
     policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
 ) -> Result<()> {
     let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
     tokio::task::spawn_blocking(move || {
-        let fs = FileSystem::new(policy);
+        let fs = FileSystem::with_cache(policy, cache);
         fs.create_file(Path::new(&path), content.as_deref())
     }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
 }
@@ -51,40 +159,92 @@ pub async fn create_file(
 pub async fn delete_file(
     path: String,
     policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
 ) -> Result<()> {
     let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
     tokio::task::spawn_blocking(move || {
-        let fs = FileSystem::new(policy);
+        let fs = FileSystem::with_cache(policy, cache);
         fs.delete_file(Path::new(&path))
     }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
 }
 
+/// Move or rename a file or directory
+#[tauri::command]
+pub async fn rename_file(
+    from: String,
+    to: String,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
+) -> Result<()> {
+    let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
+    tokio::task::spawn_blocking(move || {
+        let fs = FileSystem::with_cache(policy, cache);
+        fs.rename(Path::new(&from), Path::new(&to))
+    }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
+}
+
 /// List directory contents
 #[tauri::command]
 pub async fn list_directory(
     path: String,
     policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
 ) -> Result<DirectoryContents> {
     let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
     tokio::task::spawn_blocking(move || {
-        let fs = FileSystem::new(policy);
+        let fs = FileSystem::with_cache(policy, cache);
         fs.list_directory(Path::new(&path))
     }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
 }
 
+/// Get a file's metadata, with line and character counts when `with_stats` is set
+#[tauri::command]
+pub async fn get_file_info(
+    path: String,
+    with_stats: Option<bool>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
+) -> Result<FileInfo> {
+    let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
+    tokio::task::spawn_blocking(move || {
+        let fs = FileSystem::with_cache(policy, cache);
+        fs.get_info(Path::new(&path), with_stats.unwrap_or(false))
+    }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
+}
+
 /// Watch a directory for changes
 #[tauri::command]
 pub async fn watch_directory(
     path: String,
     policy: State<'_, Arc<SecurityPolicy>>,
+    cache: State<'_, Arc<FileCache>>,
 ) -> Result<()> {
     let policy = Arc::clone(&policy);
+    let cache = Arc::clone(&cache);
     tokio::task::spawn_blocking(move || {
-        let fs = FileSystem::new(policy);
+        let fs = FileSystem::with_cache(policy, cache);
         fs.watch_directory(Path::new(&path))
     }).await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))?
 }
 
+/// Stop one subscription to a watched directory; false if it wasn't watched
+#[tauri::command]
+pub async fn unwatch_directory(path: String, cache: State<'_, Arc<FileCache>>) -> Result<bool> {
+    let cache = Arc::clone(&cache);
+    tokio::task::spawn_blocking(move || cache.unwatch_directory(Path::new(&path)))
+        .await.map_err(|e| crate::error::ShellError::Execution(e.to_string()))
+}
+
+/// Directories being watched, with their subscription counts
+#[tauri::command]
+pub async fn list_watched_directories(cache: State<'_, Arc<FileCache>>) -> Result<Vec<WatchedDirectory>> {
+    Ok(cache.watched_directories())
+}
+
 /// Create a directory (and parent directories if needed)
 #[tauri::command]
 pub async fn create_directory(path: String) -> Result<()> {
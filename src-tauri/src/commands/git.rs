@@ -0,0 +1,101 @@
+//! Git IPC commands
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::State;
+use crate::commands::settings::load_settings;
+use crate::db::Database;
+use crate::error::{Result, ShellError};
+use crate::git::{GitAuthor, GitStatus};
+use crate::security::SecurityPolicy;
+
+/// Branch and changed files for a project; not an error outside a repository
+#[tauri::command]
+pub async fn git_status(
+    project_path: String,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<GitStatus> {
+    policy.validate_path(Path::new(&project_path))?;
+    tokio::task::spawn_blocking(move || crate::git::status(Path::new(&project_path)))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+/// Unified diff of one file, of its staged or its working-tree changes
+#[tauri::command]
+pub async fn git_diff(
+    project_path: String,
+    file_path: String,
+    staged: bool,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<String> {
+    let file = Path::new(&project_path).join(&file_path);
+    policy.validate_path(Path::new(&project_path))?;
+    policy.validate_path(&file)?;
+    tokio::task::spawn_blocking(move || crate::git::diff(Path::new(&project_path), Path::new(&file_path), staged))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+/// Stage files, given relative to the project
+#[tauri::command]
+pub async fn git_stage(
+    project_path: String,
+    paths: Vec<String>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<()> {
+    let paths = validate_paths(&project_path, paths, &policy)?;
+    tokio::task::spawn_blocking(move || crate::git::stage(Path::new(&project_path), &paths))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+/// Unstage files, keeping their working-tree changes
+#[tauri::command]
+pub async fn git_unstage(
+    project_path: String,
+    paths: Vec<String>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<()> {
+    let paths = validate_paths(&project_path, paths, &policy)?;
+    tokio::task::spawn_blocking(move || crate::git::unstage(Path::new(&project_path), &paths))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+/// Commit staged changes and return the commit id. The author defaults to
+/// the one in settings, then to the repository's git config.
+#[tauri::command]
+pub async fn git_commit(
+    project_path: String,
+    message: String,
+    author: Option<GitAuthor>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<String> {
+    policy.validate_path(Path::new(&project_path))?;
+    let author = author.or(load_settings(&db).git_author);
+    tokio::task::spawn_blocking(move || crate::git::commit(Path::new(&project_path), &message, author.as_ref()))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+/// Start tracking a project, optionally committing its current files.
+/// Refuses a project that already has a repository.
+#[tauri::command]
+pub async fn git_init(
+    project_path: String,
+    initial_commit: bool,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<Option<String>> {
+    policy.validate_path(Path::new(&project_path))?;
+    let author = load_settings(&db).git_author;
+    tokio::task::spawn_blocking(move || crate::git::init(Path::new(&project_path), initial_commit, author.as_ref()))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+fn validate_paths(project_path: &str, paths: Vec<String>, policy: &SecurityPolicy) -> Result<Vec<PathBuf>> {
+    policy.validate_path(Path::new(project_path))?;
+    paths.into_iter()
+        .map(|path| {
+            policy.validate_path(&Path::new(project_path).join(&path))?;
+            Ok(PathBuf::from(path))
+        })
+        .collect()
+}
@@ -1,11 +1,23 @@
 //! Grading IPC commands
 
+use futures_util::{FutureExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use tauri::State;
-use crate::docker::{DockerManager, ExecutionRequest};
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+use crate::analytics::AnalyticsEvent;
+use crate::cloud::CloudClient;
+use crate::commands::analytics::record_event;
+use crate::commands::settings::load_settings;
+use crate::db::{Database, SubmissionRecord};
+use crate::docker::{DockerManager, ExecutionBackend, ExecutionPhase, ExecutionRequest, ExecutionResult};
 use crate::features::FeatureFlags;
-use crate::error::{Result, ShellError};
-use crate::commands::lessons::{TestCase, Lesson};
+use crate::security::{PathSanitizer, SecurityPolicy};
+use crate::error::{ErrorContext, Result, ShellError};
+use crate::commands::lessons::{passing_score, Constraints, GradingConfig, Lesson, OutputMatch, RunOptions, TestCase, TestCheck};
+use crate::grading::{apply_hint_penalty, check_constraints, merge_rubric_scores, submission_diff, ConstraintViolation, RubricAward, RubricScore, SubmissionDiff};
 
 /// Test result
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,12 +25,38 @@ pub struct TestResult {
     pub id: String,
     pub name: String,
     pub passed: bool,
+    /// Stdout followed by stderr, for a quick look
     pub actual_output: String,
+    /// What the program printed to stdout, the stream its answer is judged on
+    #[serde(default)]
+    pub actual_stdout: String,
+    /// What it printed to stderr, e.g. a traceback after a crash
+    #[serde(default)]
+    pub actual_stderr: String,
     pub expected_output: String,
     pub points_earned: f32,
     pub points_possible: f32,
     pub execution_time_ms: u64,
     pub error: Option<String>,
+    /// Line diff of expected vs actual output, only present for failing tests
+    pub diff: Option<Vec<DiffLine>>,
+}
+
+/// One line of an expected/actual output diff
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    /// Present in the actual output but not expected
+    Added,
+    /// Expected but missing from the actual output
+    Removed,
+    Unchanged,
 }
 
 /// Grading result
@@ -33,6 +71,34 @@ pub struct GradingResult {
     pub feedback: Option<String>,
     pub graded_at: String,
     pub graded_by: GradingSource,
+    /// Lesson constraints the submission broke; tests are not run when non-empty
+    #[serde(default)]
+    pub constraint_violations: Vec<ConstraintViolation>,
+    /// Per-item breakdown of manual rubric scoring
+    #[serde(default)]
+    pub rubric_scores: Vec<RubricAward>,
+    /// Hidden tests that only run on cloud submission; their details are never included
+    #[serde(default)]
+    pub hidden_test_count: usize,
+    /// Points available from hidden tests
+    #[serde(default)]
+    pub hidden_points: f32,
+    /// Points deducted for revealed hints
+    #[serde(default)]
+    pub hint_penalty: f32,
+}
+
+/// A test finished during grading, emitted as `grading://{submission_id}/progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradingProgress {
+    pub submission_id: String,
+    pub test: TestResult,
+    /// Tests finished so far, this one included
+    pub completed: usize,
+    pub total: usize,
+    /// Points earned so far
+    pub points_earned: f32,
+    pub max_points: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,19 +107,221 @@ pub enum GradingSource {
     Cloud,
 }
 
-/// Run local tests for a submission
+/// Upper bound on tests executing at once for a single submission
+const MAX_PARALLEL_TESTS: usize = 4;
+
+/// Extra attempts for a test that hit a retriable (usually Docker) error
+const MAX_DOCKER_RETRIES: u32 = 2;
+
+/// Delay before the first Docker retry, doubled for each further retry
+const DOCKER_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Feedback when tests cannot run because Docker is missing or stopped
+const DOCKER_REQUIRED_FEEDBACK: &str = "Local tests run inside Docker, which is not available. \
+Install Docker Desktop (https://docs.docker.com/get-docker/) or start the Docker daemon, then run the tests again.";
+
+/// Run local tests for a submission. Each finished test is emitted as a
+/// `grading://{submission_id}/progress` event; pass a UUID `submission_id`
+/// to subscribe before calling.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn run_local_tests(
     lesson_path: String,
-    project_path: String,
     language: String,
     code: String,
+    project_files: Option<Vec<ProjectFile>>,
+    submission_id: Option<String>,
+    app: AppHandle,
     docker: State<'_, DockerManager>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
 ) -> Result<GradingResult> {
+    let project_files = project_files.unwrap_or_default();
+    validate_project_files(&project_files, &policy)?;
+
     // Load the lesson to get test cases
-    let lesson = crate::commands::lessons::load_lesson(lesson_path).await?;
-    
-    let grading = lesson.grading
+    let lesson = crate::commands::lessons::read_lesson(lesson_path.clone()).await?;
+    validate_lesson_options(&lesson, &policy)?;
+
+    let submission_id = match submission_id {
+        Some(id) => uuid::Uuid::parse_str(&id)
+            .map_err(|_| ShellError::Lesson(format!("Submission id '{}' is not a UUID", id)))?
+            .to_string(),
+        None => uuid::Uuid::new_v4().to_string(),
+    };
+    let emit_progress = |progress: &GradingProgress| {
+        let _ = app.emit(&format!("grading://{}/progress", progress.submission_id), progress);
+    };
+    let mut result = grade_lesson_with_progress(
+        &lesson, &language, &code, &project_files, docker.inner(), &submission_id, &emit_progress,
+    ).await?;
+
+    let per_hint = lesson.grading.as_ref().and_then(|g| g.hint_penalty).unwrap_or(0.0);
+    apply_hint_penalty(&mut result, per_hint * db.revealed_hint_count(&lesson.id)? as f32);
+
+    // Keep local history so results can be reviewed and re-scored later
+    db.upsert_lesson(&lesson.id, &lesson.title, &lesson.description, &lesson_path, &lesson.version)?;
+    db.save_submission(&SubmissionRecord { source: Some(code), ..submission_record(&result, "graded")? })?;
+    db.update_lesson_progress(&lesson.id, f64::from(passing_score(&lesson)))?;
+
+    for test in &result.test_results {
+        let (lesson_id, test_id) = (lesson.id.clone(), test.id.clone());
+        record_event(&app, if test.passed {
+            AnalyticsEvent::TestPassed { lesson_id, test_id }
+        } else {
+            AnalyticsEvent::TestFailed { lesson_id, test_id }
+        });
+    }
+    Ok(result)
+}
+
+/// Line diff of two stored submissions' code and how their test results
+/// changed from `submission_a` to `submission_b`
+#[tauri::command]
+pub async fn compare_submissions_diff(
+    submission_a: String,
+    submission_b: String,
+    db: State<'_, Database>,
+) -> Result<SubmissionDiff> {
+    let stored = |id: &str| db.get_submission(id)?
+        .ok_or_else(|| ShellError::Lesson(format!("Submission not found: {}", id)));
+    submission_diff(&stored(&submission_a)?, &stored(&submission_b)?)
+}
+
+/// Apply manual rubric scores to a stored submission; teachers only
+#[tauri::command]
+pub async fn apply_rubric(
+    submission_id: String,
+    scores: Vec<RubricScore>,
+    db: State<'_, Database>,
+    features: State<'_, std::sync::RwLock<FeatureFlags>>,
+) -> Result<GradingResult> {
+    rubric_scores(&db, &features, &submission_id, &scores).await
+}
+
+async fn rubric_scores(
+    db: &Database,
+    features: &std::sync::RwLock<FeatureFlags>,
+    submission_id: &str,
+    scores: &[RubricScore],
+) -> Result<GradingResult> {
+    // Otherwise a student could award themselves rubric points
+    let teacher = features.read()
+        .map_err(|_| ShellError::Security("Failed to read feature flags".into()))?
+        .teacher_mode;
+    if !teacher {
+        return Err(ShellError::Security("Only a teacher can apply rubric scores".into()));
+    }
+
+    let submission = db.get_submission(submission_id)?
+        .ok_or_else(|| ShellError::Lesson(format!("Submission not found: {}", submission_id)))?;
+    let mut result: GradingResult = serde_json::from_str(
+        submission.result.as_deref()
+            .ok_or_else(|| ShellError::Lesson("Submission has no grading result".into()))?
+    )?;
+
+    let lesson_path = db.get_lesson_path(&result.lesson_id)?
+        .ok_or_else(|| ShellError::Lesson(format!("Lesson not found: {}", result.lesson_id)))?;
+    let lesson = crate::commands::lessons::read_lesson(lesson_path).await?;
+    let passing_score = passing_score(&lesson);
+    let rubric = lesson.grading
+        .and_then(|g| g.rubric)
+        .filter(|r| !r.is_empty())
+        .ok_or_else(|| ShellError::Lesson("Lesson has no rubric".into()))?;
+
+    merge_rubric_scores(&mut result, &rubric, scores)?;
+    db.save_submission(&SubmissionRecord { source: submission.source, ..submission_record(&result, "reviewed")? })?;
+    db.update_lesson_progress(&result.lesson_id, f64::from(passing_score))?;
+
+    Ok(result)
+}
+
+/// Database row for a grading result
+fn submission_record(result: &GradingResult, status: &str) -> Result<SubmissionRecord> {
+    Ok(SubmissionRecord {
+        id: result.submission_id.clone(),
+        lesson_id: Some(result.lesson_id.clone()),
+        project_id: None,
+        submitted_at: result.graded_at.clone(),
+        status: status.to_string(),
+        score: Some(f64::from(result.percentage)),
+        feedback: result.feedback.clone(),
+        result: Some(serde_json::to_string(result)?),
+        source: None,
+    })
+}
+
+/// Check supporting files of a submission against the security policy
+fn validate_project_files(files: &[ProjectFile], policy: &SecurityPolicy) -> Result<()> {
+    if files.len() > policy.max_files_per_project as usize {
+        return Err(ShellError::Security(format!(
+            "Too many files in submission: {} (max {})",
+            files.len(), policy.max_files_per_project
+        )));
+    }
+
+    let total_size: u64 = files.iter().map(|f| f.content.len() as u64).sum();
+    policy.check_file_size(total_size)
+}
+
+/// Lesson bundles are untrusted, so their compiler flags, test commands and
+/// grading image go through the policy too
+fn validate_lesson_options(lesson: &Lesson, policy: &SecurityPolicy) -> Result<()> {
+    if let Some(run) = &lesson.run {
+        policy.validate_compile_flags(&run.compile_flags)?;
+    }
+    if let Some(grading) = &lesson.grading {
+        let tests = grading.local_tests.iter().chain(grading.hidden_tests.iter().flatten());
+        for command in tests.filter_map(|test| test.command.as_ref()) {
+            policy.validate_test_command(command)?;
+        }
+    }
+    match grading_image(lesson) {
+        Some(image) => policy.validate_image(image),
+        None => Ok(()),
+    }
+}
+
+/// Image the lesson asks to be graded in, instead of the language default
+fn grading_image(lesson: &Lesson) -> Option<&str> {
+    lesson.grading.as_ref()?.auto_grade.as_ref()?.image.as_deref()
+}
+
+/// Source code being graded: the entry file plus any supporting files
+struct Submission<'a> {
+    language: &'a str,
+    entry_point: &'a str,
+    code: &'a str,
+    files: &'a [ProjectFile],
+    options: &'a RunOptions,
+    /// Overrides the language's default image
+    image: Option<&'a str>,
+}
+
+/// Run a lesson's visible local tests against a submission
+async fn grade_lesson(
+    lesson: &Lesson,
+    language: &str,
+    code: &str,
+    project_files: &[ProjectFile],
+    backend: &dyn ExecutionBackend,
+) -> Result<GradingResult> {
+    let submission_id = uuid::Uuid::new_v4().to_string();
+    grade_lesson_with_progress(lesson, language, code, project_files, backend, &submission_id, &|_| {}).await
+}
+
+/// `grade_lesson`, reporting each test to `on_progress` as it finishes.
+/// Tests that never run are reported too, all at once.
+async fn grade_lesson_with_progress(
+    lesson: &Lesson,
+    language: &str,
+    code: &str,
+    project_files: &[ProjectFile],
+    backend: &dyn ExecutionBackend,
+    submission_id: &str,
+    on_progress: &(dyn Fn(&GradingProgress) + Sync),
+) -> Result<GradingResult> {
+    let grading = lesson.grading.as_ref()
         .ok_or_else(|| ShellError::Lesson("Lesson has no grading configuration".into()))?;
     
     // Filter to visible tests only (local grading)
@@ -65,26 +333,80 @@ pub async fn run_local_tests(
         return Err(ShellError::Lesson("No local tests available".into()));
     }
     
-    let mut test_results = Vec::new();
-    let mut total_points = 0.0;
     let max_points: f32 = tests.iter().map(|t| t.points).sum();
-    
-    // Run each test
-    for test in tests {
-        let result = run_single_test(
-            test,
-            &project_path,
-            &language,
-            &code,
-            &docker,
-        ).await;
-        
-        if result.passed {
-            total_points += result.points_earned;
-        }
-        
-        test_results.push(result);
+    let (hidden_test_count, hidden_points) = hidden_summary(grading);
+    let mut report = progress_reporter(submission_id, tests.len(), max_points, on_progress);
+
+    let entry_point = match &lesson.entry_point {
+        Some(entry_point) => entry_point.as_str(),
+        None => entry_file_name(language)?,
+    };
+    PathSanitizer::relative(entry_point)?;
+    for file in project_files {
+        PathSanitizer::relative(&file.path)?;
     }
+    let options = lesson.run.clone().unwrap_or_default();
+    let submission = Submission {
+        language,
+        entry_point,
+        code,
+        files: project_files,
+        options: &options,
+        image: grading_image(lesson),
+    };
+
+    // Hard constraints are checked before spending any containers; every
+    // source file in the submission's language counts
+    let extension = Path::new(entry_point).extension();
+    let sources: Vec<&str> = std::iter::once(code)
+        .chain(project_files.iter()
+            .filter(|f| f.path != entry_point && Path::new(&f.path).extension() == extension)
+            .map(|f| f.content.as_str()))
+        .collect();
+    let constraint_violations = lesson.constraints.as_ref()
+        .map(|c| check_constraints(&sources, language, c))
+        .unwrap_or_default();
+
+    if !constraint_violations.is_empty() {
+        let feedback = constraint_violations.iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut result = not_run_result(submission_id, lesson, &tests, grading, "Not run: submission violates lesson constraints");
+        result.test_results.iter().for_each(&mut report);
+        result.feedback = Some(format!("Your submission breaks the lesson rules:\n{}", feedback));
+        result.constraint_violations = constraint_violations;
+        return Ok(result);
+    }
+
+    // Without Docker every test would fail the same way, so say so once
+    if !backend.is_available().await {
+        let mut result = not_run_result(submission_id, lesson, &tests, grading, "Not run: Docker is not available");
+        result.test_results.iter().for_each(&mut report);
+        result.feedback = Some(DOCKER_REQUIRED_FEEDBACK.to_string());
+        return Ok(result);
+    }
+
+    // Run tests concurrently, keeping lesson order; a failing, timed-out or
+    // panicking test never takes the rest of the suite down with it
+    let parallelism = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_PARALLEL_TESTS);
+
+    let runs: Vec<_> = tests.iter()
+        .map(|test| run_test_guarded(test, lesson.constraints.as_ref(), &submission, backend))
+        .collect();
+    let test_results: Vec<TestResult> = futures_util::stream::iter(runs)
+        .buffered(parallelism)
+        .inspect(&mut report)
+        .collect()
+        .await;
+
+    let total_points: f32 = test_results.iter()
+        .filter(|r| r.passed)
+        .map(|r| r.points_earned)
+        .sum();
     
     let percentage = if max_points > 0.0 {
         (total_points / max_points) * 100.0
@@ -93,8 +415,8 @@ pub async fn run_local_tests(
     };
     
     Ok(GradingResult {
-        submission_id: uuid::Uuid::new_v4().to_string(),
-        lesson_id: lesson.id,
+        submission_id: submission_id.to_string(),
+        lesson_id: lesson.id.clone(),
         total_points,
         max_points,
         percentage,
@@ -102,54 +424,371 @@ pub async fn run_local_tests(
         feedback: generate_feedback(percentage),
         graded_at: chrono::Utc::now().to_rfc3339(),
         graded_by: GradingSource::Local,
+        constraint_violations: Vec::new(),
+        rubric_scores: Vec::new(),
+        hidden_test_count,
+        hidden_points,
+        hint_penalty: 0.0,
+    })
+}
+
+/// Result for a submission whose tests were never run, each failing with `reason`
+fn not_run_result(submission_id: &str, lesson: &Lesson, tests: &[&TestCase], grading: &GradingConfig, reason: &str) -> GradingResult {
+    let (hidden_test_count, hidden_points) = hidden_summary(grading);
+    GradingResult {
+        submission_id: submission_id.to_string(),
+        lesson_id: lesson.id.clone(),
+        total_points: 0.0,
+        max_points: tests.iter().map(|t| t.points).sum(),
+        percentage: 0.0,
+        test_results: tests.iter()
+            .map(|test| failed_result(test, 0, reason.to_string()))
+            .collect(),
+        feedback: None,
+        graded_at: chrono::Utc::now().to_rfc3339(),
+        graded_by: GradingSource::Local,
+        constraint_violations: Vec::new(),
+        rubric_scores: Vec::new(),
+        hidden_test_count,
+        hidden_points,
+        hint_penalty: 0.0,
+    }
+}
+
+/// Callback turning each finished test into a `GradingProgress` with running totals
+fn progress_reporter<'a>(
+    submission_id: &'a str,
+    total: usize,
+    max_points: f32,
+    on_progress: &'a (dyn Fn(&GradingProgress) + Sync),
+) -> impl FnMut(&TestResult) + Send + 'a {
+    let (mut completed, mut points_earned) = (0, 0.0);
+    move |test: &TestResult| {
+        completed += 1;
+        points_earned += test.points_earned;
+        on_progress(&GradingProgress {
+            submission_id: submission_id.to_string(),
+            test: test.clone(),
+            completed,
+            total,
+            points_earned,
+            max_points,
+        });
+    }
+}
+
+/// Count and total points of tests hidden from local grading
+fn hidden_summary(grading: &GradingConfig) -> (usize, f32) {
+    let hidden: Vec<_> = grading.local_tests.iter()
+        .filter(|t| t.hidden)
+        .chain(grading.hidden_tests.iter().flatten())
+        .collect();
+
+    (hidden.len(), hidden.iter().map(|t| t.points).sum())
+}
+
+/// Run a single test case, turning a panic into a failed result
+async fn run_test_guarded(
+    test: &TestCase,
+    constraints: Option<&Constraints>,
+    submission: &Submission<'_>,
+    backend: &dyn ExecutionBackend,
+) -> TestResult {
+    let run = run_single_test(test, constraints, submission, backend);
+    AssertUnwindSafe(run).catch_unwind().await.unwrap_or_else(|_| {
+        failed_result(test, 0, "Internal error while running test".into())
     })
 }
 
 /// Run a single test case
 async fn run_single_test(
     test: &TestCase,
-    project_path: &str,
-    language: &str,
-    _code: &str,
-    docker: &State<'_, DockerManager>,
+    constraints: Option<&Constraints>,
+    submission: &Submission<'_>,
+    backend: &dyn ExecutionBackend,
 ) -> TestResult {
     let start_time = std::time::Instant::now();
-    
-    // Get execution image and command
-    let image = match crate::commands::execution::get_language_image(language) {
-        Ok(img) => img,
-        Err(e) => {
-            return TestResult {
-                id: test.id.clone(),
-                name: test.name.clone(),
-                passed: false,
-                actual_output: String::new(),
-                expected_output: test.expected_output.clone(),
-                points_earned: 0.0,
-                points_possible: test.points,
-                execution_time_ms: 0,
-                error: Some(e.to_string()),
-            };
+    let outcome = execute_test(test, constraints, submission, backend).await;
+    let execution_time_ms = start_time.elapsed().as_millis() as u64;
+
+    let TestRun { execution, matcher, retries } = match outcome {
+        Ok(run) => run,
+        Err(e) => return failed_result(test, execution_time_ms, e.to_string()),
+    };
+
+    let mut error = if execution.phase == ExecutionPhase::Compile {
+        let diagnostics = execution.compile_output.as_deref().unwrap_or("").trim();
+        Some(format!("Compilation failed:\n{}", diagnostics).trim_end().to_string())
+    } else if execution.timed_out {
+        Some("Time limit exceeded".to_string())
+    } else if execution.exit_code != 0 {
+        Some(format!("Program exited with code {}", execution.exit_code))
+    } else {
+        None
+    };
+    let passed = error.is_none() && match test.check {
+        TestCheck::StdoutEquals => matcher.matches(&execution.stdout, &test.expected_output),
+        TestCheck::ExitCodeZero => true,
+        TestCheck::ContainsSubstring => execution.stdout.contains(&test.expected_output),
+    };
+    // Only a full-output comparison has a meaningful diff
+    let diff = (!passed && test.check == TestCheck::StdoutEquals)
+        .then(|| diff_lines(&test.expected_output, &execution.stdout));
+
+    if retries > 0 {
+        let note = format!("Retried {} time(s) after a Docker error", retries);
+        error = Some(match error {
+            Some(error) => format!("{} ({})", error, note),
+            None => note,
+        });
+    }
+
+    TestResult {
+        id: test.id.clone(),
+        name: test.name.clone(),
+        passed,
+        actual_output: combined_output(&execution.stdout, &execution.stderr),
+        actual_stdout: execution.stdout,
+        actual_stderr: execution.stderr,
+        expected_output: test.expected_output.clone(),
+        points_earned: if passed { test.points } else { 0.0 },
+        points_possible: test.points,
+        execution_time_ms,
+        error,
+        diff,
+    }
+}
+
+/// Outcome of executing one test, before its output is judged
+struct TestRun {
+    execution: ExecutionResult,
+    matcher: OutputMatcher,
+    /// Attempts repeated because of Docker infrastructure errors
+    retries: u32,
+}
+
+/// Execute the submission for one test in a scratch workspace
+async fn execute_test(
+    test: &TestCase,
+    constraints: Option<&Constraints>,
+    submission: &Submission<'_>,
+    backend: &dyn ExecutionBackend,
+) -> Result<TestRun> {
+    // Compile the comparison up front so a bad pattern is reported before running
+    let matcher = OutputMatcher::new(&test.comparison, &test.expected_output)?;
+
+    // A lesson's own image is checked against the policy before grading
+    let image = match submission.image {
+        Some(image) => image.to_string(),
+        None => crate::commands::execution::get_language_image(submission.language)?,
+    };
+    let command = match &test.command {
+        Some(command) => command.clone(),
+        None => crate::commands::execution::get_run_command(submission.language, submission.entry_point, &submission.options.run_args)?,
+    };
+    let compile_command = crate::commands::execution::get_compile_command(
+        submission.language, submission.entry_point, &submission.options.compile_flags,
+    )?;
+
+    let workspace = std::env::temp_dir().join(format!("shell-grade-{}", uuid::Uuid::new_v4()));
+    if let Err(e) = write_workspace(&workspace, submission) {
+        let _ = std::fs::remove_dir_all(&workspace);
+        return Err(e);
+    }
+
+    let mut request = ExecutionRequest {
+        id: uuid::Uuid::new_v4().to_string(),
+        image,
+        command,
+        working_dir: "/workspace".to_string(),
+        source_path: workspace.to_string_lossy().into_owned(),
+        env: HashMap::new(),
+        memory_limit: test_memory_limit(test, constraints),
+        cpu_quota: None,
+        timeout: test_timeout_secs(test, constraints),
+        step_mode: false,
+        trace_io: false,
+        stdin: Some(test.input.clone()).filter(|input| !input.is_empty()),
+        compile_command,
+        raw_output: false,
+        // Graded code must not be able to change the files being graded
+        workspace_writable: false,
+        scratch_workspace: true,
+    };
+
+    // Only infrastructure errors are retried; a wrong answer is a normal result
+    let mut retries = 0;
+    let mut delay = DOCKER_RETRY_DELAY;
+    let result = loop {
+        match backend.execute(request.clone()).await {
+            Err(e) if e.is_retriable() && retries < MAX_DOCKER_RETRIES => {
+                tracing::warn!("Transient error running test '{}', retrying: {}", test.id, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                retries += 1;
+                request.id = uuid::Uuid::new_v4().to_string();
+            }
+            other => break other,
         }
     };
-    
-    // For now, return a placeholder result
-    // TODO: Implement actual test execution with input/output comparison
-    let execution_time_ms = start_time.elapsed().as_millis() as u64;
-    
+    let _ = std::fs::remove_dir_all(&workspace);
+
+    let execution = result.map_err(|e| e.with_context(ErrorContext {
+        language: Some(submission.language.to_string()),
+        file: Some(submission.entry_point.to_string()),
+        container_id: None,
+    }))?;
+    Ok(TestRun { execution, matcher, retries })
+}
+
+/// Materialize the submission's files, writing the entry file last so it wins
+fn write_workspace(workspace: &Path, submission: &Submission<'_>) -> Result<()> {
+    let files = submission.files.iter()
+        .map(|f| (f.path.as_str(), f.content.as_str()))
+        .chain(std::iter::once((submission.entry_point, submission.code)));
+
+    for (path, content) in files {
+        let target = workspace.join(PathSanitizer::relative(path)?);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(target, content)?;
+    }
+    Ok(())
+}
+
+/// Time limit for a test: its own override, else the lesson constraint.
+/// Docker timeouts are whole seconds, so partial seconds round up.
+fn test_timeout_secs(test: &TestCase, constraints: Option<&Constraints>) -> Option<u64> {
+    test.timeout_ms
+        .or_else(|| constraints.and_then(|c| c.max_time_ms))
+        .map(|ms| ((ms + 999) / 1000).max(1))
+}
+
+/// Memory limit for a test: its own override, else the lesson constraint
+fn test_memory_limit(test: &TestCase, constraints: Option<&Constraints>) -> Option<i64> {
+    test.max_memory_bytes
+        .or_else(|| constraints.and_then(|c| c.max_memory_bytes))
+        .map(|bytes| i64::try_from(bytes).unwrap_or(i64::MAX))
+}
+
+/// Stdout then stderr, on separate lines. Docker doesn't keep the streams'
+/// interleaving, so this is for display, not for judging.
+fn combined_output(stdout: &str, stderr: &str) -> String {
+    match (stdout, stderr) {
+        (_, "") => stdout.to_string(),
+        ("", _) => stderr.to_string(),
+        _ if stdout.ends_with('\n') => format!("{}{}", stdout, stderr),
+        _ => format!("{}\n{}", stdout, stderr),
+    }
+}
+
+/// Build a failing result for a test that could not be evaluated
+fn failed_result(test: &TestCase, execution_time_ms: u64, error: String) -> TestResult {
     TestResult {
         id: test.id.clone(),
         name: test.name.clone(),
         passed: false,
-        actual_output: "Test execution pending".to_string(),
+        actual_output: String::new(),
+        actual_stdout: String::new(),
+        actual_stderr: String::new(),
         expected_output: test.expected_output.clone(),
         points_earned: 0.0,
         points_possible: test.points,
         execution_time_ms,
-        error: Some("Test execution not yet implemented".to_string()),
+        error: Some(error),
+        diff: None,
+    }
+}
+
+/// Line diff from `expected` (or an older text) to `actual`
+pub fn diff_lines(expected: &str, actual: &str) -> Vec<DiffLine> {
+    similar::TextDiff::from_lines(expected, actual)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            kind: match change.tag() {
+                similar::ChangeTag::Insert => DiffKind::Added,
+                similar::ChangeTag::Delete => DiffKind::Removed,
+                similar::ChangeTag::Equal => DiffKind::Unchanged,
+            },
+            content: change.value().trim_end_matches(['\r', '\n']).to_string(),
+        })
+        .collect()
+}
+
+/// File name the submitted code is written to for a language
+fn entry_file_name(language: &str) -> Result<&'static str> {
+    Ok(crate::languages::supported(language)?.submission_file)
+}
+
+/// Compiled form of an `OutputMatch`, built once per test case
+enum OutputMatcher {
+    Exact,
+    TrimTrailingWhitespace,
+    IgnoreAllWhitespace,
+    Regex(regex::Regex),
+    Numeric(f64),
+}
+
+impl OutputMatcher {
+    fn new(mode: &OutputMatch, expected: &str) -> Result<Self> {
+        Ok(match mode {
+            OutputMatch::Exact => Self::Exact,
+            OutputMatch::TrimTrailingWhitespace => Self::TrimTrailingWhitespace,
+            OutputMatch::IgnoreAllWhitespace => Self::IgnoreAllWhitespace,
+            OutputMatch::Regex => {
+                // Anchor so the pattern has to account for the whole output
+                let regex = regex::Regex::new(&format!("^(?:{})$", expected.trim_end()))
+                    .map_err(|e| ShellError::Lesson(format!("Invalid expected_output regex: {}", e)))?;
+                Self::Regex(regex)
+            }
+            OutputMatch::Numeric { tolerance } => {
+                if !tolerance.is_finite() || *tolerance < 0.0 {
+                    return Err(ShellError::Lesson(format!("Invalid numeric tolerance: {}", tolerance)));
+                }
+                Self::Numeric(*tolerance)
+            }
+        })
+    }
+
+    fn matches(&self, actual: &str, expected: &str) -> bool {
+        match self {
+            Self::Exact => actual == expected,
+            Self::TrimTrailingWhitespace => trim_trailing_whitespace(actual) == trim_trailing_whitespace(expected),
+            Self::IgnoreAllWhitespace => actual
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .eq(expected.chars().filter(|c| !c.is_whitespace())),
+            Self::Regex(regex) => regex.is_match(actual.trim_end()),
+            Self::Numeric(tolerance) => {
+                let mut actual_tokens = actual.split_whitespace();
+                let mut expected_tokens = expected.split_whitespace();
+                loop {
+                    match (actual_tokens.next(), expected_tokens.next()) {
+                        (None, None) => return true,
+                        (Some(a), Some(e)) => {
+                            let equal = match (a.parse::<f64>(), e.parse::<f64>()) {
+                                (Ok(a), Ok(e)) => (a - e).abs() <= *tolerance,
+                                _ => a == e,
+                            };
+                            if !equal {
+                                return false;
+                            }
+                        }
+                        _ => return false,
+                    }
+                }
+            }
+        }
     }
 }
 
+/// Strip trailing whitespace from every line and drop trailing blank lines
+fn trim_trailing_whitespace(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+    lines.join("\n").trim_end().to_string()
+}
+
 /// Generate feedback based on score
 fn generate_feedback(percentage: f32) -> Option<String> {
     let feedback = if percentage >= 100.0 {
@@ -174,6 +813,9 @@ pub struct CloudGradingRequest {
     pub code: String,
     pub language: String,
     pub project_files: Vec<ProjectFile>,
+    /// Local lesson file used to grade offline; never sent to the service
+    #[serde(default, skip_serializing)]
+    pub lesson_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -187,24 +829,539 @@ pub struct ProjectFile {
 pub async fn submit_for_grading(
     request: CloudGradingRequest,
     features: State<'_, std::sync::RwLock<FeatureFlags>>,
+    db: State<'_, Database>,
+    docker: State<'_, DockerManager>,
+    policy: State<'_, Arc<SecurityPolicy>>,
 ) -> Result<GradingResult> {
+    validate_project_files(&request.project_files, &policy)?;
+
     // Check if cloud grading is available
-    let flags = features.read()
-        .map_err(|_| ShellError::Security("Failed to read feature flags".into()))?;
-    
-    if !flags.cloud_grading {
-        return Err(ShellError::CloudRequired(
-            "Cloud grading requires a teacher license. Your code was tested locally only.".into()
-        ));
+    let token = {
+        let flags = features.read()
+            .map_err(|_| ShellError::Security("Failed to read feature flags".into()))?;
+        
+        if !flags.cloud_grading {
+            return Err(ShellError::CloudRequired(
+                "Cloud grading requires a teacher license. Your code was tested locally only.".into()
+            ));
+        }
+        if !flags.is_license_valid() {
+            return Err(ShellError::CloudRequired(
+                "Your license has expired. Your code was tested locally only.".into()
+            ));
+        }
+
+        flags.license.as_ref()
+            .and_then(|l| l.token.clone())
+            .ok_or_else(|| ShellError::CloudRequired("Your license has no cloud access token.".into()))?
+    };
+
+    let endpoint = load_settings(&db).cloud_grading_url
+        .ok_or_else(|| ShellError::Configuration("No cloud grading endpoint configured".into()))?;
+
+    let client = CloudClient::new(&endpoint, &token)?;
+    match client.submit(&request).await {
+        Err(ShellError::Network(reason)) => {
+            // Offline: grade the visible tests locally rather than blocking the student
+            let lesson_path = request.lesson_path.clone()
+                .ok_or(ShellError::Network(reason))?;
+            let lesson = crate::commands::lessons::read_lesson(lesson_path).await?;
+            validate_lesson_options(&lesson, &policy)?;
+            let mut result = grade_lesson(&lesson, &request.language, &request.code, &request.project_files, docker.inner()).await?;
+            result.feedback = Some(format!(
+                "The grading service is unreachable, so only local tests were run. {}",
+                result.feedback.unwrap_or_default()
+            ).trim_end().to_string());
+            Ok(result)
+        }
+        other => other,
     }
-    
-    // TODO: Implement actual cloud API call
-    Err(ShellError::CloudRequired(
-        "Cloud grading service not yet implemented. Local testing is available.".into()
-    ))
 }
 
 // Helper function - make it public for use in execution module
 pub fn get_language_image(language: &str) -> Result<String> {
     crate::commands::execution::get_language_image(language)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Backend that answers every request from a closure instead of Docker
+    struct MockBackend<F>(F);
+
+    #[async_trait::async_trait]
+    impl<F> ExecutionBackend for MockBackend<F>
+    where
+        F: Fn(&ExecutionRequest) -> Result<ExecutionResult> + Send + Sync,
+    {
+        async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+            (self.0)(&request)
+        }
+    }
+
+    fn execution(request: &ExecutionRequest, stdout: &str, timed_out: bool) -> ExecutionResult {
+        ExecutionResult {
+            id: request.id.clone(),
+            exit_code: if timed_out { -1 } else { 0 },
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            duration_ms: 1,
+            timed_out,
+            trace: None,
+            phase: ExecutionPhase::Run,
+            compile_output: None,
+            tests_passed: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            log_collection_timed_out: false,
+            output_truncated: false,
+        }
+    }
+
+    fn lesson(yaml: &str) -> Lesson {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const TIMED_LESSON: &str = r#"
+id: timed
+version: "1.0.0"
+title: Timed
+description: ""
+language: python
+difficulty: beginner
+tags: []
+prerequisites: []
+content:
+  explanation: ""
+  hints: []
+constraints:
+  max_time_ms: 2500
+  max_memory_bytes: 67108864
+grading:
+  local_tests:
+    - { id: fast, name: Fast, input: "", expected_output: "ok", points: 1, hidden: false }
+    - { id: slow, name: Slow, input: "", expected_output: "ok", points: 1, hidden: false, timeout_ms: 500 }
+    - { id: after, name: After, input: "", expected_output: "ok", points: 1, hidden: false }
+"#;
+
+    fn matches(mode: OutputMatch, actual: &str, expected: &str) -> bool {
+        OutputMatcher::new(&mode, expected).unwrap().matches(actual, expected)
+    }
+
+    #[test]
+    fn test_exact_match() {
+        assert!(matches(OutputMatch::Exact, "Hello\n", "Hello\n"));
+        assert!(!matches(OutputMatch::Exact, "Hello\n", "Hello"));
+    }
+
+    #[test]
+    fn test_trim_trailing_whitespace_is_default() {
+        assert_eq!(OutputMatch::default(), OutputMatch::TrimTrailingWhitespace);
+        assert!(matches(OutputMatch::default(), "a  \r\nb\n\n", "a\nb"));
+        assert!(!matches(OutputMatch::default(), " a\nb", "a\nb"));
+    }
+
+    #[test]
+    fn test_ignore_all_whitespace() {
+        assert!(matches(OutputMatch::IgnoreAllWhitespace, "1 2\n3", "12 3"));
+        assert!(!matches(OutputMatch::IgnoreAllWhitespace, "1 2 4", "123"));
+    }
+
+    #[test]
+    fn test_regex_match() {
+        assert!(matches(OutputMatch::Regex, "Took 42ms\n", r"Took \d+ms"));
+        // The pattern must cover the whole output
+        assert!(!matches(OutputMatch::Regex, "Took 42ms, done", r"Took \d+ms"));
+    }
+
+    #[test]
+    fn test_invalid_regex_is_reported() {
+        let err = OutputMatcher::new(&OutputMatch::Regex, "(unclosed").err().unwrap();
+        assert!(err.to_string().contains("Invalid expected_output regex"));
+    }
+
+    #[test]
+    fn test_numeric_tolerance() {
+        let mode = OutputMatch::Numeric { tolerance: 1e-6 };
+        assert!(matches(mode.clone(), "0.30000000000000004\n", "0.3"));
+        assert!(matches(mode.clone(), "pi = 3.1415926", "pi = 3.14159265"));
+        assert!(!matches(mode.clone(), "pi = 3.14", "pi = 3.14159265"));
+        assert!(!matches(mode.clone(), "tau = 3.1415926", "pi = 3.1415926"));
+        assert!(!matches(mode, "1 2", "1 2 3"));
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_test_does_not_abort_suite() {
+        let lesson = lesson(TIMED_LESSON);
+        // The "slow" test is the only one launched with its 1s override
+        let backend = MockBackend(|request: &ExecutionRequest| {
+            assert_eq!(request.memory_limit, Some(64 * 1024 * 1024));
+            match request.timeout {
+                Some(1) => Ok(execution(request, "", true)),
+                Some(3) => Ok(execution(request, "ok\n", false)),
+                other => panic!("unexpected timeout {:?}", other),
+            }
+        });
+
+        let result = grade_lesson(&lesson, "python", "print('ok')", &[], &backend).await.unwrap();
+
+        assert_eq!(result.test_results.len(), 3);
+        let slow = &result.test_results[1];
+        assert!(!slow.passed);
+        assert_eq!(slow.error.as_deref(), Some("Time limit exceeded"));
+        assert!(result.test_results[0].passed);
+        assert!(result.test_results[2].passed);
+        assert_eq!(result.total_points, 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_test_input_is_sent_as_stdin() {
+        let lesson = lesson(&TIMED_LESSON.replace(r#"id: fast, name: Fast, input: """#, r#"id: fast, name: Fast, input: "ok""#));
+        let backend = MockBackend(|request: &ExecutionRequest| {
+            Ok(execution(request, request.stdin.as_deref().unwrap_or("no input"), false))
+        });
+
+        let result = grade_lesson(&lesson, "python", "print(input())", &[], &backend).await.unwrap();
+
+        assert!(result.test_results[0].passed);
+        assert!(!result.test_results[2].passed);
+    }
+
+    #[test]
+    fn test_diff_highlights_mismatched_line() {
+        let diff = diff_lines("alpha\nbeta\ngamma\n", "alpha\nBETA\ngamma\n");
+        let changed: Vec<_> = diff.iter().filter(|l| l.kind != DiffKind::Unchanged).collect();
+
+        assert_eq!(changed, vec![
+            &DiffLine { kind: DiffKind::Removed, content: "beta".into() },
+            &DiffLine { kind: DiffKind::Added, content: "BETA".into() },
+        ]);
+        assert_eq!(diff.iter().filter(|l| l.kind == DiffKind::Unchanged).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_constraint_violation_skips_execution() {
+        let lesson = lesson(&TIMED_LESSON.replace("max_time_ms: 2500", "max_time_ms: 2500\n  disallowed_imports: [os]"));
+        let backend = MockBackend(|_: &ExecutionRequest| -> Result<ExecutionResult> {
+            panic!("tests must not run when constraints are violated")
+        });
+
+        let result = grade_lesson(&lesson, "python", "import os\nprint('ok')", &[], &backend).await.unwrap();
+
+        assert_eq!(result.constraint_violations.len(), 1);
+        assert_eq!(result.test_results.len(), 3);
+        assert!(result.test_results.iter().all(|t| !t.passed));
+        assert!(result.feedback.unwrap().contains("importing 'os' is not allowed"));
+    }
+
+    #[tokio::test]
+    async fn test_every_test_reports_progress() {
+        let events = std::sync::Mutex::new(Vec::new());
+        let record = |progress: &GradingProgress| events.lock().unwrap().push(progress.clone());
+        let backend = MockBackend(|request: &ExecutionRequest| Ok(execution(request, "ok", request.timeout == Some(1))));
+
+        let result = grade_lesson_with_progress(&lesson(TIMED_LESSON), "python", "print('ok')", &[], &backend, "sub-1", &record)
+            .await.unwrap();
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|e| e.submission_id == "sub-1" && e.total == 3 && e.max_points == 3.0));
+        let totals: Vec<_> = events.iter().map(|e| (e.completed, e.points_earned)).collect();
+        assert_eq!(totals, vec![(1, 1.0), (2, 1.0), (3, 2.0)]);
+        assert_eq!(result.submission_id, "sub-1");
+
+        // Tests skipped for a constraint violation are reported as well
+        let events = std::sync::Mutex::new(Vec::new());
+        let record = |progress: &GradingProgress| events.lock().unwrap().push(progress.test.id.clone());
+        let lesson = lesson(&TIMED_LESSON.replace("max_time_ms: 2500", "max_time_ms: 2500\n  disallowed_imports: [os]"));
+        grade_lesson_with_progress(&lesson, "python", "import os", &[], &backend, "sub-2", &record).await.unwrap();
+        assert_eq!(events.into_inner().unwrap(), vec!["fast", "slow", "after"]);
+    }
+
+    #[tokio::test]
+    async fn test_lesson_image_overrides_language_default() {
+        let backend = MockBackend(|request: &ExecutionRequest| Ok(execution(request, &request.image, false)));
+        let yaml = format!("{}  auto_grade: {{ enabled: true, image: \"python:3.11\" }}\n", TIMED_LESSON);
+
+        let default = grade_lesson(&lesson(TIMED_LESSON), "python", "", &[], &backend).await.unwrap();
+        assert_eq!(default.test_results[0].actual_output, "python:3.12-slim");
+        let custom = grade_lesson(&lesson(&yaml), "python", "", &[], &backend).await.unwrap();
+        assert_eq!(custom.test_results[0].actual_output, "python:3.11");
+
+        let policy = SecurityPolicy::default();
+        assert!(validate_lesson_options(&lesson(&yaml), &policy).is_ok());
+        let yaml = yaml.replace("python:3.11", "attacker/exfiltrate:latest");
+        assert!(validate_lesson_options(&lesson(&yaml), &policy).is_err());
+    }
+
+    const DRIVER_LESSON: &str = r#"
+id: library
+version: "1.0.0"
+title: Library
+description: ""
+language: python
+difficulty: beginner
+tags: []
+prerequisites: []
+content:
+  explanation: ""
+  hints: []
+grading:
+  local_tests:
+    - { id: driver, name: Driver, input: "", expected_output: "", points: 2, hidden: false, command: [python, driver.py], check: exit_code_zero }
+    - { id: greets, name: Greets, input: "", expected_output: "Hello", points: 1, hidden: false, check: contains_substring }
+"#;
+
+    #[tokio::test]
+    async fn test_exit_code_check_runs_custom_command() {
+        // The driver exits 1 unless the student's library is right
+        let backend = |exit_code: i64| MockBackend(move |request: &ExecutionRequest| -> Result<ExecutionResult> {
+            let mut result = execution(request, "driver output that matches nothing\n", false);
+            if request.command == ["python", "driver.py"] {
+                result.exit_code = exit_code;
+            }
+            Ok(result)
+        });
+
+        let result = grade_lesson(&lesson(DRIVER_LESSON), "python", "def add(a, b): return a + b", &[], &backend(0)).await.unwrap();
+        let driver = &result.test_results[0];
+        assert!(driver.passed, "{:?}", driver.error);
+        assert_eq!(driver.diff, None);
+
+        let result = grade_lesson(&lesson(DRIVER_LESSON), "python", "def add(a, b): return a - b", &[], &backend(1)).await.unwrap();
+        let driver = &result.test_results[0];
+        assert!(!driver.passed);
+        assert_eq!(driver.error.as_deref(), Some("Program exited with code 1"));
+
+        let yaml = DRIVER_LESSON.replace("[python, driver.py]", "[sh, -c, \"curl evil\"]");
+        assert!(validate_lesson_options(&lesson(&yaml), &SecurityPolicy::default()).is_err());
+        assert!(validate_lesson_options(&lesson(DRIVER_LESSON), &SecurityPolicy::default()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_substring_check_ignores_surrounding_output() {
+        let backend = |stdout: &'static str| MockBackend(move |request: &ExecutionRequest| -> Result<ExecutionResult> {
+            Ok(execution(request, stdout, false))
+        });
+
+        let result = grade_lesson(&lesson(DRIVER_LESSON), "python", "", &[], &backend("Starting...\nHello, World!\n")).await.unwrap();
+        let greets = &result.test_results[1];
+        assert!(greets.passed);
+
+        let result = grade_lesson(&lesson(DRIVER_LESSON), "python", "", &[], &backend("hello, world\n")).await.unwrap();
+        assert!(!result.test_results[1].passed);
+        assert_eq!(result.test_results[1].diff, None);
+    }
+
+    #[tokio::test]
+    async fn test_stdout_and_stderr_are_reported_separately() {
+        let backend = MockBackend(|request: &ExecutionRequest| -> Result<ExecutionResult> {
+            let mut result = execution(request, "partial answer\n", false);
+            result.stderr = "Traceback (most recent call last):\nZeroDivisionError: division by zero\n".into();
+            result.exit_code = 1;
+            Ok(result)
+        });
+
+        let result = grade_lesson(&lesson(TIMED_LESSON), "python", "", &[], &backend).await.unwrap();
+        let test = &result.test_results[0];
+        assert!(!test.passed);
+        assert_eq!(test.actual_stdout, "partial answer\n");
+        assert_eq!(test.actual_stderr, "Traceback (most recent call last):\nZeroDivisionError: division by zero\n");
+        assert_eq!(test.actual_output, "partial answer\nTraceback (most recent call last):\nZeroDivisionError: division by zero\n");
+        // The diff is of stdout alone
+        assert!(test.diff.as_ref().unwrap().iter().all(|line| !line.content.contains("Traceback")));
+
+        assert_eq!(combined_output("42", "warning\n"), "42\nwarning\n");
+        assert_eq!(combined_output("", "warning\n"), "warning\n");
+    }
+
+    /// Backend for a machine without a running Docker daemon
+    struct NoDocker;
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for NoDocker {
+        async fn execute(&self, _request: ExecutionRequest) -> Result<ExecutionResult> {
+            panic!("tests must not run without Docker")
+        }
+
+        async fn is_available(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_docker_reported_once() {
+        let lesson = lesson(TIMED_LESSON);
+
+        let result = grade_lesson(&lesson, "python", "print('ok')", &[], &NoDocker).await.unwrap();
+
+        assert_eq!(result.test_results.len(), 3);
+        assert!(result.test_results.iter().all(|t| t.error.as_deref() == Some("Not run: Docker is not available")));
+        assert_eq!(result.total_points, 0.0);
+        assert_eq!(result.max_points, 3.0);
+        assert!(result.feedback.unwrap().contains("https://docs.docker.com/get-docker/"));
+    }
+
+    /// Backend that sleeps per request and records peak concurrency
+    struct ConcurrencyProbe {
+        active: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for ConcurrencyProbe {
+        async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+            use std::sync::atomic::Ordering;
+            let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+
+            if request.timeout == Some(1) {
+                panic!("backend blew up");
+            }
+            Ok(execution(&request, "ok", false))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tests_run_concurrently_in_order() {
+        let mut lesson = lesson(TIMED_LESSON);
+        let grading = lesson.grading.as_mut().unwrap();
+        let template = grading.local_tests[0].clone();
+        for i in 0..8 {
+            grading.local_tests.push(TestCase { id: format!("extra-{}", i), ..template.clone() });
+        }
+        let probe = ConcurrencyProbe { active: 0.into(), peak: 0.into() };
+
+        let result = grade_lesson(&lesson, "python", "print('ok')", &[], &probe).await.unwrap();
+
+        let ids: Vec<_> = result.test_results.iter().map(|r| r.id.as_str()).collect();
+        let expected: Vec<_> = lesson.grading.as_ref().unwrap().local_tests.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, expected);
+
+        // The panicking "slow" test is reported, the others still complete
+        assert_eq!(result.test_results[1].error.as_deref(), Some("Internal error while running test"));
+        assert_eq!(result.test_results.iter().filter(|r| r.passed).count(), 10);
+        assert_eq!(result.total_points, 10.0);
+
+        let peak = probe.peak.load(std::sync::atomic::Ordering::SeqCst);
+        assert!(peak <= MAX_PARALLEL_TESTS);
+    }
+
+    #[tokio::test]
+    async fn test_hidden_tests_are_counted_but_not_exposed() {
+        let yaml = TIMED_LESSON.replace(
+            "    - { id: after,",
+            "    - { id: secret, name: Secret, input: \"42\", expected_output: \"forty-two\", points: 3, hidden: true }\n    - { id: after,",
+        ) + "  hidden_tests:\n    - { id: cloud, name: Cloud, input: \"7\", expected_output: \"seven\", points: 5, hidden: true }\n";
+        let lesson = lesson(&yaml);
+        let backend = MockBackend(|request: &ExecutionRequest| Ok(execution(request, "ok\n", false)));
+
+        let result = grade_lesson(&lesson, "python", "print('ok')", &[], &backend).await.unwrap();
+
+        assert_eq!(result.hidden_test_count, 2);
+        assert_eq!(result.hidden_points, 8.0);
+        assert_eq!(result.test_results.len(), 3);
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(!json.contains("forty-two") && !json.contains("seven") && !json.contains("secret"));
+    }
+
+    fn file(path: &str, content: &str) -> ProjectFile {
+        ProjectFile { path: path.into(), content: content.into() }
+    }
+
+    #[tokio::test]
+    async fn test_multi_file_submission_is_materialized() {
+        let lesson = lesson(TIMED_LESSON);
+        let files = vec![
+            file("geometry/__init__.py", "from .shapes import area\n"),
+            file("geometry/shapes.py", "from .units import scale\n\ndef area(w, h):\n    return scale(w * h)\n"),
+            file("geometry/units.py", "def scale(x):\n    return x\n"),
+        ];
+        let backend = MockBackend(|request: &ExecutionRequest| {
+            let root = Path::new(&request.source_path);
+            let entry = std::fs::read_to_string(root.join("main.py")).unwrap();
+            let shapes = std::fs::read_to_string(root.join("geometry/shapes.py")).unwrap();
+            assert!(entry.contains("from geometry import area"));
+            assert!(shapes.contains("from .units import scale"));
+            assert!(root.join("geometry/__init__.py").exists() && root.join("geometry/units.py").exists());
+            Ok(execution(request, "ok\n", false))
+        });
+
+        let code = "from geometry import area\nprint('ok' if area(2, 3) == 6 else 'bad')\n";
+        let result = grade_lesson(&lesson, "python", code, &files, &backend).await.unwrap();
+
+        assert_eq!(result.total_points, 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_multi_file_constraints_and_paths() {
+        let backend = MockBackend(|request: &ExecutionRequest| Ok(execution(request, "ok\n", false)));
+
+        // A disallowed import hidden in a helper module is still caught
+        let lesson_with_rule = lesson(&TIMED_LESSON.replace("max_time_ms: 2500", "max_time_ms: 2500\n  disallowed_imports: [os]"));
+        let files = vec![file("helpers.py", "import os\n")];
+        let result = grade_lesson(&lesson_with_rule, "python", "import helpers", &files, &backend).await.unwrap();
+        assert_eq!(result.constraint_violations.len(), 1);
+
+        let files = vec![file("../escape.py", "print('hi')")];
+        let err = grade_lesson(&lesson(TIMED_LESSON), "python", "print('ok')", &files, &backend).await.unwrap_err();
+        assert!(matches!(err, ShellError::Security(_)));
+    }
+
+    #[tokio::test]
+    async fn test_transient_docker_error_is_retried() {
+        let lesson = lesson(TIMED_LESSON);
+        let attempts = Arc::new(std::sync::Mutex::new(HashMap::<String, u32>::new()));
+        let seen = attempts.clone();
+        // Every test hits one Docker error; the 1s test then fails again and
+        // finally answers wrongly, which must not be retried
+        let backend = MockBackend(move |request: &ExecutionRequest| {
+            let mut attempts = seen.lock().unwrap();
+            let count = attempts.entry(request.source_path.clone()).or_default();
+            *count += 1;
+            let flaky = if request.timeout == Some(1) { 2 } else { 1 };
+            if *count <= flaky {
+                return Err(ShellError::Docker("socket hang up".into()));
+            }
+            let stdout = if request.timeout == Some(1) { "wrong\n" } else { "ok\n" };
+            Ok(execution(request, stdout, false))
+        });
+
+        let result = grade_lesson(&lesson, "python", "print('ok')", &[], &backend).await.unwrap();
+
+        let [fast, slow, after] = &result.test_results[..] else { panic!("expected 3 results") };
+        assert!(fast.passed && after.passed);
+        assert_eq!(fast.error.as_deref(), Some("Retried 1 time(s) after a Docker error"));
+        assert!(!slow.passed);
+        assert_eq!(slow.error.as_deref(), Some("Retried 2 time(s) after a Docker error"));
+        let mut counts: Vec<u32> = attempts.lock().unwrap().values().copied().collect();
+        counts.sort();
+        assert_eq!(counts, vec![2, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_rubric_scores_require_teacher_mode() {
+        let db = Database::in_memory().unwrap();
+        db.save_submission(&SubmissionRecord {
+            id: "sub-1".into(),
+            lesson_id: Some("timed".into()),
+            project_id: None,
+            submitted_at: "2024-01-01T00:00:00Z".into(),
+            status: "graded".into(),
+            score: Some(40.0),
+            feedback: None,
+            result: Some("{}".into()),
+            source: None,
+        }).unwrap();
+        let features = std::sync::RwLock::new(FeatureFlags::default());
+        let scores = vec![RubricScore { item_id: "style".into(), points: 10.0, comment: None }];
+
+        let err = rubric_scores(&db, &features, "sub-1", &scores).await.unwrap_err();
+        assert!(matches!(err, ShellError::Security(_)), "{}", err);
+        let stored = db.get_submission("sub-1").unwrap().unwrap();
+        assert_eq!((stored.status.as_str(), stored.score), ("graded", Some(40.0)));
+    }
+}
@@ -1,8 +1,19 @@
 //! Lesson IPC commands
 
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use crate::analytics::AnalyticsEvent;
+use crate::bundle::{self, BundleManifest};
+use crate::commands::analytics::record_event;
+use crate::db::{Database, LessonProgress};
 use crate::error::{Result, ShellError};
+use crate::security::PathSanitizer;
+use crate::lessons::{
+    compare_versions, discover, is_built_in, is_toml, is_yaml, migrate, migrate_strict, MAX_DISCOVERY_DEPTH, parse_document,
+    sanitize_lesson, sanitize_markdown, unknown_fields, upgrade, validate_document, ValidationError, LESSON_EXTENSIONS,
+};
 
 /// Lesson metadata and content
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +24,14 @@ pub struct Lesson {
     pub description: String,
     pub author: Option<Author>,
     pub language: String,
+    /// File the submission runs from, relative to the project root
+    pub entry_point: Option<String>,
+    /// Default compiler flags and program arguments
+    #[serde(default)]
+    pub run: Option<RunOptions>,
+    /// Packages `install_dependencies` may add to the run image
+    #[serde(default)]
+    pub dependencies: Vec<String>,
     pub difficulty: Difficulty,
     pub tags: Vec<String>,
     pub prerequisites: Vec<String>,
@@ -21,6 +40,14 @@ pub struct Lesson {
     pub grading: Option<GradingConfig>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunOptions {
+    #[serde(default)]
+    pub compile_flags: Vec<String>,
+    #[serde(default)]
+    pub run_args: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Author {
     pub name: String,
@@ -89,6 +116,22 @@ pub struct GradingConfig {
     pub rubric: Option<Vec<RubricItem>>,
     /// Auto-grading configuration
     pub auto_grade: Option<AutoGradeConfig>,
+    /// Minimum percentage to pass (defaults to `DEFAULT_PASSING_SCORE`)
+    pub passing_score: Option<f32>,
+    /// Points deducted per revealed hint
+    pub hint_penalty: Option<f32>,
+    /// Intended sum of the test points, checked by `validate_lesson`
+    pub total_points: Option<f32>,
+}
+
+/// Passing percentage for lessons that don't set `passing_score`
+pub const DEFAULT_PASSING_SCORE: f32 = 70.0;
+
+/// Percentage needed to pass `lesson`
+pub fn passing_score(lesson: &Lesson) -> f32 {
+    lesson.grading.as_ref()
+        .and_then(|g| g.passing_score)
+        .unwrap_or(DEFAULT_PASSING_SCORE)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +142,51 @@ pub struct TestCase {
     pub expected_output: String,
     pub points: f32,
     pub hidden: bool,
+    /// How actual output is compared against `expected_output`
+    #[serde(default)]
+    pub comparison: OutputMatch,
+    /// Time limit override (ms), takes precedence over `Constraints.max_time_ms`
+    pub timeout_ms: Option<u64>,
+    /// Memory limit override (bytes), takes precedence over `Constraints.max_memory_bytes`
+    pub max_memory_bytes: Option<u64>,
+    /// Command run in place of the submission's entry point, e.g. a provided
+    /// driver exercising a student's library
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// What the test checks to pass
+    #[serde(default)]
+    pub check: TestCheck,
+}
+
+/// Pass condition of a test case. Every check also requires the program to
+/// compile, finish in time and exit with code 0.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestCheck {
+    /// Stdout matches `expected_output` under the test's `comparison`
+    #[default]
+    StdoutEquals,
+    /// Exiting with code 0 is enough; output is ignored
+    ExitCodeZero,
+    /// Stdout contains `expected_output` anywhere
+    ContainsSubstring,
+}
+
+/// Output comparison mode for a test case
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMatch {
+    /// Byte-for-byte equality
+    Exact,
+    /// Ignore trailing whitespace on each line and trailing blank lines
+    #[default]
+    TrimTrailingWhitespace,
+    /// Ignore all whitespace characters
+    IgnoreAllWhitespace,
+    /// `expected_output` is a regex that must match the whole output
+    Regex,
+    /// Numeric tokens may differ by at most `tolerance`; other tokens must match
+    Numeric { tolerance: f64 },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -119,27 +207,46 @@ pub struct AutoGradeConfig {
     pub script: Option<String>,
 }
 
-/// Load a lesson from file
+/// Load a lesson from file and record it in the local catalog.
+/// Text is sanitized unless the lesson ships with the app.
+/// Unknown fields are ignored unless `strict` is set.
 #[tauri::command]
-pub async fn load_lesson(path: String) -> Result<Lesson> {
-    let path = Path::new(&path);
-    
+pub async fn load_lesson(
+    path: String,
+    strict: Option<bool>,
+    app: AppHandle,
+    db: State<'_, Database>,
+) -> Result<Lesson> {
+    let mut lesson = read_lesson_file(Path::new(&path), strict.unwrap_or(false))?;
+    let built_in = app.path().resource_dir().is_ok_and(|dir| is_built_in(Path::new(&path), &dir));
+    if !built_in {
+        sanitize_lesson(&mut lesson);
+    }
+    db.upsert_lesson(&lesson.id, &lesson.title, &lesson.description, &path, &lesson.version)?;
+    record_event(&app, AnalyticsEvent::LessonStarted { lesson_id: lesson.id.clone() });
+    Ok(lesson)
+}
+
+/// Read a lesson from file, ignoring fields it doesn't know
+pub async fn read_lesson(path: String) -> Result<Lesson> {
+    read_lesson_file(Path::new(&path), false)
+}
+
+/// Read a lesson from file; `strict` rejects unknown fields
+fn read_lesson_file(path: &Path, strict: bool) -> Result<Lesson> {
     if !path.exists() {
         return Err(ShellError::Lesson("Lesson file not found".into()));
     }
     
     let content = std::fs::read_to_string(path)?;
     
-    // Support both YAML and JSON
-    let lesson: Lesson = if path.extension().map(|e| e == "yaml" || e == "yml").unwrap_or(false) {
-        serde_yaml::from_str(&content)
-            .map_err(|e| ShellError::Lesson(format!("Invalid YAML: {}", e)))?
+    // Support both YAML and JSON, upgrading older lesson formats
+    let document = parse_document(path, &content)?;
+    if strict {
+        migrate_strict(document)
     } else {
-        serde_json::from_str(&content)
-            .map_err(|e| ShellError::Lesson(format!("Invalid JSON: {}", e)))?
-    };
-    
-    Ok(lesson)
+        migrate(document)
+    }
 }
 
 /// Save a lesson to file
@@ -147,9 +254,12 @@ pub async fn load_lesson(path: String) -> Result<Lesson> {
 pub async fn save_lesson(path: String, lesson: Lesson) -> Result<()> {
     let path = Path::new(&path);
     
-    let content = if path.extension().map(|e| e == "yaml" || e == "yml").unwrap_or(false) {
+    let content = if is_yaml(path) {
         serde_yaml::to_string(&lesson)
             .map_err(|e| ShellError::Lesson(format!("Failed to serialize: {}", e)))?
+    } else if is_toml(path) {
+        toml::to_string_pretty(&lesson)
+            .map_err(|e| ShellError::Lesson(format!("Failed to serialize: {}", e)))?
     } else {
         serde_json::to_string_pretty(&lesson)?
     };
@@ -158,81 +268,366 @@ pub async fn save_lesson(path: String, lesson: Lesson) -> Result<()> {
     Ok(())
 }
 
-/// List lessons in a directory
+/// List lessons in a directory.
+///
+/// Searches the whole tree (bounded depth, honouring ignore files) unless
+/// `recursive` is false, which only checks `lesson.*` files in `directory`
+/// itself. Lessons sharing an id are reported once, keeping the newest version.
 #[tauri::command]
-pub async fn list_lessons(directory: String) -> Result<Vec<LessonSummary>> {
+pub async fn list_lessons(directory: String, recursive: Option<bool>) -> Result<Vec<LessonSummary>> {
     let dir = Path::new(&directory);
-    let mut lessons = Vec::new();
+    let mut lessons: Vec<LessonSummary> = Vec::new();
     
     if !dir.is_dir() {
         return Ok(lessons);
     }
     
-    for entry in std::fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
+    let paths = if recursive.unwrap_or(true) {
+        discover(dir, MAX_DISCOVERY_DEPTH)
+    } else {
+        single_level_lessons(dir)?
+    };
+    
+    for path in paths {
+        let Ok(lesson) = read_lesson(path.to_string_lossy().to_string()).await else {
+            continue;
+        };
+        let relative_path = path.strip_prefix(dir)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let summary = summarize(lesson, path.to_string_lossy().to_string(), relative_path);
         
-        // Check for lesson files
-        if path.is_file() {
-            let name = path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("");
-            
-            if name.starts_with("lesson.") && 
-               (name.ends_with(".yaml") || name.ends_with(".yml") || name.ends_with(".json")) {
-                if let Ok(lesson) = load_lesson(path.to_string_lossy().to_string()).await {
-                    lessons.push(LessonSummary {
-                        id: lesson.id,
-                        title: lesson.title,
-                        description: lesson.description,
-                        language: lesson.language,
-                        difficulty: lesson.difficulty,
-                        path: path.to_string_lossy().to_string(),
-                    });
-                }
+        match lessons.iter_mut().find(|l| l.id == summary.id) {
+            Some(existing) if compare_versions(&summary.version, &existing.version) == Ordering::Greater => {
+                *existing = summary;
             }
+            Some(_) => {}
+            None => lessons.push(summary),
         }
     }
     
     Ok(lessons)
 }
 
-/// Validate a lesson file
+/// `lesson.yaml`/`lesson.yml`/`lesson.json`/`lesson.toml` directly inside `dir`
+fn single_level_lessons(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("");
+        
+        let extension = name.strip_prefix("lesson.").unwrap_or("");
+        if path.is_file() && LESSON_EXTENSIONS.contains(&extension) {
+            paths.push(path);
+        }
+    }
+    
+    paths.sort();
+    Ok(paths)
+}
+
+/// Validate a lesson file. Unknown fields are errors here, to catch typos.
 #[tauri::command]
 pub async fn validate_lesson(path: String) -> Result<ValidationResult> {
-    let result = load_lesson(path.clone()).await;
-    
-    match result {
-        Ok(lesson) => {
-            let mut warnings = Vec::new();
-            
-            // Check for common issues
-            if lesson.content.explanation.is_empty() {
-                warnings.push("Lesson has no explanation".to_string());
-            }
-            
-            if lesson.grading.is_none() {
-                warnings.push("Lesson has no grading configuration".to_string());
-            } else if let Some(grading) = &lesson.grading {
-                if grading.local_tests.is_empty() {
-                    warnings.push("Lesson has no local tests".to_string());
-                }
-            }
-            
-            Ok(ValidationResult {
-                valid: true,
-                errors: vec![],
-                warnings,
-            })
+    let invalid = |errors| Ok(ValidationResult { valid: false, errors, warnings: vec![] });
+
+    // Structural checks first, so errors point at the offending field
+    let document = std::fs::read_to_string(&path)
+        .map_err(|e| ShellError::Lesson(format!("Cannot read lesson file: {}", e)))
+        .and_then(|content| parse_document(Path::new(&path), &content))
+        .and_then(upgrade);
+    let document = match document {
+        Ok(document) => document,
+        Err(e) => return invalid(vec![ValidationError { path: String::new(), message: e.to_string() }]),
+    };
+    let mut errors = validate_document(&document);
+    errors.extend(unknown_fields(&document));
+    if !errors.is_empty() {
+        return invalid(errors);
+    }
+
+    let lesson = match read_lesson(path).await {
+        Ok(lesson) => lesson,
+        Err(e) => return invalid(vec![ValidationError { path: String::new(), message: e.to_string() }]),
+    };
+
+    let mut warnings = Vec::new();
+
+    // Check for common issues
+    if lesson.content.explanation.is_empty() {
+        warnings.push("Lesson has no explanation".to_string());
+    }
+
+    if lesson.grading.is_none() {
+        warnings.push("Lesson has no grading configuration".to_string());
+    } else if let Some(grading) = &lesson.grading {
+        if grading.local_tests.is_empty() {
+            warnings.push("Lesson has no local tests".to_string());
+        }
+        warnings.extend(point_warnings(grading));
+    }
+
+    Ok(ValidationResult {
+        valid: true,
+        errors: vec![],
+        warnings,
+    })
+}
+
+/// Authoring mistakes in test points that skew percentages: a zero total,
+/// a total other than the declared `total_points`, and `hidden` flags that
+/// disagree with the list a test is in. Negative points are errors, caught
+/// by `validate_document`.
+fn point_warnings(grading: &GradingConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let lists = [("local_tests", &grading.local_tests[..], false), ("hidden_tests", grading.hidden_tests.as_deref().unwrap_or(&[]), true)];
+
+    let total: f32 = lists.iter().flat_map(|(_, tests, _)| tests.iter()).map(|t| t.points).sum();
+    if total == 0.0 && lists.iter().any(|(_, tests, _)| !tests.is_empty()) {
+        warnings.push("Test points add up to 0, so every score is 0%".to_string());
+    }
+    if let Some(declared) = grading.total_points {
+        if (total - declared).abs() > 0.001 {
+            warnings.push(format!("Test points add up to {} but total_points is {}", total, declared));
         }
-        Err(e) => {
-            Ok(ValidationResult {
-                valid: false,
-                errors: vec![e.to_string()],
-                warnings: vec![],
-            })
+    }
+
+    for (list, tests, hidden) in lists {
+        for test in tests.iter().filter(|t| t.hidden != hidden) {
+            warnings.push(format!(
+                "Test '{}' in {} is marked hidden: {}; tests there should be hidden: {}",
+                test.id, list, test.hidden, hidden
+            ));
+        }
+    }
+    warnings
+}
+
+/// Export a lesson with its assets and starter files as a `.shelllesson` bundle
+#[tauri::command]
+pub async fn export_lesson(lesson_path: String, out_zip: String) -> Result<BundleManifest> {
+    let validation = validate_lesson(lesson_path.clone()).await?;
+    if !validation.valid {
+        return Err(ShellError::Lesson(format!(
+            "Cannot export an invalid lesson: {}", describe_errors(&validation.errors)
+        )));
+    }
+
+    let mut out = PathBuf::from(out_zip);
+    if out.extension().is_none() {
+        out.set_extension(bundle::BUNDLE_EXTENSION);
+    }
+
+    let lesson = read_lesson(lesson_path.clone()).await?;
+    bundle::export(Path::new(&lesson_path), &lesson, &out)
+}
+
+/// Summary of a known lesson by id, found through the local catalog rather
+/// than a directory scan. `None` when the id is unknown or its file is gone.
+#[tauri::command]
+pub async fn get_lesson_summary(lesson_id: String, db: State<'_, Database>) -> Result<Option<LessonSummary>> {
+    lesson_summary(&db, &lesson_id).await
+}
+
+async fn lesson_summary(db: &Database, lesson_id: &str) -> Result<Option<LessonSummary>> {
+    let Some(path) = db.get_lesson_path(lesson_id)? else {
+        return Ok(None);
+    };
+    let lesson = match read_lesson(path.clone()).await {
+        Ok(lesson) if lesson.id == lesson_id => lesson,
+        _ => {
+            tracing::warn!("Catalog entry for lesson {} points at {}, which no longer holds it", lesson_id, path);
+            return Ok(None);
         }
+    };
+    // Not listed from a directory, so the path is relative to the lesson's own
+    let relative_path = Path::new(&path).file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Some(summarize(lesson, path, relative_path)))
+}
+
+/// Summaries are shown before a lesson is opened, so their text is always
+/// sanitized
+fn summarize(lesson: Lesson, path: String, relative_path: String) -> LessonSummary {
+    LessonSummary {
+        id: lesson.id,
+        title: sanitize_markdown(&lesson.title),
+        description: sanitize_markdown(&lesson.description),
+        language: lesson.language,
+        difficulty: lesson.difficulty,
+        version: lesson.version,
+        path,
+        relative_path,
+    }
+}
+
+/// Import a `.shelllesson` bundle into `dest_dir/<lesson id>` and register it
+#[tauri::command]
+pub async fn import_lesson(
+    bundle_path: String,
+    dest_dir: String,
+    db: State<'_, Database>,
+) -> Result<LessonSummary> {
+    let dest_dir = Path::new(&dest_dir);
+    std::fs::create_dir_all(dest_dir)?;
+
+    // Extract next to the destination so nothing is left behind on failure
+    let staging = dest_dir.join(format!(".shell-import-{}", uuid::Uuid::new_v4()));
+    let imported = import_staged(Path::new(&bundle_path), &staging, dest_dir).await;
+    if staging.exists() {
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+    let (lesson, path) = imported?;
+
+    let relative_path = path.strip_prefix(dest_dir)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .replace('\\', "/");
+    let path = path.to_string_lossy().to_string();
+    db.upsert_lesson(&lesson.id, &lesson.title, &lesson.description, &path, &lesson.version)?;
+
+    Ok(summarize(lesson, path, relative_path))
+}
+
+/// Extract and validate a bundle in `staging`, then move it into place
+async fn import_staged(bundle_path: &Path, staging: &Path, dest_dir: &Path) -> Result<(Lesson, PathBuf)> {
+    let manifest = bundle::extract(bundle_path, staging, bundle::MAX_BUNDLE_SIZE)?;
+
+    let staged_lesson = staging.join(&manifest.lesson_file);
+    let validation = validate_lesson(staged_lesson.to_string_lossy().to_string()).await?;
+    if !validation.valid {
+        return Err(ShellError::Lesson(format!(
+            "Bundle contains an invalid lesson: {}", describe_errors(&validation.errors)
+        )));
     }
+    let lesson = read_lesson(staged_lesson.to_string_lossy().to_string()).await?;
+
+    let id_dir = PathSanitizer::relative(&lesson.id)?;
+    if id_dir.components().count() != 1 {
+        return Err(ShellError::Security(format!("Invalid lesson id for a directory name: {}", lesson.id)));
+    }
+    let target = dest_dir.join(id_dir);
+    if target.exists() {
+        return Err(ShellError::Lesson(format!("Lesson already exists: {}", target.display())));
+    }
+
+    std::fs::rename(staging, &target)?;
+    Ok((lesson, target.join(&manifest.lesson_file)))
+}
+
+/// Write a skeleton `lesson.yaml` into `dest` and return its path
+#[tauri::command]
+pub async fn create_lesson_template(
+    language: String,
+    difficulty: Difficulty,
+    dest: String,
+    with_io_diagram: Option<bool>,
+) -> Result<String> {
+    let lesson = crate::lessons::template(&language, difficulty, with_io_diagram.unwrap_or(false))?;
+
+    let dest = Path::new(&dest);
+    std::fs::create_dir_all(dest)?;
+    let path = dest.join("lesson.yaml");
+    if path.exists() {
+        return Err(ShellError::Lesson(format!("Lesson file already exists: {}", path.display())));
+    }
+
+    let path = path.to_string_lossy().to_string();
+    save_lesson(path.clone(), lesson).await?;
+    Ok(path)
+}
+
+/// Reveal a lesson hint. Hints unlock in order; already revealed hints can
+/// be shown again.
+#[tauri::command]
+pub async fn reveal_hint(lesson_id: String, index: usize, app: AppHandle, db: State<'_, Database>) -> Result<String> {
+    let path = db.get_lesson_path(&lesson_id)?
+        .ok_or_else(|| ShellError::Lesson(format!("Lesson not found: {}", lesson_id)))?;
+    let lesson = read_lesson(path).await?;
+
+    let hint = reveal_next_hint(&db, &lesson, index)?;
+    record_event(&app, AnalyticsEvent::HintRevealed { lesson_id, index });
+    Ok(hint)
+}
+
+fn reveal_next_hint(db: &Database, lesson: &Lesson, index: usize) -> Result<String> {
+    let hints = &lesson.content.hints;
+    let revealed = db.revealed_hint_count(&lesson.id)?;
+
+    if index >= hints.len() {
+        return Err(ShellError::Lesson("No more hints for this lesson".into()));
+    }
+    if index > revealed {
+        return Err(ShellError::Lesson(format!("Reveal hint {} first", revealed + 1)));
+    }
+
+    db.record_hint_reveal(&lesson.id, index)?;
+    Ok(hints[index].clone())
+}
+
+/// Check which prerequisites of a lesson the student has completed
+#[tauri::command]
+pub async fn check_prerequisites(lesson_id: String, db: State<'_, Database>) -> Result<PrereqStatus> {
+    let path = db.get_lesson_path(&lesson_id)?
+        .ok_or_else(|| ShellError::Lesson(format!("Lesson not found: {}", lesson_id)))?;
+    let lesson = read_lesson(path).await?;
+
+    prerequisite_status(&db, &lesson).await
+}
+
+async fn prerequisite_status(db: &Database, lesson: &Lesson) -> Result<PrereqStatus> {
+    let mut prerequisites = Vec::new();
+
+    for id in &lesson.prerequisites {
+        let path = db.get_lesson_path(id)?;
+        let best_score = db.best_score(id)?;
+
+        // Use the prerequisite's own passing score when its file is available
+        let passing_score = match &path {
+            Some(path) => read_lesson(path.clone()).await.ok()
+                .map_or(DEFAULT_PASSING_SCORE, |l| passing_score(&l)),
+            None => DEFAULT_PASSING_SCORE,
+        };
+
+        let state = match best_score {
+            Some(score) if score >= f64::from(passing_score) => PrereqState::Satisfied,
+            Some(_) => PrereqState::Failed,
+            None if path.is_some() => PrereqState::NotAttempted,
+            None => PrereqState::Unknown,
+        };
+        prerequisites.push(Prerequisite { lesson_id: id.clone(), state, best_score });
+    }
+
+    Ok(PrereqStatus {
+        lesson_id: lesson.id.clone(),
+        satisfied: prerequisites.iter().all(|p| p.state == PrereqState::Satisfied),
+        prerequisites,
+    })
+}
+
+/// Best score and completion of a lesson, or `None` if it was never attempted
+#[tauri::command]
+pub async fn get_lesson_progress(lesson_id: String, db: State<'_, Database>) -> Result<Option<LessonProgress>> {
+    db.get_lesson_progress(&lesson_id)
+}
+
+/// Progress on every attempted lesson, most recently submitted first
+#[tauri::command]
+pub async fn list_lesson_progress(db: State<'_, Database>) -> Result<Vec<LessonProgress>> {
+    db.list_lesson_progress()
+}
+
+/// One-line summary of validation errors for an error message
+fn describe_errors(errors: &[ValidationError]) -> String {
+    errors.iter()
+        .map(|e| if e.path.is_empty() { e.message.clone() } else { format!("{}: {}", e.path, e.message) })
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -242,12 +637,229 @@ pub struct LessonSummary {
     pub description: String,
     pub language: String,
     pub difficulty: Difficulty,
+    #[serde(default)]
+    pub version: String,
     pub path: String,
+    /// Path relative to the listed directory, `/`-separated
+    #[serde(default)]
+    pub relative_path: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub valid: bool,
-    pub errors: Vec<String>,
+    pub errors: Vec<ValidationError>,
     pub warnings: Vec<String>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrereqStatus {
+    pub lesson_id: String,
+    /// True when every prerequisite is satisfied
+    pub satisfied: bool,
+    pub prerequisites: Vec<Prerequisite>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prerequisite {
+    pub lesson_id: String,
+    pub state: PrereqState,
+    /// Best percentage scored on the prerequisite
+    pub best_score: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrereqState {
+    /// Passed at least once
+    Satisfied,
+    /// Attempted, but never reached the passing score
+    Failed,
+    /// Known locally but never attempted
+    NotAttempted,
+    /// Not in the local lesson catalog and never attempted
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SubmissionRecord;
+
+    fn submission(id: &str, lesson_id: &str, score: f64) -> SubmissionRecord {
+        SubmissionRecord {
+            id: id.into(),
+            lesson_id: Some(lesson_id.into()),
+            project_id: None,
+            submitted_at: "2026-01-13T10:30:00Z".into(),
+            status: "graded".into(),
+            score: Some(score),
+            feedback: None,
+            result: None,
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prerequisite_states() {
+        let db = Database::in_memory().unwrap();
+        for id in ["basics", "loops", "functions"] {
+            db.upsert_lesson(id, id, "", &format!("/missing/{}/lesson.yaml", id), "1.0.0").unwrap();
+        }
+        db.save_submission(&submission("s1", "basics", 40.0)).unwrap();
+        db.save_submission(&submission("s2", "basics", 85.0)).unwrap();
+        db.save_submission(&submission("s3", "loops", 69.5)).unwrap();
+
+        let mut lesson = crate::lessons::template("python", Difficulty::Beginner, false).unwrap();
+        lesson.prerequisites = vec!["basics".into(), "loops".into(), "functions".into(), "elsewhere".into()];
+
+        let status = prerequisite_status(&db, &lesson).await.unwrap();
+
+        let states: Vec<_> = status.prerequisites.iter().map(|p| p.state.clone()).collect();
+        assert_eq!(states, vec![
+            PrereqState::Satisfied,
+            PrereqState::Failed,
+            PrereqState::NotAttempted,
+            PrereqState::Unknown,
+        ]);
+        assert_eq!(status.prerequisites[0].best_score, Some(85.0));
+        assert!(!status.satisfied);
+
+        lesson.prerequisites = vec!["basics".into()];
+        assert!(prerequisite_status(&db, &lesson).await.unwrap().satisfied);
+    }
+
+    #[tokio::test]
+    async fn test_toml_lesson_round_trip() {
+        let root = std::env::temp_dir().join(format!("shell-toml-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("lesson.toml").to_string_lossy().to_string();
+        let mut lesson = crate::lessons::template("python", Difficulty::Beginner, true).unwrap();
+        lesson.constraints = Some(Constraints {
+            max_time_ms: Some(2000),
+            max_memory_bytes: None,
+            allowed_imports: None,
+            disallowed_imports: Some(vec!["os".into()]),
+            required_symbols: None,
+            max_lines: Some(20),
+        });
+        lesson.grading.as_mut().unwrap().local_tests[0].comparison = OutputMatch::Numeric { tolerance: 0.5 };
+
+        save_lesson(path.clone(), lesson.clone()).await.unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("[content]"));
+        let read = read_lesson(path).await.unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::to_value(&lesson).unwrap());
+
+        let listed = list_lessons(root.to_string_lossy().to_string(), Some(false)).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].relative_path, "lesson.toml");
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_list_lessons_dedupes_by_newest_version() {
+        let root = std::env::temp_dir().join(format!("shell-library-{}", uuid::Uuid::new_v4()));
+        for (dir, version) in [("", "1.0.0"), ("unit1/topic", "1.2.0"), ("unit2", "1.10.0"), ("unit3", "1.3.0")] {
+            let mut lesson = crate::lessons::template("python", Difficulty::Beginner, false).unwrap();
+            lesson.version = version.into();
+            if dir == "unit3" {
+                lesson.id = "other".into();
+            }
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            save_lesson(root.join(dir).join("lesson.yaml").to_string_lossy().to_string(), lesson).await.unwrap();
+        }
+        let directory = root.to_string_lossy().to_string();
+
+        let lessons = list_lessons(directory.clone(), None).await.unwrap();
+        let found: Vec<_> = lessons.iter().map(|l| (l.version.as_str(), l.relative_path.as_str())).collect();
+        assert_eq!(found, vec![("1.10.0", "unit2/lesson.yaml"), ("1.3.0", "unit3/lesson.yaml")]);
+
+        let top_level = list_lessons(directory, Some(false)).await.unwrap();
+        assert_eq!(top_level.len(), 1);
+        assert_eq!(top_level[0].relative_path, "lesson.yaml");
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_point_totals_are_checked() {
+        let mut lesson = crate::lessons::template("python", Difficulty::Beginner, false).unwrap();
+        let grading = lesson.grading.as_mut().unwrap();
+        assert!(point_warnings(grading).is_empty());
+
+        // Declared total that the tests don't reach
+        grading.total_points = Some(120.0);
+        assert_eq!(point_warnings(grading), vec!["Test points add up to 100 but total_points is 120"]);
+        grading.total_points = Some(100.0);
+        assert!(point_warnings(grading).is_empty());
+
+        // Zero-sum
+        grading.total_points = None;
+        grading.local_tests[0].points = 0.0;
+        assert_eq!(point_warnings(grading), vec!["Test points add up to 0, so every score is 0%"]);
+
+        // Hidden flags that disagree with the list
+        grading.local_tests[0].points = 50.0;
+        let mut secret = grading.local_tests[0].clone();
+        secret.id = "secret".into();
+        grading.hidden_tests = Some(vec![secret]);
+        grading.local_tests[0].hidden = true;
+        assert_eq!(point_warnings(grading), vec![
+            "Test 'prints-greeting' in local_tests is marked hidden: true; tests there should be hidden: false",
+            "Test 'secret' in hidden_tests is marked hidden: false; tests there should be hidden: true",
+        ]);
+
+        // Negative points fail validation outright
+        let root = std::env::temp_dir().join(format!("shell-points-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("lesson.yaml").to_string_lossy().to_string();
+        let grading = lesson.grading.as_mut().unwrap();
+        grading.hidden_tests = None;
+        grading.local_tests[0].hidden = false;
+        grading.local_tests[0].points = -10.0;
+        save_lesson(path.clone(), lesson).await.unwrap();
+        let validation = validate_lesson(path).await.unwrap();
+        assert!(!validation.valid);
+        assert_eq!(validation.errors[0].path, "grading.local_tests[0].points");
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_lesson_summary_by_id() {
+        let root = std::env::temp_dir().join(format!("shell-summary-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("lesson.yaml").to_string_lossy().to_string();
+        let mut lesson = crate::lessons::template("python", Difficulty::Beginner, false).unwrap();
+        lesson.title = "Loops<script>alert(1)</script>".into();
+        save_lesson(path.clone(), lesson.clone()).await.unwrap();
+
+        let db = Database::in_memory().unwrap();
+        db.upsert_lesson(&lesson.id, &lesson.title, &lesson.description, &path, &lesson.version).unwrap();
+
+        // Summaries are sanitized like the lessons they describe
+        let summary = lesson_summary(&db, &lesson.id).await.unwrap().unwrap();
+        assert_eq!((summary.id.as_str(), summary.title.as_str()), (lesson.id.as_str(), "Loops"));
+        assert_eq!(summary.language, "python");
+        assert_eq!((summary.path.as_str(), summary.relative_path.as_str()), (path.as_str(), "lesson.yaml"));
+
+        assert!(lesson_summary(&db, "missing").await.unwrap().is_none());
+        // A catalog entry whose file was deleted is as good as unknown
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(lesson_summary(&db, &lesson.id).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_hints_reveal_in_order() {
+        let db = Database::in_memory().unwrap();
+        let mut lesson = crate::lessons::template("python", Difficulty::Beginner, false).unwrap();
+        lesson.content.hints = vec!["first".into(), "second".into()];
+
+        assert!(reveal_next_hint(&db, &lesson, 1).unwrap_err().to_string().contains("Reveal hint 1 first"));
+        assert_eq!(reveal_next_hint(&db, &lesson, 0).unwrap(), "first");
+        assert_eq!(reveal_next_hint(&db, &lesson, 0).unwrap(), "first");
+        assert_eq!(db.revealed_hint_count(&lesson.id).unwrap(), 1);
+        assert_eq!(reveal_next_hint(&db, &lesson, 1).unwrap(), "second");
+        assert!(reveal_next_hint(&db, &lesson, 2).unwrap_err().to_string().contains("No more hints"));
+        assert_eq!(db.revealed_hint_count(&lesson.id).unwrap(), 2);
+    }
+}
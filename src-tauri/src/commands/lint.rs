@@ -0,0 +1,25 @@
+//! Lint IPC commands
+
+use std::path::Path;
+use std::sync::Arc;
+use tauri::State;
+use crate::docker::DockerManager;
+use crate::error::{Result, ShellError};
+use crate::lint::Diagnostic;
+use crate::security::SecurityPolicy;
+
+/// Lint a project with its language's linter, in a container
+#[tauri::command]
+pub async fn lint_code(
+    language: String,
+    project_path: String,
+    docker: State<'_, DockerManager>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<Vec<Diagnostic>> {
+    // The project is mounted into the linter's container
+    policy.validate_mount(Path::new(&project_path))?;
+    if !docker.is_available().await {
+        return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
+    }
+    crate::lint::lint(&language, &project_path, docker.inner()).await
+}
@@ -1,8 +1,16 @@
 //! LSP IPC commands
+//!
+//! Server messages are emitted as `lsp://{service_id}/message` events, and
+//! `lsp://{service_id}/exit` once the server stops.
 
 use serde::{Deserialize, Serialize};
-use tauri::State;
-use crate::db::Database;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+use crate::db::{Database, LspConfig};
+use crate::lsp::{LspEvent, LspSessions};
+use crate::security::SecurityPolicy;
 use crate::services::{ServiceManager, ServiceConfig, ServiceType};
 use crate::error::{Result, ShellError};
 
@@ -128,7 +136,7 @@ fn get_known_servers() -> Vec<LspServerInfo> {
 }
 
 /// Check if a command exists in PATH
-fn command_exists(command: &str) -> bool {
+pub fn command_exists(command: &str) -> bool {
     std::process::Command::new("which")
         .arg(command)
         .output()
@@ -136,31 +144,21 @@ fn command_exists(command: &str) -> bool {
         .unwrap_or(false)
 }
 
-/// Start a language server
-#[tauri::command]
-pub async fn start_language_server(
-    language: String,
-    project_path: String,
-    services: State<'_, ServiceManager>,
-    db: State<'_, Database>,
-) -> Result<String> {
+/// How to run `language`'s server: the user's custom configuration if
+/// there is one, otherwise an installed well-known server
+fn lsp_service(language: &str, project_path: &str, db: &Database) -> Result<ServiceConfig> {
+    let service_id = format!("lsp-{}", language);
+
     // Check if we have a custom config
-    if let Ok(Some(config)) = db.get_lsp_config(&language) {
-        let service_id = format!("lsp-{}", language);
-        
-        let service_config = ServiceConfig {
-            id: service_id.clone(),
-            service_type: ServiceType::Lsp(language.clone()),
+    if let Ok(Some(config)) = db.get_lsp_config(language) {
+        return Ok(ServiceConfig {
+            id: service_id,
+            service_type: ServiceType::Lsp(language.to_string()),
             command: config.server_path,
             args: config.args.map(|a| serde_json::from_str(&a).unwrap_or_default()).unwrap_or_default(),
-            working_dir: Some(std::path::PathBuf::from(&project_path)),
+            working_dir: Some(std::path::PathBuf::from(project_path)),
             env: std::collections::HashMap::new(),
-        };
-        
-        services.register(service_config).await;
-        services.start(&service_id).await?;
-        
-        return Ok(service_id);
+        });
     }
     
     // Find a known server for this language
@@ -178,31 +176,149 @@ pub async fn start_language_server(
         )));
     }
     
-    let service_id = format!("lsp-{}", language);
-    
-    let service_config = ServiceConfig {
-        id: service_id.clone(),
-        service_type: ServiceType::Lsp(language),
+    Ok(ServiceConfig {
+        id: service_id,
+        service_type: ServiceType::Lsp(language.to_string()),
         command: server.command.clone(),
         args: server.args.clone(),
-        working_dir: Some(std::path::PathBuf::from(&project_path)),
+        working_dir: Some(std::path::PathBuf::from(project_path)),
         env: std::collections::HashMap::new(),
-    };
-    
-    services.register(service_config).await;
-    services.start(&service_id).await?;
-    
+    })
+}
+
+/// Start a language server. `initialize_options` go into the UI's first
+/// `initialize` request; without them the stored custom settings are used.
+#[tauri::command]
+pub async fn start_language_server(
+    language: String,
+    project_path: String,
+    initialize_options: Option<Value>,
+    app: AppHandle,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    services: State<'_, ServiceManager>,
+    sessions: State<'_, LspSessions>,
+    db: State<'_, Database>,
+) -> Result<String> {
+    // The server runs on the host, so it's held to the same rules as a
+    // local terminal or debugger
+    policy.check_host_execution()?;
+    policy.validate_path(Path::new(&project_path))?;
+    let service_config = lsp_service(&language, &project_path, &db)?;
+    let service_id = service_config.id.clone();
+    let initialize_options = initialize_options.or_else(|| {
+        let settings = db.get_lsp_config(&language).ok().flatten()?.settings?;
+        serde_json::from_str(&settings).ok()
+    });
+
+    let listener = Arc::new(move |service_id: &str, event: LspEvent| {
+        let _ = match event {
+            LspEvent::Message(message) => app.emit(&format!("lsp://{}/message", service_id), message),
+            LspEvent::Closed => app.emit(&format!("lsp://{}/exit", service_id), ()),
+        };
+    });
+    sessions.start(&services, service_config, initialize_options, listener).await?;
     Ok(service_id)
 }
 
+/// Send a JSON-RPC message to a language's server
+#[tauri::command]
+pub async fn lsp_send(
+    language: String,
+    message: Value,
+    sessions: State<'_, LspSessions>,
+) -> Result<()> {
+    sessions.send(&format!("lsp-{}", language), message).await
+}
+
+/// Push new workspace settings to a language's server with
+/// `workspace/didChangeConfiguration`
+#[tauri::command]
+pub async fn lsp_change_configuration(
+    language: String,
+    settings: Value,
+    sessions: State<'_, LspSessions>,
+) -> Result<()> {
+    sessions.change_configuration(&format!("lsp-{}", language), settings).await
+}
+
+/// Check that a custom server is an absolute path to an executable file
+fn validate_server_path(server_path: &str) -> Result<()> {
+    let path = Path::new(server_path);
+    if !path.is_absolute() {
+        return Err(ShellError::Configuration(format!("LSP server path must be absolute: {}", server_path)));
+    }
+    let metadata = std::fs::metadata(path)
+        .map_err(|_| ShellError::Configuration(format!("LSP server not found: {}", server_path)))?;
+    if !metadata.is_file() {
+        return Err(ShellError::Configuration(format!("LSP server is not a file: {}", server_path)));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(ShellError::Configuration(format!("LSP server is not executable: {}", server_path)));
+        }
+    }
+    Ok(())
+}
+
+/// Use a custom language server for `language`, e.g. a locally built
+/// rust-analyzer. Replaces any earlier configuration.
+#[tauri::command]
+pub async fn set_lsp_config(
+    language: String,
+    server_path: String,
+    args: Option<Vec<String>>,
+    settings: Option<Value>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<LspConfig> {
+    // A custom server is any host program, which an exam rules out
+    if policy.exam().is_some() {
+        return Err(ShellError::Security("Language server settings can't be changed during an exam".into()));
+    }
+    save_lsp_config(&db, &language, &server_path, args, settings)
+}
+
+fn save_lsp_config(
+    db: &Database,
+    language: &str,
+    server_path: &str,
+    args: Option<Vec<String>>,
+    settings: Option<Value>,
+) -> Result<LspConfig> {
+    if language.is_empty() || !language.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(ShellError::Configuration(format!("Invalid language: {}", language)));
+    }
+    validate_server_path(server_path)?;
+
+    let args = args.map(|args| serde_json::to_string(&args)).transpose()?;
+    let settings = settings.map(|settings| settings.to_string());
+    db.save_lsp_config(language, server_path, args.as_deref(), settings.as_deref())?;
+    Ok(LspConfig { language: language.to_string(), server_path: server_path.to_string(), args, settings })
+}
+
+/// The custom server configured for `language`, if any
+#[tauri::command]
+pub async fn get_lsp_config(language: String, db: State<'_, Database>) -> Result<Option<LspConfig>> {
+    db.get_lsp_config(&language)
+}
+
+/// Every custom server configuration
+#[tauri::command]
+pub async fn list_lsp_configs(db: State<'_, Database>) -> Result<Vec<LspConfig>> {
+    db.list_lsp_configs()
+}
+
 /// Stop a language server
 #[tauri::command]
 pub async fn stop_language_server(
     language: String,
     services: State<'_, ServiceManager>,
+    sessions: State<'_, LspSessions>,
 ) -> Result<()> {
     let service_id = format!("lsp-{}", language);
-    services.stop(&service_id).await
+    sessions.stop(&services, &service_id).await
 }
 
 /// Get available language servers
@@ -217,3 +333,42 @@ pub async fn get_available_servers() -> Result<Vec<LspServerInfo>> {
     
     Ok(servers)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_custom_config_round_trips_and_starts_server() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("shell-lsp-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let server = dir.join("rust-analyzer");
+        std::fs::write(&server, "#!/bin/sh\n").unwrap();
+        let server_path = server.to_string_lossy().into_owned();
+        let db = Database::in_memory().unwrap();
+
+        std::fs::set_permissions(&server, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let err = save_lsp_config(&db, "rust", &server_path, None, None).unwrap_err();
+        assert!(err.to_string().contains("not executable"));
+        assert!(save_lsp_config(&db, "rust", "rust-analyzer", None, None).is_err());
+        assert!(save_lsp_config(&db, "rust", "/nonexistent/rust-analyzer", None, None).is_err());
+
+        std::fs::set_permissions(&server, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let settings = serde_json::json!({ "checkOnSave": false });
+        save_lsp_config(&db, "rust", &server_path, Some(vec!["--log-file".into(), "/tmp/ra.log".into()]), Some(settings)).unwrap();
+
+        let stored = db.get_lsp_config("rust").unwrap().unwrap();
+        assert_eq!(stored.args.as_deref(), Some(r#"["--log-file","/tmp/ra.log"]"#));
+        assert_eq!(stored.settings.as_deref(), Some(r#"{"checkOnSave":false}"#));
+        assert_eq!(db.list_lsp_configs().unwrap().len(), 1);
+
+        let service = lsp_service("rust", "/projects/demo", &db).unwrap();
+        assert_eq!(service.id, "lsp-rust");
+        assert_eq!(service.command, server_path);
+        assert_eq!(service.args, vec!["--log-file", "/tmp/ra.log"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
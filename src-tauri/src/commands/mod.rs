@@ -2,10 +2,23 @@
 //!
 //! All Tauri commands that bridge Rust and the frontend.
 
+pub mod analytics;
+pub mod classroom;
+pub mod dap;
+pub mod dependencies;
+pub mod exam;
 pub mod execution;
 pub mod features;
+pub mod format;
 pub mod fs;
+pub mod git;
 pub mod grading;
 pub mod lessons;
+pub mod lint;
 pub mod lsp;
+pub mod plagiarism;
+pub mod search;
+pub mod session;
 pub mod settings;
+pub mod snapshot;
+pub mod terminal;
@@ -0,0 +1,54 @@
+//! Plagiarism detection IPC commands
+//!
+//! Only stored submissions are compared; both commands require the
+//! `plagiarism_detection` feature.
+
+use std::sync::RwLock;
+use tauri::State;
+use crate::db::Database;
+use crate::error::{Result, ShellError};
+use crate::features::FeatureFlags;
+use crate::plagiarism::{self, SimilarPair, SimilarityReport};
+
+/// Compare the code of two stored submissions
+#[tauri::command]
+pub async fn compare_submissions(
+    a: String,
+    b: String,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<SimilarityReport> {
+    require_plagiarism_detection(&features)?;
+    Ok(plagiarism::compare(&submission_source(&db, &a)?, &submission_source(&db, &b)?))
+}
+
+/// Pairs of a lesson's submissions with similarity at or above `threshold` (0 to 1)
+#[tauri::command]
+pub async fn detect_similar(
+    lesson_id: String,
+    threshold: f64,
+    db: State<'_, Database>,
+    features: State<'_, RwLock<FeatureFlags>>,
+) -> Result<Vec<SimilarPair>> {
+    require_plagiarism_detection(&features)?;
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(ShellError::Configuration("Similarity threshold must be between 0 and 1".into()));
+    }
+    Ok(plagiarism::detect_similar(&db.list_submission_sources(&lesson_id)?, threshold))
+}
+
+fn require_plagiarism_detection(features: &RwLock<FeatureFlags>) -> Result<()> {
+    let flags = features.read()
+        .map_err(|_| ShellError::Security("Failed to read feature flags".into()))?;
+    if !flags.plagiarism_detection {
+        return Err(ShellError::FeatureNotAvailable("Plagiarism detection requires an institution license.".into()));
+    }
+    Ok(())
+}
+
+fn submission_source(db: &Database, id: &str) -> Result<String> {
+    db.get_submission(id)?
+        .ok_or_else(|| ShellError::Lesson(format!("Submission not found: {}", id)))?
+        .source
+        .ok_or_else(|| ShellError::Lesson(format!("Submission {} has no stored code", id)))
+}
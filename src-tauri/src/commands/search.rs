@@ -0,0 +1,77 @@
+//! Project search IPC commands
+//!
+//! Searches and tree walks accept an optional `operation_id` that
+//! `cancel_operation` can later cancel. Quick open uses a per-project file
+//! index kept in memory.
+
+use std::path::Path;
+use std::sync::Arc;
+use tauri::State;
+use crate::error::{Result, ShellError};
+use crate::file_index::{FileIndex, FileIndexes, FileMatch};
+use crate::operations::OperationRegistry;
+use crate::search::{self, FileTree, SearchOptions, SearchResults};
+use crate::security::SecurityPolicy;
+
+/// Search the text files of a project
+#[tauri::command]
+pub async fn search_project(
+    root: String,
+    query: String,
+    options: Option<SearchOptions>,
+    operation_id: Option<String>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    operations: State<'_, OperationRegistry>,
+) -> Result<SearchResults> {
+    policy.validate_path(Path::new(&root))?;
+    let operation = operations.register(operation_id)?;
+    let token = operation.token().clone();
+    tokio::task::spawn_blocking(move || {
+        search::search(Path::new(&root), &query, &options.unwrap_or_default(), &token)
+    }).await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+/// List every file and directory in a project
+#[tauri::command]
+pub async fn read_tree(
+    root: String,
+    operation_id: Option<String>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    operations: State<'_, OperationRegistry>,
+) -> Result<FileTree> {
+    policy.validate_path(Path::new(&root))?;
+    let operation = operations.register(operation_id)?;
+    let token = operation.token().clone();
+    tokio::task::spawn_blocking(move || search::read_tree(Path::new(&root), &token))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+/// Index a project's files for quick open, replacing any earlier index
+#[tauri::command]
+pub async fn build_file_index(
+    project_path: String,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    indexes: State<'_, FileIndexes>,
+) -> Result<FileIndex> {
+    policy.validate_path(Path::new(&project_path))?;
+    indexes.build(Path::new(&project_path))
+}
+
+/// Fuzzy-match `query` against a project's indexed files, best first
+#[tauri::command]
+pub async fn query_file_index(
+    project_path: String,
+    query: String,
+    limit: Option<usize>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    indexes: State<'_, FileIndexes>,
+) -> Result<Vec<FileMatch>> {
+    policy.validate_path(Path::new(&project_path))?;
+    indexes.query(Path::new(&project_path), &query, limit)
+}
+
+/// Cancel a running operation. Returns false if it had already finished.
+#[tauri::command]
+pub fn cancel_operation(operation_id: String, operations: State<'_, OperationRegistry>) -> bool {
+    operations.cancel(&operation_id)
+}
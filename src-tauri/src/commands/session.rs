@@ -0,0 +1,18 @@
+//! Editor session IPC commands
+
+use tauri::State;
+use crate::db::Database;
+use crate::error::Result;
+use crate::session::{self, SessionState};
+
+/// Remember a project's open files, active file and cursors
+#[tauri::command]
+pub async fn save_session(project_id: String, state: SessionState, db: State<'_, Database>) -> Result<()> {
+    session::save(&db, &project_id, &state)
+}
+
+/// A project's saved session, without files deleted since it was saved
+#[tauri::command]
+pub async fn load_session(project_id: String, db: State<'_, Database>) -> Result<Option<SessionState>> {
+    session::load(&db, &project_id)
+}
@@ -1,9 +1,55 @@
 //! Settings IPC commands
 
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
 use tauri::State;
 use crate::db::Database;
-use crate::error::Result;
+use crate::error::{Result, ShellError};
+use crate::fs::FileSystem;
+use crate::git::GitAuthor;
+use crate::security::SecurityPolicy;
+use crate::terminal::TerminalBackend;
+
+/// Format version written by `export_settings`
+const SETTINGS_EXPORT_VERSION: u32 = 1;
+
+/// A project's settings override: a subset of `Settings` fields
+pub type SettingsOverride = serde_json::Map<String, serde_json::Value>;
+
+/// Settings in effect for a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveSettings {
+    pub settings: Settings,
+    /// Fields the project overrides, in name order
+    pub overridden: Vec<String>,
+}
+
+/// Shareable settings file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsExport {
+    pub version: u32,
+    pub settings: Settings,
+    /// Per-project overrides, keyed by project id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, SettingsOverride>,
+}
+
+/// A field left out of an import
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedSetting {
+    /// Setting name, prefixed with `projects.<id>.` for project overrides
+    pub field: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsImport {
+    /// Global settings after the import
+    pub settings: Settings,
+    pub skipped: Vec<SkippedSetting>,
+}
 
 /// User settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,7 +68,125 @@ pub struct Settings {
     pub format_on_save: bool,
     pub default_language: Option<String>,
     pub docker_enabled: bool,
+    /// Default run timeout, in seconds
     pub execution_timeout: u32,
+    /// Default memory limit of a run, in MB
+    #[serde(default = "default_execution_memory_mb")]
+    pub execution_memory_mb: u32,
+    /// Default CPU share of a run, as a percentage of one CPU
+    #[serde(default = "default_execution_cpu_percent")]
+    pub execution_cpu_percent: u32,
+    /// Base URL of the cloud grading service
+    #[serde(default)]
+    pub cloud_grading_url: Option<String>,
+    /// Where opted-in analytics are uploaded; kept local when unset
+    #[serde(default)]
+    pub analytics_url: Option<String>,
+    #[serde(default = "default_keybindings")]
+    pub keybindings: Vec<Keybinding>,
+    /// Author for commits made in the IDE; falls back to git config
+    #[serde(default)]
+    pub git_author: Option<GitAuthor>,
+    /// Where terminal sessions run
+    #[serde(default)]
+    pub terminal_backend: TerminalBackend,
+}
+
+/// A keyboard shortcut for an editor command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keybinding {
+    /// Command id, e.g. `file.save`
+    pub command: String,
+    /// Modifiers and key joined by `+`, e.g. `CmdOrCtrl+Shift+N`
+    pub key: String,
+    /// Context the binding is limited to, e.g. `editorFocus`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+}
+
+/// Modifiers in the order normalized keys list them
+const KEY_MODIFIERS: [&str; 5] = ["cmdorctrl", "cmd", "ctrl", "alt", "shift"];
+
+impl Keybinding {
+    fn new(command: &str, key: &str) -> Self {
+        Self { command: command.to_string(), key: key.to_string(), when: None }
+    }
+
+    /// Lowercase key with modifiers in canonical order, so `shift+ctrl+p`
+    /// and `Ctrl+Shift+P` compare equal
+    pub fn normalized_key(&self) -> Result<String> {
+        let invalid = |reason: &str| ShellError::Configuration(format!("Invalid key '{}' for {}: {}", self.key, self.command, reason));
+
+        let lower = self.key.to_lowercase();
+        // `+` itself can be the key, as in `Ctrl++`
+        let (modifiers, key) = match lower.strip_suffix("++") {
+            Some(rest) => (rest, "+"),
+            None => lower.rsplit_once('+').unwrap_or(("", &lower)),
+        };
+        if key.is_empty() {
+            return Err(invalid("missing key"));
+        }
+
+        let mut parts: Vec<&str> = Vec::new();
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            let Some(canonical) = KEY_MODIFIERS.iter().find(|m| **m == modifier) else {
+                return Err(invalid(&format!("unknown modifier '{}'", modifier)));
+            };
+            if !parts.contains(canonical) {
+                parts.push(canonical);
+            }
+        }
+        parts.sort_by_key(|m| KEY_MODIFIERS.iter().position(|k| k == m));
+        parts.push(key);
+        Ok(parts.join("+"))
+    }
+}
+
+fn default_execution_memory_mb() -> u32 {
+    256
+}
+
+fn default_execution_cpu_percent() -> u32 {
+    50
+}
+
+fn default_keybindings() -> Vec<Keybinding> {
+    [
+        ("file.save", "CmdOrCtrl+S"),
+        ("tab.close", "CmdOrCtrl+W"),
+        ("tab.next", "Ctrl+Tab"),
+        ("tab.previous", "Ctrl+Shift+Tab"),
+        ("project.new", "CmdOrCtrl+N"),
+        ("window.new", "CmdOrCtrl+Shift+N"),
+        ("terminal.toggle", "Ctrl+`"),
+        ("panel.toggle", "CmdOrCtrl+J"),
+        ("sidebar.toggle", "CmdOrCtrl+B"),
+        ("explorer.show", "CmdOrCtrl+Shift+E"),
+        ("search.files", "CmdOrCtrl+Shift+F"),
+        ("lessons.show", "CmdOrCtrl+Shift+L"),
+        ("settings.open", "CmdOrCtrl+,"),
+        ("code.run", "CmdOrCtrl+Shift+Enter"),
+    ]
+    .iter().map(|(command, key)| Keybinding::new(command, key)).collect()
+}
+
+/// Reject malformed keys and keys bound to two commands in the same context
+pub fn validate_keybindings(keybindings: &[Keybinding]) -> Result<()> {
+    let mut seen: HashMap<(String, Option<&str>), &str> = HashMap::new();
+    for binding in keybindings {
+        if binding.command.trim().is_empty() {
+            return Err(ShellError::Configuration(format!("Keybinding '{}' has no command", binding.key)));
+        }
+        let slot = (binding.normalized_key()?, binding.when.as_deref());
+        if let Some(existing) = seen.insert(slot, &binding.command) {
+            if existing != binding.command {
+                return Err(ShellError::Configuration(format!(
+                    "Keybinding conflict: {} is bound to both {} and {}", binding.key, existing, binding.command
+                )));
+            }
+        }
+    }
+    Ok(())
 }
 
 impl Default for Settings {
@@ -43,26 +207,372 @@ impl Default for Settings {
             default_language: None,
             docker_enabled: true,
             execution_timeout: 30,
+            execution_memory_mb: default_execution_memory_mb(),
+            execution_cpu_percent: default_execution_cpu_percent(),
+            cloud_grading_url: None,
+            analytics_url: None,
+            keybindings: default_keybindings(),
+            git_author: None,
+            terminal_backend: TerminalBackend::Auto,
         }
     }
 }
 
-/// Get user settings
-#[tauri::command]
-pub async fn get_settings(db: State<'_, Database>) -> Result<Settings> {
+/// Load stored settings, falling back to defaults
+pub fn load_settings(db: &Database) -> Settings {
     if let Ok(Some(json)) = db.get_setting("settings") {
         if let Ok(settings) = serde_json::from_str(&json) {
-            return Ok(settings);
+            return settings;
         }
     }
     
-    Ok(Settings::default())
+    Settings::default()
+}
+
+/// Get user settings
+#[tauri::command]
+pub async fn get_settings(db: State<'_, Database>) -> Result<Settings> {
+    Ok(load_settings(&db))
 }
 
 /// Update user settings
 #[tauri::command]
 pub async fn update_settings(settings: Settings, db: State<'_, Database>) -> Result<()> {
+    validate_keybindings(&settings.keybindings)?;
     let json = serde_json::to_string(&settings)?;
     db.set_setting("settings", &json)?;
     Ok(())
 }
+
+/// Restore global settings to defaults in a single write
+fn reset_all(db: &Database) -> Result<Settings> {
+    let settings = Settings::default();
+    db.set_setting("settings", &serde_json::to_string(&settings)?)?;
+    Ok(settings)
+}
+
+/// Restore one global setting to its default, keeping the rest
+fn reset_field(db: &Database, field_name: &str) -> Result<Settings> {
+    let default = serde_json::to_value(Settings::default())?;
+    let value = default.get(field_name)
+        .ok_or_else(|| ShellError::Configuration(format!("Unknown setting: {}", field_name)))?;
+
+    let mut current = serde_json::to_value(load_settings(db))?;
+    current[field_name] = value.clone();
+    let settings: Settings = serde_json::from_value(current)?;
+    db.set_setting("settings", &serde_json::to_string(&settings)?)?;
+    Ok(settings)
+}
+
+/// Reset all settings to defaults
+#[tauri::command]
+pub async fn reset_settings(db: State<'_, Database>) -> Result<Settings> {
+    reset_all(&db)
+}
+
+/// Reset a single setting to its default
+#[tauri::command]
+pub async fn reset_settings_field(field_name: String, db: State<'_, Database>) -> Result<Settings> {
+    reset_field(&db, &field_name)
+}
+
+/// Get the keybindings in effect
+#[tauri::command]
+pub async fn get_keybindings(db: State<'_, Database>) -> Result<Vec<Keybinding>> {
+    Ok(load_settings(&db).keybindings)
+}
+
+/// Replace all keybindings, rejecting conflicts
+#[tauri::command]
+pub async fn update_keybindings(keybindings: Vec<Keybinding>, db: State<'_, Database>) -> Result<Vec<Keybinding>> {
+    validate_keybindings(&keybindings)?;
+    let settings = Settings { keybindings, ..load_settings(&db) };
+    db.set_setting("settings", &serde_json::to_string(&settings)?)?;
+    Ok(settings.keybindings)
+}
+
+/// Restore the default keybindings
+#[tauri::command]
+pub async fn reset_keybindings(db: State<'_, Database>) -> Result<Vec<Keybinding>> {
+    Ok(reset_field(&db, "keybindings")?.keybindings)
+}
+
+/// Layer a project's override on top of global settings
+pub fn layer_settings(global: &Settings, overrides: &SettingsOverride) -> Result<EffectiveSettings> {
+    let serde_json::Value::Object(mut merged) = serde_json::to_value(global)? else {
+        unreachable!("Settings serializes to an object");
+    };
+    for (field, value) in overrides {
+        if !merged.contains_key(field) {
+            return Err(ShellError::Configuration(format!("Unknown setting: {}", field)));
+        }
+        merged.insert(field.clone(), value.clone());
+    }
+
+    let settings: Settings = serde_json::from_value(serde_json::Value::Object(merged))
+        .map_err(|e| ShellError::Configuration(format!("Invalid project setting: {}", e)))?;
+    validate_keybindings(&settings.keybindings)?;
+    let mut overridden: Vec<String> = overrides.keys().cloned().collect();
+    overridden.sort();
+    Ok(EffectiveSettings { settings, overridden })
+}
+
+fn load_override(db: &Database, project_id: &str) -> Result<SettingsOverride> {
+    match db.get_project_settings(project_id)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(SettingsOverride::new()),
+    }
+}
+
+fn effective_settings(db: &Database, project_id: &str) -> Result<EffectiveSettings> {
+    layer_settings(&load_settings(db), &load_override(db, project_id)?)
+}
+
+/// Merge `partial` into a project's override. A `null` field clears the
+/// override so the global value applies again.
+fn apply_project_settings(db: &Database, project_id: &str, partial: SettingsOverride) -> Result<EffectiveSettings> {
+    let mut overrides = load_override(db, project_id)?;
+    for (field, value) in partial {
+        if value.is_null() {
+            overrides.remove(&field);
+        } else {
+            overrides.insert(field, value);
+        }
+    }
+
+    // Validate against the current globals before storing
+    let effective = layer_settings(&load_settings(db), &overrides)?;
+    let json = if overrides.is_empty() { None } else { Some(serde_json::to_string(&overrides)?) };
+    db.set_project_settings(project_id, json.as_deref())?;
+    Ok(effective)
+}
+
+fn export_document(db: &Database, include_projects: bool) -> Result<SettingsExport> {
+    let mut projects = BTreeMap::new();
+    if include_projects {
+        for (id, json) in db.list_project_settings()? {
+            projects.insert(id, serde_json::from_str(&json)?);
+        }
+    }
+    Ok(SettingsExport { version: SETTINGS_EXPORT_VERSION, settings: load_settings(db), projects })
+}
+
+/// Split `fields` into those valid for `Settings` and skipped ones
+fn valid_fields(fields: SettingsOverride, prefix: &str, skipped: &mut Vec<SkippedSetting>) -> SettingsOverride {
+    let default = Settings::default();
+    fields.into_iter()
+        .filter(|(field, value)| {
+            let single = SettingsOverride::from_iter([(field.clone(), value.clone())]);
+            match layer_settings(&default, &single) {
+                Ok(_) => true,
+                Err(e) => {
+                    skipped.push(SkippedSetting { field: format!("{}{}", prefix, field), reason: e.to_string() });
+                    false
+                }
+            }
+        })
+        .collect()
+}
+
+/// Apply a settings file. `merge` keeps current values for fields the file
+/// omits; otherwise they reset to defaults. Invalid fields are skipped.
+fn import_document(db: &Database, content: &str, merge: bool) -> Result<SettingsImport> {
+    let document: serde_json::Value = serde_json::from_str(content)?;
+    let Some(fields) = document.get("settings").and_then(|v| v.as_object()) else {
+        return Err(ShellError::Configuration("Not a settings file: missing \"settings\" object".into()));
+    };
+
+    let mut skipped = Vec::new();
+    let fields = valid_fields(fields.clone(), "", &mut skipped);
+    let base = if merge { load_settings(db) } else { Settings::default() };
+    let settings = layer_settings(&base, &fields)?.settings;
+
+    let projects = document.get("projects").and_then(|v| v.as_object()).cloned().unwrap_or_default();
+    for (id, overrides) in projects {
+        let prefix = format!("projects.{}.", id);
+        let Some(overrides) = overrides.as_object() else {
+            skipped.push(SkippedSetting { field: id, reason: "Project override is not an object".into() });
+            continue;
+        };
+        let overrides = valid_fields(overrides.clone(), &prefix, &mut skipped);
+        let result = if merge {
+            apply_project_settings(db, &id, overrides).map(|_| ())
+        } else {
+            let json = serde_json::to_string(&overrides)?;
+            db.set_project_settings(&id, (!overrides.is_empty()).then_some(json.as_str()))
+        };
+        if let Err(e) = result {
+            skipped.push(SkippedSetting { field: prefix.trim_end_matches('.').to_string(), reason: e.to_string() });
+        }
+    }
+
+    db.set_setting("settings", &serde_json::to_string(&settings)?)?;
+    Ok(SettingsImport { settings, skipped })
+}
+
+/// Write current settings, optionally with project overrides, to a JSON file
+#[tauri::command]
+pub async fn export_settings(
+    path: String,
+    include_projects: Option<bool>,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<()> {
+    let document = export_document(&db, include_projects.unwrap_or(false))?;
+    FileSystem::new(Arc::clone(&policy)).write_file(Path::new(&path), &serde_json::to_string_pretty(&document)?)
+}
+
+/// Load settings from a file written by `export_settings`
+#[tauri::command]
+pub async fn import_settings(
+    path: String,
+    merge: bool,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<SettingsImport> {
+    let file = FileSystem::new(Arc::clone(&policy)).read_file(Path::new(&path))?;
+    import_document(&db, &file.content, merge)
+}
+
+/// Get settings for a project, with its overrides applied
+#[tauri::command]
+pub async fn get_effective_settings(project_id: String, db: State<'_, Database>) -> Result<EffectiveSettings> {
+    effective_settings(&db, &project_id)
+}
+
+/// Override some settings for a project; `null` fields revert to global
+#[tauri::command]
+pub async fn update_project_settings(
+    project_id: String,
+    partial: SettingsOverride,
+    db: State<'_, Database>,
+) -> Result<EffectiveSettings> {
+    apply_project_settings(&db, &project_id, partial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn partial(value: serde_json::Value) -> SettingsOverride {
+        value.as_object().unwrap().clone()
+    }
+
+    fn project_db() -> Database {
+        let db = Database::in_memory().unwrap();
+        db.register_project("p1", "Project", "/projects/p1", Some("python")).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_keybinding_conflicts() {
+        assert!(validate_keybindings(&default_keybindings()).is_ok());
+
+        let mut bindings = default_keybindings();
+        bindings.push(Keybinding::new("editor.format", "shift+cmdorctrl+f"));
+        let err = validate_keybindings(&bindings).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid configuration: Keybinding conflict: shift+cmdorctrl+f is bound to both search.files and editor.format");
+
+        // Same key in a different context is fine
+        bindings.last_mut().unwrap().when = Some("editorFocus".into());
+        assert!(validate_keybindings(&bindings).is_ok());
+
+        assert!(validate_keybindings(&[Keybinding::new("file.save", "Hyper+S")]).is_err());
+        assert_eq!(Keybinding::new("zoom.in", "Ctrl++").normalized_key().unwrap(), "ctrl++");
+
+        let conflicting = json!([{"command": "a", "key": "Ctrl+K"}, {"command": "b", "key": "ctrl+k"}]);
+        assert!(layer_settings(&Settings::default(), &partial(json!({"keybindings": conflicting}))).is_err());
+    }
+
+    #[test]
+    fn test_reset_round_trip() {
+        let db = Database::in_memory().unwrap();
+        let mutated = Settings { tab_size: 8, word_wrap: true, theme: "broken".into(), ..Settings::default() };
+        db.set_setting("settings", &serde_json::to_string(&mutated).unwrap()).unwrap();
+
+        let settings = reset_field(&db, "theme").unwrap();
+        assert_eq!(load_settings(&db).theme, Settings::default().theme);
+        assert_eq!((settings.tab_size, load_settings(&db).tab_size), (8, 8));
+        assert!(reset_field(&db, "no_such_field").is_err());
+
+        reset_all(&db).unwrap();
+        assert_eq!(serde_json::to_value(load_settings(&db)).unwrap(), serde_json::to_value(Settings::default()).unwrap());
+    }
+
+    #[test]
+    fn test_import_merge_vs_replace() {
+        let db = project_db();
+        let current = Settings { font_size: 18, ..Settings::default() };
+        db.set_setting("settings", &serde_json::to_string(&current).unwrap()).unwrap();
+        let file = json!({"version": 1, "settings": {"tab_size": 2}}).to_string();
+
+        let merged = import_document(&db, &file, true).unwrap().settings;
+        assert_eq!((merged.tab_size, merged.font_size), (2, 18));
+
+        let replaced = import_document(&db, &file, false).unwrap().settings;
+        assert_eq!((replaced.tab_size, replaced.font_size), (2, Settings::default().font_size));
+        assert_eq!(load_settings(&db).font_size, Settings::default().font_size);
+    }
+
+    #[test]
+    fn test_import_skips_invalid_fields() {
+        let db = project_db();
+        apply_project_settings(&db, "p1", partial(json!({"tab_size": 2}))).unwrap();
+        let exported = serde_json::to_value(export_document(&db, true).unwrap()).unwrap();
+        assert_eq!(exported["projects"]["p1"], json!({"tab_size": 2}));
+
+        let file = json!({
+            "version": 1,
+            "settings": {"word_wrap": true, "tab_size": "wide", "vim_mode": true},
+            "projects": {"p1": {"format_on_save": true, "colour": "red"}, "gone": {"tab_size": 3}},
+        });
+        let import = import_document(&db, &file.to_string(), true).unwrap();
+
+        assert!(import.settings.word_wrap);
+        assert_eq!(import.settings.tab_size, Settings::default().tab_size);
+        let skipped: Vec<_> = import.skipped.iter().map(|s| s.field.as_str()).collect();
+        assert_eq!(skipped, vec!["tab_size", "vim_mode", "projects.gone", "projects.p1.colour"]);
+        assert_eq!(effective_settings(&db, "p1").unwrap().overridden, vec!["format_on_save", "tab_size"]);
+
+        assert!(import_document(&db, "{\"theme\": \"x\"}", true).is_err());
+    }
+
+    #[test]
+    fn test_project_override_layers_on_globals() {
+        let db = project_db();
+        let global = Settings { font_size: 16, ..Settings::default() };
+        db.set_setting("settings", &serde_json::to_string(&global).unwrap()).unwrap();
+
+        let effective = apply_project_settings(&db, "p1", partial(json!({"tab_size": 2, "format_on_save": true}))).unwrap();
+        assert_eq!(effective.overridden, vec!["format_on_save", "tab_size"]);
+        assert_eq!((effective.settings.tab_size, effective.settings.format_on_save), (2, true));
+        assert_eq!(effective.settings.font_size, 16);
+
+        // Re-registering the project keeps its override
+        db.register_project("p1", "Renamed", "/projects/p1", Some("python")).unwrap();
+        assert_eq!(effective_settings(&db, "p1").unwrap().settings.tab_size, 2);
+    }
+
+    #[test]
+    fn test_clearing_override_reverts_to_global() {
+        let db = project_db();
+        apply_project_settings(&db, "p1", partial(json!({"tab_size": 2, "word_wrap": true}))).unwrap();
+
+        let effective = apply_project_settings(&db, "p1", partial(json!({"tab_size": null}))).unwrap();
+        assert_eq!(effective.overridden, vec!["word_wrap"]);
+        assert_eq!(effective.settings.tab_size, Settings::default().tab_size);
+
+        apply_project_settings(&db, "p1", partial(json!({"word_wrap": null}))).unwrap();
+        assert_eq!(db.get_project_settings("p1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_overrides_are_rejected() {
+        let db = project_db();
+        assert!(apply_project_settings(&db, "p1", partial(json!({"tab_width": 2}))).is_err());
+        assert!(apply_project_settings(&db, "p1", partial(json!({"tab_size": "two"}))).is_err());
+        assert!(apply_project_settings(&db, "missing", partial(json!({"tab_size": 2}))).is_err());
+        assert_eq!(db.get_project_settings("p1").unwrap(), None);
+    }
+}
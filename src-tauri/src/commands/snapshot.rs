@@ -0,0 +1,66 @@
+//! Project snapshot IPC commands
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use crate::commands::features::app_data_dir;
+use crate::db::{Database, SnapshotRecord};
+use crate::error::{Result, ShellError};
+use crate::security::SecurityPolicy;
+use crate::snapshot;
+
+/// Archive a project's current files; returns the snapshot id
+#[tauri::command]
+pub async fn create_snapshot(
+    project_path: String,
+    label: String,
+    app: AppHandle,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<String> {
+    let project = project_dir(&project_path, &policy)?;
+    let snapshots_dir = snapshots_dir(&app)?;
+    let record = tokio::task::spawn_blocking(move || snapshot::create(&project, &label, &snapshots_dir))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))??;
+    db.save_snapshot(&record)?;
+    Ok(record.id)
+}
+
+/// A project's snapshots, newest first
+#[tauri::command]
+pub async fn list_snapshots(
+    project_path: String,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<Vec<SnapshotRecord>> {
+    let project = project_dir(&project_path, &policy)?;
+    db.list_snapshots(&project.to_string_lossy())
+}
+
+/// Replace a project's files with a snapshot's
+#[tauri::command]
+pub async fn restore_snapshot(
+    snapshot_id: String,
+    app: AppHandle,
+    db: State<'_, Database>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+) -> Result<()> {
+    let record = db.get_snapshot(&snapshot_id)?
+        .ok_or_else(|| ShellError::Configuration(format!("Unknown snapshot: {}", snapshot_id)))?;
+    let project = project_dir(&record.project_path, &policy)?;
+    let archive = snapshot::archive_path(&snapshots_dir(&app)?, &record.id);
+    tokio::task::spawn_blocking(move || snapshot::restore(&project, &archive))
+        .await.map_err(|e| ShellError::Execution(e.to_string()))?
+}
+
+/// Canonical form of a policy-checked project directory, so snapshots of
+/// one project share a key however its path was spelled
+fn project_dir(project_path: &str, policy: &SecurityPolicy) -> Result<PathBuf> {
+    let project = Path::new(project_path);
+    policy.validate_path(project)?;
+    Ok(project.canonicalize()?)
+}
+
+fn snapshots_dir(app: &AppHandle) -> Result<PathBuf> {
+    Ok(app_data_dir(app)?.join("snapshots"))
+}
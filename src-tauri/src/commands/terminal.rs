@@ -0,0 +1,90 @@
+//! Terminal IPC commands
+//!
+//! Output is emitted as `terminal://{session_id}/data` events, and
+//! `terminal://{session_id}/exit` once the session ends.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+use crate::commands::settings::load_settings;
+use crate::db::Database;
+use crate::docker::DockerManager;
+use crate::error::{Result, ShellError};
+use crate::security::SecurityPolicy;
+use crate::terminal::{TerminalBackend, TerminalEvent, TerminalManager, TerminalOpened};
+
+/// Open an interactive session for a language. Runs in a container unless
+/// Docker is unavailable or settings choose the local backend; the result
+/// says which backend is active.
+#[tauri::command]
+pub async fn terminal_open(
+    language: String,
+    cwd: Option<String>,
+    app: AppHandle,
+    db: State<'_, Database>,
+    docker: State<'_, DockerManager>,
+    policy: State<'_, Arc<SecurityPolicy>>,
+    terminals: State<'_, TerminalManager>,
+) -> Result<TerminalOpened> {
+    let backend = match load_settings(&db).terminal_backend {
+        TerminalBackend::Auto if docker.is_available().await => TerminalBackend::Docker,
+        TerminalBackend::Auto => TerminalBackend::Local,
+        TerminalBackend::Docker if !docker.is_available().await => {
+            return Err(ShellError::Docker("Docker is not available. Please install and start Docker.".into()));
+        }
+        chosen => chosen,
+    };
+
+    let emitter = app.clone();
+    let listener = Arc::new(move |session_id: &str, event: TerminalEvent| {
+        let _ = match event {
+            TerminalEvent::Data(data) => emitter.emit(&format!("terminal://{}/data", session_id), data),
+            TerminalEvent::Closed => emitter.emit(&format!("terminal://{}/exit", session_id), ()),
+        };
+    });
+
+    let session_id = if backend == TerminalBackend::Local {
+        policy.check_host_execution()?;
+        let cwd = match cwd {
+            Some(cwd) => PathBuf::from(cwd),
+            None => policy.allowed_paths.first().cloned()
+                .ok_or_else(|| ShellError::Security("No allowed directory for a local terminal".into()))?,
+        };
+        policy.validate_path(&cwd)?;
+        terminals.open_local(&cwd, listener).await?
+    } else {
+        terminals.open_container(&docker, &language, listener).await?
+    };
+    Ok(TerminalOpened { session_id, backend })
+}
+
+/// Send input to a session
+#[tauri::command]
+pub async fn terminal_write(
+    session_id: String,
+    data: String,
+    terminals: State<'_, TerminalManager>,
+) -> Result<()> {
+    terminals.write(&session_id, &data).await
+}
+
+#[tauri::command]
+pub async fn terminal_resize(
+    session_id: String,
+    rows: u16,
+    cols: u16,
+    docker: State<'_, DockerManager>,
+    terminals: State<'_, TerminalManager>,
+) -> Result<()> {
+    terminals.resize(&docker, &session_id, rows, cols).await
+}
+
+/// Close a session and remove its container
+#[tauri::command]
+pub async fn terminal_close(
+    session_id: String,
+    docker: State<'_, DockerManager>,
+    terminals: State<'_, TerminalManager>,
+) -> Result<()> {
+    terminals.close(&docker, &session_id).await
+}
@@ -0,0 +1,335 @@
+//! Test coverage
+//!
+//! Wraps a run or test command with the language's coverage tool and
+//! normalizes its report into per-file line coverage. The report is printed
+//! after a marker line once the program exits, so it comes back with the
+//! program's own output and is split off here.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::docker::{shell_join, ExecutionResult};
+use crate::error::{Result, ShellError};
+use crate::plagiarism::LineRange;
+
+/// Exit code the coverage script uses when the tool isn't in the image
+const NOT_INSTALLED: i64 = 127;
+
+/// Scratch directory for coverage data; the project is mounted read-only
+const COVERAGE_DIR: &str = "/tmp/shell-coverage";
+
+/// Where the project is mounted in the container
+const WORKSPACE: &str = "/workspace";
+
+/// Line coverage of one file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileCoverage {
+    /// Path relative to the project
+    pub file: String,
+    pub covered_lines: u32,
+    /// Lines the tool considers executable
+    pub total_lines: u32,
+    pub percent: f64,
+    /// Runs of executable lines that never ran; non-executable lines
+    /// between them don't split a run
+    pub uncovered: Vec<LineRange>,
+}
+
+/// Coverage of a whole run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageReport {
+    /// Sorted by path
+    pub files: Vec<FileCoverage>,
+    pub covered_lines: u32,
+    pub total_lines: u32,
+    pub percent: f64,
+    /// The run itself, without the coverage report in its output
+    pub run: ExecutionResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Lcov,
+    GoProfile,
+}
+
+/// A coverage tool and how to run it in a language's image
+#[derive(Debug, Clone, PartialEq)]
+struct CoverageTool {
+    name: &'static str,
+    /// Shell command that succeeds when the tool is available
+    probe: &'static str,
+    /// Writes the report after the run, for tools that don't do it themselves
+    report: Option<&'static str>,
+    report_file: &'static str,
+    format: Format,
+    installation_instructions: &'static str,
+}
+
+fn tool_for(language: &str) -> Result<CoverageTool> {
+    Ok(match language.to_lowercase().as_str() {
+        "python" | "py" => CoverageTool {
+            name: "coverage.py",
+            probe: "coverage --version",
+            report: Some("coverage lcov -q -o /tmp/shell-coverage/lcov.info"),
+            report_file: "/tmp/shell-coverage/lcov.info",
+            format: Format::Lcov,
+            installation_instructions: "Add coverage to the Python sandbox image (pip install coverage)",
+        },
+        "javascript" | "js" | "node" | "typescript" | "ts" => CoverageTool {
+            name: "nyc",
+            probe: "npx --no-install nyc --version",
+            report: None,
+            report_file: "/tmp/shell-coverage/lcov.info",
+            format: Format::Lcov,
+            installation_instructions: "Add nyc to the project (npm install --save-dev nyc)",
+        },
+        "rust" | "rs" => CoverageTool {
+            name: "cargo-llvm-cov",
+            probe: "cargo llvm-cov --version",
+            report: None,
+            report_file: "/tmp/shell-coverage/lcov.info",
+            format: Format::Lcov,
+            installation_instructions: "Add cargo-llvm-cov to the Rust sandbox image (cargo install cargo-llvm-cov)",
+        },
+        "go" | "golang" => CoverageTool {
+            name: "go test -cover",
+            probe: "go version",
+            report: None,
+            report_file: "/tmp/shell-coverage/cover.out",
+            format: Format::GoProfile,
+            installation_instructions: "Use a Go sandbox image that includes the Go toolchain",
+        },
+        _ => return Err(ShellError::Execution(format!("Coverage is not supported for {}", language))),
+    })
+}
+
+/// `command` (from `get_run_command` or `get_test_command`) run under the
+/// language's coverage tool
+fn instrumented(language: &str, command: &[String]) -> Result<Vec<String>> {
+    let rest = |skip: usize| command.iter().skip(skip).cloned();
+    Ok(match (language.to_lowercase().as_str(), command) {
+        ("python" | "py", [python, ..]) if python == "python" => {
+            vec!["coverage".into(), "run".into()].into_iter().chain(rest(1)).collect()
+        }
+        ("javascript" | "js" | "node" | "typescript" | "ts", _) => [
+            "npx", "--no-install", "nyc", "--reporter=lcovonly",
+            "--report-dir=/tmp/shell-coverage", "--temp-dir=/tmp/shell-coverage/.nyc_output",
+        ].iter().map(|s| s.to_string()).chain(rest(0)).collect(),
+        ("rust" | "rs", [cargo, subcommand, ..]) if cargo == "cargo" => {
+            ["cargo", "llvm-cov", subcommand.as_str(), "--offline", "--lcov", "--output-path=/tmp/shell-coverage/lcov.info"]
+                .iter().map(|s| s.to_string()).chain(rest(2)).collect()
+        }
+        ("go" | "golang", [go, test, ..]) if go == "go" && test == "test" => {
+            ["go", "test", "-coverprofile=/tmp/shell-coverage/cover.out"]
+                .iter().map(|s| s.to_string()).chain(rest(2)).collect()
+        }
+        _ => return Err(ShellError::Execution(format!("Coverage of {} is measured through its tests", language))),
+    })
+}
+
+/// `sh -c` script that runs `command` under coverage, then prints `marker`
+/// and the report. The script exits with the program's status.
+pub fn coverage_command(language: &str, command: &[String], marker: &str) -> Result<Vec<String>> {
+    let tool = tool_for(language)?;
+    let report = tool.report.map(|r| format!("{} >/dev/null 2>&1; ", r)).unwrap_or_default();
+    let script = format!(
+        "{probe} >/dev/null 2>&1 || exit {missing}; mkdir -p {dir}; export COVERAGE_FILE={dir}/.coverage CARGO_TARGET_DIR=/tmp/target; \
+         {command}; status=$?; {report}if [ -f {file} ]; then echo {marker}; cat {file}; fi; exit $status",
+        probe = tool.probe,
+        missing = NOT_INSTALLED,
+        dir = COVERAGE_DIR,
+        command = shell_join(&instrumented(language, command)?),
+        file = tool.report_file,
+    );
+    Ok(vec!["sh".to_string(), "-c".to_string(), script])
+}
+
+/// Split the report printed after `marker` off `run` and parse it
+pub fn report(language: &str, project_path: &Path, mut run: ExecutionResult, marker: &str) -> Result<CoverageReport> {
+    let tool = tool_for(language)?;
+    if run.exit_code == NOT_INSTALLED {
+        return Err(ShellError::Service(format!(
+            "Coverage tool '{}' not installed. {}", tool.name, tool.installation_instructions
+        )));
+    }
+
+    let line = format!("{}\n", marker);
+    let (output, raw) = match run.stdout.split_once(&line) {
+        Some((output, raw)) => (output.to_string(), raw.to_string()),
+        None if run.timed_out => return Err(ShellError::Execution("Run timed out before coverage was reported".into())),
+        None => return Err(ShellError::Service(format!(
+            "{} produced no coverage report: {}", tool.name, run.stderr.trim()
+        ))),
+    };
+    run.stdout = output;
+
+    let lines = match tool.format {
+        Format::Lcov => parse_lcov(&raw),
+        Format::GoProfile => parse_go_profile(&raw, go_module(project_path).as_deref()),
+    };
+    let files: Vec<FileCoverage> = lines.into_iter().map(|(file, lines)| file_coverage(file, &lines)).collect();
+    let covered_lines = files.iter().map(|f| f.covered_lines).sum();
+    let total_lines = files.iter().map(|f| f.total_lines).sum();
+    Ok(CoverageReport { files, covered_lines, total_lines, percent: percent(covered_lines, total_lines), run })
+}
+
+fn percent(covered: u32, total: u32) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    }
+}
+
+/// Per-file executable lines and whether each ran
+type LineHits = BTreeMap<String, BTreeMap<u32, bool>>;
+
+fn file_coverage(file: String, lines: &BTreeMap<u32, bool>) -> FileCoverage {
+    let mut uncovered: Vec<LineRange> = Vec::new();
+    let mut extend = false;
+    for (&line, &hit) in lines {
+        if hit {
+            extend = false;
+        } else if extend {
+            uncovered.last_mut().expect("open range").end = line as usize;
+        } else {
+            uncovered.push(LineRange { start: line as usize, end: line as usize });
+            extend = true;
+        }
+    }
+    let covered_lines = lines.values().filter(|&&hit| hit).count() as u32;
+    let total_lines = lines.len() as u32;
+    FileCoverage { file, covered_lines, total_lines, percent: percent(covered_lines, total_lines), uncovered }
+}
+
+/// Project-relative form of a reported path; `None` for files outside the
+/// project, such as dependencies
+fn project_file(path: &str) -> Option<String> {
+    let relative = match path.strip_prefix(WORKSPACE) {
+        Some(rest) => rest.strip_prefix('/')?,
+        None if path.starts_with('/') => return None,
+        None => path,
+    };
+    Some(relative.trim_start_matches("./").to_string())
+}
+
+/// LCOV tracefile: `SF:` starts a file, `DA:<line>,<hits>` records a line
+fn parse_lcov(output: &str) -> LineHits {
+    let mut files = LineHits::new();
+    let mut current = None;
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix("SF:") {
+            current = project_file(path.trim());
+        } else if line == "end_of_record" {
+            current = None;
+        } else if let (Some(file), Some(data)) = (&current, line.strip_prefix("DA:")) {
+            let mut fields = data.split(',');
+            let number = fields.next().and_then(|n| n.trim().parse::<u32>().ok());
+            let hits = fields.next().and_then(|h| h.trim().parse::<u64>().ok());
+            if let (Some(number), Some(hits)) = (number, hits) {
+                *files.entry(file.clone()).or_default().entry(number).or_default() |= hits > 0;
+            }
+        }
+    }
+    files
+}
+
+/// `go test -coverprofile`: `<import path>:<l>.<c>,<l>.<c> <statements> <count>`
+/// per block. A line counts as covered when any block on it ran.
+fn parse_go_profile(output: &str, module: Option<&str>) -> LineHits {
+    let mut files = LineHits::new();
+    for line in output.lines().filter(|line| !line.starts_with("mode:")) {
+        let parsed = (|| {
+            let (location, rest) = line.rsplit_once(':')?;
+            let mut counts = rest.split_whitespace();
+            let span = counts.next()?;
+            let ran = counts.nth(1)?.parse::<u64>().ok()? > 0;
+            let (start, end) = span.split_once(',')?;
+            let line_of = |pos: &str| pos.split('.').next()?.parse::<u32>().ok();
+            Some((location, line_of(start)?, line_of(end)?, ran))
+        })();
+        let Some((path, start, end, ran)) = parsed else { continue };
+        let file = module
+            .and_then(|m| path.strip_prefix(m)?.strip_prefix('/'))
+            .unwrap_or(path);
+        let lines = files.entry(file.to_string()).or_default();
+        for number in start..=end {
+            *lines.entry(number).or_default() |= ran;
+        }
+    }
+    files
+}
+
+/// Module path from the project's `go.mod`, which prefixes profile paths
+fn go_module(project_path: &Path) -> Option<String> {
+    let go_mod = std::fs::read_to_string(project_path.join("go.mod")).ok()?;
+    go_mod.lines()
+        .find_map(|line| line.trim().strip_prefix("module "))
+        .map(|module| module.trim().trim_matches('"').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::ExecutionPhase;
+
+    fn result(exit_code: i64, stdout: &str) -> ExecutionResult {
+        ExecutionResult {
+            id: "run".into(),
+            exit_code,
+            stdout: stdout.into(),
+            stderr: String::new(),
+            duration_ms: 5,
+            timed_out: false,
+            phase: ExecutionPhase::Run,
+            compile_output: None,
+            tests_passed: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            log_collection_timed_out: false,
+            output_truncated: false,
+            trace: None,
+        }
+    }
+
+    #[test]
+    fn test_coverage_py_lcov_report_is_normalized() {
+        // `coverage lcov` output for a module with an untested branch
+        let stdout = "1 passed\n__cov__\nSF:calc.py\nDA:1,1\nDA:2,1\nDA:3,0\nDA:4,0\nDA:6,0\nDA:8,1\nLF:6\nLH:3\nend_of_record\n\
+                      SF:test_calc.py\nDA:1,1\nDA:3,1\nend_of_record\nSF:/usr/lib/python3/site.py\nDA:1,1\nend_of_record\n";
+        let report = report("python", Path::new("/nonexistent"), result(0, stdout), "__cov__").unwrap();
+
+        assert_eq!(report.run.stdout, "1 passed\n");
+        assert_eq!(report.files.len(), 2);
+        let calc = &report.files[0];
+        assert_eq!(calc.file, "calc.py");
+        assert_eq!((calc.covered_lines, calc.total_lines), (3, 6));
+        assert_eq!(calc.percent, 50.0);
+        assert_eq!(calc.uncovered, vec![LineRange { start: 3, end: 6 }]);
+        assert_eq!(report.files[1].percent, 100.0);
+        assert_eq!((report.covered_lines, report.total_lines), (5, 8));
+    }
+
+    #[test]
+    fn test_go_profile_paths_are_project_relative() {
+        let profile = "mode: set\nexample.com/calc/calc.go:3.20,5.2 1 1\nexample.com/calc/calc.go:7.20,9.2 1 0\n";
+        let files = parse_go_profile(profile, Some("example.com/calc"));
+        let calc = file_coverage("calc.go".into(), &files["calc.go"]);
+        assert_eq!((calc.covered_lines, calc.total_lines), (3, 6));
+        assert_eq!(calc.uncovered, vec![LineRange { start: 7, end: 9 }]);
+    }
+
+    #[test]
+    fn test_missing_tool_and_report_are_errors() {
+        let err = report("python", Path::new("/nonexistent"), result(NOT_INSTALLED, ""), "__cov__").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Service error: Coverage tool 'coverage.py' not installed. Add coverage to the Python sandbox image (pip install coverage)"
+        );
+        assert!(report("python", Path::new("/nonexistent"), result(1, "Traceback\n"), "__cov__").is_err());
+        assert!(coverage_command("go", &["go".into(), "run".into(), "main.go".into()], "__cov__").is_err());
+        let command = coverage_command("python", &["python".into(), "-m".into(), "pytest".into()], "__cov__").unwrap();
+        assert!(command[2].contains("coverage run -m pytest; status=$?"));
+    }
+}
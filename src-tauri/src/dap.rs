@@ -0,0 +1,222 @@
+//! Debug Adapter Protocol bridge
+//!
+//! A debug adapter runs as a `ServiceManager` process speaking DAP over
+//! stdio. Messages from the UI are framed and written to it; messages it
+//! sends back are passed to a listener. The UI drives the protocol itself
+//! (`initialize`, `launch`, breakpoints, ...).
+//!
+//! The adapter and debuggee run on the host: the stock execution images
+//! don't ship debugpy and the sandbox has no network to install it.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::ChildStdin;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use crate::error::{Result, ShellError};
+use crate::services::{ServiceConfig, ServiceManager, ServiceType};
+
+/// Something an adapter produced
+#[derive(Debug, Clone, PartialEq)]
+pub enum DapEvent {
+    /// A response, event or reverse request
+    Message(Value),
+    /// The adapter exited
+    Closed,
+}
+
+/// Receives a session's id and what its adapter produced
+pub type DapListener = Arc<dyn Fn(&str, DapEvent) + Send + Sync>;
+
+/// How to start a language's debug adapter
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugAdapter {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+/// Debug adapter for a language
+pub fn adapter_for(language: &str) -> Result<DebugAdapter> {
+    match language.to_lowercase().as_str() {
+        "python" | "py" => Ok(DebugAdapter {
+            command: if cfg!(windows) { "python" } else { "python3" }.to_string(),
+            args: vec!["-m".to_string(), "debugpy.adapter".to_string()],
+        }),
+        _ => Err(ShellError::Service(format!("Debugging is not supported for {} yet", language))),
+    }
+}
+
+/// Arguments for the UI's `launch` request
+pub fn launch_arguments(language: &str, program: &Path, cwd: &Path) -> Value {
+    let mut arguments = serde_json::json!({
+        "program": program,
+        "cwd": cwd,
+        "console": "internalConsole",
+    });
+    if matches!(language.to_lowercase().as_str(), "python" | "py") {
+        arguments["justMyCode"] = Value::Bool(true);
+    }
+    arguments
+}
+
+/// Frame a message with its `Content-Length` header
+pub fn encode_message(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    let mut framed = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    framed.extend_from_slice(body.as_bytes());
+    framed
+}
+
+/// Read one framed message; `None` at end of stream
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| ShellError::Service("Message without Content-Length".into()))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Open debug sessions, keyed by session id
+pub struct DapSessions {
+    writers: Mutex<HashMap<String, ChildStdin>>,
+}
+
+impl DapSessions {
+    pub fn new() -> Self {
+        Self { writers: Mutex::new(HashMap::new()) }
+    }
+
+    /// Start `adapter` in `cwd` and relay its messages to `listener`.
+    /// Returns the session id.
+    pub async fn start(
+        &self,
+        services: &ServiceManager,
+        language: &str,
+        adapter: DebugAdapter,
+        cwd: &Path,
+        listener: DapListener,
+    ) -> Result<String> {
+        let session_id = format!("dap-{}", uuid::Uuid::new_v4());
+        services.register(ServiceConfig {
+            id: session_id.clone(),
+            service_type: ServiceType::Debugger(language.to_string()),
+            command: adapter.command,
+            args: adapter.args,
+            working_dir: Some(cwd.to_path_buf()),
+            env: HashMap::new(),
+        }).await;
+        services.start(&session_id).await?;
+
+        let (stdin, stdout) = services.take_stdio(&session_id).await
+            .ok_or_else(|| ShellError::Service("Debug adapter has no stdio".into()))?;
+
+        let id = session_id.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut reader = BufReader::new(stdout);
+            loop {
+                match read_message(&mut reader) {
+                    Ok(Some(message)) => listener(&id, DapEvent::Message(message)),
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::warn!("Dropping debug session {}: {}", id, e);
+                        break;
+                    }
+                }
+            }
+            listener(&id, DapEvent::Closed);
+        });
+
+        self.writers.lock().await.insert(session_id.clone(), stdin);
+        Ok(session_id)
+    }
+
+    /// Send a DAP message to a session's adapter
+    pub async fn send(&self, session_id: &str, message: &Value) -> Result<()> {
+        let mut writers = self.writers.lock().await;
+        let writer = writers.get_mut(session_id)
+            .ok_or_else(|| ShellError::Service(format!("No debug session {}", session_id)))?;
+        writer.write_all(&encode_message(message))?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Stop a session's adapter
+    pub async fn stop(&self, services: &ServiceManager, session_id: &str) -> Result<()> {
+        self.writers.lock().await.remove(session_id);
+        services.stop(session_id).await
+    }
+}
+
+impl Default for DapSessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_framing_round_trip() {
+        let first = json!({"seq": 1, "type": "request", "command": "initialize"});
+        let second = json!({"seq": 2, "type": "event", "event": "output", "body": {"output": "héllo\r\n"}});
+        let mut stream = encode_message(&first);
+        stream.extend(encode_message(&second));
+
+        let mut reader = std::io::Cursor::new(stream);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(first));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(second));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_python_adapter_and_launch() {
+        assert_eq!(adapter_for("python").unwrap().args, vec!["-m", "debugpy.adapter"]);
+        assert!(adapter_for("cobol").is_err());
+
+        let launch = launch_arguments("python", Path::new("/p/main.py"), Path::new("/p"));
+        assert_eq!(launch, json!({"program": "/p/main.py", "cwd": "/p", "console": "internalConsole", "justMyCode": true}));
+    }
+
+    /// `cat` echoes every framed message, standing in for an adapter
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_messages_are_proxied() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let listener: DapListener = Arc::new(move |_: &str, event| {
+            let _ = sender.send(event);
+        });
+        let services = ServiceManager::new();
+        let sessions = DapSessions::new();
+        let echo = DebugAdapter { command: "cat".into(), args: vec![] };
+        let id = sessions.start(&services, "python", echo, &std::env::temp_dir(), listener).await.unwrap();
+
+        let request = json!({"seq": 1, "type": "request", "command": "threads"});
+        sessions.send(&id, &request).await.unwrap();
+        assert_eq!(receiver.recv().await, Some(DapEvent::Message(request)));
+
+        sessions.stop(&services, &id).await.unwrap();
+        assert_eq!(receiver.recv().await, Some(DapEvent::Closed));
+        assert!(sessions.send(&id, &json!({})).await.is_err());
+    }
+}
@@ -6,6 +6,8 @@
 use rusqlite::{Connection, params};
 use std::path::Path;
 use std::sync::Mutex;
+use crate::analytics::{RecordedEvent, TelemetryLogEntry};
+use crate::classroom::{Classroom, Student, StudentProgress};
 use crate::error::{Result, ShellError};
 
 pub struct Database {
@@ -17,7 +19,16 @@ impl Database {
     pub fn init(app_data: &Path) -> Result<Self> {
         let db_path = app_data.join("shell.db");
         let conn = Connection::open(&db_path)?;
+        Self::from_connection(conn)
+    }
+
+    /// In-memory database for tests
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
 
+    fn from_connection(conn: Connection) -> Result<Self> {
         // Create tables
         conn.execute_batch(
             r#"
@@ -54,6 +65,7 @@ impl Database {
                 status TEXT NOT NULL,
                 score REAL,
                 feedback TEXT,
+                result TEXT,
                 FOREIGN KEY (lesson_id) REFERENCES lessons(id),
                 FOREIGN KEY (project_id) REFERENCES projects(id)
             );
@@ -84,13 +96,103 @@ impl Database {
                 settings TEXT
             );
 
+            -- Point-in-time project archives, stored under app data
+            CREATE TABLE IF NOT EXISTS snapshots (
+                id TEXT PRIMARY KEY,
+                project_path TEXT NOT NULL,
+                label TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                file_count INTEGER NOT NULL,
+                size_bytes INTEGER NOT NULL
+            );
+
+            -- Images with a project's installed packages, per base image
+            CREATE TABLE IF NOT EXISTS dependency_images (
+                project_path TEXT NOT NULL,
+                base_image TEXT NOT NULL,
+                image TEXT NOT NULL,
+                packages TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (project_path, base_image)
+            );
+
+            -- Learning analytics, recorded only with the user's opt-in
+            CREATE TABLE IF NOT EXISTS analytics_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recorded_at TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                event TEXT NOT NULL,
+                uploaded INTEGER NOT NULL DEFAULT 0
+            );
+
+            -- Every telemetry upload, exactly as sent
+            CREATE TABLE IF NOT EXISTS telemetry_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sent_at TEXT NOT NULL,
+                url TEXT NOT NULL,
+                payload TEXT NOT NULL
+            );
+
+            -- Teacher classrooms and their rosters
+            CREATE TABLE IF NOT EXISTS classrooms (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS classroom_students (
+                classroom_id TEXT NOT NULL,
+                id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                email TEXT,
+                added_at TEXT NOT NULL,
+                PRIMARY KEY (classroom_id, id),
+                FOREIGN KEY (classroom_id) REFERENCES classrooms(id)
+            );
+
+            -- Which classroom student made a submission. No key on the
+            -- submission: re-grading replaces its row.
+            CREATE TABLE IF NOT EXISTS classroom_submissions (
+                submission_id TEXT PRIMARY KEY,
+                classroom_id TEXT NOT NULL,
+                student_id TEXT NOT NULL,
+                FOREIGN KEY (classroom_id, student_id) REFERENCES classroom_students(classroom_id, id)
+            );
+
+            -- Lesson hints revealed by the student
+            CREATE TABLE IF NOT EXISTS hint_reveals (
+                lesson_id TEXT NOT NULL,
+                hint_index INTEGER NOT NULL,
+                revealed_at TEXT NOT NULL,
+                PRIMARY KEY (lesson_id, hint_index)
+            );
+
+            -- Best score and completion per lesson, derived from submissions
+            CREATE TABLE IF NOT EXISTS lesson_progress (
+                lesson_id TEXT PRIMARY KEY,
+                best_score REAL NOT NULL,
+                attempts INTEGER NOT NULL,
+                passing_score REAL NOT NULL,
+                completed INTEGER NOT NULL,
+                last_submitted_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
             -- Indexes for performance
             CREATE INDEX IF NOT EXISTS idx_projects_path ON projects(path);
             CREATE INDEX IF NOT EXISTS idx_lessons_path ON lessons(path);
             CREATE INDEX IF NOT EXISTS idx_executions_project ON executions(project_id);
+            CREATE INDEX IF NOT EXISTS idx_snapshots_project ON snapshots(project_path);
+            CREATE INDEX IF NOT EXISTS idx_analytics_recorded ON analytics_events(recorded_at);
+            CREATE INDEX IF NOT EXISTS idx_classroom_submissions_student ON classroom_submissions(classroom_id, student_id);
             "#,
         )?;
 
+        // Columns added after the first release
+        ensure_column(&conn, "submissions", "result", "TEXT")?;
+        ensure_column(&conn, "submissions", "source", "TEXT")?;
+        ensure_column(&conn, "projects", "session", "TEXT")?;
+
         Ok(Self {
             conn: Mutex::new(conn),
         })
@@ -98,7 +200,7 @@ impl Database {
 
     /// Get a setting value
     pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
-        let conn = self.conn.lock().map_err(|e| ShellError::Database(
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
             rusqlite::Error::InvalidQuery
         ))?;
         
@@ -114,7 +216,7 @@ impl Database {
 
     /// Set a setting value
     pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
-        let conn = self.conn.lock().map_err(|e| ShellError::Database(
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
             rusqlite::Error::InvalidQuery
         ))?;
         
@@ -126,27 +228,169 @@ impl Database {
         Ok(())
     }
 
+    /// Remove a setting
+    pub fn delete_setting(&self, key: &str) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        conn.execute("DELETE FROM settings WHERE key = ?", params![key])?;
+        Ok(())
+    }
+
     /// Register a project
     pub fn register_project(&self, id: &str, name: &str, path: &str, language: Option<&str>) -> Result<()> {
-        let conn = self.conn.lock().map_err(|e| ShellError::Database(
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
             rusqlite::Error::InvalidQuery
         ))?;
         
         let now = chrono::Utc::now().to_rfc3339();
         
+        // Upsert so re-registering keeps the project's settings
         conn.execute(
-            r#"INSERT OR REPLACE INTO projects 
-               (id, name, path, language, created_at, updated_at) 
-               VALUES (?, ?, ?, ?, ?, ?)"#,
-            params![id, name, path, language, now, now],
+            r#"INSERT INTO projects (id, name, path, language, created_at, updated_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+               ON CONFLICT(id) DO UPDATE SET
+                   name = excluded.name,
+                   path = excluded.path,
+                   language = excluded.language,
+                   updated_at = excluded.updated_at"#,
+            params![id, name, path, language, now],
         )?;
         
         Ok(())
     }
 
+    /// Settings override stored for a project. `Ok(None)` if the project
+    /// has none; an error if the project is unknown.
+    pub fn get_project_settings(&self, id: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let result = conn.query_row("SELECT settings FROM projects WHERE id = ?", params![id], |row| row.get(0));
+        
+        match result {
+            Ok(settings) => Ok(settings),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err(unknown_project(id)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Projects with a settings override, as `(id, settings)` pairs
+    pub fn list_project_settings(&self) -> Result<Vec<(String, String)>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let mut stmt = conn.prepare("SELECT id, settings FROM projects WHERE settings IS NOT NULL ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        
+        rows.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Replace a project's settings override; `None` clears it
+    pub fn set_project_settings(&self, id: &str, settings: Option<&str>) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let updated = conn.execute(
+            "UPDATE projects SET settings = ?, updated_at = ? WHERE id = ?",
+            params![settings, chrono::Utc::now().to_rfc3339(), id],
+        )?;
+        
+        if updated == 0 {
+            return Err(unknown_project(id));
+        }
+        Ok(())
+    }
+
+    /// A registered project by id
+    pub fn get_project(&self, id: &str) -> Result<Option<Project>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let result = conn.query_row(
+            "SELECT id, name, path, language, created_at, updated_at FROM projects WHERE id = ?",
+            params![id],
+            |row| Ok(Project {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                path: row.get(2)?,
+                language: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            }),
+        );
+        
+        match result {
+            Ok(project) => Ok(Some(project)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saved editor session of a project, as JSON
+    pub fn get_project_session(&self, id: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let result = conn.query_row("SELECT session FROM projects WHERE id = ?", params![id], |row| row.get(0));
+        
+        match result {
+            Ok(session) => Ok(session),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err(unknown_project(id)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace a project's saved editor session
+    pub fn set_project_session(&self, id: &str, session: &str) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        // The session isn't a settings change, so `updated_at` stays put
+        let updated = conn.execute("UPDATE projects SET session = ? WHERE id = ?", params![session, id])?;
+        
+        if updated == 0 {
+            return Err(unknown_project(id));
+        }
+        Ok(())
+    }
+
+    /// Project registered at exactly `path`
+    pub fn get_project_by_path(&self, path: &str) -> Result<Option<Project>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let result = conn.query_row(
+            "SELECT id, name, path, language, created_at, updated_at FROM projects WHERE path = ? ORDER BY created_at LIMIT 1",
+            params![path],
+            |row| Ok(Project {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                path: row.get(2)?,
+                language: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            }),
+        );
+        
+        match result {
+            Ok(project) => Ok(Some(project)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// List all projects
     pub fn list_projects(&self) -> Result<Vec<Project>> {
-        let conn = self.conn.lock().map_err(|e| ShellError::Database(
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
             rusqlite::Error::InvalidQuery
         ))?;
         
@@ -170,7 +414,7 @@ impl Database {
 
     /// Save LSP configuration
     pub fn save_lsp_config(&self, language: &str, server_path: &str, args: Option<&str>, settings: Option<&str>) -> Result<()> {
-        let conn = self.conn.lock().map_err(|e| ShellError::Database(
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
             rusqlite::Error::InvalidQuery
         ))?;
         
@@ -184,7 +428,7 @@ impl Database {
 
     /// Get LSP configuration for a language
     pub fn get_lsp_config(&self, language: &str) -> Result<Option<LspConfig>> {
-        let conn = self.conn.lock().map_err(|e| ShellError::Database(
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
             rusqlite::Error::InvalidQuery
         ))?;
         
@@ -207,6 +451,572 @@ impl Database {
             Err(e) => Err(e.into()),
         }
     }
+
+    /// All custom LSP configurations, by language
+    pub fn list_lsp_configs(&self) -> Result<Vec<LspConfig>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT language, server_path, args, settings FROM lsp_configs ORDER BY language"
+        )?;
+
+        let configs = stmt.query_map([], |row| {
+            Ok(LspConfig {
+                language: row.get(0)?,
+                server_path: row.get(1)?,
+                args: row.get(2)?,
+                settings: row.get(3)?,
+            })
+        })?;
+
+        configs.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Record a lesson in the local catalog, keyed by lesson id
+    pub fn upsert_lesson(&self, id: &str, title: &str, description: &str, path: &str, version: &str) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let now = chrono::Utc::now().to_rfc3339();
+        
+        conn.execute(
+            r#"INSERT INTO lessons (id, title, description, path, version, created_at, updated_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+               ON CONFLICT(id) DO UPDATE SET
+                   title = excluded.title,
+                   description = excluded.description,
+                   path = excluded.path,
+                   version = excluded.version,
+                   updated_at = excluded.updated_at"#,
+            params![id, title, description, path, version, now],
+        )?;
+        
+        Ok(())
+    }
+
+    /// Get the file path of a known lesson
+    pub fn get_lesson_path(&self, id: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let result = conn.query_row("SELECT path FROM lessons WHERE id = ?", params![id], |row| row.get(0));
+        
+        match result {
+            Ok(path) => Ok(Some(path)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Insert or replace a submission with its serialized grading result
+    pub fn save_submission(&self, submission: &SubmissionRecord) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        conn.execute(
+            r#"INSERT OR REPLACE INTO submissions
+               (id, lesson_id, project_id, submitted_at, status, score, feedback, result, source)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            params![
+                submission.id,
+                submission.lesson_id,
+                submission.project_id,
+                submission.submitted_at,
+                submission.status,
+                submission.score,
+                submission.feedback,
+                submission.result,
+                submission.source,
+            ],
+        )?;
+        
+        Ok(())
+    }
+
+    /// Get a submission by id
+    pub fn get_submission(&self, id: &str) -> Result<Option<SubmissionRecord>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let result = conn.query_row(
+            "SELECT id, lesson_id, project_id, submitted_at, status, score, feedback, result, source FROM submissions WHERE id = ?",
+            params![id],
+            |row| Ok(SubmissionRecord {
+                id: row.get(0)?,
+                lesson_id: row.get(1)?,
+                project_id: row.get(2)?,
+                submitted_at: row.get(3)?,
+                status: row.get(4)?,
+                score: row.get(5)?,
+                feedback: row.get(6)?,
+                result: row.get(7)?,
+                source: row.get(8)?,
+            }),
+        );
+        
+        match result {
+            Ok(submission) => Ok(Some(submission)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Ids and submitted code of a lesson's submissions, oldest first
+    pub fn list_submission_sources(&self, lesson_id: &str) -> Result<Vec<(String, String)>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, source FROM submissions WHERE lesson_id = ? AND source IS NOT NULL ORDER BY submitted_at, id"
+        )?;
+        let rows = stmt.query_map(params![lesson_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<std::result::Result<_, _>>().map_err(Into::into)
+    }
+
+    /// Record a snapshot's metadata
+    pub fn save_snapshot(&self, snapshot: &SnapshotRecord) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        conn.execute(
+            "INSERT INTO snapshots (id, project_path, label, created_at, file_count, size_bytes) VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                snapshot.id,
+                snapshot.project_path,
+                snapshot.label,
+                snapshot.created_at,
+                snapshot.file_count as i64,
+                snapshot.size_bytes as i64,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get a snapshot by id
+    pub fn get_snapshot(&self, id: &str) -> Result<Option<SnapshotRecord>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let result = conn.query_row(
+            "SELECT id, project_path, label, created_at, file_count, size_bytes FROM snapshots WHERE id = ?",
+            params![id],
+            snapshot_row,
+        );
+        match result {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// A project's snapshots, newest first
+    pub fn list_snapshots(&self, project_path: &str) -> Result<Vec<SnapshotRecord>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, project_path, label, created_at, file_count, size_bytes FROM snapshots
+             WHERE project_path = ? ORDER BY created_at DESC, rowid DESC"
+        )?;
+        let rows = stmt.query_map(params![project_path], snapshot_row)?;
+        rows.collect::<std::result::Result<_, _>>().map_err(Into::into)
+    }
+
+    /// Record the image a project's runs on `base_image` should use
+    pub fn set_dependency_image(&self, project_path: &str, base_image: &str, image: &str, packages: &[String]) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO dependency_images (project_path, base_image, image, packages, created_at) VALUES (?, ?, ?, ?, ?)",
+            params![project_path, base_image, image, serde_json::to_string(packages)?, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Image with a project's installed packages, if any were installed
+    pub fn get_dependency_image(&self, project_path: &str, base_image: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let result = conn.query_row(
+            "SELECT image FROM dependency_images WHERE project_path = ? AND base_image = ?",
+            params![project_path, base_image],
+            |row| row.get(0),
+        );
+        match result {
+            Ok(image) => Ok(Some(image)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record a new classroom
+    pub fn create_classroom(&self, classroom: &Classroom) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        conn.execute(
+            "INSERT INTO classrooms (id, name, created_at) VALUES (?, ?, ?)",
+            params![classroom.id, classroom.name, classroom.created_at],
+        )?;
+        Ok(())
+    }
+
+    /// Get a classroom by id
+    pub fn get_classroom(&self, id: &str) -> Result<Option<Classroom>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let result = conn.query_row(
+            "SELECT id, name, created_at FROM classrooms WHERE id = ?",
+            params![id],
+            |row| Ok(Classroom { id: row.get(0)?, name: row.get(1)?, created_at: row.get(2)? }),
+        );
+        match result {
+            Ok(classroom) => Ok(Some(classroom)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// All classrooms, by name
+    pub fn list_classrooms(&self) -> Result<Vec<Classroom>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare("SELECT id, name, created_at FROM classrooms ORDER BY name, created_at")?;
+        let rows = stmt.query_map([], |row| Ok(Classroom { id: row.get(0)?, name: row.get(1)?, created_at: row.get(2)? }))?;
+        rows.collect::<std::result::Result<_, _>>().map_err(Into::into)
+    }
+
+    /// Add a student to a classroom's roster
+    pub fn add_classroom_student(&self, classroom_id: &str, student: &Student) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        conn.execute(
+            "INSERT INTO classroom_students (classroom_id, id, name, email, added_at) VALUES (?, ?, ?, ?, ?)",
+            params![classroom_id, student.id, student.name, student.email, student.added_at],
+        )?;
+        Ok(())
+    }
+
+    /// A classroom's roster, by name
+    pub fn list_classroom_students(&self, classroom_id: &str) -> Result<Vec<Student>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, name, email, added_at FROM classroom_students WHERE classroom_id = ? ORDER BY name, id"
+        )?;
+        let rows = stmt.query_map(params![classroom_id], student_row)?;
+        rows.collect::<std::result::Result<_, _>>().map_err(Into::into)
+    }
+
+    /// Take a student off a roster along with their submission links;
+    /// returns whether they were on it
+    pub fn remove_classroom_student(&self, classroom_id: &str, student_id: &str) -> Result<bool> {
+        let mut conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM classroom_submissions WHERE classroom_id = ? AND student_id = ?",
+            params![classroom_id, student_id],
+        )?;
+        let removed = tx.execute(
+            "DELETE FROM classroom_students WHERE classroom_id = ? AND id = ?",
+            params![classroom_id, student_id],
+        )?;
+        tx.commit()?;
+        Ok(removed > 0)
+    }
+
+    /// Attribute a submission to a classroom student
+    pub fn link_classroom_submission(&self, submission_id: &str, classroom_id: &str, student_id: &str) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO classroom_submissions (submission_id, classroom_id, student_id) VALUES (?, ?, ?)",
+            params![submission_id, classroom_id, student_id],
+        )?;
+        Ok(())
+    }
+
+    /// Every student on a roster with their submissions for a lesson
+    pub fn classroom_progress(&self, classroom_id: &str, lesson_id: &str) -> Result<Vec<StudentProgress>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare(
+            r#"SELECT st.id, st.name, st.email, st.added_at, COUNT(s.id), MAX(s.score), MAX(s.submitted_at),
+                   (SELECT latest.status FROM classroom_submissions link
+                    JOIN submissions latest ON latest.id = link.submission_id
+                    WHERE link.classroom_id = st.classroom_id AND link.student_id = st.id AND latest.lesson_id = ?2
+                    ORDER BY latest.submitted_at DESC LIMIT 1)
+               FROM classroom_students st
+               LEFT JOIN classroom_submissions cs ON cs.classroom_id = st.classroom_id AND cs.student_id = st.id
+               LEFT JOIN submissions s ON s.id = cs.submission_id AND s.lesson_id = ?2
+               WHERE st.classroom_id = ?1
+               GROUP BY st.id
+               ORDER BY st.name, st.id"#
+        )?;
+        let rows = stmt.query_map(params![classroom_id, lesson_id], |row| Ok(StudentProgress {
+            student: student_row(row)?,
+            submissions: row.get(4)?,
+            best_score: row.get(5)?,
+            last_submitted_at: row.get(6)?,
+            last_status: row.get(7)?,
+        }))?;
+        rows.collect::<std::result::Result<_, _>>().map_err(Into::into)
+    }
+
+    /// Store analytics events in one transaction
+    pub fn insert_analytics_events(&self, events: &[RecordedEvent]) -> Result<()> {
+        let mut conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let tx = conn.transaction()?;
+        for recorded in events {
+            tx.execute(
+                "INSERT INTO analytics_events (recorded_at, kind, event) VALUES (?, ?, ?)",
+                params![recorded.recorded_at, recorded.event.kind(), serde_json::to_string(&recorded.event)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Analytics events recorded in `[since, until)`, oldest first
+    pub fn list_analytics_events(&self, since: Option<&str>, until: Option<&str>) -> Result<Vec<RecordedEvent>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT recorded_at, event FROM analytics_events
+             WHERE (?1 IS NULL OR recorded_at >= ?1) AND (?2 IS NULL OR recorded_at < ?2)
+             ORDER BY recorded_at, id"
+        )?;
+        let rows = stmt.query_map(params![since, until], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (recorded_at, event) = row?;
+            events.push(RecordedEvent { recorded_at, event: serde_json::from_str(&event)? });
+        }
+        Ok(events)
+    }
+
+    /// Up to `limit` analytics events not yet uploaded, with their ids
+    pub fn pending_analytics_events(&self, limit: usize) -> Result<Vec<(i64, RecordedEvent)>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, recorded_at, event FROM analytics_events WHERE uploaded = 0 ORDER BY id LIMIT ?"
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| Ok((row.get(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (id, recorded_at, event) = row?;
+            events.push((id, RecordedEvent { recorded_at, event: serde_json::from_str(&event)? }));
+        }
+        Ok(events)
+    }
+
+    /// How many analytics events haven't been uploaded
+    pub fn count_pending_analytics_events(&self) -> Result<usize> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM analytics_events WHERE uploaded = 0", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Mark analytics events as sent
+    pub fn mark_analytics_uploaded(&self, ids: &[i64]) -> Result<()> {
+        let mut conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let tx = conn.transaction()?;
+        for id in ids {
+            tx.execute("UPDATE analytics_events SET uploaded = 1 WHERE id = ?", params![id])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Delete every stored analytics event
+    pub fn purge_analytics_events(&self) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        conn.execute("DELETE FROM analytics_events", [])?;
+        Ok(())
+    }
+
+    /// Record a telemetry upload
+    pub fn insert_telemetry_log(&self, entry: &TelemetryLogEntry) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        conn.execute(
+            "INSERT INTO telemetry_log (sent_at, url, payload) VALUES (?, ?, ?)",
+            params![entry.sent_at, entry.url, entry.payload],
+        )?;
+        Ok(())
+    }
+
+    /// Every telemetry upload, oldest first
+    pub fn list_telemetry_log(&self) -> Result<Vec<TelemetryLogEntry>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare("SELECT sent_at, url, payload FROM telemetry_log ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok(TelemetryLogEntry {
+            sent_at: row.get(0)?,
+            url: row.get(1)?,
+            payload: row.get(2)?,
+        }))?;
+        rows.collect::<std::result::Result<_, _>>().map_err(Into::into)
+    }
+
+    /// Best score recorded for a lesson, if it was ever graded
+    pub fn best_score(&self, lesson_id: &str) -> Result<Option<f64>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        conn.query_row(
+            "SELECT MAX(score) FROM submissions WHERE lesson_id = ?",
+            params![lesson_id],
+            |row| row.get(0),
+        ).map_err(Into::into)
+    }
+
+    /// Recompute a lesson's progress from its submissions; a lesson is
+    /// completed once its best score reaches `passing_score`. Returns `None`
+    /// for a lesson never attempted.
+    pub fn update_lesson_progress(&self, lesson_id: &str, passing_score: f64) -> Result<Option<LessonProgress>> {
+        {
+            let conn = self.conn.lock().map_err(|_| ShellError::Database(
+                rusqlite::Error::InvalidQuery
+            ))?;
+
+            conn.execute(
+                r#"INSERT OR REPLACE INTO lesson_progress
+                   (lesson_id, best_score, attempts, passing_score, completed, last_submitted_at, updated_at)
+                   SELECT lesson_id, MAX(score), COUNT(*), ?2, MAX(score) >= ?2, MAX(submitted_at), ?3
+                   FROM submissions
+                   WHERE lesson_id = ?1 AND score IS NOT NULL
+                   GROUP BY lesson_id"#,
+                params![lesson_id, passing_score, chrono::Utc::now().to_rfc3339()],
+            )?;
+        }
+        self.get_lesson_progress(lesson_id)
+    }
+
+    /// Progress on a lesson, or `None` if it was never attempted
+    pub fn get_lesson_progress(&self, lesson_id: &str) -> Result<Option<LessonProgress>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let result = conn.query_row(
+            &format!("{} WHERE lesson_id = ?", LESSON_PROGRESS_SELECT),
+            params![lesson_id],
+            lesson_progress_row,
+        );
+
+        match result {
+            Ok(progress) => Ok(Some(progress)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Progress on every attempted lesson, most recently submitted first
+    pub fn list_lesson_progress(&self) -> Result<Vec<LessonProgress>> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+
+        let mut stmt = conn.prepare(&format!("{} ORDER BY last_submitted_at DESC", LESSON_PROGRESS_SELECT))?;
+        let rows = stmt.query_map([], lesson_progress_row)?;
+        rows.collect::<std::result::Result<_, _>>().map_err(Into::into)
+    }
+
+    /// Number of hints revealed for a lesson
+    pub fn revealed_hint_count(&self, lesson_id: &str) -> Result<usize> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM hint_reveals WHERE lesson_id = ?",
+            params![lesson_id],
+            |row| row.get(0),
+        )?;
+        
+        Ok(count as usize)
+    }
+
+    /// Record that a hint was revealed; revealing it again is a no-op
+    pub fn record_hint_reveal(&self, lesson_id: &str, index: usize) -> Result<()> {
+        let conn = self.conn.lock().map_err(|_| ShellError::Database(
+            rusqlite::Error::InvalidQuery
+        ))?;
+        
+        conn.execute(
+            "INSERT OR IGNORE INTO hint_reveals (lesson_id, hint_index, revealed_at) VALUES (?, ?, ?)",
+            params![lesson_id, index as i64, chrono::Utc::now().to_rfc3339()],
+        )?;
+        
+        Ok(())
+    }
+}
+
+/// Add a column to a table created before the column existed
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists = conn.prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|name| name.ok())
+        .any(|name| name == column);
+    
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    
+    Ok(())
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -223,6 +1033,174 @@ pub struct Project {
 pub struct LspConfig {
     pub language: String,
     pub server_path: String,
+    /// JSON array of arguments
     pub args: Option<String>,
+    /// JSON initialization options
     pub settings: Option<String>,
 }
+
+/// A row of the `submissions` table
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SubmissionRecord {
+    pub id: String,
+    pub lesson_id: Option<String>,
+    pub project_id: Option<String>,
+    pub submitted_at: String,
+    pub status: String,
+    pub score: Option<f64>,
+    pub feedback: Option<String>,
+    /// Serialized `GradingResult`
+    pub result: Option<String>,
+    /// Code that was submitted
+    pub source: Option<String>,
+}
+
+fn student_row(row: &rusqlite::Row) -> rusqlite::Result<Student> {
+    Ok(Student { id: row.get(0)?, name: row.get(1)?, email: row.get(2)?, added_at: row.get(3)? })
+}
+
+/// A row of the `snapshots` table
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotRecord {
+    pub id: String,
+    pub project_path: String,
+    pub label: String,
+    pub created_at: String,
+    pub file_count: u64,
+    /// Uncompressed size of the archived files
+    pub size_bytes: u64,
+}
+
+fn snapshot_row(row: &rusqlite::Row) -> rusqlite::Result<SnapshotRecord> {
+    Ok(SnapshotRecord {
+        id: row.get(0)?,
+        project_path: row.get(1)?,
+        label: row.get(2)?,
+        created_at: row.get(3)?,
+        file_count: row.get::<_, i64>(4)? as u64,
+        size_bytes: row.get::<_, i64>(5)? as u64,
+    })
+}
+
+/// A row of the `lesson_progress` table
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LessonProgress {
+    pub lesson_id: String,
+    /// Best percentage scored
+    pub best_score: f64,
+    pub attempts: u32,
+    /// Percentage the lesson needed when progress was last updated
+    pub passing_score: f64,
+    pub completed: bool,
+    pub last_submitted_at: String,
+}
+
+const LESSON_PROGRESS_SELECT: &str =
+    "SELECT lesson_id, best_score, attempts, passing_score, completed, last_submitted_at FROM lesson_progress";
+
+fn lesson_progress_row(row: &rusqlite::Row) -> rusqlite::Result<LessonProgress> {
+    Ok(LessonProgress {
+        lesson_id: row.get(0)?,
+        best_score: row.get(1)?,
+        attempts: row.get::<_, i64>(2)? as u32,
+        passing_score: row.get(3)?,
+        completed: row.get(4)?,
+        last_submitted_at: row.get(5)?,
+    })
+}
+
+fn unknown_project(id: &str) -> ShellError {
+    ShellError::Configuration(format!("Unknown project: {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_submission_round_trip() {
+        let db = Database::in_memory().unwrap();
+        db.upsert_lesson("lesson-1", "Lesson", "", "/lessons/one/lesson.yaml", "1.0.0").unwrap();
+        db.upsert_lesson("lesson-1", "Lesson", "", "/lessons/moved/lesson.yaml", "1.1.0").unwrap();
+
+        let mut record = SubmissionRecord {
+            id: "sub-1".into(),
+            lesson_id: Some("lesson-1".into()),
+            project_id: None,
+            submitted_at: "2026-01-13T10:30:00Z".into(),
+            status: "graded".into(),
+            score: Some(50.0),
+            feedback: None,
+            result: Some("{}".into()),
+            source: Some("print(1)".into()),
+        };
+        db.save_submission(&record).unwrap();
+        record.status = "reviewed".into();
+        db.save_submission(&record).unwrap();
+
+        let stored = db.get_submission("sub-1").unwrap().unwrap();
+        assert_eq!(stored.status, "reviewed");
+        assert_eq!(stored.result.as_deref(), Some("{}"));
+        assert_eq!(db.list_submission_sources("lesson-1").unwrap(), vec![("sub-1".to_string(), "print(1)".to_string())]);
+        assert_eq!(db.get_lesson_path("lesson-1").unwrap().as_deref(), Some("/lessons/moved/lesson.yaml"));
+        assert!(db.get_submission("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_lesson_progress_tracks_best_score() {
+        let db = Database::in_memory().unwrap();
+        let submission = |id: &str, lesson_id: &str, submitted_at: &str, score: f64| SubmissionRecord {
+            id: id.into(),
+            lesson_id: Some(lesson_id.into()),
+            project_id: None,
+            submitted_at: submitted_at.into(),
+            status: "graded".into(),
+            score: Some(score),
+            feedback: None,
+            result: None,
+            source: None,
+        };
+        db.save_submission(&submission("s1", "loops", "2026-01-13T10:00:00Z", 40.0)).unwrap();
+        db.save_submission(&submission("s2", "loops", "2026-01-14T10:00:00Z", 65.5)).unwrap();
+        db.save_submission(&submission("s3", "loops", "2026-01-15T10:00:00Z", 50.0)).unwrap();
+
+        let progress = db.update_lesson_progress("loops", 70.0).unwrap().unwrap();
+        assert_eq!(progress.best_score, 65.5);
+        assert_eq!(progress.attempts, 3);
+        assert!(!progress.completed);
+        assert_eq!(progress.last_submitted_at, "2026-01-15T10:00:00Z");
+
+        db.save_submission(&submission("s4", "loops", "2026-01-16T10:00:00Z", 70.0)).unwrap();
+        db.save_submission(&submission("s5", "basics", "2026-01-12T10:00:00Z", 100.0)).unwrap();
+        assert!(db.update_lesson_progress("loops", 70.0).unwrap().unwrap().completed);
+        db.update_lesson_progress("basics", 70.0).unwrap();
+
+        let listed: Vec<_> = db.list_lesson_progress().unwrap().into_iter().map(|p| (p.lesson_id, p.best_score)).collect();
+        assert_eq!(listed, vec![("loops".to_string(), 70.0), ("basics".to_string(), 100.0)]);
+
+        // Never attempted
+        assert_eq!(db.update_lesson_progress("functions", 70.0).unwrap(), None);
+        assert_eq!(db.get_lesson_progress("functions").unwrap(), None);
+    }
+
+    #[test]
+    fn test_snapshots_listed_per_project() {
+        let db = Database::in_memory().unwrap();
+        let snapshot = |id: &str, project: &str, created_at: &str| SnapshotRecord {
+            id: id.into(),
+            project_path: project.into(),
+            label: format!("label {}", id),
+            created_at: created_at.into(),
+            file_count: 2,
+            size_bytes: 10,
+        };
+        db.save_snapshot(&snapshot("s1", "/p", "2026-01-13T10:00:00Z")).unwrap();
+        db.save_snapshot(&snapshot("s2", "/p", "2026-01-13T11:00:00Z")).unwrap();
+        db.save_snapshot(&snapshot("s3", "/other", "2026-01-13T12:00:00Z")).unwrap();
+
+        let ids: Vec<_> = db.list_snapshots("/p").unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["s2", "s1"]);
+        assert_eq!(db.get_snapshot("s3").unwrap(), Some(snapshot("s3", "/other", "2026-01-13T12:00:00Z")));
+        assert!(db.get_snapshot("missing").unwrap().is_none());
+    }
+}
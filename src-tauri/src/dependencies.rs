@@ -0,0 +1,216 @@
+//! Lesson dependencies
+//!
+//! Run containers have no network, so third-party packages are installed
+//! ahead of time: a build container with network access installs them on
+//! top of the language's image and is committed as a new image that later
+//! runs use instead. Only packages the lesson lists may be installed, and
+//! the image is tagged by its contents so a second install reuses it.
+
+use bollard::container::{Config, CreateContainerOptions, LogsOptions, RemoveContainerOptions, StartContainerOptions, WaitContainerOptions};
+use bollard::image::CommitContainerOptions;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use crate::commands::execution::get_language_image;
+use crate::docker::DockerManager;
+use crate::error::{Result, ShellError};
+
+/// Repository dependency images are committed to
+const IMAGE_REPOSITORY: &str = "shell-deps";
+/// How long an install may run before it's killed
+const INSTALL_TIMEOUT: Duration = Duration::from_secs(300);
+/// Package managers need more room than a run
+const INSTALL_MEMORY_LIMIT: i64 = 1024 * 1024 * 1024;
+
+/// Outcome of installing a lesson's packages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallResult {
+    /// Image later runs of the project use
+    pub image: String,
+    /// Installed packages, sorted
+    pub packages: Vec<String>,
+    /// Whether an earlier install had already built the image
+    pub cached: bool,
+    /// Package manager output; empty when cached
+    pub log: String,
+}
+
+/// Install command and environment for a language's package manager
+fn installer(language: &str) -> Result<(Vec<&'static str>, Vec<&'static str>)> {
+    Ok(match language.to_lowercase().as_str() {
+        "python" | "py" => (
+            vec!["pip", "install", "--no-cache-dir", "--disable-pip-version-check", "--root-user-action=ignore"],
+            vec![],
+        ),
+        // Global modules aren't on the resolution path unless NODE_PATH says so
+        "javascript" | "js" | "node" | "typescript" | "ts" => (
+            vec!["npm", "install", "--global", "--no-audit", "--no-fund"],
+            vec!["NODE_PATH=/usr/local/lib/node_modules"],
+        ),
+        "ruby" | "rb" => (vec!["gem", "install", "--no-document"], vec![]),
+        _ => return Err(ShellError::Execution(format!("Installing packages is not supported for {}", language))),
+    })
+}
+
+/// Check `requested` against the lesson's `allowlist`, returning the
+/// packages sorted and deduplicated
+pub fn allowed_packages(requested: &[String], allowlist: &[String]) -> Result<Vec<String>> {
+    if requested.is_empty() {
+        return Err(ShellError::Execution("No packages requested".into()));
+    }
+    let mut packages = Vec::new();
+    for package in requested {
+        if !allowlist.contains(package) {
+            return Err(ShellError::Security(format!("Package '{}' is not allowed by this lesson", package)));
+        }
+        // Lesson files are untrusted too; nothing may read as an option
+        let safe = !package.starts_with('-')
+            && package.chars().all(|c| c.is_ascii_alphanumeric() || "._-@/=<>~^".contains(c));
+        if !safe {
+            return Err(ShellError::Security(format!("Invalid package name: {}", package)));
+        }
+        packages.push(package.clone());
+    }
+    packages.sort();
+    packages.dedup();
+    Ok(packages)
+}
+
+/// Tag for `base_image` with `packages` installed, stable across installs
+pub fn image_tag(base_image: &str, packages: &[String]) -> String {
+    let key = format!("{}\n{}", base_image, packages.join("\n"));
+    let digest = ring::digest::digest(&ring::digest::SHA256, key.as_bytes());
+    let hex: String = digest.as_ref().iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    format!("{}:{}", IMAGE_REPOSITORY, hex)
+}
+
+/// Build (or reuse) the image for `language` with `packages` installed.
+/// Packages must already be checked with `allowed_packages`.
+pub async fn install(docker: &DockerManager, language: &str, packages: &[String]) -> Result<InstallResult> {
+    let (command, env) = installer(language)?;
+    let base_image = get_language_image(language)?;
+    let image = image_tag(&base_image, packages);
+    let client = docker.client().await?;
+
+    if client.inspect_image(&image).await.is_ok() {
+        return Ok(InstallResult { image, packages: packages.to_vec(), cached: true, log: String::new() });
+    }
+
+    let cmd = command.iter().map(|s| s.to_string()).chain(packages.iter().cloned()).collect();
+    let config = Config {
+        image: Some(base_image),
+        cmd: Some(cmd),
+        env: Some(env.iter().map(|s| s.to_string()).collect()),
+        // The one container that may reach package registries
+        host_config: Some(bollard::models::HostConfig {
+            network_mode: None,
+            ..DockerManager::host_config(Some(INSTALL_MEMORY_LIMIT), None)
+        }),
+        labels: Some(docker.labels("build")),
+        ..Default::default()
+    };
+    let container = client.create_container(None::<CreateContainerOptions<String>>, config).await
+        .map_err(|e| ShellError::Docker(format!("Failed to create build container: {}", e)))?;
+
+    let built = build(&client, &container.id, &image).await;
+    let _ = client.remove_container(&container.id, Some(RemoveContainerOptions { force: true, ..Default::default() })).await;
+    let log = built?;
+    Ok(InstallResult { image, packages: packages.to_vec(), cached: false, log })
+}
+
+/// Run the install in `container` and commit it as `image`, returning the log
+async fn build(client: &bollard::Docker, container: &str, image: &str) -> Result<String> {
+    client.start_container(container, None::<StartContainerOptions<String>>).await
+        .map_err(|e| ShellError::Docker(format!("Failed to start build container: {}", e)))?;
+
+    let waited = tokio::time::timeout(
+        INSTALL_TIMEOUT,
+        client.wait_container(container, None::<WaitContainerOptions<String>>).next(),
+    ).await;
+
+    let mut log = String::new();
+    let mut logs = client.logs(container, Some(LogsOptions::<String> { stdout: true, stderr: true, ..Default::default() }));
+    while let Some(Ok(output)) = logs.next().await {
+        log.push_str(&output.to_string());
+    }
+
+    // A failed install reports a non-zero exit as an error
+    let exit_code = match waited {
+        Ok(Some(Ok(response))) => response.status_code,
+        Ok(Some(Err(bollard::errors::Error::DockerContainerWaitError { code, .. }))) => code,
+        Ok(_) => -1,
+        Err(_) => return Err(ShellError::Execution(format!("Package install timed out\n{}", log))),
+    };
+    if exit_code != 0 {
+        return Err(ShellError::Execution(format!("Package install failed with exit code {}\n{}", exit_code, log)));
+    }
+
+    let (repo, tag) = image.split_once(':').expect("tagged image");
+    let options = CommitContainerOptions { container, repo, tag, comment: "Shell lesson dependencies", ..Default::default() };
+    client.commit_container(options, Config::<String>::default()).await
+        .map_err(|e| ShellError::Docker(format!("Failed to save dependency image: {}", e)))?;
+    Ok(log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::{ExecutionBackend, ExecutionRequest};
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_only_allowlisted_packages_install() {
+        let allowlist = strings(&["requests", "six"]);
+        assert_eq!(allowed_packages(&strings(&["six", "requests", "six"]), &allowlist).unwrap(), strings(&["requests", "six"]));
+        let err = allowed_packages(&strings(&["six", "numpy"]), &allowlist).unwrap_err();
+        assert_eq!(err.to_string(), "Security error: Package 'numpy' is not allowed by this lesson");
+        assert!(allowed_packages(&strings(&["--index-url=x"]), &strings(&["--index-url=x"])).is_err());
+        assert!(allowed_packages(&[], &allowlist).is_err());
+    }
+
+    #[test]
+    fn test_image_tag_depends_on_contents() {
+        let tag = image_tag("python:3.12-slim", &strings(&["six"]));
+        assert!(tag.starts_with("shell-deps:"));
+        assert_eq!(tag, image_tag("python:3.12-slim", &strings(&["six"])));
+        assert_ne!(tag, image_tag("python:3.12-slim", &strings(&["requests"])));
+        assert_ne!(tag, image_tag("python:3.11-slim", &strings(&["six"])));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Docker and network access"]
+    async fn test_installed_package_is_importable_in_later_runs() {
+        let docker = DockerManager::new();
+        docker.connect().await.unwrap();
+        let packages = allowed_packages(&strings(&["six"]), &strings(&["six"])).unwrap();
+
+        let installed = install(&docker, "python", &packages).await.unwrap();
+        assert!(installed.cached || installed.log.contains("six"));
+        assert!(install(&docker, "python", &packages).await.unwrap().cached);
+
+        let project = std::env::temp_dir();
+        let result = docker.execute(ExecutionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            image: installed.image,
+            command: strings(&["python", "-c", "import six; print(six.__name__)"]),
+            working_dir: "/workspace".into(),
+            source_path: project.to_string_lossy().into_owned(),
+            env: Default::default(),
+            memory_limit: None,
+            cpu_quota: None,
+            timeout: Some(30),
+            step_mode: false,
+            trace_io: false,
+            stdin: None,
+            compile_command: None,
+            raw_output: false,
+            workspace_writable: false,
+            scratch_workspace: false,
+        }).await.unwrap();
+        assert_eq!(result.exit_code, 0, "{}", result.stderr);
+        assert_eq!(result.stdout, "six\n");
+    }
+}
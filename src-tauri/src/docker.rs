@@ -7,14 +7,18 @@
 //! - Read-only mounts where possible
 
 use bollard::Docker;
-use bollard::container::{Config, CreateContainerOptions, StartContainerOptions, LogsOptions, WaitContainerOptions};
-use bollard::models::{HostConfig, Mount, MountTypeEnum};
+use bollard::container::{
+    AttachContainerOptions, Config, CreateContainerOptions, ListContainersOptions, StartContainerOptions, LogOutput, LogsOptions, WaitContainerOptions,
+};
+use bollard::image::{CreateImageOptions, ListImagesOptions};
+use bollard::models::{CreateImageInfo, HostConfig, ImageInspect, Mount, MountTypeEnum, SystemInfo};
 use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use crate::error::{Result, ShellError};
+use tokio::sync::{Mutex, Semaphore};
+use crate::error::{ErrorContext, Result, ShellError};
+use crate::security::SecurityPolicy;
 
 /// Default resource limits
 const DEFAULT_MEMORY_LIMIT: i64 = 256 * 1024 * 1024; // 256 MB
@@ -22,17 +26,61 @@ const DEFAULT_CPU_PERIOD: i64 = 100_000; // 100ms
 const DEFAULT_CPU_QUOTA: i64 = 50_000; // 50% of one CPU
 const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
 
+/// Bounds configured limits are clamped to
+const MEMORY_LIMIT_MB_RANGE: (u32, u32) = (64, 16 * 1024);
+const CPU_PERCENT_RANGE: (u32, u32) = (10, 400);
+const TIMEOUT_SECONDS_RANGE: (u32, u32) = (1, 600);
+
+/// Shortest time allowed for draining a container's logs
+const MIN_LOG_COLLECTION: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How long a finished run's output stays available to `execution_output`
+const OUTPUT_TAIL_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Most output kept per stream; later output is dropped
+const MAX_STREAM_OUTPUT: usize = 1024 * 1024;
+
+/// Most of a single line kept, so a huge line without newlines (a JSON dump,
+/// say) can't fill the whole budget or be buffered in one piece
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// Maximum number of execution containers alive at once
+const MAX_CONCURRENT_CONTAINERS: usize = 4;
+
+/// How long a `docker_info` probe is reused
+const INFO_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Labels marking containers this app created
+pub const LABEL_MANAGED: &str = "dev.shell.managed";
+pub const LABEL_INSTANCE: &str = "dev.shell.instance";
+pub const LABEL_KIND: &str = "dev.shell.kind";
+
 pub struct DockerManager {
     client: Arc<Mutex<Option<Docker>>>,
+    /// Distinguishes this app instance's containers from another's
+    instance_id: String,
     running_containers: Arc<Mutex<HashMap<String, ContainerInfo>>>,
+    container_slots: Arc<Semaphore>,
+    listener: Option<LifecycleListener>,
+    /// Last capability probe and when it ran
+    info_cache: Arc<Mutex<Option<(std::time::Instant, DockerInfo)>>>,
+    /// Output of current and recently finished runs
+    output_tails: Arc<OutputTails>,
+    /// Checks every project bind-mounted into a run
+    policy: Option<Arc<SecurityPolicy>>,
 }
 
+/// Receives lifecycle events for every run
+pub type LifecycleListener = Arc<dyn Fn(&LifecycleEvent) + Send + Sync>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContainerInfo {
     pub id: String,
     pub execution_id: String,
     pub started_at: chrono::DateTime<chrono::Utc>,
     pub status: ContainerStatus,
+    /// Limits the container was created with
+    pub limits: ResourceLimits,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -44,6 +92,212 @@ pub enum ContainerStatus {
     Failed,
 }
 
+/// Lifecycle transition of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LifecycleStage {
+    /// Waiting for a container slot
+    Queued,
+    /// Container created and started
+    Started,
+    /// Run over, whether it succeeded, failed or timed out
+    Finished,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LifecycleEvent {
+    pub execution_id: String,
+    pub stage: LifecycleStage,
+    pub status: ContainerStatus,
+    /// Time since the run was queued
+    pub elapsed_ms: u64,
+    /// Set once the run has finished and the program exited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i64>,
+}
+
+impl LifecycleEvent {
+    /// Tauri event name, e.g. `execution://<id>/started`
+    pub fn name(&self) -> String {
+        let stage = match self.stage {
+            LifecycleStage::Queued => "queued",
+            LifecycleStage::Started => "started",
+            LifecycleStage::Finished => "finished",
+        };
+        format!("execution://{}/{}", self.execution_id, stage)
+    }
+
+    /// Final event for a run's outcome
+    pub fn finished(execution_id: &str, outcome: &Result<ExecutionResult>, elapsed_ms: u64) -> Self {
+        let (status, exit_code) = match outcome {
+            Ok(result) if result.timed_out => (ContainerStatus::TimedOut, None),
+            Ok(result) => (ContainerStatus::Completed, Some(result.exit_code)),
+            Err(_) => (ContainerStatus::Failed, None),
+        };
+        Self { execution_id: execution_id.to_string(), stage: LifecycleStage::Finished, status, elapsed_ms, exit_code }
+    }
+}
+
+/// Limits for runs that don't set their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Bytes
+    pub memory_limit: i64,
+    /// Microseconds of CPU time per `DEFAULT_CPU_PERIOD`
+    pub cpu_quota: i64,
+    pub timeout_seconds: u64,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self { memory_limit: DEFAULT_MEMORY_LIMIT, cpu_quota: DEFAULT_CPU_QUOTA, timeout_seconds: DEFAULT_TIMEOUT_SECONDS }
+    }
+}
+
+impl ResourceLimits {
+    /// Limits from configured values, clamped to sane bounds. `cpu_percent`
+    /// is of one CPU, so 200 allows two.
+    pub fn new(memory_mb: u32, cpu_percent: u32, timeout_seconds: u32) -> Self {
+        let memory_mb = memory_mb.clamp(MEMORY_LIMIT_MB_RANGE.0, MEMORY_LIMIT_MB_RANGE.1);
+        let cpu_percent = cpu_percent.clamp(CPU_PERCENT_RANGE.0, CPU_PERCENT_RANGE.1);
+        Self {
+            memory_limit: i64::from(memory_mb) * 1024 * 1024,
+            cpu_quota: DEFAULT_CPU_PERIOD * i64::from(cpu_percent) / 100,
+            timeout_seconds: u64::from(timeout_seconds.clamp(TIMEOUT_SECONDS_RANGE.0, TIMEOUT_SECONDS_RANGE.1)),
+        }
+    }
+
+    /// Limits a run of `request` gets: its own, else the defaults
+    pub fn for_request(request: &ExecutionRequest) -> Self {
+        let defaults = Self::default();
+        Self {
+            memory_limit: request.memory_limit.unwrap_or(defaults.memory_limit),
+            cpu_quota: request.cpu_quota.unwrap_or(defaults.cpu_quota),
+            timeout_seconds: request.timeout.map_or(defaults.timeout_seconds, Self::clamp_timeout),
+        }
+    }
+
+    /// A requested time limit, clamped like configured ones; requests come
+    /// from the frontend and can carry any value
+    pub fn clamp_timeout(seconds: u64) -> u64 {
+        seconds.clamp(u64::from(TIMEOUT_SECONDS_RANGE.0), u64::from(TIMEOUT_SECONDS_RANGE.1))
+    }
+}
+
+/// One message of an image pull
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PullProgress {
+    pub image: String,
+    /// Layer the message is about; absent for image-wide status
+    pub layer: Option<String>,
+    /// e.g. `Downloading`, `Extracting`, `Pull complete`
+    pub status: String,
+    /// Bytes of the layer done so far
+    pub current: Option<u64>,
+    /// Layer size in bytes, when Docker knows it
+    pub total: Option<u64>,
+}
+
+impl PullProgress {
+    /// Parse a message of the pull stream. Errors Docker reports in the
+    /// stream become `Err`; messages without a status are skipped.
+    pub fn from_info(image: &str, info: &CreateImageInfo) -> Result<Option<Self>> {
+        if let Some(error) = &info.error {
+            return Err(ShellError::Docker(format!("Failed to pull {}: {}", image, error)));
+        }
+        let Some(status) = &info.status else { return Ok(None) };
+        let detail = info.progress_detail.as_ref();
+        let bytes = |value: Option<i64>| value.and_then(|v| u64::try_from(v).ok()).filter(|&v| v > 0);
+        Ok(Some(Self {
+            image: image.to_string(),
+            // Image-wide lines use the tag as their id
+            layer: info.id.clone().filter(|id| id != split_reference(image).1),
+            status: status.clone(),
+            current: bytes(detail.and_then(|d| d.current)),
+            total: bytes(detail.and_then(|d| d.total)),
+        }))
+    }
+}
+
+/// Whether a language's image is already pulled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImagePresence {
+    pub language: String,
+    pub image: String,
+    pub present: bool,
+}
+
+/// What the Docker daemon offers, for the UI to tailor itself. When Docker
+/// can't be reached, `available` is false and `reason` says why.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DockerInfo {
+    pub available: bool,
+    pub reason: Option<String>,
+    pub version: Option<String>,
+    pub os: Option<String>,
+    pub architecture: Option<String>,
+    pub cpus: Option<u32>,
+    /// Memory of the daemon's host (or VM), in bytes
+    pub memory_total: Option<u64>,
+    /// `memory_total` less the memory limits of running containers
+    pub memory_available: Option<u64>,
+    pub storage_driver: Option<String>,
+    pub images: Vec<ImagePresence>,
+}
+
+impl DockerInfo {
+    pub fn unavailable(reason: impl Into<String>) -> Self {
+        Self { available: false, reason: Some(reason.into()), ..Default::default() }
+    }
+
+    /// Shape the daemon's `info`, given the memory limits of running
+    /// containers and the tags of local images
+    pub fn from_daemon(info: &SystemInfo, reserved_memory: u64, local_tags: &[String], languages: &[(String, String)]) -> Self {
+        let memory_total = info.mem_total.and_then(|m| u64::try_from(m).ok()).filter(|&m| m > 0);
+        Self {
+            available: true,
+            reason: None,
+            version: info.server_version.clone(),
+            os: info.operating_system.clone(),
+            architecture: info.architecture.clone(),
+            cpus: info.ncpu.and_then(|n| u32::try_from(n).ok()),
+            memory_total,
+            memory_available: memory_total.map(|total| total.saturating_sub(reserved_memory)),
+            storage_driver: info.driver.clone(),
+            images: languages.iter().map(|(language, image)| ImagePresence {
+                language: language.clone(),
+                image: image.clone(),
+                present: local_tags.contains(image),
+            }).collect(),
+        }
+    }
+}
+
+/// Split an image reference into name and tag, defaulting to `latest`
+fn split_reference(image: &str) -> (&str, &str) {
+    match image.rsplit_once(':') {
+        // A colon before the last slash belongs to a registry port
+        Some((name, tag)) if !tag.contains('/') => (name, tag),
+        _ => (image, "latest"),
+    }
+}
+
+/// Whether `image` is local, given the daemon's answer to inspecting it.
+/// A missing image is `ImageNotFound` so the UI can offer a pull; a name
+/// the daemon rejects is a configuration error, which pulling won't fix.
+fn check_image(image: &str, inspected: std::result::Result<ImageInspect, bollard::errors::Error>) -> Result<()> {
+    match inspected {
+        Ok(_) => Ok(()),
+        Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => {
+            Err(ShellError::ImageNotFound(image.to_string()))
+        }
+        Err(bollard::errors::Error::DockerResponseServerError { status_code: 400, message }) => {
+            Err(ShellError::Configuration(format!("Invalid image name {}: {}", image, message)))
+        }
+        Err(e) => Err(ShellError::Docker(format!("Failed to inspect image {}: {}", image, e))),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionRequest {
     /// Unique execution ID
@@ -68,6 +322,35 @@ pub struct ExecutionRequest {
     pub step_mode: bool,
     /// Capture stdin/stdout/stderr
     pub trace_io: bool,
+    /// Input written to the program's stdin, which is then closed
+    #[serde(default)]
+    pub stdin: Option<String>,
+    /// Build step run before `command`; the run is skipped if it fails
+    #[serde(default)]
+    pub compile_command: Option<Vec<String>>,
+    /// Also return a stream's exact bytes, base64-encoded, when it isn't
+    /// valid UTF-8
+    #[serde(default)]
+    pub raw_output: bool,
+    /// Mount the source read-write so the program can change files in
+    /// `/workspace`. Those edits land in the host directory, so this is
+    /// only for lessons that edit in place, never for grading.
+    #[serde(default)]
+    pub workspace_writable: bool,
+    /// `source_path` is a scratch directory the app created for this run,
+    /// such as a grading workspace, rather than a project; it's outside the
+    /// policy's allowed roots by design
+    #[serde(default)]
+    pub scratch_workspace: bool,
+}
+
+/// Which step of a run produced the result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionPhase {
+    /// Compilation failed; the program never ran
+    Compile,
+    #[default]
+    Run,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,6 +363,27 @@ pub struct ExecutionResult {
     pub timed_out: bool,
     /// Execution trace for educational features
     pub trace: Option<ExecutionTrace>,
+    #[serde(default)]
+    pub phase: ExecutionPhase,
+    /// Compiler output (diagnostics, warnings) for compiled languages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compile_output: Option<String>,
+    /// Test framework verdict, set only for test runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tests_passed: Option<bool>,
+    /// Exact stdout bytes, set when raw output was requested and `stdout`
+    /// had to replace invalid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_base64: Option<String>,
+    /// Same as `stdout_base64`, for stderr
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_base64: Option<String>,
+    /// The log stream did not finish in time; output may be incomplete
+    #[serde(default)]
+    pub log_collection_timed_out: bool,
+    /// Output went past the per-stream or per-line limit and was cut
+    #[serde(default)]
+    pub output_truncated: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,7 +412,42 @@ impl DockerManager {
     pub fn new() -> Self {
         Self {
             client: Arc::new(Mutex::new(None)),
+            instance_id: uuid::Uuid::new_v4().to_string(),
             running_containers: Arc::new(Mutex::new(HashMap::new())),
+            container_slots: Arc::new(Semaphore::new(MAX_CONCURRENT_CONTAINERS)),
+            listener: None,
+            info_cache: Arc::new(Mutex::new(None)),
+            output_tails: Arc::new(OutputTails::default()),
+            policy: None,
+        }
+    }
+
+    /// Report run lifecycle transitions to `listener`
+    pub fn with_listener(mut self, listener: LifecycleListener) -> Self {
+        self.listener = Some(listener);
+        self
+    }
+
+    /// Check every mounted project against `policy`, whichever command
+    /// started the run
+    pub fn with_policy(mut self, policy: Arc<SecurityPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    fn emit(&self, execution_id: &str, stage: LifecycleStage, status: ContainerStatus, start_time: std::time::Instant) {
+        self.emit_event(&LifecycleEvent {
+            execution_id: execution_id.to_string(),
+            stage,
+            status,
+            elapsed_ms: start_time.elapsed().as_millis() as u64,
+            exit_code: None,
+        });
+    }
+
+    fn emit_event(&self, event: &LifecycleEvent) {
+        if let Some(listener) = &self.listener {
+            listener(event);
         }
     }
 
@@ -139,31 +478,157 @@ impl DockerManager {
         }
     }
 
-    /// Run code in a container
+    /// Connected client handle, cheap to clone
+    pub async fn client(&self) -> Result<Docker> {
+        self.client.lock().await.clone()
+            .ok_or_else(|| ShellError::Docker("Docker not connected".into()))
+    }
+
+    /// Probe the daemon, reusing a recent probe. `languages` pairs each
+    /// language with its image so the result says which are pulled.
+    pub async fn info(&self, languages: &[(String, String)]) -> DockerInfo {
+        let mut cache = self.info_cache.lock().await;
+        if let Some((probed_at, info)) = cache.as_ref() {
+            if probed_at.elapsed() < INFO_CACHE_TTL {
+                return info.clone();
+            }
+        }
+        let info = match self.probe(languages).await {
+            Ok(info) => info,
+            Err(e) => DockerInfo::unavailable(e.to_string()),
+        };
+        *cache = Some((std::time::Instant::now(), info.clone()));
+        info
+    }
+
+    async fn probe(&self, languages: &[(String, String)]) -> Result<DockerInfo> {
+        if !self.is_available().await {
+            return Err(ShellError::Docker("Docker is not installed or not running".into()));
+        }
+        let client = self.client().await?;
+        let info = client.info().await
+            .map_err(|e| ShellError::Docker(format!("Failed to read Docker info: {}", e)))?;
+
+        let local_tags: Vec<String> = client.list_images(None::<ListImagesOptions<String>>).await
+            .map_err(|e| ShellError::Docker(format!("Failed to list images: {}", e)))?
+            .into_iter()
+            .flat_map(|image| image.repo_tags)
+            .collect();
+
+        let mut reserved_memory = 0u64;
+        let running = client.list_containers(None::<ListContainersOptions<String>>).await
+            .map_err(|e| ShellError::Docker(format!("Failed to list containers: {}", e)))?;
+        for container in running {
+            let Some(id) = container.id else { continue };
+            if let Ok(details) = client.inspect_container(&id, None).await {
+                let limit = details.host_config.and_then(|c| c.memory).unwrap_or(0);
+                reserved_memory += u64::try_from(limit).unwrap_or(0);
+            }
+        }
+        Ok(DockerInfo::from_daemon(&info, reserved_memory, &local_tags, languages))
+    }
+
+    /// Pull `image` unless it's already local, reporting each stream message
+    /// to `on_progress`. Returns whether a pull happened.
+    pub async fn pull_image(&self, image: &str, on_progress: impl Fn(PullProgress)) -> Result<bool> {
+        let client = self.client().await?;
+        match check_image(image, client.inspect_image(image).await) {
+            Ok(()) => return Ok(false),
+            Err(ShellError::ImageNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let (from_image, tag) = split_reference(image);
+        let options = CreateImageOptions { from_image, tag, ..Default::default() };
+        let mut stream = client.create_image(Some(options), None, None);
+        while let Some(info) = stream.next().await {
+            let info = info.map_err(|e| ShellError::Docker(format!("Failed to pull {}: {}", image, e)))?;
+            if let Some(progress) = PullProgress::from_info(image, &info)? {
+                on_progress(progress);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Sandbox limits shared by every container: memory, CPU and no network
+    pub fn host_config(memory_limit: Option<i64>, cpu_quota: Option<i64>) -> HostConfig {
+        HostConfig {
+            memory: Some(memory_limit.unwrap_or(DEFAULT_MEMORY_LIMIT)),
+            cpu_period: Some(DEFAULT_CPU_PERIOD),
+            cpu_quota: Some(cpu_quota.unwrap_or(DEFAULT_CPU_QUOTA)),
+            network_mode: Some("none".to_string()), // No network access
+            ..Default::default()
+        }
+    }
+
+    /// Labels for a container of `kind` (`run`, `terminal`) owned by this instance
+    pub fn labels(&self, kind: &str) -> HashMap<String, String> {
+        HashMap::from([
+            (LABEL_MANAGED.to_string(), "true".to_string()),
+            (LABEL_INSTANCE.to_string(), self.instance_id.clone()),
+            (LABEL_KIND.to_string(), kind.to_string()),
+        ])
+    }
+
+    /// Run code in a container.
+    ///
+    /// Emits `queued`, `started` and `finished` lifecycle events; `finished`
+    /// fires on every path, including errors and timeouts.
     pub async fn run(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
-        let client = self.client.lock().await;
-        let docker = client.as_ref()
-            .ok_or_else(|| ShellError::Docker("Docker not connected".into()))?;
+        let queued_at = std::time::Instant::now();
+        let execution_id = request.id.clone();
+        self.emit(&execution_id, LifecycleStage::Queued, ContainerStatus::Starting, queued_at);
+
+        let outcome = self.run_container(request, queued_at).await;
+
+        // Error paths can leave the container tracked
+        self.running_containers.lock().await.remove(&execution_id);
+        self.output_tails.finish(&execution_id);
+        self.emit_event(&LifecycleEvent::finished(&execution_id, &outcome, queued_at.elapsed().as_millis() as u64));
+        outcome
+    }
+
+    /// Output so far of a running or recently finished execution
+    pub fn execution_output(&self, execution_id: &str) -> Result<ExecutionOutput> {
+        self.output_tails.get(execution_id)
+            .ok_or_else(|| ShellError::Execution(format!("No output recorded for execution {}", execution_id)))
+    }
+
+    /// Projects are checked here, where they're mounted, so no caller can
+    /// mount one the policy doesn't allow
+    fn check_mount(&self, request: &ExecutionRequest) -> Result<()> {
+        if let (Some(policy), false) = (&self.policy, request.scratch_workspace) {
+            policy.validate_mount(std::path::Path::new(&request.source_path))?;
+        }
+        check_source_path(&request.source_path)
+    }
+
+    async fn run_container(&self, request: ExecutionRequest, queued_at: std::time::Instant) -> Result<ExecutionResult> {
+        self.check_mount(&request)?;
+
+        // Clone the handle so concurrent runs don't serialize on the client lock
+        let docker = self.client().await?;
+
+        // Wait for a free slot so parallel callers can't exhaust the host
+        let _slot = self.container_slots.acquire().await
+            .map_err(|e| ShellError::Docker(e.to_string()))?;
 
         let start_time = std::time::Instant::now();
         let execution_id = request.id.clone();
+        let limits = ResourceLimits::for_request(&request);
 
         // Build container configuration
         let host_config = HostConfig {
-            memory: Some(request.memory_limit.unwrap_or(DEFAULT_MEMORY_LIMIT)),
-            cpu_period: Some(DEFAULT_CPU_PERIOD),
-            cpu_quota: Some(request.cpu_quota.unwrap_or(DEFAULT_CPU_QUOTA)),
-            network_mode: Some("none".to_string()), // No network access
             mounts: Some(vec![
                 Mount {
                     target: Some("/workspace".to_string()),
                     source: Some(request.source_path.clone()),
                     typ: Some(MountTypeEnum::BIND),
-                    read_only: Some(true), // Source is read-only
+                    read_only: Some(!request.workspace_writable), // Source is read-only unless asked otherwise
                     ..Default::default()
                 },
             ]),
-            ..Default::default()
+            ..Self::host_config(Some(limits.memory_limit), Some(limits.cpu_quota))
         };
 
         let env: Vec<String> = request.env
@@ -171,15 +636,32 @@ impl DockerManager {
             .map(|(k, v)| format!("{}={}", k, v))
             .collect();
 
+        // Compile and run share one container; a marker line printed between
+        // them tells the phases' output apart
+        let marker = request.compile_command.as_ref().map(|_| format!("__shell_run_{}__", execution_id.replace('-', "")));
+        let command = match (&request.compile_command, &marker) {
+            (Some(compile), Some(marker)) => phased_command(compile, &request.command, marker),
+            _ => request.command.clone(),
+        };
+
+        let has_stdin = request.stdin.is_some();
         let config = Config {
             image: Some(request.image.clone()),
-            cmd: Some(request.command.clone()),
+            cmd: Some(command),
             working_dir: Some(request.working_dir.clone()),
             env: Some(env),
             host_config: Some(host_config),
+            open_stdin: Some(has_stdin),
+            stdin_once: Some(has_stdin),
+            attach_stdin: Some(has_stdin),
+            labels: Some(self.labels("run")),
             ..Default::default()
         };
 
+        // Runs don't pull on their own; a missing image gets a clear error
+        // instead of create_container's raw one
+        check_image(&request.image, docker.inspect_image(&request.image).await)?;
+
         // Create container
         let container_name = format!("shell-exec-{}", &execution_id[..8]);
 
@@ -197,12 +679,41 @@ impl DockerManager {
                 execution_id: execution_id.clone(),
                 started_at: chrono::Utc::now(),
                 status: ContainerStatus::Starting,
+                limits,
             });
         }
 
-        // Start container
-        docker.start_container(&container.id, None::<StartContainerOptions<String>>).await
-            .map_err(|e| ShellError::Docker(format!("Failed to start container: {}", e)))?;
+        // Attach before starting so no input is missed
+        let started = async {
+            let stdin_writer = match request.stdin {
+                Some(_) => {
+                    let options = AttachContainerOptions::<String> {
+                        stdin: Some(true),
+                        stream: Some(true),
+                        ..Default::default()
+                    };
+                    let attached = docker.attach_container(&container.id, Some(options)).await
+                        .map_err(|e| ShellError::Docker(format!("Failed to attach stdin: {}", e))
+                            .with_context(ErrorContext { container_id: Some(container.id.clone()), ..Default::default() }))?;
+                    Some(attached.input)
+                }
+                None => None,
+            };
+
+            // Start container
+            docker.start_container(&container.id, None::<StartContainerOptions<String>>).await
+                .map_err(|e| ShellError::Docker(format!("Failed to start container: {}", e))
+                    .with_context(ErrorContext { container_id: Some(container.id.clone()), ..Default::default() }))?;
+            Ok::<_, ShellError>(stdin_writer)
+        }.await;
+        let stdin_writer = match started {
+            Ok(writer) => writer,
+            Err(e) => {
+                // Otherwise the created container would be left behind
+                remove_forced(&docker, &container.id).await;
+                return Err(e);
+            }
+        };
 
         // Update status
         {
@@ -211,77 +722,92 @@ impl DockerManager {
                 info.status = ContainerStatus::Running;
             }
         }
+        self.emit(&execution_id, LifecycleStage::Started, ContainerStatus::Running, queued_at);
 
-        // Wait for completion with timeout
-        let timeout = request.timeout.unwrap_or(DEFAULT_TIMEOUT_SECONDS);
-        let wait_result = tokio::time::timeout(
-            std::time::Duration::from_secs(timeout),
-            docker.wait_container(&container.id, None::<WaitContainerOptions<String>>).next()
-        ).await;
-
-        let (exit_code, timed_out) = match wait_result {
-            Ok(Some(Ok(response))) => (response.status_code, false),
-            Ok(_) => (-1, false),
-            Err(_) => {
-                // Timeout - kill the container
-                let _ = docker.kill_container(&container.id, None::<bollard::container::KillContainerOptions<String>>).await;
-                (-1, true)
+        let mut io_events = Vec::new();
+        if let (Some(mut writer), Some(input)) = (stdin_writer, request.stdin.clone()) {
+            if request.trace_io {
+                io_events.push(IoEvent {
+                    timestamp_ms: start_time.elapsed().as_millis() as u64,
+                    stream: "stdin".to_string(),
+                    data: input.clone(),
+                });
             }
-        };
+            // Written in the background: a program that never reads its
+            // input must not block the wait below
+            tokio::spawn(async move {
+                use tokio::io::AsyncWriteExt;
+                let _ = writer.write_all(input.as_bytes()).await;
+                let _ = writer.shutdown().await;
+            });
+        }
 
-        // Collect logs
+        // Logs are followed while the program runs, so late-joining clients
+        // can catch up on what it has printed so far
         let log_options = LogsOptions::<String> {
+            follow: true,
             stdout: true,
             stderr: true,
             ..Default::default()
         };
+        let logs = docker.logs(&container.id, Some(log_options));
+        self.output_tails.begin(&execution_id, marker.clone());
 
-        let mut stdout = String::new();
-        let mut stderr = String::new();
-        let mut io_events = Vec::new();
+        // Wait for completion with timeout
+        let timeout = limits.timeout_seconds;
+        let wait = async {
+            let wait_result = tokio::time::timeout(
+                std::time::Duration::from_secs(timeout),
+                docker.wait_container(&container.id, None::<WaitContainerOptions<String>>).next()
+            ).await;
 
-        let mut logs = docker.logs(&container.id, Some(log_options));
-        while let Some(log) = logs.next().await {
-            if let Ok(log) = log {
-                match log {
-                    bollard::container::LogOutput::StdOut { message } => {
-                        let msg = String::from_utf8_lossy(&message).to_string();
-                        if request.trace_io {
-                            io_events.push(IoEvent {
-                                timestamp_ms: start_time.elapsed().as_millis() as u64,
-                                stream: "stdout".to_string(),
-                                data: msg.clone(),
-                            });
-                        }
-                        stdout.push_str(&msg);
-                    }
-                    bollard::container::LogOutput::StdErr { message } => {
-                        let msg = String::from_utf8_lossy(&message).to_string();
-                        if request.trace_io {
-                            io_events.push(IoEvent {
-                                timestamp_ms: start_time.elapsed().as_millis() as u64,
-                                stream: "stderr".to_string(),
-                                data: msg.clone(),
-                            });
-                        }
-                        stderr.push_str(&msg);
-                    }
-                    _ => {}
+            match wait_result {
+                Ok(Some(Ok(response))) => (response.status_code, false),
+                Ok(_) => (-1, false),
+                Err(_) => {
+                    // Timeout - SIGKILL the container's init, which takes every
+                    // process in it down, forked background children included
+                    let _ = docker.kill_container(&container.id, Some(kill_options())).await;
+                    (-1, true)
                 }
             }
-        }
+        };
 
-        // Cleanup container
-        let _ = docker.remove_container(&container.id, None::<bollard::container::RemoveContainerOptions>).await;
+        // A stuck log stream must not stall the run, so draining ends a short
+        // grace period after the time limit even if the stream doesn't
+        let budget = std::time::Duration::from_secs(timeout).saturating_add(MIN_LOG_COLLECTION);
+        let drain = collect_logs(logs, budget, request.trace_io, start_time, |stream, bytes| {
+            self.output_tails.append(&execution_id, stream, bytes);
+        });
+        let (collected, (exit_code, timed_out)) = tokio::join!(drain, wait);
+        let CollectedLogs { stdout: stdout_bytes, stderr: stderr_bytes, io_events: log_events, timed_out: log_collection_timed_out, truncated: output_truncated } =
+            collected;
+        io_events.extend(log_events);
+        let mut stdout = String::from_utf8_lossy(&stdout_bytes).into_owned();
+        let mut stderr = String::from_utf8_lossy(&stderr_bytes).into_owned();
+        let raw = |bytes: &[u8]| if request.raw_output { raw_output(bytes, marker.as_deref()) } else { None };
+        let (stdout_base64, stderr_base64) = (raw(&stdout_bytes), raw(&stderr_bytes));
 
-        // Remove from tracking
-        {
-            let mut running = self.running_containers.lock().await;
-            running.remove(&execution_id);
-        }
+        // Cleanup container, forced in case it is still shutting down or a
+        // stuck log stream outlived it
+        remove_forced(&docker, &container.id).await;
 
         let duration_ms = start_time.elapsed().as_millis() as u64;
 
+        let (phase, compile_output) = match &marker {
+            Some(marker) => {
+                let phases = split_phases(&stdout, &stderr, marker);
+                io_events.retain_mut(|event| {
+                    event.data = event.data.replace(&format!("{}\n", marker), "");
+                    !event.data.is_empty()
+                });
+                stdout = phases.stdout;
+                stderr = phases.stderr;
+                (phases.phase, Some(phases.compile_output).filter(|o| !o.is_empty()))
+            }
+            None => (ExecutionPhase::Run, None),
+        };
+
         Ok(ExecutionResult {
             id: execution_id,
             exit_code,
@@ -289,6 +815,13 @@ impl DockerManager {
             stderr,
             duration_ms,
             timed_out,
+            phase,
+            compile_output,
+            tests_passed: None,
+            stdout_base64,
+            stderr_base64,
+            log_collection_timed_out,
+            output_truncated,
             trace: if request.trace_io {
                 Some(ExecutionTrace {
                     steps: Vec::new(), // TODO: Implement step tracing
@@ -308,13 +841,33 @@ impl DockerManager {
 
         let running = self.running_containers.lock().await;
         if let Some(info) = running.get(execution_id) {
-            docker.kill_container(&info.id, None::<bollard::container::KillContainerOptions<String>>).await
+            docker.kill_container(&info.id, Some(kill_options())).await
                 .map_err(|e| ShellError::Docker(format!("Failed to stop container: {}", e)))?;
         }
 
         Ok(())
     }
 
+    /// Kill every tracked run's container, returning the executions stopped.
+    /// Containers that can't be reached are left to the orphan cleanup.
+    pub async fn stop_all(&self) -> Vec<String> {
+        let Some(docker) = self.client.lock().await.clone() else { return Vec::new() };
+        let tracked: Vec<ContainerInfo> = self.running_containers.lock().await.values().cloned().collect();
+        let kills = tracked.iter().map(|info| {
+            let docker = &docker;
+            async move {
+                match docker.kill_container(&info.id, Some(kill_options())).await {
+                    Ok(()) => Some(info.execution_id.clone()),
+                    Err(e) => {
+                        tracing::warn!("Failed to stop execution {}: {}", info.execution_id, e);
+                        None
+                    }
+                }
+            }
+        });
+        futures_util::future::join_all(kills).await.into_iter().flatten().collect()
+    }
+
     /// Get status of running containers
     pub async fn get_running(&self) -> Vec<ContainerInfo> {
         let running = self.running_containers.lock().await;
@@ -322,8 +875,726 @@ impl DockerManager {
     }
 }
 
+/// Kill with SIGKILL so nothing in the container can trap or ignore it
+fn kill_options() -> bollard::container::KillContainerOptions<String> {
+    bollard::container::KillContainerOptions { signal: "SIGKILL".to_string() }
+}
+
+/// Remove a run's container even if it is still running. Failures are left
+/// to the orphan cleanup.
+async fn remove_forced(docker: &Docker, container_id: &str) {
+    let options = bollard::container::RemoveContainerOptions { force: true, ..Default::default() };
+    let _ = docker.remove_container(container_id, Some(options)).await;
+}
+
+/// Output of a compile-then-run command, split at the marker line
+#[derive(Debug, PartialEq)]
+pub struct Phases {
+    pub phase: ExecutionPhase,
+    pub compile_output: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// `sh -c` script that compiles, prints `marker` on both streams, then runs
+pub fn phased_command(compile: &[String], run: &[String], marker: &str) -> Vec<String> {
+    let script = format!(
+        "{} && echo {marker} && echo {marker} >&2 && exec {}",
+        shell_join(compile),
+        shell_join(run),
+    );
+    vec!["sh".to_string(), "-c".to_string(), script]
+}
+
+/// Base64 of a stream's program output (what follows the compile `marker`,
+/// if any) when it isn't valid UTF-8 and decoding it would lose bytes
+pub fn raw_output(bytes: &[u8], marker: Option<&str>) -> Option<String> {
+    let bytes = match marker {
+        Some(marker) => {
+            let line = format!("{}\n", marker);
+            let start = bytes.windows(line.len()).position(|window| window == line.as_bytes())?;
+            &bytes[start + line.len()..]
+        }
+        None => bytes,
+    };
+    if std::str::from_utf8(bytes).is_ok() {
+        return None;
+    }
+    Some(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes))
+}
+
+/// Separate compiler output from program output. Without the marker the
+/// compile step failed and everything is compiler output.
+pub fn split_phases(stdout: &str, stderr: &str, marker: &str) -> Phases {
+    let line = format!("{}\n", marker);
+    match stdout.split_once(&line) {
+        Some((compile_stdout, run_stdout)) => {
+            let (compile_stderr, run_stderr) = stderr.split_once(&line).unwrap_or(("", stderr));
+            Phases {
+                phase: ExecutionPhase::Run,
+                compile_output: format!("{}{}", compile_stdout, compile_stderr),
+                stdout: run_stdout.to_string(),
+                stderr: run_stderr.to_string(),
+            }
+        }
+        None => Phases {
+            phase: ExecutionPhase::Compile,
+            compile_output: format!("{}{}", stdout, stderr),
+            stdout: String::new(),
+            stderr: String::new(),
+        },
+    }
+}
+
+/// The project bind-mounted at `/workspace` must be a directory when the
+/// container is created; Docker would otherwise create an empty one or fail
+/// with an obscure error
+fn check_source_path(path: &str) -> Result<()> {
+    let unavailable = |kind, reason: &str| {
+        ShellError::Filesystem(std::io::Error::new(kind, format!("Project directory {} {}", path, reason)))
+    };
+    // Scratch workspaces skip the policy, and a manager may have none
+    if crate::security::holds_app_data(std::path::Path::new(path)) {
+        return Err(ShellError::Security(format!("Access denied: {} contains Shell's app data and can't be mounted", path)));
+    }
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => Err(unavailable(std::io::ErrorKind::InvalidInput, "is not a directory")),
+        Err(e) => Err(unavailable(e.kind(), &format!("is unavailable: {}", e))),
+    }
+}
+
+/// A run's output so far, for a client catching up before it subscribes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// The run has finished; no more output will arrive
+    pub complete: bool,
+}
+
+/// Output of runs as it arrives, by execution id. Entries are dropped
+/// `OUTPUT_TAIL_TTL` after their run finishes.
+#[derive(Default)]
+pub struct OutputTails {
+    runs: std::sync::Mutex<HashMap<String, OutputTail>>,
+}
+
+struct OutputTail {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    /// Compile/run marker line, hidden from readers
+    marker: Option<String>,
+    finished_at: Option<std::time::Instant>,
+}
+
+impl OutputTails {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, OutputTail>> {
+        self.runs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn begin(&self, execution_id: &str, marker: Option<String>) {
+        let mut runs = self.lock();
+        runs.retain(|_, tail| tail.finished_at.map_or(true, |at| at.elapsed() < OUTPUT_TAIL_TTL));
+        runs.insert(execution_id.to_string(), OutputTail { stdout: Vec::new(), stderr: Vec::new(), marker, finished_at: None });
+    }
+
+    fn append(&self, execution_id: &str, stream: &str, bytes: &[u8]) {
+        if let Some(tail) = self.lock().get_mut(execution_id) {
+            match stream {
+                "stdout" => tail.stdout.extend_from_slice(bytes),
+                _ => tail.stderr.extend_from_slice(bytes),
+            }
+        }
+    }
+
+    fn finish(&self, execution_id: &str) {
+        if let Some(tail) = self.lock().get_mut(execution_id) {
+            tail.finished_at = Some(std::time::Instant::now());
+        }
+    }
+
+    /// Output of a running or recently finished run
+    pub fn get(&self, execution_id: &str) -> Option<ExecutionOutput> {
+        let runs = self.lock();
+        let tail = runs.get(execution_id)
+            .filter(|tail| tail.finished_at.map_or(true, |at| at.elapsed() < OUTPUT_TAIL_TTL))?;
+        let decode = |bytes: &[u8]| {
+            // A character may still be arriving; leave its start for later
+            let bytes = match std::str::from_utf8(bytes) {
+                Err(e) if e.error_len().is_none() => &bytes[..e.valid_up_to()],
+                _ => bytes,
+            };
+            let text = String::from_utf8_lossy(bytes);
+            match &tail.marker {
+                Some(marker) => text.replace(&format!("{}\n", marker), ""),
+                None => text.into_owned(),
+            }
+        };
+        Some(ExecutionOutput {
+            stdout: decode(&tail.stdout),
+            stderr: decode(&tail.stderr),
+            complete: tail.finished_at.is_some(),
+        })
+    }
+}
+
+/// Output drained from a container's log stream
+#[derive(Debug, Default)]
+pub struct CollectedLogs {
+    /// Raw bytes, decoded once complete so characters split across messages survive
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub io_events: Vec<IoEvent>,
+    /// `budget` ran out before the stream ended
+    pub timed_out: bool,
+    /// Some output was dropped to stay within the output limits
+    pub truncated: bool,
+}
+
+/// One stream's output, kept within `MAX_STREAM_OUTPUT` and with no line
+/// longer than `MAX_LINE_LENGTH`
+#[derive(Debug, Default)]
+struct BoundedOutput {
+    bytes: Vec<u8>,
+    /// Bytes kept of the line still being written
+    line_length: usize,
+    truncated: bool,
+}
+
+impl BoundedOutput {
+    /// Append what fits of `message`, returning the part that was kept
+    fn push(&mut self, mut message: &[u8]) -> &[u8] {
+        let start = self.bytes.len();
+        while !message.is_empty() {
+            let (line, ends_line, rest) = match message.iter().position(|&b| b == b'\n') {
+                Some(newline) => (&message[..newline], true, &message[newline + 1..]),
+                None => (message, false, &[][..]),
+            };
+            let room = MAX_STREAM_OUTPUT - self.bytes.len();
+            let kept = line.len().min(MAX_LINE_LENGTH - self.line_length).min(room);
+            self.bytes.extend_from_slice(&line[..kept]);
+            self.line_length += kept;
+            if kept < line.len() {
+                self.truncated = true;
+            }
+            if ends_line {
+                if room > kept {
+                    self.bytes.push(b'\n');
+                } else {
+                    self.truncated = true;
+                }
+                self.line_length = 0;
+            }
+            message = rest;
+        }
+        &self.bytes[start..]
+    }
+}
+
+/// Drain `logs` for at most `budget`, keeping whatever arrived in time and
+/// fits the output limits. Each message's kept part is passed to `on_output`
+/// as it arrives and, with `trace_io`, recorded as an `IoEvent`.
+pub async fn collect_logs<S, E>(
+    mut logs: S,
+    budget: std::time::Duration,
+    trace_io: bool,
+    start_time: std::time::Instant,
+    mut on_output: impl FnMut(&str, &[u8]),
+) -> CollectedLogs
+where
+    S: futures_util::Stream<Item = std::result::Result<LogOutput, E>> + Unpin,
+{
+    let (mut stdout, mut stderr) = (BoundedOutput::default(), BoundedOutput::default());
+    let mut io_events = Vec::new();
+    let drain = async {
+        while let Some(log) = logs.next().await {
+            let (stream, message, buffer) = match log {
+                Ok(LogOutput::StdOut { message }) => ("stdout", message, &mut stdout),
+                Ok(LogOutput::StdErr { message }) => ("stderr", message, &mut stderr),
+                _ => continue,
+            };
+            let kept = buffer.push(&message);
+            if kept.is_empty() {
+                continue;
+            }
+            on_output(stream, kept);
+            if trace_io {
+                io_events.push(IoEvent {
+                    timestamp_ms: start_time.elapsed().as_millis() as u64,
+                    stream: stream.to_string(),
+                    data: String::from_utf8_lossy(kept).into_owned(),
+                });
+            }
+        }
+    };
+    let timed_out = tokio::time::timeout(budget, drain).await.is_err();
+    CollectedLogs {
+        truncated: stdout.truncated || stderr.truncated,
+        stdout: stdout.bytes,
+        stderr: stderr.bytes,
+        io_events,
+        timed_out,
+    }
+}
+
+/// Quote `args` for an `sh -c` script
+pub fn shell_join(args: &[String]) -> String {
+    args.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>().join(" ")
+}
+
+fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "_./=+-:@,".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Something that can execute an `ExecutionRequest`.
+///
+/// `DockerManager` is the real implementation; grading takes this trait so
+/// test runs can be exercised without a Docker daemon.
+#[async_trait::async_trait]
+pub trait ExecutionBackend: Send + Sync {
+    async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult>;
+
+    /// Whether requests can run at all, checked once before a batch
+    async fn is_available(&self) -> bool {
+        true
+    }
+
+    /// Stop every request still running, returning their execution ids
+    async fn stop_all(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl ExecutionBackend for DockerManager {
+    async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+        self.run(request).await
+    }
+
+    async fn is_available(&self) -> bool {
+        DockerManager::is_available(self).await
+    }
+
+    async fn stop_all(&self) -> Vec<String> {
+        DockerManager::stop_all(self).await
+    }
+}
+
 impl Default for DockerManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKER: &str = "__shell_run_abc__";
+
+    #[test]
+    fn test_docker_info_shaping() {
+        let info: SystemInfo = serde_json::from_str(
+            r#"{"ServerVersion":"25.0.3","OperatingSystem":"Docker Desktop","Architecture":"aarch64","NCPU":8,"MemTotal":8589934592,"Driver":"overlay2"}"#,
+        ).unwrap();
+        let languages = vec![
+            ("python".to_string(), "python:3.12-slim".to_string()),
+            ("go".to_string(), "golang:1.21-alpine".to_string()),
+        ];
+        let tags = vec!["python:3.12-slim".to_string(), "shell-deps:0011aabb".to_string()];
+
+        let shaped = DockerInfo::from_daemon(&info, 512 * 1024 * 1024, &tags, &languages);
+        assert!(shaped.available);
+        assert_eq!(shaped.version.as_deref(), Some("25.0.3"));
+        assert_eq!(shaped.storage_driver.as_deref(), Some("overlay2"));
+        assert_eq!(shaped.cpus, Some(8));
+        assert_eq!(shaped.memory_total, Some(8 * 1024 * 1024 * 1024));
+        assert_eq!(shaped.memory_available, Some(8 * 1024 * 1024 * 1024 - 512 * 1024 * 1024));
+        let present: Vec<_> = shaped.images.iter().map(|i| (i.language.as_str(), i.present)).collect();
+        assert_eq!(present, vec![("python", true), ("go", false)]);
+
+        let absent = DockerInfo::unavailable("Docker is not installed or not running");
+        assert!(!absent.available && absent.images.is_empty());
+        assert_eq!(absent.reason.as_deref(), Some("Docker is not installed or not running"));
+    }
+
+    #[test]
+    fn test_image_presence_decision() {
+        let response = |status_code, message: &str| Err(bollard::errors::Error::DockerResponseServerError {
+            status_code,
+            message: message.to_string(),
+        });
+
+        assert!(check_image("python:3.12-slim", Ok(ImageInspect::default())).is_ok());
+
+        let missing = check_image("golang:1.21-alpine", response(404, "No such image: golang:1.21-alpine")).unwrap_err();
+        assert!(matches!(&missing, ShellError::ImageNotFound(image) if image == "golang:1.21-alpine"));
+        assert_eq!(missing.code(), "image_not_found");
+
+        let invalid = check_image("Golang::1", response(400, "invalid reference format")).unwrap_err();
+        assert_eq!(invalid.code(), "configuration");
+        assert_eq!(invalid.to_string(), "Invalid configuration: Invalid image name Golang::1: invalid reference format");
+
+        assert_eq!(check_image("python:3.12-slim", response(500, "daemon busy")).unwrap_err().code(), "docker");
+    }
+
+    #[test]
+    fn test_pull_progress_parsing() {
+        let info: CreateImageInfo = serde_json::from_str(
+            r#"{"status":"Downloading","progressDetail":{"current":5242880,"total":29126484},"progress":"[=>  ]","id":"a2abf6c4d29d"}"#,
+        ).unwrap();
+        let progress = PullProgress::from_info("python:3.12-slim", &info).unwrap().unwrap();
+        assert_eq!(progress.layer.as_deref(), Some("a2abf6c4d29d"));
+        assert_eq!(progress.status, "Downloading");
+        assert_eq!((progress.current, progress.total), (Some(5_242_880), Some(29_126_484)));
+
+        let info: CreateImageInfo = serde_json::from_str(r#"{"status":"Pulling from library/python","id":"3.12-slim","progressDetail":{}}"#).unwrap();
+        let progress = PullProgress::from_info("python:3.12-slim", &info).unwrap().unwrap();
+        assert_eq!((progress.layer, progress.current, progress.total), (None, None, None));
+
+        let info: CreateImageInfo = serde_json::from_str(r#"{"error":"manifest unknown"}"#).unwrap();
+        assert!(PullProgress::from_info("python:3.12-slim", &info).is_err());
+        assert_eq!(PullProgress::from_info("python:3.12-slim", &CreateImageInfo::default()).unwrap(), None);
+
+        assert_eq!(split_reference("python:3.12-slim"), ("python", "3.12-slim"));
+        assert_eq!(split_reference("localhost:5000/tools"), ("localhost:5000/tools", "latest"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_source_path_fails_before_docker() {
+        let missing = std::env::temp_dir().join(format!("shell-missing-{}", uuid::Uuid::new_v4()));
+        let request: ExecutionRequest = serde_json::from_value(serde_json::json!({
+            "id": "run", "image": "python:3.12-slim", "command": ["python", "main.py"],
+            "working_dir": "/workspace", "source_path": missing, "env": {},
+            "step_mode": false, "trace_io": false,
+        })).unwrap();
+
+        // No daemon is needed to reject it
+        let err = DockerManager::new().run(request).await.unwrap_err();
+        assert!(matches!(&err, ShellError::Filesystem(e) if e.kind() == std::io::ErrorKind::NotFound), "{}", err);
+        assert!(err.to_string().contains("is unavailable"));
+
+        let file = std::env::temp_dir().join(format!("shell-file-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&file, "").unwrap();
+        let err = check_source_path(&file.to_string_lossy()).unwrap_err();
+        std::fs::remove_file(&file).unwrap();
+        assert!(err.to_string().ends_with("is not a directory"));
+    }
+
+    #[test]
+    fn test_mounts_are_checked_against_policy() {
+        let dir = std::env::temp_dir().join(format!("shell-mount-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut request: ExecutionRequest = serde_json::from_value(serde_json::json!({
+            "id": "run", "image": "python:3.12-slim", "command": ["python", "main.py"],
+            "working_dir": "/workspace", "source_path": dir, "env": {},
+            "step_mode": false, "trace_io": false,
+        })).unwrap();
+        let mut policy = SecurityPolicy::default();
+        policy.allowed_paths = vec![std::path::PathBuf::from("/nonexistent-projects")];
+        let docker = DockerManager::new().with_policy(Arc::new(policy));
+
+        assert!(matches!(docker.check_mount(&request), Err(ShellError::Security(_))));
+        assert!(DockerManager::new().check_mount(&request).is_ok());
+        // The app's own scratch workspaces aren't projects
+        request.scratch_workspace = true;
+        assert!(docker.check_mount(&request).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recorded_limits_match_request() {
+        let mut request: ExecutionRequest = serde_json::from_value(serde_json::json!({
+            "id": "run", "image": "python:3.12-slim", "command": ["python", "main.py"],
+            "working_dir": "/workspace", "source_path": "/tmp", "env": {},
+            "memory_limit": 128 * 1024 * 1024, "cpu_quota": 25_000, "timeout": 7, "step_mode": false, "trace_io": false,
+        })).unwrap();
+        let limits = ResourceLimits::for_request(&request);
+        assert_eq!(limits, ResourceLimits { memory_limit: 128 * 1024 * 1024, cpu_quota: 25_000, timeout_seconds: 7 });
+
+        let info = ContainerInfo {
+            id: "c".into(),
+            execution_id: "run".into(),
+            started_at: chrono::Utc::now(),
+            status: ContainerStatus::Running,
+            limits,
+        };
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["limits"]["memory_limit"], 128 * 1024 * 1024);
+        assert_eq!(json["limits"]["timeout_seconds"], 7);
+
+        (request.memory_limit, request.cpu_quota, request.timeout) = (None, None, None);
+        assert_eq!(ResourceLimits::for_request(&request), ResourceLimits::default());
+
+        // Out-of-range time limits from the frontend are clamped
+        request.timeout = Some(u64::MAX);
+        assert_eq!(ResourceLimits::for_request(&request).timeout_seconds, 600);
+        request.timeout = Some(0);
+        assert_eq!(ResourceLimits::for_request(&request).timeout_seconds, 1);
+    }
+
+    #[test]
+    fn test_finished_event_covers_every_outcome() {
+        let result = |exit_code, timed_out| Ok(ExecutionResult {
+            id: "run".into(),
+            exit_code,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: 5,
+            timed_out,
+            trace: None,
+            phase: ExecutionPhase::Run,
+            compile_output: None,
+            tests_passed: None,
+            stdout_base64: None,
+            stderr_base64: None,
+            log_collection_timed_out: false,
+            output_truncated: false,
+        });
+
+        let event = LifecycleEvent::finished("run", &result(1, false), 5);
+        assert_eq!(event.name(), "execution://run/finished");
+        assert_eq!((event.status, event.exit_code), (ContainerStatus::Completed, Some(1)));
+        assert_eq!(LifecycleEvent::finished("run", &result(-1, true), 5).status, ContainerStatus::TimedOut);
+        assert_eq!(LifecycleEvent::finished("run", &Err(ShellError::Docker("gone".into())), 5).status, ContainerStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_finished_fires_when_run_fails() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let docker = DockerManager::new().with_listener(Arc::new(move |event: &LifecycleEvent| {
+            recorded.lock().unwrap().push((event.stage, event.status.clone()));
+        }));
+
+        let request: ExecutionRequest = serde_json::from_value(serde_json::json!({
+            "id": "0f8e5a2c-run", "image": "python:3.12-slim", "command": ["python", "main.py"],
+            "working_dir": "/workspace", "source_path": "/tmp", "env": {},
+            "memory_limit": null, "cpu_quota": null, "timeout": null, "step_mode": false, "trace_io": false,
+        })).unwrap();
+        assert!(docker.run(request).await.is_err());
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            (LifecycleStage::Queued, ContainerStatus::Starting),
+            (LifecycleStage::Finished, ContainerStatus::Failed),
+        ]);
+    }
+
+    #[test]
+    fn test_phased_command_quotes_arguments() {
+        let compile = vec!["gcc".to_string(), "my file.c".to_string(), "-o".to_string(), "/tmp/a.out".to_string()];
+        let command = phased_command(&compile, &["/tmp/a.out".to_string()], MARKER);
+        assert_eq!(command[2], format!(
+            "gcc 'my file.c' -o /tmp/a.out && echo {m} && echo {m} >&2 && exec /tmp/a.out", m = MARKER
+        ));
+    }
+
+    #[test]
+    fn test_syntax_error_is_compile_phase() {
+        let stderr = "main.c:3:5: error: expected ';' before 'return'\n";
+        let phases = split_phases("", stderr, MARKER);
+
+        assert_eq!(phases.phase, ExecutionPhase::Compile);
+        assert_eq!(phases.compile_output, stderr);
+        assert!(phases.stdout.is_empty() && phases.stderr.is_empty());
+    }
+
+    #[test]
+    fn test_crash_is_run_phase() {
+        // A segfaulting program after a compiler warning
+        let stdout = format!("{}\nbefore crash\n", MARKER);
+        let stderr = format!("main.c:2: warning: unused variable 'x'\n{}\nSegmentation fault\n", MARKER);
+        let phases = split_phases(&stdout, &stderr, MARKER);
+
+        assert_eq!(phases.phase, ExecutionPhase::Run);
+        assert_eq!(phases.compile_output, "main.c:2: warning: unused variable 'x'\n");
+        assert_eq!(phases.stdout, "before crash\n");
+        assert_eq!(phases.stderr, "Segmentation fault\n");
+    }
+
+    #[test]
+    fn test_raw_output_only_for_invalid_utf8() {
+        assert_eq!(raw_output("héllo\n".as_bytes(), None), None);
+        assert_eq!(raw_output(&[0xff, 0xfe, 0x41], None).as_deref(), Some("//5B"));
+
+        let mut compiled = format!("warning: naïve\n{}\n", MARKER).into_bytes();
+        assert_eq!(raw_output(&compiled, Some(MARKER)), None);
+        compiled.extend_from_slice(&[0x80, 0x0a]);
+        assert_eq!(raw_output(&compiled, Some(MARKER)).as_deref(), Some("gAo="));
+        // Nothing ran if the compile step never printed the marker
+        assert_eq!(raw_output(&[0xff], Some(MARKER)), None);
+    }
+
+    #[tokio::test]
+    async fn test_stuck_log_stream_keeps_collected_output() {
+        let messages = vec![
+            Ok::<_, std::io::Error>(LogOutput::StdOut { message: "partial ".into() }),
+            Ok(LogOutput::StdErr { message: "warn\n".into() }),
+            Ok(LogOutput::StdOut { message: "output".into() }),
+        ];
+        // The stream never ends after the last message
+        let stuck = futures_util::stream::iter(messages).chain(futures_util::stream::pending());
+        let budget = std::time::Duration::from_millis(50);
+
+        let started = std::time::Instant::now();
+        let collected = collect_logs(stuck, budget, true, started, |_, _| {}).await;
+        assert!(collected.timed_out);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(collected.stdout, b"partial output");
+        assert_eq!(collected.stderr, b"warn\n");
+        assert_eq!(collected.io_events.len(), 3);
+
+        let finished = futures_util::stream::iter(vec![Ok::<_, std::io::Error>(LogOutput::StdOut { message: "done".into() })]);
+        let collected = collect_logs(finished, budget, false, started, |_, _| {}).await;
+        assert!(!collected.timed_out);
+        assert_eq!(collected.stdout, b"done");
+        assert!(collected.io_events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_partial_output_is_readable_mid_run() {
+        let tails = OutputTails::default();
+        tails.begin("run", Some("__marker__".into()));
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel::<std::result::Result<LogOutput, std::io::Error>>();
+        let logs = Box::pin(futures_util::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|message| (message, receiver))
+        }));
+        let drain = collect_logs(logs, std::time::Duration::from_secs(5), false, std::time::Instant::now(), |stream, bytes| {
+            tails.append("run", stream, bytes);
+        });
+
+        let reader = async {
+            sender.send(Ok(LogOutput::StdOut { message: "compiled\n__marker__\nstep 1\n".into() })).unwrap();
+            sender.send(Ok(LogOutput::StdErr { message: "warn\n".into() })).unwrap();
+            // "é" split across messages
+            sender.send(Ok(LogOutput::StdOut { message: vec![b'c', b'a', b'f', 0xc3].into() })).unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            let partial = tails.get("run").unwrap();
+            sender.send(Ok(LogOutput::StdOut { message: vec![0xa9, b'\n'].into() })).unwrap();
+            drop(sender);
+            partial
+        };
+        let (collected, partial) = tokio::join!(drain, reader);
+        assert_eq!(partial, ExecutionOutput { stdout: "compiled\nstep 1\ncaf".into(), stderr: "warn\n".into(), complete: false });
+        assert!(!collected.timed_out);
+
+        tails.finish("run");
+        let finished = tails.get("run").unwrap();
+        assert_eq!((finished.stdout.as_str(), finished.complete), ("compiled\nstep 1\ncafé\n", true));
+        assert!(tails.get("other").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_enormous_line_is_cut_to_line_limit() {
+        // 8 MiB of JSON on one line, in Docker-sized frames, then a short line
+        let messages = std::iter::repeat_with(|| Ok::<_, std::io::Error>(LogOutput::StdOut { message: vec![b'x'; 16 * 1024].into() }))
+            .take(512)
+            .chain([Ok(LogOutput::StdOut { message: "\nafter\n".into() })]);
+        let started = std::time::Instant::now();
+        let collected = collect_logs(futures_util::stream::iter(messages), std::time::Duration::from_secs(5), true, started, |_, _| {}).await;
+
+        assert!(collected.truncated);
+        assert!(!collected.timed_out);
+        assert_eq!(collected.stdout.len(), MAX_LINE_LENGTH + "\nafter\n".len());
+        assert!(collected.stdout.ends_with(b"x\nafter\n"));
+        assert!(collected.stdout.capacity() <= 2 * MAX_LINE_LENGTH);
+        // Frames past the line limit leave no events behind
+        let traced: usize = collected.io_events.iter().map(|e| e.data.len()).sum();
+        assert_eq!(traced, collected.stdout.len());
+
+        // Many short lines stop at the stream limit
+        let mut output = BoundedOutput::default();
+        for _ in 0..(MAX_STREAM_OUTPUT / 1000 + 10) {
+            output.push(&[b'y'; 1000]);
+            output.push(b"\n");
+        }
+        assert!(output.truncated);
+        assert_eq!(output.bytes.len(), MAX_STREAM_OUTPUT);
+
+        // Output within both limits is untouched
+        let mut output = BoundedOutput::default();
+        assert_eq!(output.push(b"one\ntwo"), b"one\ntwo");
+        assert!(!output.truncated);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Docker"]
+    async fn test_writable_workspace_accepts_writes() {
+        let docker = DockerManager::new();
+        docker.connect().await.unwrap();
+        let workspace = std::env::temp_dir().join(format!("shell-writable-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&workspace).unwrap();
+        let request = |writable: bool| -> ExecutionRequest {
+            serde_json::from_value(serde_json::json!({
+                "id": uuid::Uuid::new_v4().to_string(), "image": "python:3.12-slim",
+                "command": ["python", "-c", "open('/workspace/out.txt', 'w').write('fixed')"],
+                "working_dir": "/workspace", "source_path": workspace, "env": {},
+                "memory_limit": null, "cpu_quota": null, "timeout": 30, "step_mode": false, "trace_io": false,
+                "workspace_writable": writable,
+            })).unwrap()
+        };
+
+        let result = docker.run(request(false)).await.unwrap();
+        assert_ne!(result.exit_code, 0);
+        assert!(result.stderr.contains("Read-only file system"));
+        assert!(!workspace.join("out.txt").exists());
+
+        let result = docker.run(request(true)).await.unwrap();
+        assert_eq!(result.exit_code, 0, "{}", result.stderr);
+        assert_eq!(std::fs::read_to_string(workspace.join("out.txt")).unwrap(), "fixed");
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Docker"]
+    async fn test_timeout_leaves_no_containers_behind() {
+        let docker = DockerManager::new();
+        docker.connect().await.unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+        let request: ExecutionRequest = serde_json::from_value(serde_json::json!({
+            "id": id, "image": "python:3.12-slim",
+            "command": ["sh", "-c", "(sleep 300 &) ; nohup sleep 300 >/dev/null 2>&1 & trap '' TERM; sleep 300"],
+            "working_dir": "/workspace", "source_path": std::env::temp_dir(), "env": {},
+            "memory_limit": null, "cpu_quota": null, "timeout": 1, "step_mode": false, "trace_io": false,
+        })).unwrap();
+
+        let result = docker.run(request).await.unwrap();
+        assert!(result.timed_out);
+        assert!(docker.get_running().await.is_empty());
+
+        let options = ListContainersOptions::<String> {
+            all: true,
+            filters: HashMap::from([("name".to_string(), vec![format!("shell-exec-{}", &id[..8])])]),
+            ..Default::default()
+        };
+        let leftover = docker.client().await.unwrap().list_containers(Some(options)).await.unwrap();
+        assert!(leftover.is_empty(), "{:?}", leftover);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Docker"]
+    async fn test_raw_output_preserves_program_bytes() {
+        let docker = DockerManager::new();
+        docker.connect().await.unwrap();
+        let bytes = [0xff, 0xfe, 0x41, 0x00, 0xc3, 0x28];
+        let request: ExecutionRequest = serde_json::from_value(serde_json::json!({
+            "id": uuid::Uuid::new_v4().to_string(), "image": "python:3.12-slim",
+            "command": ["python", "-c", "import sys; sys.stdout.buffer.write(bytes([0xff, 0xfe, 0x41, 0x00, 0xc3, 0x28]))"],
+            "working_dir": "/workspace", "source_path": std::env::temp_dir(), "env": {},
+            "memory_limit": null, "cpu_quota": null, "timeout": 30, "step_mode": false, "trace_io": false,
+            "raw_output": true,
+        })).unwrap();
+
+        let result = docker.run(request).await.unwrap();
+        let raw = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, result.stdout_base64.unwrap()).unwrap();
+        assert_eq!(raw, bytes);
+        assert_eq!(result.stdout, String::from_utf8_lossy(&bytes));
+        assert_eq!(result.stderr_base64, None);
+    }
+}
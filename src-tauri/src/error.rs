@@ -1,7 +1,20 @@
 //! Custom error types for Shell IDE
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// Where an error happened, so the UI can point at it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// File being run, relative to the project
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+}
+
 #[derive(Error, Debug)]
 pub enum ShellError {
     #[error("Filesystem error: {0}")]
@@ -13,6 +26,10 @@ pub enum ShellError {
     #[error("Docker error: {0}")]
     Docker(String),
 
+    /// The image isn't local; pulling it would fix the run
+    #[error("Image not found: {0}")]
+    ImageNotFound(String),
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
@@ -31,21 +48,198 @@ pub enum ShellError {
     #[error("Feature not available: {0}")]
     FeatureNotAvailable(String),
 
+    #[error("Network error: {0}")]
+    Network(String),
+
     #[error("Cloud required: {0}")]
     CloudRequired(String),
 
     #[error("Invalid configuration: {0}")]
     Configuration(String),
+
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+
+    /// Another error with details about where it happened
+    #[error("{error}")]
+    WithContext {
+        error: Box<ShellError>,
+        context: ErrorContext,
+    },
+}
+
+impl ShellError {
+    /// Stable identifier the frontend can branch on
+    pub fn code(&self) -> &'static str {
+        match self {
+            ShellError::Filesystem(_) => "filesystem",
+            ShellError::Database(_) => "database",
+            ShellError::Docker(_) => "docker",
+            ShellError::ImageNotFound(_) => "image_not_found",
+            ShellError::Serialization(_) => "serialization",
+            ShellError::Lesson(_) => "lesson",
+            ShellError::Execution(_) => "execution",
+            ShellError::Security(_) => "security",
+            ShellError::Service(_) => "service",
+            ShellError::FeatureNotAvailable(_) => "feature_not_available",
+            ShellError::Network(_) => "network",
+            ShellError::CloudRequired(_) => "cloud_required",
+            ShellError::Configuration(_) => "configuration",
+            ShellError::Git(_) => "git",
+            ShellError::WithContext { error, .. } => error.code(),
+        }
+    }
+
+    /// Whether the same operation may succeed if tried again.
+    ///
+    /// - `Docker`, `Network`: retriable (daemon/socket hiccups, timeouts)
+    /// - `Filesystem`: only interrupted or timed-out I/O
+    /// - `Database`: only a busy or locked database
+    /// - everything else (security denials, invalid lessons, bad
+    ///   configuration, service rejections): fatal
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            ShellError::Docker(_) | ShellError::Network(_) => true,
+            ShellError::Filesystem(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::Interrupted | std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ),
+            ShellError::Database(rusqlite::Error::SqliteFailure(e, _)) => matches!(
+                e.code,
+                rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+            ),
+            ShellError::WithContext { error, .. } => error.is_retriable(),
+            ShellError::Database(_)
+            | ShellError::ImageNotFound(_)
+            | ShellError::Serialization(_)
+            | ShellError::Lesson(_)
+            | ShellError::Execution(_)
+            | ShellError::Security(_)
+            | ShellError::Service(_)
+            | ShellError::FeatureNotAvailable(_)
+            | ShellError::CloudRequired(_)
+            | ShellError::Configuration(_)
+            | ShellError::Git(_) => false,
+        }
+    }
+
+    /// Attach context, keeping fields an inner layer already set
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            ShellError::WithContext { error, context: inner } => ShellError::WithContext {
+                error,
+                context: ErrorContext {
+                    language: inner.language.or(context.language),
+                    file: inner.file.or(context.file),
+                    container_id: inner.container_id.or(context.container_id),
+                },
+            },
+            error => ShellError::WithContext { error: Box::new(error), context },
+        }
+    }
+
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            ShellError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
 }
 
-// Make errors serializable for Tauri
-impl serde::Serialize for ShellError {
+// Make errors serializable for Tauri as `{ code, message, context? }`
+impl Serialize for ShellError {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        use serde::ser::SerializeStruct;
+
+        let mut error = serializer.serialize_struct("ShellError", 3)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        match self.context() {
+            Some(context) => error.serialize_field("context", context)?,
+            None => error.skip_field("context")?,
+        }
+        error.end()
     }
 }
 
 pub type Result<T> = std::result::Result<T, ShellError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let cases = [
+            (ShellError::Filesystem(io), "filesystem"),
+            (ShellError::Database(rusqlite::Error::InvalidQuery), "database"),
+            (ShellError::Docker("x".into()), "docker"),
+            (ShellError::ImageNotFound("x".into()), "image_not_found"),
+            (ShellError::Serialization(json), "serialization"),
+            (ShellError::Lesson("x".into()), "lesson"),
+            (ShellError::Execution("x".into()), "execution"),
+            (ShellError::Security("x".into()), "security"),
+            (ShellError::Service("x".into()), "service"),
+            (ShellError::FeatureNotAvailable("x".into()), "feature_not_available"),
+            (ShellError::Network("x".into()), "network"),
+            (ShellError::CloudRequired("x".into()), "cloud_required"),
+            (ShellError::Configuration("x".into()), "configuration"),
+            (ShellError::Git(git2::Error::from_str("x")), "git"),
+        ];
+
+        for (error, code) in cases {
+            let value = serde_json::to_value(&error).unwrap();
+            assert_eq!(value["code"], code);
+            assert_eq!(value["message"], error.to_string());
+        }
+    }
+
+    #[test]
+    fn test_context_is_serialized_without_changing_message() {
+        let error = ShellError::Execution("compilation failed".into())
+            .with_context(ErrorContext { container_id: Some("abc123".into()), ..Default::default() })
+            .with_context(ErrorContext {
+                language: Some("go".into()),
+                file: Some("main.go".into()),
+                container_id: Some("outer".into()),
+            });
+
+        assert_eq!(error.to_string(), "Execution error: compilation failed");
+
+        let value = serde_json::to_value(&error).unwrap();
+        assert_eq!(value["code"], "execution");
+        assert_eq!(value["context"], serde_json::json!({
+            "language": "go",
+            "file": "main.go",
+            "container_id": "abc123",
+        }));
+
+        let plain = serde_json::to_value(ShellError::Docker("down".into())).unwrap();
+        assert!(plain.get("context").is_none());
+    }
+
+    #[test]
+    fn test_retriable_classification() {
+        let busy = rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY), None);
+        let interrupted = std::io::Error::new(std::io::ErrorKind::Interrupted, "signal");
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+
+        assert!(ShellError::Docker("socket hang up".into()).is_retriable());
+        assert!(ShellError::Network("timed out".into()).is_retriable());
+        assert!(ShellError::Database(busy).is_retriable());
+        assert!(ShellError::Filesystem(interrupted).is_retriable());
+        assert!(ShellError::Docker("x".into()).with_context(ErrorContext::default()).is_retriable());
+
+        assert!(!ShellError::Filesystem(missing).is_retriable());
+        assert!(!ShellError::Database(rusqlite::Error::InvalidQuery).is_retriable());
+        assert!(!ShellError::Security("denied".into()).is_retriable());
+        assert!(!ShellError::Lesson("invalid".into()).is_retriable());
+        assert!(!ShellError::Service("bad response".into()).is_retriable());
+        assert!(!ShellError::CloudRequired("x".into()).is_retriable());
+    }
+}
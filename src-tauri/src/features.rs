@@ -2,10 +2,36 @@
 //!
 //! All differences between Student and Teacher editions are controlled
 //! via feature flags. There is NO code fork between editions.
+//!
+//! Flags resolve as env > file > default: the stored license decides what
+//! is unlocked, and in development builds (or with
+//! `SHELL_ALLOW_FEATURE_OVERRIDE=1`) `SHELL_FEATURE_<FLAG>=1|0` variables
+//! force individual flags on top. Overrides are never saved.
 
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use base64::engine::general_purpose::STANDARD;
+use ring::signature::{UnparsedPublicKey, ED25519};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::RwLock;
+use crate::error::{Result, ShellError};
+use crate::seats::SeatLedger;
+
+/// Ed25519 public key (base64) that issued licenses are signed with
+const LICENSE_PUBLIC_KEY: &str = "7Fp/gs/8rEhRGQ//LJDMau7XHH40uWIiWn6xq1DAIXY=";
+
+/// Days an expired license keeps working when it doesn't set its own grace period
+pub const DEFAULT_GRACE_DAYS: u32 = 7;
+
+/// Prefix of environment variables that force a flag on or off
+const OVERRIDE_PREFIX: &str = "SHELL_FEATURE_";
+
+/// Allows `SHELL_FEATURE_*` overrides in release builds
+const ALLOW_OVERRIDE_VAR: &str = "SHELL_ALLOW_FEATURE_OVERRIDE";
+
+/// How often a running app re-checks license expiry
+pub const LICENSE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
 
 /// Feature flags that control available functionality
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,13 +63,118 @@ pub struct FeatureFlags {
     /// License information
     #[serde(skip_serializing_if = "Option::is_none")]
     pub license: Option<License>,
+
+    /// The license is past `valid_until` (features stay on during the grace period)
+    #[serde(default)]
+    pub license_expired: bool,
+
+    /// Flags forced from the environment
+    #[serde(skip)]
+    overrides: Vec<FlagOverride>,
+}
+
+#[derive(Debug, Clone)]
+struct FlagOverride {
+    flag: String,
+    value: bool,
+    /// Value the override hides, written back on save
+    replaced: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct License {
     pub license_type: LicenseType,
-    pub valid_until: Option<chrono::DateTime<chrono::Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
     pub seats: Option<u32>,
+    /// Days the license keeps working after `valid_until`
+    #[serde(default)]
+    pub grace_days: Option<u32>,
+    /// Bearer token for cloud services
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Base64 Ed25519 signature over the other fields
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+/// The signed fields of a license, in a fixed order
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    license_type: &'a LicenseType,
+    valid_until: &'a Option<DateTime<Utc>>,
+    seats: &'a Option<u32>,
+    token: &'a Option<String>,
+    // Omitted when unset so licenses issued before it existed still verify
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grace_days: &'a Option<u32>,
+}
+
+/// Where a license stands relative to its expiry date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LicenseState {
+    Active,
+    /// Past `valid_until` but within the grace period
+    Grace,
+    Expired,
+}
+
+impl License {
+    /// Bytes covered by the signature
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let fields = SignedFields {
+            license_type: &self.license_type,
+            valid_until: &self.valid_until,
+            seats: &self.seats,
+            token: &self.token,
+            grace_days: &self.grace_days,
+        };
+        serde_json::to_vec(&fields).expect("license fields serialize")
+    }
+
+    /// Parse a license as issued: JSON, or base64-encoded JSON
+    pub fn from_blob(blob: &str) -> Result<Self> {
+        let blob = blob.trim();
+        let json = if blob.starts_with('{') {
+            blob.as_bytes().to_vec()
+        } else {
+            STANDARD.decode(blob).map_err(|_| ShellError::Security("License is not valid base64".into()))?
+        };
+        serde_json::from_slice(&json).map_err(|e| ShellError::Security(format!("Invalid license: {}", e)))
+    }
+
+    /// Stable id derived from the signed fields, used to track seats
+    pub fn id(&self) -> String {
+        let digest = ring::digest::digest(&ring::digest::SHA256, &self.signing_payload());
+        digest.as_ref()[..16].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Check the signature against an Ed25519 public key
+    pub fn verify(&self, public_key: &[u8]) -> Result<()> {
+        let signature = self.signature.as_deref()
+            .ok_or_else(|| ShellError::Security("License is not signed".into()))?;
+        let signature = STANDARD.decode(signature)
+            .map_err(|_| ShellError::Security("License signature is malformed".into()))?;
+
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(&self.signing_payload(), &signature)
+            .map_err(|_| ShellError::Security("License signature is invalid".into()))
+    }
+
+    /// Expiry state at `now`
+    pub fn state_at(&self, now: DateTime<Utc>) -> LicenseState {
+        let Some(valid_until) = self.valid_until else {
+            return LicenseState::Active;
+        };
+        let grace = Duration::days(self.grace_days.unwrap_or(DEFAULT_GRACE_DAYS).into());
+
+        if now <= valid_until {
+            LicenseState::Active
+        } else if now <= valid_until + grace {
+            LicenseState::Grace
+        } else {
+            LicenseState::Expired
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -74,19 +205,36 @@ impl Default for FeatureFlags {
                 license_type: LicenseType::Student,
                 valid_until: None,
                 seats: None,
+                grace_days: None,
+                token: None,
+                signature: None,
             }),
+            license_expired: false,
+            overrides: Vec::new(),
         }
     }
 }
 
 impl FeatureFlags {
-    /// Load feature flags from app data directory
+    /// Load feature flags from app data directory.
+    ///
+    /// Flags are re-derived from the stored license, so editing
+    /// `features.json` by hand cannot unlock anything.
     pub fn load(app_data: &Path) -> Self {
+        let mut flags = Self::load_file(app_data);
+        flags.apply_env_overrides(std::env::vars());
+        flags
+    }
+
+    fn load_file(app_data: &Path) -> Self {
         let flags_path = app_data.join("features.json");
         
         if flags_path.exists() {
             if let Ok(content) = std::fs::read_to_string(&flags_path) {
-                if let Ok(flags) = serde_json::from_str(&content) {
+                if let Ok(stored) = serde_json::from_str::<FeatureFlags>(&content) {
+                    let now = Utc::now();
+                    let mut flags = Self::from_stored(stored, &license_public_key(), now);
+                    flags.enforce_seat_at(&SeatLedger::load(app_data), now);
                     return flags;
                 }
             }
@@ -95,10 +243,136 @@ impl FeatureFlags {
         Self::default()
     }
 
-    /// Save feature flags to app data directory
+    /// Apply `SHELL_FEATURE_*` overrides when they are allowed
+    fn apply_env_overrides(&mut self, vars: impl IntoIterator<Item = (String, String)>) {
+        let vars: Vec<(String, String)> = vars.into_iter().collect();
+        let allowed = cfg!(debug_assertions)
+            || vars.iter().any(|(name, value)| name == ALLOW_OVERRIDE_VAR && value == "1");
+        if !allowed {
+            return;
+        }
+
+        for (name, value) in &vars {
+            let Some(flag) = name.strip_prefix(OVERRIDE_PREFIX).map(str::to_lowercase) else {
+                continue;
+            };
+            let value = match value.to_lowercase().as_str() {
+                "1" | "true" | "on" | "yes" => true,
+                "0" | "false" | "off" | "no" => false,
+                _ => {
+                    tracing::warn!("Ignoring {}: expected 1 or 0, got '{}'", name, value);
+                    continue;
+                }
+            };
+            if self.flag_mut(&flag).is_none() {
+                tracing::warn!("Ignoring {}: unknown feature '{}'", name, flag);
+                continue;
+            }
+            tracing::info!("Feature '{}' forced {} by {}", flag, if value { "on" } else { "off" }, name);
+            self.overrides.push(FlagOverride { flag, value, replaced: false });
+        }
+        self.reapply_overrides();
+    }
+
+    /// Re-apply environment overrides after flags were recomputed
+    fn reapply_overrides(&mut self) {
+        let mut overrides = std::mem::take(&mut self.overrides);
+        for o in &mut overrides {
+            if let Some(flag) = self.flag_mut(&o.flag) {
+                o.replaced = *flag;
+                *flag = o.value;
+            }
+        }
+        self.overrides = overrides;
+    }
+
+    fn flag_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "teacher_mode" => Some(&mut self.teacher_mode),
+            "cloud_sync" => Some(&mut self.cloud_sync),
+            "classrooms" => Some(&mut self.classrooms),
+            "cloud_grading" => Some(&mut self.cloud_grading),
+            "analytics" => Some(&mut self.analytics),
+            "exam_mode" => Some(&mut self.exam_mode),
+            "plagiarism_detection" => Some(&mut self.plagiarism_detection),
+            "marketplace" => Some(&mut self.marketplace),
+            _ => None,
+        }
+    }
+
+    fn from_stored(stored: FeatureFlags, public_key: &[u8], now: DateTime<Utc>) -> Self {
+        let mut flags = Self::default();
+        if let Some(license) = stored.license {
+            if let Err(e) = flags.activate_verified(license, public_key) {
+                tracing::warn!("Ignoring stored license: {}", e);
+            }
+        }
+        flags.enforce_expiry_at(now);
+        flags
+    }
+
+    /// Whether the license still grants its features, counting the grace period
+    pub fn is_license_valid(&self) -> bool {
+        self.is_license_valid_at(Utc::now())
+    }
+
+    pub fn is_license_valid_at(&self, now: DateTime<Utc>) -> bool {
+        !matches!(self.license.as_ref(), Some(l) if l.state_at(now) == LicenseState::Expired)
+    }
+
+    /// Flag an expired license and fall back to student defaults once the
+    /// grace period is over. The license itself is kept so the UI can show it.
+    pub fn enforce_expiry_at(&mut self, now: DateTime<Utc>) {
+        let Some(state) = self.license.as_ref().map(|l| l.state_at(now)) else {
+            return;
+        };
+
+        self.license_expired = state != LicenseState::Active;
+        if state == LicenseState::Expired && self.teacher_mode {
+            tracing::warn!("License expired, reverting to student features");
+            self.revert_to_student();
+        }
+    }
+
+    /// Turn teacher features off when this install no longer holds a seat
+    pub fn enforce_seat_at(&mut self, ledger: &SeatLedger, now: DateTime<Utc>) {
+        let Some(license) = self.license.as_ref().filter(|l| l.seats.is_some()) else {
+            return;
+        };
+        if self.teacher_mode && !ledger.holds_seat_at(&license.id(), now) {
+            tracing::warn!("No seat registered for this install, reverting to student features");
+            self.revert_to_student();
+        }
+    }
+
+    /// Drop the license and return to the student edition
+    pub fn deactivate(&mut self) {
+        self.license = Self::default().license;
+        self.license_expired = false;
+        self.revert_to_student();
+    }
+
+    /// Student defaults, keeping the license and its status for the UI
+    fn revert_to_student(&mut self) {
+        *self = Self {
+            license: self.license.take(),
+            license_expired: self.license_expired,
+            overrides: std::mem::take(&mut self.overrides),
+            ..Self::default()
+        };
+        self.reapply_overrides();
+    }
+
+    /// Save feature flags to app data directory, without environment overrides
     pub fn save(&self, app_data: &Path) -> std::io::Result<()> {
         let flags_path = app_data.join("features.json");
-        let content = serde_json::to_string_pretty(self)?;
+        let mut on_disk = self.clone();
+        for o in self.overrides.iter().rev() {
+            if let Some(flag) = on_disk.flag_mut(&o.flag) {
+                *flag = o.replaced;
+            }
+        }
+        let content = serde_json::to_string_pretty(&on_disk)?;
         std::fs::write(flags_path, content)
     }
 
@@ -110,8 +384,28 @@ impl FeatureFlags {
         )
     }
 
-    /// Upgrade to teacher mode with license
-    pub fn activate_teacher(&mut self, license: License) {
+    /// Check a license's signature and unlock the features it grants
+    pub fn verify_and_activate(&mut self, license: License) -> Result<()> {
+        let now = Utc::now();
+        if license.state_at(now) == LicenseState::Expired {
+            return Err(ShellError::Security("License has expired".into()));
+        }
+        self.activate_verified(license, &license_public_key())?;
+        self.enforce_expiry_at(now);
+        Ok(())
+    }
+
+    fn activate_verified(&mut self, license: License, public_key: &[u8]) -> Result<()> {
+        if license.license_type != LicenseType::Student {
+            check_signature(&license, public_key)?;
+        }
+        self.activate_teacher(license);
+        self.reapply_overrides();
+        Ok(())
+    }
+
+    /// Upgrade to teacher mode with an already verified license
+    fn activate_teacher(&mut self, license: License) {
         self.license = Some(license.clone());
         
         match license.license_type {
@@ -138,6 +432,25 @@ impl FeatureFlags {
     }
 }
 
+fn license_public_key() -> Vec<u8> {
+    STANDARD.decode(LICENSE_PUBLIC_KEY).expect("valid embedded license key")
+}
+
+#[cfg(not(debug_assertions))]
+fn check_signature(license: &License, public_key: &[u8]) -> Result<()> {
+    license.verify(public_key)
+}
+
+/// Development builds accept unsigned licenses; a bad signature still fails
+#[cfg(debug_assertions)]
+fn check_signature(license: &License, public_key: &[u8]) -> Result<()> {
+    if license.signature.is_none() {
+        tracing::warn!("Accepting unsigned {:?} license in a development build", license.license_type);
+        return Ok(());
+    }
+    license.verify(public_key)
+}
+
 /// Thread-safe wrapper for feature flags
 pub struct FeatureFlagsState(pub RwLock<FeatureFlags>);
 
@@ -152,3 +465,172 @@ impl From<FeatureFlags> for FeatureFlagsState {
         Self::new(flags)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    /// Sign a license the way the license server does
+    pub(crate) fn sign(mut license: License, key: &Ed25519KeyPair) -> License {
+        license.signature = Some(STANDARD.encode(key.sign(&license.signing_payload())));
+        license
+    }
+
+    fn teacher_license() -> License {
+        License {
+            license_type: LicenseType::TeacherIndividual,
+            valid_until: None,
+            seats: Some(30),
+            grace_days: None,
+            token: Some("token".into()),
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn test_signed_license_activates() {
+        let key = key_pair();
+        let mut flags = FeatureFlags::default();
+        flags.activate_verified(sign(teacher_license(), &key), key.public_key().as_ref()).unwrap();
+        assert!(flags.teacher_mode && flags.cloud_grading);
+        assert!(!flags.exam_mode);
+    }
+
+    #[test]
+    fn test_tampered_license_rejected() {
+        let key = key_pair();
+        let mut license = sign(teacher_license(), &key);
+        license.license_type = LicenseType::Enterprise;
+
+        let mut flags = FeatureFlags::default();
+        assert!(flags.activate_verified(license, key.public_key().as_ref()).is_err());
+        assert!(!flags.teacher_mode);
+    }
+
+    #[test]
+    fn test_unsigned_or_foreign_license_fails_verification() {
+        let key = key_pair();
+        assert!(teacher_license().verify(key.public_key().as_ref()).is_err());
+        assert!(sign(teacher_license(), &key_pair()).verify(key.public_key().as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_stored_flags_are_rederived_from_license() {
+        let key = key_pair();
+        let forged = FeatureFlags {
+            exam_mode: true,
+            license: Some(sign(teacher_license(), &key)),
+            ..FeatureFlags::default()
+        };
+
+        let flags = FeatureFlags::from_stored(forged, key.public_key().as_ref(), Utc::now());
+        assert!(flags.teacher_mode);
+        assert!(!flags.exam_mode);
+    }
+
+    #[test]
+    fn test_embedded_key_is_valid() {
+        assert_eq!(license_public_key().len(), 32);
+    }
+
+    fn at(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date).unwrap().with_timezone(&Utc)
+    }
+
+    fn stored_with_expiry(key: &Ed25519KeyPair, valid_until: &str, grace_days: Option<u32>) -> FeatureFlags {
+        let license = License { valid_until: Some(at(valid_until)), grace_days, ..teacher_license() };
+        FeatureFlags { license: Some(sign(license, key)), ..FeatureFlags::default() }
+    }
+
+    #[test]
+    fn test_license_not_yet_expired() {
+        let key = key_pair();
+        let stored = stored_with_expiry(&key, "2026-06-30T00:00:00Z", None);
+        let flags = FeatureFlags::from_stored(stored, key.public_key().as_ref(), at("2026-06-01T00:00:00Z"));
+
+        assert!(flags.teacher_mode && !flags.license_expired);
+        assert!(flags.is_license_valid_at(at("2026-06-01T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_license_within_grace_period() {
+        let key = key_pair();
+        let stored = stored_with_expiry(&key, "2026-06-30T00:00:00Z", Some(14));
+        let now = at("2026-07-10T00:00:00Z");
+        let flags = FeatureFlags::from_stored(stored, key.public_key().as_ref(), now);
+
+        assert!(flags.teacher_mode && flags.license_expired);
+        assert!(flags.is_license_valid_at(now));
+    }
+
+    #[test]
+    fn test_expired_license_downgrades() {
+        let key = key_pair();
+        let stored = stored_with_expiry(&key, "2026-06-30T00:00:00Z", None);
+        let now = at("2026-07-10T00:00:00Z");
+        let mut flags = FeatureFlags::from_stored(stored.clone(), key.public_key().as_ref(), at("2026-06-01T00:00:00Z"));
+        assert!(flags.teacher_mode);
+
+        flags.enforce_expiry_at(now);
+        assert!(!flags.teacher_mode && !flags.cloud_grading && flags.license_expired);
+        assert!(!flags.is_license_valid_at(now));
+        assert_eq!(flags.license.unwrap().license_type, LicenseType::TeacherIndividual);
+    }
+
+    #[test]
+    fn test_seat_required_for_seat_limited_license() {
+        let key = key_pair();
+        let license = sign(License { seats: Some(5), ..teacher_license() }, &key);
+        let stored = FeatureFlags { license: Some(license.clone()), ..FeatureFlags::default() };
+        let now = Utc::now();
+        let mut ledger = SeatLedger::new();
+
+        let mut flags = FeatureFlags::from_stored(stored.clone(), key.public_key().as_ref(), now);
+        flags.enforce_seat_at(&ledger, now);
+        assert!(!flags.teacher_mode);
+
+        ledger.claim_local(&license.id(), 5, now).unwrap();
+        let mut flags = FeatureFlags::from_stored(stored, key.public_key().as_ref(), now);
+        flags.enforce_seat_at(&ledger, now);
+        assert!(flags.teacher_mode);
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_env_overrides_win_over_license() {
+        let key = key_pair();
+        let mut flags = FeatureFlags::default();
+        flags.apply_env_overrides(vars(&[("SHELL_FEATURE_EXAM_MODE", "1"), ("SHELL_FEATURE_CLOUD_SYNC", "0")]));
+        flags.activate_verified(sign(teacher_license(), &key), key.public_key().as_ref()).unwrap();
+
+        assert!(flags.exam_mode && flags.teacher_mode);
+        assert!(!flags.cloud_sync);
+    }
+
+    #[test]
+    fn test_env_overrides_are_not_saved() {
+        let dir = std::env::temp_dir().join(format!("shell-flags-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The only test that touches the real environment
+        std::env::set_var("SHELL_FEATURE_PLAGIARISM_DETECTION", "1");
+        let flags = FeatureFlags::load(&dir);
+        std::env::remove_var("SHELL_FEATURE_PLAGIARISM_DETECTION");
+        assert!(flags.plagiarism_detection);
+
+        flags.save(&dir).unwrap();
+        let saved: FeatureFlags = serde_json::from_str(&std::fs::read_to_string(dir.join("features.json")).unwrap()).unwrap();
+        assert!(!saved.plagiarism_detection);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
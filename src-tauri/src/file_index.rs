@@ -0,0 +1,275 @@
+//! Quick-open file index
+//!
+//! Each project's files are indexed once, kept in memory, and updated from
+//! filesystem events. Queries are ranked with a fuzzy match that prefers
+//! file names, word boundaries and runs of consecutive characters.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use crate::error::{Result, ShellError};
+use crate::lessons::IGNORE_FILE;
+use crate::search::{relative, walker};
+
+/// Most files indexed per project
+const MAX_INDEXED_FILES: usize = 50_000;
+/// Matches returned when the caller doesn't say
+const DEFAULT_QUERY_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileIndex {
+    pub project_path: String,
+    /// Relative paths, sorted
+    pub files: Vec<String>,
+    /// Whether indexing stopped at the file limit
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileMatch {
+    pub path: String,
+    pub score: i64,
+    /// Character positions in `path` that matched, for highlighting
+    pub positions: Vec<usize>,
+}
+
+/// Files of one project, kept current by a watcher
+struct ProjectIndex {
+    root: PathBuf,
+    files: Mutex<BTreeSet<String>>,
+    /// Root-level ignore rules, for files created after indexing
+    ignore: Gitignore,
+    truncated: bool,
+}
+
+impl ProjectIndex {
+    fn build(root: PathBuf) -> Self {
+        let mut files = BTreeSet::new();
+        let mut truncated = false;
+        for entry in walker(&root).filter_map(|entry| entry.ok()) {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            if files.len() == MAX_INDEXED_FILES {
+                truncated = true;
+                break;
+            }
+            files.insert(relative(&root, entry.path()));
+        }
+
+        let mut ignore = GitignoreBuilder::new(&root);
+        for name in [".gitignore", ".ignore", IGNORE_FILE] {
+            ignore.add(root.join(name));
+        }
+        let ignore = ignore.build().unwrap_or_else(|_| Gitignore::empty());
+        Self { root, files: Mutex::new(files), ignore, truncated }
+    }
+
+    /// Bring the entry for `path` (absolute) in line with the disk
+    fn update(&self, path: &Path) {
+        let Ok(relative_path) = path.strip_prefix(&self.root) else { return };
+        let key = relative(&self.root, path);
+        let mut files = self.lock();
+        if path.is_file() {
+            let hidden = relative_path.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+            let ignored = self.ignore.matched_path_or_any_parents(relative_path, false).is_ignore();
+            if !hidden && !ignored && files.len() < MAX_INDEXED_FILES {
+                files.insert(key);
+            }
+        } else if !path.exists() {
+            // A removed directory takes its files with it
+            let prefix = format!("{}/", key);
+            files.retain(|file| file != &key && !file.starts_with(&prefix));
+        }
+    }
+
+    fn snapshot(&self) -> FileIndex {
+        FileIndex {
+            project_path: self.root.to_string_lossy().into_owned(),
+            files: self.lock().iter().cloned().collect(),
+            truncated: self.truncated,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeSet<String>> {
+        self.files.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A project's index and the watcher keeping it current
+type WatchedIndex = (Arc<ProjectIndex>, Option<RecommendedWatcher>);
+
+/// In-memory indexes by canonical project path
+#[derive(Default)]
+pub struct FileIndexes {
+    projects: Mutex<HashMap<PathBuf, WatchedIndex>>,
+}
+
+impl FileIndexes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// (Re)index a project and watch it for changes
+    pub fn build(&self, project_path: &Path) -> Result<FileIndex> {
+        let root = project_path.canonicalize()?;
+        let index = Arc::new(ProjectIndex::build(root.clone()));
+
+        let watched = Arc::downgrade(&index);
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let (Ok(event), Some(index)) = (event, watched.upgrade()) else { return };
+            if !event.kind.is_access() {
+                for path in &event.paths {
+                    index.update(path);
+                }
+            }
+        }).and_then(|mut watcher| watcher.watch(&root, RecursiveMode::Recursive).map(|_| watcher));
+        // Without a watcher the index still works; it just goes stale
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                tracing::warn!("Failed to watch {} for the file index: {}", root.display(), e);
+                None
+            }
+        };
+
+        let snapshot = index.snapshot();
+        self.lock().insert(root, (index, watcher));
+        Ok(snapshot)
+    }
+
+    /// Best matches for `query` among a project's files, indexing it first
+    /// if needed
+    pub fn query(&self, project_path: &Path, query: &str, limit: Option<usize>) -> Result<Vec<FileMatch>> {
+        let root = project_path.canonicalize()?;
+        let index = self.lock().get(&root).map(|(index, _)| Arc::clone(index));
+        let index = match index {
+            Some(index) => index,
+            None => {
+                self.build(&root)?;
+                self.lock().get(&root).map(|(index, _)| Arc::clone(index))
+                    .ok_or_else(|| ShellError::Execution(format!("No file index for {}", root.display())))?
+            }
+        };
+
+        let files = index.lock();
+        let mut matches: Vec<FileMatch> = files.iter()
+            .filter_map(|path| {
+                let (score, positions) = fuzzy_match(query, path)?;
+                Some(FileMatch { path: path.clone(), score, positions })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.path.len().cmp(&b.path.len())).then(a.path.cmp(&b.path)));
+        matches.truncate(limit.unwrap_or(DEFAULT_QUERY_LIMIT));
+        Ok(matches)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, WatchedIndex>> {
+        self.projects.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Greedy case-insensitive subsequence match of `query` in `candidate[start..]`
+fn subsequence(query: &[char], candidate: &[char], start: usize) -> Option<Vec<usize>> {
+    let mut positions = Vec::with_capacity(query.len());
+    let mut next = start;
+    for q in query {
+        let offset = candidate[next..].iter().position(|c| c.to_lowercase().eq(q.to_lowercase()))?;
+        positions.push(next + offset);
+        next += offset + 1;
+    }
+    Some(positions)
+}
+
+/// Score `query` against `path`, or `None` if its characters don't all
+/// appear in order. Matches inside the file name are tried first.
+pub fn fuzzy_match(query: &str, path: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    let candidate: Vec<char> = path.chars().collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let name_start = candidate.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+    let (positions, in_name) = match subsequence(&query, &candidate, name_start) {
+        Some(positions) => (positions, true),
+        None => (subsequence(&query, &candidate, 0)?, false),
+    };
+
+    let mut score = if in_name { 40 } else { 0 };
+    let mut previous: Option<usize> = None;
+    for &i in &positions {
+        score += 10;
+        let boundary = i == 0
+            || matches!(candidate[i - 1], '/' | '_' | '-' | '.' | ' ')
+            || (candidate[i - 1].is_lowercase() && candidate[i].is_uppercase());
+        if boundary {
+            score += 10;
+        }
+        match previous {
+            Some(p) if i == p + 1 => score += 15,
+            Some(p) => score -= ((i - p - 1) as i64).min(5),
+            None => {}
+        }
+        previous = Some(i);
+    }
+    score -= candidate.len() as i64 / 8;
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(matches: &[FileMatch]) -> Vec<&str> {
+        matches.iter().map(|m| m.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_index_skips_ignored_and_follows_changes() {
+        let root = std::env::temp_dir().join(format!("shell-index-{}", uuid::Uuid::new_v4()));
+        for file in ["src/main.rs", "src/lib.rs", "target/debug/app", ".git/HEAD"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+
+        let index = ProjectIndex::build(root.canonicalize().unwrap());
+        assert_eq!(index.snapshot().files, vec!["src/lib.rs", "src/main.rs"]);
+
+        std::fs::write(root.join("src/util.rs"), "").unwrap();
+        std::fs::write(root.join("target/debug/new"), "").unwrap();
+        std::fs::remove_file(root.join("src/lib.rs")).unwrap();
+        for file in ["src/util.rs", "target/debug/new", "src/lib.rs"] {
+            index.update(&index.root.join(file));
+        }
+        assert_eq!(index.snapshot().files, vec!["src/main.rs", "src/util.rs"]);
+
+        std::fs::remove_dir_all(root.join("src")).unwrap();
+        index.update(&index.root.join("src"));
+        assert!(index.snapshot().files.is_empty());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_query_ranks_file_names_and_boundaries_first() {
+        let root = std::env::temp_dir().join(format!("shell-index-{}", uuid::Uuid::new_v4()));
+        for file in ["src/main.rs", "src/domain/main_helpers.rs", "lib/my_app_interface.rs", "src/commands/mod.rs"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let indexes = FileIndexes::new();
+
+        let found = indexes.query(&root, "MAIN", None).unwrap();
+        assert_eq!(paths(&found), vec!["src/main.rs", "src/domain/main_helpers.rs", "lib/my_app_interface.rs"]);
+        assert_eq!(found[0].positions, vec![4, 5, 6, 7]);
+
+        assert_eq!(paths(&indexes.query(&root, "cmod", Some(1)).unwrap()), vec!["src/commands/mod.rs"]);
+        assert!(indexes.query(&root, "xyz", None).unwrap().is_empty());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+//! Code formatting
+//!
+//! Formatters are short-lived host tools, found on PATH like language
+//! servers: source goes in on stdin and formatted source comes out on
+//! stdout. The stock execution images don't ship most of them and have no
+//! network to install them, so they don't run in a container.
+
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use crate::commands::lsp::command_exists;
+use crate::error::{Result, ShellError};
+
+/// How long a formatter may run before it's killed
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A language's formatter
+#[derive(Debug, Clone)]
+pub struct FormatterInfo {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub installation_instructions: Option<String>,
+}
+
+fn formatter(name: &str, command: &str, args: &[&str], instructions: &str) -> FormatterInfo {
+    FormatterInfo {
+        name: name.to_string(),
+        command: command.to_string(),
+        args: args.iter().map(|s| s.to_string()).collect(),
+        installation_instructions: Some(instructions.to_string()),
+    }
+}
+
+/// Formatter for a language
+pub fn formatter_for(language: &str) -> Result<FormatterInfo> {
+    let prettier = |file: &str| {
+        formatter("Prettier", "prettier", &["--stdin-filepath", file], "npm install -g prettier")
+    };
+    let clang_format = |file: &str| {
+        formatter("clang-format", "clang-format", &[&format!("--assume-filename={}", file)], "Install LLVM/Clang")
+    };
+
+    Ok(match language.to_lowercase().as_str() {
+        "python" | "py" => formatter("Black", "black", &["--quiet", "-"], "pip install black"),
+        "javascript" | "js" => prettier("main.js"),
+        "typescript" | "ts" => prettier("main.ts"),
+        "html" => prettier("index.html"),
+        "css" => prettier("style.css"),
+        "json" => prettier("data.json"),
+        "rust" | "rs" => formatter("rustfmt", "rustfmt", &["--edition", "2021"], "rustup component add rustfmt"),
+        "go" => formatter("gofmt", "gofmt", &[], "Install Go from https://go.dev/dl"),
+        "c" => clang_format("main.c"),
+        "cpp" | "c++" => clang_format("main.cpp"),
+        _ => return Err(ShellError::Service(format!("No formatter for: {}", language))),
+    })
+}
+
+/// Format `code` with `formatter`. On any failure the error is returned
+/// and nothing of the formatter's output is kept.
+pub async fn format_with(formatter: &FormatterInfo, code: &str) -> Result<String> {
+    if !command_exists(&formatter.command) {
+        return Err(ShellError::Service(format!(
+            "Formatter '{}' not installed. {}",
+            formatter.name,
+            formatter.installation_instructions.as_deref().unwrap_or("Please install it manually.")
+        )));
+    }
+
+    let mut child = tokio::process::Command::new(&formatter.command)
+        .args(&formatter.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let input = code.to_string();
+    // Written concurrently so a large file can't deadlock on a full pipe;
+    // stdin is dropped afterwards so the formatter sees end of input
+    let writer = tokio::spawn(async move { stdin.write_all(input.as_bytes()).await });
+
+    let output = tokio::time::timeout(FORMAT_TIMEOUT, child.wait_with_output()).await
+        .map_err(|_| ShellError::Service(format!("{} timed out", formatter.name)))??;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ShellError::Service(format!("{} failed: {}", formatter.name, stderr.trim())));
+    }
+    writer.await.map_err(|e| ShellError::Service(e.to_string()))??;
+    String::from_utf8(output.stdout)
+        .map_err(|_| ShellError::Service(format!("{} produced invalid UTF-8", formatter.name)))
+}
+
+/// Format `code` with its language's formatter
+pub async fn format(language: &str, code: &str) -> Result<String> {
+    format_with(&formatter_for(language)?, code).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatter_per_language() {
+        assert_eq!(formatter_for("py").unwrap().command, "black");
+        assert_eq!(formatter_for("ts").unwrap().args, vec!["--stdin-filepath", "main.ts"]);
+        assert_eq!(formatter_for("rust").unwrap().command, "rustfmt");
+        assert!(formatter_for("cobol").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_formatter_explains_install() {
+        let missing = FormatterInfo { command: "shell-missing-formatter".into(), ..formatter_for("python").unwrap() };
+        let err = format_with(&missing, "x=1").await.unwrap_err();
+        assert_eq!(err.to_string(), "Service error: Formatter 'Black' not installed. pip install black");
+    }
+
+    #[tokio::test]
+    async fn test_rustfmt_formats_and_rejects_invalid_code() {
+        assert_eq!(format("rust", "fn main(){let x=1;}").await.unwrap(), "fn main() {\n    let x = 1;\n}\n");
+        assert!(format("rust", "fn main( {").await.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires black"]
+    async fn test_black_formats_python() {
+        assert_eq!(format("python", "x = [1,2 ,3]\n").await.unwrap(), "x = [1, 2, 3]\n");
+    }
+}
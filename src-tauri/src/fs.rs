@@ -4,9 +4,13 @@
 //! The frontend cannot directly access the filesystem.
 //! Optimized for speed with async operations and caching.
 
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
 use crate::error::{Result, ShellError};
 use crate::security::SecurityPolicy;
 
@@ -18,6 +22,12 @@ pub struct FileInfo {
     pub size: u64,
     pub modified: Option<String>,
     pub extension: Option<String>,
+    /// Lines in a small text file; a trailing newline starts an empty last line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_count: Option<u64>,
+    /// Characters in a small text file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_count: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,40 +40,242 @@ pub struct DirectoryContents {
 pub struct FileContents {
     pub path: String,
     pub content: String,
+    /// Encoding the file was decoded from, e.g. `utf-8` or `utf-16le`
     pub encoding: String,
+    /// Window of the file in `content`, set only for ranged reads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<FileRange>,
+}
+
+/// Byte window of a partially read file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileRange {
+    /// First byte read, moved forward to a character boundary
+    pub start: u64,
+    /// One past the last byte read, moved back to a character boundary
+    pub end: u64,
+    pub total_size: u64,
+}
+
+/// Largest file `get_info` counts lines and characters for
+pub const STATS_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Default byte budget of the file cache
+pub const DEFAULT_CACHE_BYTES: usize = 32 * 1024 * 1024;
+
+struct CacheEntry {
+    content: Arc<str>,
+    modified: Option<SystemTime>,
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<PathBuf, CacheEntry>,
+    bytes: usize,
+    tick: u64,
+}
+
+impl CacheState {
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.bytes -= entry.content.len();
+        }
+    }
+}
+
+/// LRU cache of file contents, bounded by total bytes. An entry is only
+/// used while the file's modified time and size still match.
+pub struct FileCache {
+    state: Mutex<CacheState>,
+    max_bytes: usize,
+    /// Watchers whose change events invalidate entries, by watched directory
+    watchers: Mutex<HashMap<PathBuf, DirectoryWatch>>,
+}
+
+/// A directory watcher and how many callers asked for it
+struct DirectoryWatch {
+    watcher: RecommendedWatcher,
+    subscribers: usize,
+}
+
+/// An active directory watch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchedDirectory {
+    pub path: String,
+    /// `watch_directory` calls not yet matched by `unwatch_directory`
+    pub subscribers: usize,
+}
+
+impl Default for FileCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_BYTES)
+    }
+}
+
+impl FileCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            state: Mutex::new(CacheState::default()),
+            max_bytes,
+            watchers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Cached content of `path` if it was read at this `modified` time and `size`
+    fn get(&self, path: &Path, modified: Option<SystemTime>, size: u64) -> Option<Arc<str>> {
+        let mut state = self.lock();
+        state.tick += 1;
+        let tick = state.tick;
+        match state.entries.get_mut(path) {
+            Some(entry) if entry.modified == modified && entry.size == size => {
+                entry.last_used = tick;
+                Some(Arc::clone(&entry.content))
+            }
+            Some(_) => {
+                state.remove(path);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, path: &Path, content: Arc<str>, modified: Option<SystemTime>, size: u64) {
+        // Files larger than the whole budget would only evict everything else
+        if content.len() > self.max_bytes {
+            return;
+        }
+        let mut state = self.lock();
+        state.remove(path);
+        while state.bytes + content.len() > self.max_bytes {
+            let oldest = state.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone());
+            match oldest {
+                Some(oldest) => state.remove(&oldest),
+                None => break,
+            }
+        }
+        state.tick += 1;
+        let last_used = state.tick;
+        state.bytes += content.len();
+        state.entries.insert(path.to_path_buf(), CacheEntry { content, modified, size, last_used });
+    }
+
+    /// Drop `path` and anything cached below it
+    pub fn invalidate(&self, path: &Path) {
+        let mut state = self.lock();
+        let stale: Vec<PathBuf> = state.entries.keys().filter(|p| p.starts_with(path)).cloned().collect();
+        for p in stale {
+            state.remove(&p);
+        }
+    }
+
+    /// Directories being watched, by path
+    pub fn watched_directories(&self) -> Vec<WatchedDirectory> {
+        let mut watched: Vec<_> = self.lock_watchers().iter()
+            .map(|(path, watch)| WatchedDirectory {
+                path: path.to_string_lossy().into_owned(),
+                subscribers: watch.subscribers,
+            })
+            .collect();
+        watched.sort_by(|a, b| a.path.cmp(&b.path));
+        watched
+    }
+
+    /// Drop one subscription to `path`, stopping its watcher with the last.
+    /// False if the directory wasn't watched.
+    pub fn unwatch_directory(&self, path: &Path) -> bool {
+        let stopped = {
+            let mut watchers = self.lock_watchers();
+            let Some(watch) = watchers.get_mut(path) else { return false };
+            watch.subscribers -= 1;
+            if watch.subscribers > 0 {
+                return true;
+            }
+            watchers.remove(path)
+        };
+        // Dropped outside the lock; stopping a watcher may wait on its callback
+        drop(stopped);
+        true
+    }
+
+    /// Forget a watch that stopped working, e.g. because its directory was
+    /// removed. Called from the watcher's own callback, which can't drop it.
+    fn drop_failed_watch(&self, path: &Path) {
+        let failed = self.lock_watchers().remove(path);
+        if let Some(failed) = failed {
+            std::thread::spawn(move || drop(failed));
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        // The state stays consistent across a panic, so keep using it
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_watchers(&self) -> MutexGuard<'_, HashMap<PathBuf, DirectoryWatch>> {
+        self.watchers.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
 }
 
 /// Optimized file system operations with shared security policy
 pub struct FileSystem {
     policy: Arc<SecurityPolicy>,
+    cache: Arc<FileCache>,
 }
 
 impl FileSystem {
     pub fn new(policy: Arc<SecurityPolicy>) -> Self {
-        Self { policy }
+        Self::with_cache(policy, Arc::new(FileCache::default()))
     }
 
-    /// Read a file's contents (optimized with capacity hint)
+    /// Operations sharing a long-lived cache
+    pub fn with_cache(policy: Arc<SecurityPolicy>, cache: Arc<FileCache>) -> Self {
+        Self { policy, cache }
+    }
+
+    /// Read a file's contents as UTF-8, from the cache when the file is
+    /// unchanged. Other encodings are detected and transcoded.
     pub fn read_file(&self, path: &Path) -> Result<FileContents> {
         self.policy.validate_path(path)?;
         
         let metadata = std::fs::metadata(path)?;
         self.policy.check_file_size(metadata.len())?;
+        let modified = metadata.modified().ok();
         
-        // Pre-allocate string with known capacity for speed
-        let content = std::fs::read_to_string(path)?;
+        // Only UTF-8 files are cached, so a cache hit is always UTF-8
+        let (content, encoding) = match self.cache.get(path, modified, metadata.len()) {
+            Some(content) => (content.to_string(), UTF_8),
+            None => {
+                let (content, encoding) = decode(&std::fs::read(path)?);
+                if encoding == UTF_8 {
+                    self.cache.insert(path, Arc::from(content.as_str()), modified, metadata.len());
+                }
+                (content, encoding)
+            }
+        };
         
         Ok(FileContents {
             path: path.to_string_lossy().into_owned(),
             content,
-            encoding: "utf-8".to_string(),
+            encoding: encoding.name().to_lowercase(),
+            range: None,
         })
     }
 
     /// Write content to a file (async-friendly)
     pub fn write_file(&self, path: &Path, content: &str) -> Result<()> {
+        self.write_file_as(path, content, "utf-8")
+    }
+
+    /// Write content to a file in `encoding`, e.g. the one it was read
+    /// from, so round-tripping keeps a file's original encoding
+    pub fn write_file_as(&self, path: &Path, content: &str, encoding: &str) -> Result<()> {
         self.policy.validate_path(path)?;
-        self.policy.check_file_size(content.len() as u64)?;
+        let bytes = encode(content, encoding)?;
+        self.policy.check_file_size(bytes.len() as u64)?;
         
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
@@ -72,10 +284,93 @@ impl FileSystem {
             }
         }
         
-        std::fs::write(path, content)?;
+        self.cache.invalidate(path);
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Like `write_file_as`, but through a temporary sibling renamed into
+    /// place, so a crash mid-write leaves the old file rather than half of
+    /// the new one
+    pub fn write_file_atomic(&self, path: &Path, content: &str, encoding: &str) -> Result<()> {
+        use std::io::Write;
+
+        self.policy.validate_path(path)?;
+        let bytes = encode(content, encoding)?;
+        self.policy.check_file_size(bytes.len() as u64)?;
+        let name = path.file_name().ok_or_else(|| ShellError::Filesystem(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        )))?;
+        let temp = path.with_file_name(format!(".{}.shell-save", name.to_string_lossy()));
+
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let written = (|| -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&temp)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            // Keep the replaced file's permissions, e.g. an executable script
+            if let Ok(metadata) = std::fs::metadata(path) {
+                std::fs::set_permissions(&temp, metadata.permissions())?;
+            }
+            std::fs::rename(&temp, path)
+        })();
+        self.cache.invalidate(path);
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e.into());
+        }
         Ok(())
     }
 
+    /// Read up to `len` bytes from `start`, trimmed to whole UTF-8
+    /// characters, so large files can be paged through
+    pub fn read_range(&self, path: &Path, start: u64, len: u64) -> Result<FileContents> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        self.policy.validate_path(path)?;
+        self.policy.check_file_size(len)?;
+
+        let mut file = std::fs::File::open(path)?;
+        let total_size = file.metadata()?.len();
+        if start > total_size {
+            return Err(ShellError::Filesystem(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Range starts at byte {} but the file has {} bytes", start, total_size),
+            )));
+        }
+
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::new();
+        file.take(len.min(total_size - start)).read_to_end(&mut bytes)?;
+
+        // Drop the tail of a character cut by the window's start, and the
+        // head of one cut by its end
+        let skipped = bytes.iter().take(3).take_while(|&&b| b & 0xC0 == 0x80).count();
+        let mut window = &bytes[skipped..];
+        if let Err(e) = std::str::from_utf8(window) {
+            if e.error_len().is_none() {
+                window = &window[..e.valid_up_to()];
+            }
+        }
+
+        Ok(FileContents {
+            path: path.to_string_lossy().into_owned(),
+            content: String::from_utf8_lossy(window).into_owned(),
+            encoding: "utf-8".to_string(),
+            range: Some(FileRange {
+                start: start + skipped as u64,
+                end: start + (skipped + window.len()) as u64,
+                total_size,
+            }),
+        })
+    }
+
     /// Create a new file
     pub fn create_file(&self, path: &Path, content: Option<&str>) -> Result<()> {
         self.policy.validate_path(path)?;
@@ -101,6 +396,7 @@ impl FileSystem {
     /// Delete a file or directory
     pub fn delete_file(&self, path: &Path) -> Result<()> {
         self.policy.validate_path(path)?;
+        self.cache.invalidate(path);
         
         if path.is_dir() {
             std::fs::remove_dir_all(path)?;
@@ -111,6 +407,16 @@ impl FileSystem {
         Ok(())
     }
 
+    /// Move a file or directory
+    pub fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.policy.validate_path(from)?;
+        self.policy.validate_path(to)?;
+        self.cache.invalidate(from);
+        self.cache.invalidate(to);
+        std::fs::rename(from, to)?;
+        Ok(())
+    }
+
     /// List directory contents (optimized with pre-allocation)
     pub fn list_directory(&self, path: &Path) -> Result<DirectoryContents> {
         self.policy.validate_path(path)?;
@@ -134,6 +440,8 @@ impl FileSystem {
                     chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()
                 }),
                 extension: entry_path.extension().map(|e| e.to_string_lossy().into_owned()),
+                line_count: None,
+                char_count: None,
             });
         }
         
@@ -165,11 +473,17 @@ impl FileSystem {
         Ok(path.exists())
     }
 
-    /// Get file info
-    pub fn get_info(&self, path: &Path) -> Result<FileInfo> {
+    /// Get file info. With `with_stats`, text files up to
+    /// `STATS_MAX_BYTES` also get line and character counts.
+    pub fn get_info(&self, path: &Path, with_stats: bool) -> Result<FileInfo> {
         self.policy.validate_path(path)?;
         
         let metadata = std::fs::metadata(path)?;
+        let (line_count, char_count) = if with_stats && metadata.is_file() && metadata.len() <= STATS_MAX_BYTES {
+            text_stats(path)?.unzip()
+        } else {
+            (None, None)
+        };
         
         Ok(FileInfo {
             path: path.to_string_lossy().into_owned(),
@@ -182,16 +496,132 @@ impl FileSystem {
                 chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()
             }),
             extension: path.extension().map(|e| e.to_string_lossy().into_owned()),
+            line_count,
+            char_count,
         })
     }
 
-    /// Watch a directory for changes
-    pub fn watch_directory(&self, _path: &Path) -> Result<()> {
-        // TODO: Implement file watching using notify crate
+    /// Watch a directory, invalidating cached files as they change outside
+    /// Shell. Each call adds a subscription that `unwatch_directory` drops.
+    pub fn watch_directory(&self, path: &Path) -> Result<()> {
+        self.policy.validate_path(path)?;
+        let mut watchers = self.cache.lock_watchers();
+        if let Some(watch) = watchers.get_mut(path) {
+            watch.subscribers += 1;
+            return Ok(());
+        }
+
+        let cache = Arc::downgrade(&self.cache);
+        let root = path.to_path_buf();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Some(cache) = cache.upgrade() else { return };
+            match event {
+                Ok(event) if event.kind.is_access() => {}
+                Ok(event) => {
+                    for path in &event.paths {
+                        cache.invalidate(path);
+                    }
+                    if event.kind.is_remove() && event.paths.contains(&root) {
+                        cache.drop_failed_watch(&root);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Stopped watching {}: {}", root.display(), e);
+                    cache.invalidate(&root);
+                    cache.drop_failed_watch(&root);
+                }
+            }
+        }).map_err(|e| ShellError::Execution(format!("Failed to watch {}: {}", path.display(), e)))?;
+        watcher.watch(path, RecursiveMode::Recursive)
+            .map_err(|e| ShellError::Execution(format!("Failed to watch {}: {}", path.display(), e)))?;
+        watchers.insert(path.to_path_buf(), DirectoryWatch { watcher, subscribers: 1 });
         Ok(())
     }
 }
 
+/// Decode a file's bytes: a BOM decides the encoding, then valid UTF-8
+/// is taken as is, and anything else gets a statistical guess
+fn decode(bytes: &[u8]) -> (String, &'static Encoding) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        return (text.into_owned(), encoding);
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return (text.to_string(), UTF_8);
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+    (text.into_owned(), encoding)
+}
+
+/// Encode `content` as the encoding labelled `label`. UTF-16 gets a BOM so
+/// it is recognized on the next read.
+fn encode(content: &str, label: &str) -> Result<Vec<u8>> {
+    let encoding = Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| ShellError::Configuration(format!("Unknown encoding: {}", label)))?;
+
+    // encoding_rs only encodes to UTF-8 for the UTF-16 family
+    let utf16 = |to_bytes: fn(u16) -> [u8; 2]| {
+        std::iter::once(0xFEFF).chain(content.encode_utf16()).flat_map(to_bytes).collect()
+    };
+    if encoding == UTF_16LE {
+        return Ok(utf16(u16::to_le_bytes));
+    }
+    if encoding == UTF_16BE {
+        return Ok(utf16(u16::to_be_bytes));
+    }
+
+    let (bytes, _, unmappable) = encoding.encode(content);
+    if unmappable {
+        return Err(ShellError::Filesystem(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Content has characters that {} cannot represent", encoding.name()),
+        )));
+    }
+    Ok(bytes.into_owned())
+}
+
+/// Line and character counts of a UTF-8 text file, read in chunks.
+/// `None` for binary files: ones with NUL bytes or invalid UTF-8.
+fn text_stats(path: &Path) -> Result<Option<(u64, u64)>> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut buffer = [0u8; 8192];
+    // Bytes of a character split across reads, carried to the next chunk
+    let mut pending = 0;
+    let (mut newlines, mut chars) = (0u64, 0u64);
+    loop {
+        let read = file.read(&mut buffer[pending..])?;
+        if read == 0 {
+            break;
+        }
+        let filled = pending + read;
+        let valid = match std::str::from_utf8(&buffer[..filled]) {
+            Ok(_) => filled,
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Ok(None),
+        };
+        let text = &buffer[..valid];
+        if text.contains(&0) {
+            return Ok(None);
+        }
+        newlines += text.iter().filter(|&&b| b == b'\n').count() as u64;
+        chars += text.iter().filter(|&&b| b & 0xC0 != 0x80).count() as u64;
+        buffer.copy_within(valid..filled, 0);
+        pending = filled - valid;
+    }
+
+    // A character still incomplete at the end of the file
+    if pending > 0 {
+        return Ok(None);
+    }
+    Ok(Some((newlines + 1, chars)))
+}
+
 /// Project structure detection
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectInfo {
@@ -229,7 +659,7 @@ impl ProjectInfo {
                 }
                 "go.mod" => language = Some("go".to_string()),
                 "pom.xml" | "build.gradle" => language = Some("java".to_string()),
-                "lesson.yaml" | "lesson.json" => has_lesson = true,
+                "lesson.yaml" | "lesson.json" | "lesson.toml" => has_lesson = true,
                 _ => {}
             }
         }
@@ -244,3 +674,201 @@ impl ProjectInfo {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(max_bytes: usize) -> (FileSystem, Arc<FileCache>, PathBuf) {
+        let root = std::env::temp_dir().canonicalize().unwrap().join(format!("shell-fs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut policy = SecurityPolicy::default();
+        policy.allowed_paths = vec![root.clone()];
+        let policy = Arc::new(policy);
+        let cache = Arc::new(FileCache::new(max_bytes));
+        (FileSystem::with_cache(policy, Arc::clone(&cache)), cache, root)
+    }
+
+    #[test]
+    fn test_unchanged_file_is_served_from_cache() {
+        let (fs, cache, root) = setup(DEFAULT_CACHE_BYTES);
+        let path = root.join("main.py");
+        fs.write_file(&path, "print(1)").unwrap();
+        assert_eq!(fs.read_file(&path).unwrap().content, "print(1)");
+        assert_eq!(cache.lock().entries.len(), 1);
+
+        // Same metadata, so the cached copy is returned rather than the disk's
+        let metadata = std::fs::metadata(&path).unwrap();
+        cache.insert(&path, Arc::from("print(2)"), metadata.modified().ok(), metadata.len());
+        assert_eq!(fs.read_file(&path).unwrap().content, "print(2)");
+
+        fs.write_file(&path, "print(3)").unwrap();
+        assert!(cache.lock().entries.is_empty());
+        assert_eq!(fs.read_file(&path).unwrap().content, "print(3)");
+        fs.delete_file(&path).unwrap();
+        assert!(cache.lock().entries.is_empty());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_external_modification_is_reread() {
+        let (fs, cache, root) = setup(DEFAULT_CACHE_BYTES);
+        let path = root.join("notes.txt");
+        std::fs::write(&path, "draft").unwrap();
+        assert_eq!(fs.read_file(&path).unwrap().content, "draft");
+
+        std::fs::write(&path, "final version").unwrap();
+        assert_eq!(fs.read_file(&path).unwrap().content, "final version");
+        assert_eq!(cache.lock().bytes, "final version".len());
+
+        let moved = root.join("moved.txt");
+        fs.rename(&path, &moved).unwrap();
+        assert!(cache.lock().entries.is_empty());
+        assert_eq!(fs.read_file(&moved).unwrap().content, "final version");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_atomic_write_replaces_file_in_place() {
+        let (fs, cache, root) = setup(DEFAULT_CACHE_BYTES);
+        let path = root.join("main.py");
+        fs.write_file(&path, "print(1)").unwrap();
+        assert_eq!(fs.read_file(&path).unwrap().content, "print(1)");
+
+        fs.write_file_atomic(&path, "print(2)", "utf-8").unwrap();
+        assert!(cache.lock().entries.is_empty());
+        assert_eq!(fs.read_file(&path).unwrap().content, "print(2)");
+        // The temporary file was renamed away
+        let names: Vec<_> = std::fs::read_dir(&root).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, vec!["main.py"]);
+
+        assert!(fs.write_file_atomic(Path::new("/etc/passwd"), "", "utf-8").is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_read_range_pages_through_large_file() {
+        let (fs, _, root) = setup(DEFAULT_CACHE_BYTES);
+        let path = root.join("big.log");
+        // 100k lines of "line NNNNN é\n", each 14 bytes
+        let content: String = (0..100_000).map(|i| format!("line {:05} é\n", i)).collect();
+        std::fs::write(&path, &content).unwrap();
+        let total_size = content.len() as u64;
+
+        let head = fs.read_range(&path, 0, 28).unwrap();
+        assert_eq!(head.content, "line 00000 é\nline 00001 é\n");
+        assert_eq!(head.range, Some(FileRange { start: 0, end: 28, total_size }));
+
+        // Starting and ending inside "é" moves both edges to whole characters
+        let middle = fs.read_range(&path, 700_000 + 12, 14).unwrap();
+        assert_eq!(middle.content, "\nline 50001 ");
+        assert_eq!(middle.range, Some(FileRange { start: 700_013, end: 700_025, total_size }));
+
+        let tail = fs.read_range(&path, total_size - 14, 1024).unwrap();
+        assert_eq!(tail.content, "line 99999 é\n");
+        assert_eq!(tail.range.unwrap().end, total_size);
+        assert_eq!(fs.read_range(&path, total_size, 10).unwrap().content, "");
+        assert!(fs.read_range(&path, total_size + 1, 10).is_err());
+
+        assert!(fs.read_range(&std::env::temp_dir().join("outside.log"), 0, 10).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_info_stats_only_for_small_text_files() {
+        let (fs, _, root) = setup(DEFAULT_CACHE_BYTES);
+        let text = root.join("notes.txt");
+        std::fs::write(&text, "héllo\nwörld\n").unwrap();
+        let info = fs.get_info(&text, true).unwrap();
+        assert_eq!((info.line_count, info.char_count), (Some(3), Some(12)));
+        assert_eq!(fs.get_info(&text, false).unwrap().line_count, None);
+
+        let binary = root.join("image.png");
+        std::fs::write(&binary, [0x89, b'P', b'N', b'G', 0x00, 0x0a]).unwrap();
+        let info = fs.get_info(&binary, true).unwrap();
+        assert_eq!((info.line_count, info.char_count), (None, None));
+
+        let large = root.join("large.txt");
+        std::fs::write(&large, "x".repeat(STATS_MAX_BYTES as usize + 1)).unwrap();
+        let info = fs.get_info(&large, true).unwrap();
+        assert_eq!((info.line_count, info.char_count), (None, None));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_utf16_with_bom_is_transcoded() {
+        let (fs, cache, root) = setup(DEFAULT_CACHE_BYTES);
+        let path = root.join("notes.txt");
+        let bytes: Vec<u8> = [0xFF, 0xFE].into_iter()
+            .chain("héllo\r\nwörld".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        std::fs::write(&path, bytes).unwrap();
+
+        let file = fs.read_file(&path).unwrap();
+        assert_eq!(file.content, "héllo\r\nwörld");
+        assert_eq!(file.encoding, "utf-16le");
+        assert!(cache.lock().entries.is_empty());
+
+        // Writing back in the reported encoding keeps the file UTF-16
+        fs.write_file_as(&path, "héllo again", &file.encoding).unwrap();
+        let file = fs.read_file(&path).unwrap();
+        assert_eq!((file.content.as_str(), file.encoding.as_str()), ("héllo again", "utf-16le"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_latin1_is_transcoded() {
+        let (fs, _, root) = setup(DEFAULT_CACHE_BYTES);
+        let path = root.join("recette.txt");
+        let text = "Le café est très chaud, à côté de la crème brûlée.\n";
+        // Every character is in Latin-1, one byte each
+        std::fs::write(&path, text.chars().map(|c| c as u8).collect::<Vec<u8>>()).unwrap();
+
+        let file = fs.read_file(&path).unwrap();
+        assert_eq!(file.content, text);
+        assert_eq!(file.encoding, "windows-1252");
+
+        fs.write_file(&path, text).unwrap();
+        assert_eq!(fs.read_file(&path).unwrap().encoding, "utf-8");
+        assert!(fs.write_file_as(&path, "日本", "windows-1252").is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_watched_directories_track_subscriptions() {
+        let (fs, cache, root) = setup(DEFAULT_CACHE_BYTES);
+        let watched = |subscribers| vec![WatchedDirectory { path: root.to_string_lossy().into_owned(), subscribers }];
+
+        fs.watch_directory(&root).unwrap();
+        fs.watch_directory(&root).unwrap();
+        assert_eq!(cache.watched_directories(), watched(2));
+        assert!(fs.watch_directory(&root.join("missing")).is_err());
+        assert_eq!(cache.watched_directories(), watched(2));
+
+        assert!(cache.unwatch_directory(&root));
+        assert_eq!(cache.watched_directories(), watched(1));
+        assert!(cache.unwatch_directory(&root));
+        assert!(cache.watched_directories().is_empty());
+        assert!(!cache.unwatch_directory(&root));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let (fs, cache, root) = setup(10);
+        for name in ["a", "b", "c"] {
+            std::fs::write(root.join(name), "1234").unwrap();
+        }
+        fs.read_file(&root.join("a")).unwrap();
+        fs.read_file(&root.join("b")).unwrap();
+        fs.read_file(&root.join("a")).unwrap();
+        fs.read_file(&root.join("c")).unwrap();
+
+        assert_eq!(cache.lock().bytes, 8);
+        let cached: Vec<bool> = ["a", "b", "c"].iter()
+            .map(|name| cache.lock().entries.contains_key(&root.join(name)))
+            .collect();
+        assert_eq!(cached, vec![true, false, true]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
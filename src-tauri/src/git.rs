@@ -0,0 +1,395 @@
+//! Version control for open projects
+//!
+//! Status and diffs, plus staging and committing local changes. Nothing
+//! here talks to remotes.
+
+use git2::{DiffFormat, DiffOptions, IndexAddOption, Repository, RepositoryInitOptions, Signature, Status, StatusOptions};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use crate::error::{Result, ShellError};
+use crate::fs::ProjectInfo;
+
+/// State of a changed file. A file staged and then edited again has both
+/// `Staged` and `Modified`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileState {
+    Untracked,
+    Modified,
+    Staged,
+    Deleted,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitFileStatus {
+    /// Path relative to the repository root
+    pub path: String,
+    pub states: Vec<FileState>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GitStatus {
+    /// False when the project is not inside a git repository
+    pub is_repository: bool,
+    /// Current branch; `None` on a detached HEAD
+    pub branch: Option<String>,
+    /// Repository working directory
+    pub root: Option<String>,
+    pub files: Vec<GitFileStatus>,
+}
+
+/// Commit author identity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+/// Status of the repository containing `project_path`
+pub fn status(project_path: &Path) -> Result<GitStatus> {
+    let repo = match Repository::discover(project_path) {
+        Ok(repo) => repo,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(GitStatus::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true).include_ignored(false);
+
+    let files = repo.statuses(Some(&mut options))?
+        .iter()
+        .filter_map(|entry| {
+            let states = file_states(entry.status());
+            Some(GitFileStatus { path: entry.path()?.to_string(), states }).filter(|f| !f.states.is_empty())
+        })
+        .collect();
+
+    Ok(GitStatus {
+        is_repository: true,
+        branch: branch(&repo),
+        root: repo.workdir().map(|dir| dir.to_string_lossy().into_owned()),
+        files,
+    })
+}
+
+/// Unified diff of one file: index against HEAD when `staged`, otherwise
+/// the working tree against the index. Binary files yield a single
+/// "Binary file … differs" line. Empty when the file is unchanged.
+pub fn diff(project_path: &Path, file_path: &Path, staged: bool) -> Result<String> {
+    let repo = Repository::discover(project_path)?;
+    let relative = repo_relative(&repo, &project_path.join(file_path))?;
+
+    let mut options = DiffOptions::new();
+    options.pathspec(&relative).disable_pathspec_match(true);
+    let diff = if staged {
+        let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+        repo.diff_tree_to_index(head.as_ref(), None, Some(&mut options))?
+    } else {
+        options.include_untracked(true).show_untracked_content(true);
+        repo.diff_index_to_workdir(None, Some(&mut options))?
+    };
+
+    let mut patch = String::new();
+    diff.print(DiffFormat::Patch, |delta, _hunk, line| {
+        if delta.flags().is_binary() {
+            if patch.is_empty() {
+                patch = format!("Binary file {} differs\n", relative.display());
+            }
+            return true;
+        }
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin());
+        }
+        patch.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })?;
+    Ok(patch)
+}
+
+/// `path` relative to the repository's working directory
+fn repo_relative(repo: &Repository, path: &Path) -> Result<PathBuf> {
+    let workdir = repo.workdir()
+        .ok_or_else(|| ShellError::Configuration("Repository has no working directory".into()))?;
+    let workdir = workdir.canonicalize()?;
+    // The file may be deleted, so canonicalize through its parent
+    let parent = path.parent().unwrap_or(path).canonicalize()?;
+    let absolute = match path.file_name() {
+        Some(name) => parent.join(name),
+        None => parent,
+    };
+    absolute.strip_prefix(&workdir).map(Path::to_path_buf).map_err(|_| {
+        ShellError::Security(format!("{} is outside the repository", path.display()))
+    })
+}
+
+fn file_states(status: Status) -> Vec<FileState> {
+    let mut states = Vec::new();
+    if status.is_wt_new() {
+        states.push(FileState::Untracked);
+    }
+    if status.intersects(Status::INDEX_NEW | Status::INDEX_MODIFIED | Status::INDEX_RENAMED | Status::INDEX_TYPECHANGE | Status::INDEX_DELETED) {
+        states.push(FileState::Staged);
+    }
+    if status.intersects(Status::WT_MODIFIED | Status::WT_RENAMED | Status::WT_TYPECHANGE) {
+        states.push(FileState::Modified);
+    }
+    if status.intersects(Status::WT_DELETED | Status::INDEX_DELETED) {
+        states.push(FileState::Deleted);
+    }
+    states
+}
+
+/// Add files' current contents to the index; missing files stage a deletion
+pub fn stage(project_path: &Path, paths: &[PathBuf]) -> Result<()> {
+    let repo = Repository::discover(project_path)?;
+    let mut index = repo.index()?;
+    for path in paths {
+        let absolute = project_path.join(path);
+        let relative = repo_relative(&repo, &absolute)?;
+        if absolute.exists() {
+            index.add_path(&relative)?;
+        } else {
+            index.remove_path(&relative)?;
+        }
+    }
+    index.write()?;
+    Ok(())
+}
+
+/// Reset files in the index to HEAD, keeping working-tree changes
+pub fn unstage(project_path: &Path, paths: &[PathBuf]) -> Result<()> {
+    let repo = Repository::discover(project_path)?;
+    let relative = paths.iter()
+        .map(|path| repo_relative(&repo, &project_path.join(path)))
+        .collect::<Result<Vec<_>>>()?;
+
+    match repo.head().ok().and_then(|head| head.peel_to_commit().ok()) {
+        Some(head) => repo.reset_default(Some(head.as_object()), relative.iter())?,
+        // No commits yet: unstaging means dropping the entries
+        None => {
+            let mut index = repo.index()?;
+            for path in &relative {
+                index.remove_path(path)?;
+            }
+            index.write()?;
+        }
+    }
+    Ok(())
+}
+
+/// Commit the index on the current branch, returning the new commit id.
+/// Without `author` the repository's `user.name`/`user.email` are used.
+pub fn commit(project_path: &Path, message: &str, author: Option<&GitAuthor>) -> Result<String> {
+    if message.trim().is_empty() {
+        return Err(git2::Error::from_str("Commit message is empty").into());
+    }
+    let repo = Repository::discover(project_path)?;
+    let signature = match author {
+        Some(author) => Signature::now(&author.name, &author.email)?,
+        None => repo.signature().map_err(|_| git2::Error::from_str(
+            "No commit author: set one in settings or configure user.name and user.email",
+        ))?,
+    };
+
+    let mut index = repo.index()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let unchanged = match &parent {
+        Some(parent) => parent.tree_id() == tree.id(),
+        None => index.is_empty(),
+    };
+    if unchanged {
+        return Err(git2::Error::from_str("Nothing staged to commit").into());
+    }
+
+    let parents: Vec<_> = parent.iter().collect();
+    let id = repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+    Ok(id.to_string())
+}
+
+/// Create a repository for a project with a `.gitignore` for its detected
+/// language, optionally committing the scaffold. Returns the initial
+/// commit id when one was made.
+pub fn init(project_path: &Path, initial_commit: bool, author: Option<&GitAuthor>) -> Result<Option<String>> {
+    if Repository::open(project_path).is_ok() {
+        return Err(git2::Error::from_str("Project is already a git repository").into());
+    }
+
+    let language = ProjectInfo::detect(project_path)?.language;
+    let repo = Repository::init_opts(project_path, RepositoryInitOptions::new().initial_head("main"))?;
+
+    // Keep an ignore file the scaffold already ships
+    let gitignore = project_path.join(".gitignore");
+    if !gitignore.exists() {
+        std::fs::write(&gitignore, gitignore_for(language.as_deref()))?;
+    }
+
+    if !initial_commit {
+        return Ok(None);
+    }
+    let mut index = repo.index()?;
+    index.add_all(["*"], IndexAddOption::DEFAULT, None)?;
+    index.write()?;
+    commit(project_path, "Initial commit", author).map(Some)
+}
+
+/// Ignore rules for build output and caches of `language`
+fn gitignore_for(language: Option<&str>) -> String {
+    let rules: &[&str] = match language.map(str::to_lowercase).as_deref() {
+        Some("python" | "py") => &["__pycache__/", "*.py[cod]", ".venv/", "venv/", ".pytest_cache/", "*.egg-info/"],
+        Some("javascript" | "js" | "node" | "typescript" | "ts") => &["node_modules/", "dist/", "build/", "npm-debug.log*"],
+        Some("rust" | "rs") => &["target/"],
+        Some("go" | "golang") => &["bin/", "*.exe", "*.test"],
+        Some("java") => &["target/", "build/", ".gradle/", "*.class"],
+        Some("c" | "cpp" | "c++") => &["*.o", "*.out", "build/"],
+        Some("ruby" | "rb") => &[".bundle/", "vendor/bundle/"],
+        _ => &[],
+    };
+    ["# Editor and OS files", ".DS_Store", "*.swp", ".idea/"].iter()
+        .chain(if rules.is_empty() { &[][..] } else { &["", "# Build output and caches"][..] })
+        .chain(rules)
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// Branch HEAD points at, including one with no commits yet
+fn branch(repo: &Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    head.symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/heads/"))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Repository with one file of each state, on branch `main`
+    fn fixture_repo() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("shell-git-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        for name in ["clean.py", "modified.py", "deleted.py"] {
+            std::fs::write(dir.join(name), "print('v1')\n").unwrap();
+        }
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Student", "student@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[]).unwrap();
+
+        std::fs::write(dir.join("modified.py"), "print('v2')\n").unwrap();
+        std::fs::remove_file(dir.join("deleted.py")).unwrap();
+        std::fs::write(dir.join("staged.py"), "print('new')\n").unwrap();
+        index.add_path(Path::new("staged.py")).unwrap();
+        index.write().unwrap();
+        std::fs::create_dir_all(dir.join("notes")).unwrap();
+        std::fs::write(dir.join("notes/untracked.txt"), "todo\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_status_reports_mixed_states() {
+        let dir = fixture_repo();
+        let status = status(&dir.join("notes")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(status.is_repository);
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        let files: Vec<_> = status.files.iter().map(|f| (f.path.as_str(), f.states.clone())).collect();
+        assert_eq!(files, vec![
+            ("deleted.py", vec![FileState::Deleted]),
+            ("modified.py", vec![FileState::Modified]),
+            ("notes/untracked.txt", vec![FileState::Untracked]),
+            ("staged.py", vec![FileState::Staged]),
+        ]);
+    }
+
+    #[test]
+    fn test_diff_modified_added_and_binary() {
+        let dir = fixture_repo();
+        std::fs::write(dir.join("image.bin"), [0u8, 159, 146, 150, 0, 1]).unwrap();
+
+        let modified = diff(&dir, Path::new("modified.py"), false).unwrap();
+        let staged = diff(&dir, Path::new("staged.py"), true).unwrap();
+        let unstaged_new = diff(&dir, Path::new("staged.py"), false).unwrap();
+        let binary = diff(&dir, Path::new("image.bin"), false).unwrap();
+        let outside = diff(&dir, Path::new("../elsewhere.py"), false);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(modified.starts_with("diff --git a/modified.py b/modified.py\n"), "{}", modified);
+        assert!(modified.ends_with("-print('v1')\n+print('v2')\n"), "{}", modified);
+        assert!(staged.contains("new file mode") && staged.ends_with("+print('new')\n"), "{}", staged);
+        assert_eq!(unstaged_new, "");
+        assert_eq!(binary, "Binary file image.bin differs\n");
+        assert!(matches!(outside, Err(ShellError::Security(_))));
+    }
+
+    #[test]
+    fn test_stage_commit_and_unstage() {
+        let dir = fixture_repo();
+        let author = GitAuthor { name: "Ada".into(), email: "ada@example.com".into() };
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+        stage(&dir, &paths(&["modified.py", "deleted.py"])).unwrap();
+        unstage(&dir, &paths(&["staged.py"])).unwrap();
+        let id = commit(&dir, "Update solution", Some(&author)).unwrap();
+
+        let repo = Repository::open(&dir).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let mut log = repo.revwalk().unwrap();
+        log.push_head().unwrap();
+        assert_eq!(log.count(), 2);
+        assert_eq!((head.id().to_string(), head.message(), head.author().name()), (id, Some("Update solution"), Some("Ada")));
+        assert!(head.tree().unwrap().get_name("modified.py").is_some());
+        assert!(head.tree().unwrap().get_name("deleted.py").is_none());
+        assert!(head.tree().unwrap().get_name("staged.py").is_none());
+
+        let nothing_staged = commit(&dir, "Again", Some(&author));
+        let empty_message = commit(&dir, "  ", Some(&author));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(nothing_staged.unwrap_err().to_string(), "Git error: Nothing staged to commit");
+        assert!(empty_message.is_err());
+    }
+
+    #[test]
+    fn test_init_commits_scaffold_with_gitignore() {
+        let dir = std::env::temp_dir().join(format!("shell-init-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("__pycache__")).unwrap();
+        std::fs::write(dir.join("requirements.txt"), "pytest\n").unwrap();
+        std::fs::write(dir.join("main.py"), "print('hi')\n").unwrap();
+        std::fs::write(dir.join("__pycache__/main.cpython-312.pyc"), [0u8; 4]).unwrap();
+
+        let author = GitAuthor { name: "Ada".into(), email: "ada@example.com".into() };
+        let id = init(&dir, true, Some(&author)).unwrap();
+        let again = init(&dir, true, Some(&author));
+
+        let repo = Repository::open(&dir).unwrap();
+        let head = repo.head().unwrap();
+        let tree = head.peel_to_tree().unwrap();
+        let mut files: Vec<_> = tree.iter().map(|entry| entry.name().unwrap().to_string()).collect();
+        files.sort();
+        let gitignore = std::fs::read_to_string(dir.join(".gitignore")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(id, Some(head.peel_to_commit().unwrap().id().to_string()));
+        assert_eq!(head.shorthand(), Some("main"));
+        assert_eq!(files, vec![".gitignore", "main.py", "requirements.txt"]);
+        assert!(gitignore.contains("__pycache__/\n"));
+        assert!(again.is_err());
+    }
+
+    #[test]
+    fn test_outside_repository_is_not_an_error() {
+        let dir = std::env::temp_dir().join(format!("shell-nogit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let status = status(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!status.unwrap().is_repository);
+    }
+}
@@ -0,0 +1,621 @@
+//! Grading logic for Shell IDE
+//!
+//! - Enforces lesson `Constraints` before any test is executed:
+//!   allowed/disallowed imports, maximum lines, required symbols
+//! - Merges manual rubric scores into automated results
+//! - Compares two stored submissions of a lesson
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use crate::commands::grading::{diff_lines, DiffLine, GradingResult};
+use crate::commands::lessons::{Constraints, RubricItem};
+use crate::db::SubmissionRecord;
+use crate::error::{Result, ShellError};
+
+/// A lesson constraint the submission does not satisfy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConstraintViolation {
+    /// Import listed in `disallowed_imports`
+    DisallowedImport { module: String, line: usize },
+    /// Import missing from a non-empty `allowed_imports`
+    ImportNotAllowed { module: String, line: usize },
+    /// Symbol from `required_symbols` not found
+    MissingSymbol { symbol: String },
+    /// More lines of code than `max_lines`
+    TooManyLines { lines: u32, max: u32 },
+}
+
+impl std::fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DisallowedImport { module, line } => {
+                write!(f, "Line {}: importing '{}' is not allowed in this lesson", line, module)
+            }
+            Self::ImportNotAllowed { module, line } => {
+                write!(f, "Line {}: '{}' is not one of the allowed imports", line, module)
+            }
+            Self::MissingSymbol { symbol } => write!(f, "Required symbol '{}' is missing", symbol),
+            Self::TooManyLines { lines, max } => {
+                write!(f, "Solution has {} lines of code (max: {})", lines, max)
+            }
+        }
+    }
+}
+
+/// Check submitted source files against a lesson's constraints.
+///
+/// Imports are checked per file, required symbols may be defined in any file
+/// and the line limit applies to the total.
+pub fn check_constraints(sources: &[&str], language: &str, constraints: &Constraints) -> Vec<ConstraintViolation> {
+    let mut violations = Vec::new();
+    let parsed: Vec<_> = sources.iter().map(|code| code_lines(code, language)).collect();
+    let total_lines: usize = parsed.iter().map(|lines| lines.len()).sum();
+
+    for lines in &parsed {
+        if constraints.allowed_imports.is_none() && constraints.disallowed_imports.is_none() {
+            continue;
+        }
+        for (line, module) in find_imports(lines, language) {
+            let disallowed = constraints.disallowed_imports.iter().flatten()
+                .any(|name| module_matches(&module, name));
+            let allowed = constraints.allowed_imports.as_ref()
+                .map(|names| names.iter().any(|name| module_matches(&module, name)))
+                .unwrap_or(true);
+
+            if disallowed {
+                violations.push(ConstraintViolation::DisallowedImport { module, line });
+            } else if !allowed {
+                violations.push(ConstraintViolation::ImportNotAllowed { module, line });
+            }
+        }
+    }
+
+    for symbol in constraints.required_symbols.iter().flatten() {
+        let defined = symbol_definition(symbol, language)
+            .map(|re| parsed.iter().flatten().any(|(_, line)| re.is_match(line)))
+            .unwrap_or(false);
+        if !defined {
+            violations.push(ConstraintViolation::MissingSymbol { symbol: symbol.clone() });
+        }
+    }
+
+    if let Some(max) = constraints.max_lines {
+        let count = u32::try_from(total_lines).unwrap_or(u32::MAX);
+        if count > max {
+            violations.push(ConstraintViolation::TooManyLines { lines: count, max });
+        }
+    }
+
+    violations
+}
+
+/// Pattern matching a definition of `symbol` on a comment-free, trimmed line.
+///
+/// Languages without specific rules fall back to any whole-word use.
+fn symbol_definition(symbol: &str, language: &str) -> Option<Regex> {
+    let name = regex::escape(symbol);
+    let pattern = match language.to_lowercase().as_str() {
+        "python" | "py" => format!(r"^(?:async\s+)?(?:def|class)\s+{}\b", name),
+        "javascript" | "js" | "node" | "typescript" | "ts" => format!(
+            r"^(?:export\s+(?:default\s+)?)?(?:(?:async\s+)?function\s*\*?\s*{0}\b|(?:abstract\s+)?class\s+{0}\b|(?:const|let|var)\s+{0}\s*(?::[^=]+)?=)",
+            name
+        ),
+        "rust" | "rs" => format!(
+            r#"^(?:pub(?:\([^)]*\))?\s+)?(?:const\s+)?(?:async\s+)?(?:unsafe\s+)?(?:extern\s+"[^"]*"\s+)?(?:fn|struct|enum|trait)\s+{}\b"#,
+            name
+        ),
+        _ => format!(r"\b{}\b", name),
+    };
+    Regex::new(&pattern).ok()
+}
+
+/// Non-blank source lines with comments removed, paired with 1-based line numbers
+fn code_lines(code: &str, language: &str) -> Vec<(usize, String)> {
+    match language.to_lowercase().as_str() {
+        "python" | "py" | "ruby" | "rb" => code.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| (i, line.to_string()))
+            .collect(),
+        _ => strip_c_style_comments(code),
+    }
+}
+
+/// Remove `//` and `/* */` comments, keeping lines that still contain code
+fn strip_c_style_comments(code: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut in_block = false;
+
+    for (i, line) in code.lines().enumerate() {
+        let mut rest = line;
+        let mut kept = String::new();
+
+        loop {
+            if in_block {
+                match rest.find("*/") {
+                    Some(end) => {
+                        rest = &rest[end + 2..];
+                        in_block = false;
+                    }
+                    None => break,
+                }
+            } else {
+                match (rest.find("//"), rest.find("/*")) {
+                    (Some(line_comment), block) if block.map_or(true, |b| line_comment < b) => {
+                        kept.push_str(&rest[..line_comment]);
+                        break;
+                    }
+                    (_, Some(block)) => {
+                        kept.push_str(&rest[..block]);
+                        rest = &rest[block + 2..];
+                        in_block = true;
+                    }
+                    _ => {
+                        kept.push_str(rest);
+                        break;
+                    }
+                }
+            }
+        }
+
+        let kept = kept.trim();
+        if !kept.is_empty() {
+            lines.push((i + 1, kept.to_string()));
+        }
+    }
+
+    lines
+}
+
+/// Modules imported by the code, with the line they appear on.
+///
+/// Python and JavaScript/TypeScript are recognized; other languages report
+/// no imports.
+fn find_imports(lines: &[(usize, String)], language: &str) -> Vec<(usize, String)> {
+    match language.to_lowercase().as_str() {
+        "python" | "py" => lines.iter()
+            .flat_map(|(n, line)| line.split(';').flat_map(|stmt| python_imports(stmt.trim())).map(move |m| (*n, m)))
+            .collect(),
+        "javascript" | "js" | "node" | "typescript" | "ts" => lines.iter()
+            .flat_map(|(n, line)| js_imports(line).into_iter().map(move |m| (*n, m)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Modules named by a Python `import` or `from ... import` statement
+fn python_imports(statement: &str) -> Vec<String> {
+    if let Some(rest) = statement.strip_prefix("import ") {
+        rest.split(',')
+            .filter_map(|part| part.split_whitespace().next())
+            .map(str::to_string)
+            .collect()
+    } else if let Some(rest) = statement.strip_prefix("from ") {
+        rest.split_whitespace()
+            .next()
+            // Relative imports refer to the student's own modules
+            .filter(|module| !module.starts_with('.'))
+            .map(|module| vec![module.to_string()])
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Packages named by `require()`, `import ... from`, bare and dynamic imports
+fn js_imports(line: &str) -> Vec<String> {
+    static IMPORT_RE: OnceLock<Regex> = OnceLock::new();
+    let re = IMPORT_RE.get_or_init(|| {
+        Regex::new(r#"(?:\brequire\s*\(\s*|\bimport\s*\(\s*|\bfrom\s+|^import\s+)['"`]([^'"`]+)['"`]"#)
+            .expect("valid import regex")
+    });
+
+    re.captures_iter(line)
+        .map(|caps| caps[1].to_string())
+        // Relative paths are the student's own files
+        .filter(|module| !module.starts_with('.') && !module.starts_with('/'))
+        .map(|module| module.trim_start_matches("node:").to_string())
+        .collect()
+}
+
+/// Whether an imported module is covered by a constraint entry (`os` covers `os.path`)
+fn module_matches(module: &str, name: &str) -> bool {
+    module == name
+        || module.strip_prefix(name)
+            .map(|rest| rest.starts_with('.') || rest.starts_with('/'))
+            .unwrap_or(false)
+}
+
+/// Score a grader assigns to one rubric item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RubricScore {
+    pub item_id: String,
+    pub points: f32,
+    pub comment: Option<String>,
+}
+
+/// Points awarded for one rubric item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RubricAward {
+    pub item_id: String,
+    pub name: String,
+    pub points_awarded: f32,
+    pub points_possible: f32,
+    pub comment: Option<String>,
+}
+
+/// Combine rubric scores with the automated test points of a result.
+///
+/// Any previous rubric scoring is replaced, so a submission can be reopened
+/// and re-scored. Rubric items without a score are awarded zero points.
+pub fn merge_rubric_scores(result: &mut GradingResult, rubric: &[RubricItem], scores: &[RubricScore]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for score in scores {
+        let item = rubric.iter()
+            .find(|item| item.id == score.item_id)
+            .ok_or_else(|| ShellError::Lesson(format!("Unknown rubric item: {}", score.item_id)))?;
+
+        if !seen.insert(score.item_id.as_str()) {
+            return Err(ShellError::Lesson(format!("Rubric item scored twice: {}", score.item_id)));
+        }
+        if !(0.0..=item.points).contains(&score.points) {
+            return Err(ShellError::Lesson(format!(
+                "Score for '{}' must be between 0 and {} (got {})",
+                item.name, item.points, score.points
+            )));
+        }
+    }
+
+    result.rubric_scores = rubric.iter()
+        .map(|item| {
+            let score = scores.iter().find(|s| s.item_id == item.id);
+            RubricAward {
+                item_id: item.id.clone(),
+                name: item.name.clone(),
+                points_awarded: score.map(|s| s.points).unwrap_or(0.0),
+                points_possible: item.points,
+                comment: score.and_then(|s| s.comment.clone()),
+            }
+        })
+        .collect();
+
+    let test_points: f32 = result.test_results.iter().filter(|t| t.passed).map(|t| t.points_earned).sum();
+    let test_max: f32 = result.test_results.iter().map(|t| t.points_possible).sum();
+    let rubric_points: f32 = result.rubric_scores.iter().map(|a| a.points_awarded).sum();
+    let rubric_max: f32 = result.rubric_scores.iter().map(|a| a.points_possible).sum();
+
+    result.total_points = (test_points + rubric_points - result.hint_penalty).max(0.0);
+    result.max_points = test_max + rubric_max;
+    result.percentage = percentage(result.total_points, result.max_points);
+
+    Ok(())
+}
+
+/// Deduct points for hints the student revealed, never going below zero
+pub fn apply_hint_penalty(result: &mut GradingResult, penalty: f32) {
+    if penalty <= 0.0 {
+        return;
+    }
+    result.hint_penalty = penalty;
+    result.total_points = (result.total_points - penalty).max(0.0);
+    result.percentage = percentage(result.total_points, result.max_points);
+}
+
+fn percentage(points: f32, max_points: f32) -> f32 {
+    if max_points > 0.0 {
+        (points / max_points) * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// How a test's result changed between two submissions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestChange {
+    NewlyPassing,
+    NewlyFailing,
+    StillPassing,
+    StillFailing,
+    /// Only the second submission ran it
+    Added,
+    /// Only the first submission ran it
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestDelta {
+    pub id: String,
+    pub name: String,
+    pub change: TestChange,
+}
+
+/// Structured comparison of two submissions of one lesson, from `a` to `b`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmissionDiff {
+    pub lesson_id: String,
+    pub submission_a: String,
+    pub submission_b: String,
+    pub score_a: Option<f64>,
+    pub score_b: Option<f64>,
+    /// Line diff of the code; `added` lines are only in `b`
+    pub code: Vec<DiffLine>,
+    /// Every test either submission ran, in `b`'s order then `a`'s
+    pub tests: Vec<TestDelta>,
+}
+
+/// Compare two stored submissions. Both must be for the same lesson and
+/// have their code stored.
+pub fn submission_diff(a: &SubmissionRecord, b: &SubmissionRecord) -> Result<SubmissionDiff> {
+    let lesson_id = match (&a.lesson_id, &b.lesson_id) {
+        (Some(first), Some(second)) if first == second => first.clone(),
+        (first, second) => return Err(ShellError::Lesson(format!(
+            "Can't compare submissions for different lessons ({} and {})",
+            first.as_deref().unwrap_or("none"),
+            second.as_deref().unwrap_or("none"),
+        ))),
+    };
+    let source = |s: &SubmissionRecord| s.source.clone()
+        .ok_or_else(|| ShellError::Lesson(format!("Submission {} has no stored code", s.id)));
+    let results = |s: &SubmissionRecord| -> Result<Vec<(String, String, bool)>> {
+        Ok(match &s.result {
+            Some(json) => serde_json::from_str::<GradingResult>(json)?.test_results.into_iter()
+                .map(|t| (t.id, t.name, t.passed))
+                .collect(),
+            None => Vec::new(),
+        })
+    };
+    let (before, after) = (results(a)?, results(b)?);
+
+    let mut tests: Vec<TestDelta> = after.iter().map(|(id, name, passed)| {
+        let was = before.iter().find(|(old, _, _)| old == id).map(|(_, _, p)| *p);
+        let change = match (was, passed) {
+            (None, _) => TestChange::Added,
+            (Some(false), true) => TestChange::NewlyPassing,
+            (Some(true), false) => TestChange::NewlyFailing,
+            (Some(true), true) => TestChange::StillPassing,
+            (Some(false), false) => TestChange::StillFailing,
+        };
+        TestDelta { id: id.clone(), name: name.clone(), change }
+    }).collect();
+    tests.extend(before.iter()
+        .filter(|(id, _, _)| !after.iter().any(|(new, _, _)| new == id))
+        .map(|(id, name, _)| TestDelta { id: id.clone(), name: name.clone(), change: TestChange::Removed }));
+
+    Ok(SubmissionDiff {
+        lesson_id,
+        submission_a: a.id.clone(),
+        submission_b: b.id.clone(),
+        score_a: a.score,
+        score_b: b.score,
+        code: diff_lines(&source(a)?, &source(b)?),
+        tests,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::grading::DiffKind;
+
+    fn constraints() -> Constraints {
+        Constraints {
+            max_time_ms: None,
+            max_memory_bytes: None,
+            allowed_imports: None,
+            disallowed_imports: None,
+            required_symbols: None,
+            max_lines: None,
+        }
+    }
+
+    #[test]
+    fn test_python_disallowed_imports() {
+        let c = Constraints { disallowed_imports: Some(vec!["os".into(), "subprocess".into()]), ..constraints() };
+        let code = "import math, os.path as p\n# import subprocess\nfrom subprocess import run\nfrom . import helper\n";
+
+        assert_eq!(check_constraints(&[code], "python", &c), vec![
+            ConstraintViolation::DisallowedImport { module: "os.path".into(), line: 1 },
+            ConstraintViolation::DisallowedImport { module: "subprocess".into(), line: 3 },
+        ]);
+    }
+
+    #[test]
+    fn test_python_allowed_imports() {
+        let c = Constraints { allowed_imports: Some(vec!["math".into()]), ..constraints() };
+        let code = "import math\nfrom collections import deque\n";
+
+        assert_eq!(check_constraints(&[code], "py", &c), vec![
+            ConstraintViolation::ImportNotAllowed { module: "collections".into(), line: 2 },
+        ]);
+    }
+
+    #[test]
+    fn test_js_require_and_import() {
+        let c = Constraints { disallowed_imports: Some(vec!["fs".into(), "lodash".into()]), ..constraints() };
+        let code = "const fs = require('node:fs');\nimport _ from \"lodash/fp\";\n// require('fs')\nimport './local.js';\nconst m = await import('fs');\n";
+
+        assert_eq!(check_constraints(&[code], "javascript", &c), vec![
+            ConstraintViolation::DisallowedImport { module: "fs".into(), line: 1 },
+            ConstraintViolation::DisallowedImport { module: "lodash/fp".into(), line: 2 },
+            ConstraintViolation::DisallowedImport { module: "fs".into(), line: 5 },
+        ]);
+    }
+
+    #[test]
+    fn test_line_count_ignores_comments_and_blanks() {
+        let c = Constraints { max_lines: Some(2), ..constraints() };
+
+        let python = "# header\n\nx = 1\n   \nprint(x)  # trailing comment\n";
+        assert!(check_constraints(&[python], "python", &c).is_empty());
+
+        let js = "/* multi\n   line */ let a = 1;\n\n// note\nlet b = 2; /* tail */\nlet c = 3;\n";
+        assert_eq!(check_constraints(&[js], "js", &c), vec![
+            ConstraintViolation::TooManyLines { lines: 3, max: 2 },
+        ]);
+
+        assert!(check_constraints(&[""], "python", &c).is_empty());
+    }
+
+    #[test]
+    fn test_required_symbols() {
+        let c = Constraints { required_symbols: Some(vec!["solve".into()]), ..constraints() };
+
+        assert!(check_constraints(&["def solve(n):\n    return n\n"], "python", &c).is_empty());
+        assert_eq!(check_constraints(&["def solver(n):\n    return n\n"], "python", &c), vec![
+            ConstraintViolation::MissingSymbol { symbol: "solve".into() },
+        ]);
+    }
+
+    #[test]
+    fn test_required_symbols_must_be_defined() {
+        let c = Constraints { required_symbols: Some(vec!["solve".into(), "Grid".into()]), ..constraints() };
+        let missing = |symbols: &[&str]| symbols.iter()
+            .map(|s| ConstraintViolation::MissingSymbol { symbol: s.to_string() })
+            .collect::<Vec<_>>();
+
+        // Mentions in comments or calls don't count as definitions
+        let python = "# TODO: def solve(n)\nprint(solve(3))\nclass Grid:\n    pass\n";
+        assert_eq!(check_constraints(&[python], "python", &c), missing(&["solve"]));
+        assert!(check_constraints(&["class Grid:\n    async def solve(self):\n        pass\n"], "python", &c).is_empty());
+
+        let js = "// function solve() {}\n/* class Grid {} */\nsolve();\n";
+        assert_eq!(check_constraints(&[js], "javascript", &c), missing(&["solve", "Grid"]));
+        let js = "export const solve = (n) => n;\nexport default class Grid {}\n";
+        assert!(check_constraints(&[js], "js", &c).is_empty());
+        let ts = "const solve: (n: number) => number = (n) => n;\nclass Grid {}\n";
+        assert!(check_constraints(&[ts], "typescript", &c).is_empty());
+
+        let rust = "// fn solve() {}\nfn main() { solve(); }\npub struct Grid;\n";
+        assert_eq!(check_constraints(&[rust], "rust", &c), missing(&["solve"]));
+        assert!(check_constraints(&["pub(crate) async fn solve() {}\nstruct Grid;\n"], "rust", &c).is_empty());
+    }
+
+    fn graded(test_points: &[(f32, bool)]) -> GradingResult {
+        serde_json::from_value(serde_json::json!({
+            "submission_id": "sub-1",
+            "lesson_id": "lesson-1",
+            "total_points": 0.0,
+            "max_points": 0.0,
+            "percentage": 0.0,
+            "test_results": test_points.iter().enumerate().map(|(i, (points, passed))| serde_json::json!({
+                "id": format!("t{}", i),
+                "name": "test",
+                "passed": passed,
+                "actual_output": "",
+                "expected_output": "",
+                "points_earned": if *passed { *points } else { 0.0 },
+                "points_possible": points,
+                "execution_time_ms": 0,
+                "error": null,
+                "diff": null,
+            })).collect::<Vec<_>>(),
+            "feedback": null,
+            "graded_at": "2026-01-13T10:30:00Z",
+            "graded_by": "Local",
+        })).unwrap()
+    }
+
+    fn rubric() -> Vec<RubricItem> {
+        vec![
+            RubricItem { id: "style".into(), name: "Style".into(), description: String::new(), points: 10.0, criteria: vec![] },
+            RubricItem { id: "approach".into(), name: "Approach".into(), description: String::new(), points: 20.0, criteria: vec![] },
+        ]
+    }
+
+    fn score(item_id: &str, points: f32) -> RubricScore {
+        RubricScore { item_id: item_id.into(), points, comment: None }
+    }
+
+    #[test]
+    fn test_rubric_merges_with_test_points() {
+        let mut result = graded(&[(30.0, true), (40.0, false)]);
+
+        merge_rubric_scores(&mut result, &rubric(), &[score("style", 7.5), score("approach", 20.0)]).unwrap();
+
+        assert_eq!(result.total_points, 57.5);
+        assert_eq!(result.max_points, 100.0);
+        assert_eq!(result.percentage, 57.5);
+        assert_eq!(result.rubric_scores[0].points_awarded, 7.5);
+        assert_eq!(result.rubric_scores[1].points_possible, 20.0);
+    }
+
+    #[test]
+    fn test_rubric_rescoring_replaces_previous_scores() {
+        let mut result = graded(&[(70.0, true)]);
+
+        merge_rubric_scores(&mut result, &rubric(), &[score("style", 10.0), score("approach", 20.0)]).unwrap();
+        merge_rubric_scores(&mut result, &rubric(), &[score("style", 5.0)]).unwrap();
+
+        assert_eq!(result.total_points, 75.0);
+        assert_eq!(result.rubric_scores[1].points_awarded, 0.0);
+    }
+
+    fn stored(id: &str, lesson_id: &str, code: &str, tests: &[(f32, bool)]) -> SubmissionRecord {
+        SubmissionRecord {
+            id: id.into(),
+            lesson_id: Some(lesson_id.into()),
+            project_id: None,
+            submitted_at: "2026-01-13T10:30:00Z".into(),
+            status: "graded".into(),
+            score: None,
+            feedback: None,
+            result: Some(serde_json::to_string(&graded(tests)).unwrap()),
+            source: Some(code.into()),
+        }
+    }
+
+    #[test]
+    fn test_submission_diff_shows_newly_passing_test() {
+        let first = stored("s1", "fizzbuzz", "def fizz(n):\n    return str(n)\n", &[(50.0, true), (50.0, false)]);
+        let second = stored(
+            "s2", "fizzbuzz",
+            "def fizz(n):\n    if n % 3 == 0:\n        return 'Fizz'\n    return str(n)\n",
+            &[(50.0, true), (50.0, true)],
+        );
+
+        let diff = submission_diff(&first, &second).unwrap();
+        assert_eq!(diff.lesson_id, "fizzbuzz");
+        let changes: Vec<_> = diff.tests.iter().map(|t| (t.id.as_str(), t.change)).collect();
+        assert_eq!(changes, vec![("t0", TestChange::StillPassing), ("t1", TestChange::NewlyPassing)]);
+        let added: Vec<_> = diff.code.iter().filter(|l| l.kind == DiffKind::Added).map(|l| l.content.as_str()).collect();
+        assert_eq!(added, vec!["    if n % 3 == 0:", "        return 'Fizz'"]);
+        assert!(diff.code.iter().all(|l| l.kind != DiffKind::Removed));
+
+        let other = stored("s3", "loops", "", &[]);
+        let err = submission_diff(&first, &other).unwrap_err();
+        assert_eq!(err.to_string(), "Lesson error: Can't compare submissions for different lessons (fizzbuzz and loops)");
+    }
+
+    #[test]
+    fn test_hint_penalty_survives_rescoring() {
+        let mut result = graded(&[(80.0, true)]);
+        result.total_points = 80.0;
+        result.max_points = 80.0;
+
+        apply_hint_penalty(&mut result, 10.0);
+        assert_eq!(result.total_points, 70.0);
+        assert_eq!(result.percentage, 87.5);
+
+        merge_rubric_scores(&mut result, &rubric(), &[score("style", 10.0)]).unwrap();
+        assert_eq!(result.total_points, 80.0);
+
+        apply_hint_penalty(&mut result, 500.0);
+        assert_eq!(result.total_points, 0.0);
+    }
+
+    #[test]
+    fn test_rubric_score_validation() {
+        let mut result = graded(&[(70.0, true)]);
+
+        assert!(merge_rubric_scores(&mut result, &rubric(), &[score("style", 11.0)]).is_err());
+        assert!(merge_rubric_scores(&mut result, &rubric(), &[score("style", -1.0)]).is_err());
+        assert!(merge_rubric_scores(&mut result, &rubric(), &[score("unknown", 1.0)]).is_err());
+        assert!(merge_rubric_scores(&mut result, &rubric(), &[score("style", 1.0), score("style", 2.0)]).is_err());
+        assert!(result.rubric_scores.is_empty());
+    }
+}
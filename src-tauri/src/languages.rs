@@ -0,0 +1,313 @@
+//! Language registry
+//!
+//! Everything Shell knows about running a language lives in one table: the
+//! image it runs in, how programs and tests are started, which files belong
+//! to it and which tools format and lint it. Adding a language means adding
+//! a `LanguageSpec` here.
+
+use serde::Serialize;
+use crate::docker::ImagePresence;
+use crate::error::{Result, ShellError};
+
+/// How Shell runs and tools one language
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LanguageSpec {
+    /// Canonical name, e.g. `python`
+    pub id: &'static str,
+    /// Name shown in the UI, e.g. `C++`
+    pub display_name: &'static str,
+    /// Other names callers may use, e.g. `py`
+    pub aliases: &'static [&'static str],
+    /// Image programs run in
+    pub image: &'static str,
+    /// Run command; `{entry}` stands for the entry point
+    pub run_command_template: &'static [&'static str],
+    /// Compiler for languages built before they run
+    pub compiler: Option<&'static str>,
+    /// Test framework command, for languages that have one
+    pub test_command: Option<&'static [&'static str]>,
+    /// Framework `test_command` needs that an image with the toolchain may
+    /// still lack, a command that fails without it, and how to get it
+    pub test_framework: Option<&'static str>,
+    pub test_framework_probe: &'static [&'static str],
+    pub test_framework_guidance: &'static str,
+    /// File extensions, without the dot
+    pub extensions: &'static [&'static str],
+    /// Conventional entry files, in order of preference
+    pub entry_points: &'static [&'static str],
+    /// File a graded submission is written to
+    pub submission_file: &'static str,
+    /// Binary the run command needs, and a command printing its version
+    pub toolchain: &'static str,
+    pub toolchain_probe: &'static [&'static str],
+    /// How to get the toolchain when the image lacks it
+    pub toolchain_guidance: &'static str,
+    /// Name of the host formatter (see `format::formatter_for`)
+    pub formatter: Option<&'static str>,
+    /// Name of the in-image linter (see `lint`)
+    pub linter: Option<&'static str>,
+}
+
+/// Where compiled languages put their binary inside the container
+pub const COMPILED_BINARY: &str = "/tmp/a.out";
+
+/// Every supported language, in the order the UI lists them
+pub static LANGUAGES: &[LanguageSpec] = &[
+    LanguageSpec {
+        id: "python",
+        display_name: "Python",
+        aliases: &["py"],
+        image: "python:3.12-slim",
+        run_command_template: &["python", "{entry}"],
+        compiler: None,
+        test_command: Some(&["python", "-m", "pytest"]),
+        test_framework: Some("pytest"),
+        test_framework_probe: &["python", "-m", "pytest", "--version"],
+        test_framework_guidance: "Use a Python image with pytest installed (pip install pytest)",
+        extensions: &["py"],
+        entry_points: &["main.py", "app.py", "__main__.py"],
+        submission_file: "main.py",
+        toolchain: "python",
+        toolchain_probe: &["python", "--version"],
+        toolchain_guidance: "Use a Python image that provides `python`",
+        formatter: Some("Black"),
+        linter: Some("ruff"),
+    },
+    LanguageSpec {
+        id: "javascript",
+        display_name: "JavaScript",
+        aliases: &["js", "node"],
+        image: "node:20-slim",
+        run_command_template: &["node", "{entry}"],
+        compiler: None,
+        test_command: Some(&["npm", "test"]),
+        test_framework: None,
+        test_framework_probe: &[],
+        test_framework_guidance: "",
+        extensions: &["js", "mjs", "cjs"],
+        entry_points: &["index.js", "main.js", "app.js"],
+        submission_file: "main.js",
+        toolchain: "node",
+        toolchain_probe: &["node", "--version"],
+        toolchain_guidance: "Use a Node.js image that provides `node`",
+        formatter: Some("Prettier"),
+        linter: Some("ESLint"),
+    },
+    LanguageSpec {
+        id: "typescript",
+        display_name: "TypeScript",
+        aliases: &["ts"],
+        image: "node:20-slim",
+        run_command_template: &["npx", "tsx", "{entry}"],
+        compiler: None,
+        test_command: Some(&["npm", "test"]),
+        test_framework: None,
+        test_framework_probe: &[],
+        test_framework_guidance: "",
+        extensions: &["ts", "mts", "cts"],
+        entry_points: &["index.ts", "main.ts", "src/index.ts"],
+        submission_file: "main.ts",
+        toolchain: "tsx",
+        toolchain_probe: &["npx", "--no-install", "tsx", "--version"],
+        toolchain_guidance: "Install tsx in the Node.js image (npm install -g tsx); runs have no network to fetch it",
+        formatter: Some("Prettier"),
+        linter: Some("ESLint"),
+    },
+    LanguageSpec {
+        id: "rust",
+        display_name: "Rust",
+        aliases: &["rs"],
+        image: "rust:1.75-slim",
+        run_command_template: &["cargo", "run"],
+        compiler: None,
+        test_command: Some(&["cargo", "test"]),
+        test_framework: None,
+        test_framework_probe: &[],
+        test_framework_guidance: "",
+        extensions: &["rs"],
+        entry_points: &["src/main.rs", "main.rs"],
+        submission_file: "main.rs",
+        toolchain: "cargo",
+        toolchain_probe: &["cargo", "--version"],
+        toolchain_guidance: "Use a Rust image that includes cargo, e.g. rust:1.75-slim",
+        formatter: Some("rustfmt"),
+        linter: Some("Clippy"),
+    },
+    LanguageSpec {
+        id: "go",
+        display_name: "Go",
+        aliases: &["golang"],
+        image: "golang:1.21-alpine",
+        run_command_template: &["go", "run", "{entry}"],
+        compiler: None,
+        test_command: Some(&["go", "test", "./..."]),
+        test_framework: None,
+        test_framework_probe: &[],
+        test_framework_guidance: "",
+        extensions: &["go"],
+        entry_points: &["main.go"],
+        submission_file: "main.go",
+        toolchain: "go",
+        toolchain_probe: &["go", "version"],
+        toolchain_guidance: "Use a Go image that includes the Go toolchain",
+        formatter: Some("gofmt"),
+        linter: Some("go vet"),
+    },
+    LanguageSpec {
+        id: "java",
+        display_name: "Java",
+        aliases: &[],
+        image: "eclipse-temurin:21-jdk",
+        run_command_template: &["java", "{entry}"],
+        compiler: None,
+        test_command: None,
+        test_framework: None,
+        test_framework_probe: &[],
+        test_framework_guidance: "",
+        extensions: &["java"],
+        entry_points: &["Main.java"],
+        submission_file: "Main.java",
+        toolchain: "java",
+        toolchain_probe: &["java", "-version"],
+        toolchain_guidance: "Use a JDK image, e.g. eclipse-temurin:21-jdk",
+        formatter: None,
+        linter: None,
+    },
+    LanguageSpec {
+        id: "c",
+        display_name: "C",
+        aliases: &[],
+        image: "gcc:13",
+        run_command_template: &[COMPILED_BINARY],
+        compiler: Some("gcc"),
+        test_command: None,
+        test_framework: None,
+        test_framework_probe: &[],
+        test_framework_guidance: "",
+        extensions: &["c", "h"],
+        entry_points: &["main.c"],
+        submission_file: "main.c",
+        toolchain: "gcc",
+        toolchain_probe: &["gcc", "--version"],
+        toolchain_guidance: "Use an image with gcc installed, e.g. gcc:13",
+        formatter: Some("clang-format"),
+        linter: None,
+    },
+    LanguageSpec {
+        id: "cpp",
+        display_name: "C++",
+        aliases: &["c++"],
+        image: "gcc:13",
+        run_command_template: &[COMPILED_BINARY],
+        compiler: Some("g++"),
+        test_command: None,
+        test_framework: None,
+        test_framework_probe: &[],
+        test_framework_guidance: "",
+        extensions: &["cpp", "cc", "cxx", "hpp"],
+        entry_points: &["main.cpp"],
+        submission_file: "main.cpp",
+        toolchain: "g++",
+        toolchain_probe: &["g++", "--version"],
+        toolchain_guidance: "Use an image with g++ installed, e.g. gcc:13",
+        formatter: Some("clang-format"),
+        linter: None,
+    },
+    LanguageSpec {
+        id: "ruby",
+        display_name: "Ruby",
+        aliases: &["rb"],
+        image: "ruby:3.3-slim",
+        run_command_template: &["ruby", "{entry}"],
+        compiler: None,
+        test_command: None,
+        test_framework: None,
+        test_framework_probe: &[],
+        test_framework_guidance: "",
+        extensions: &["rb"],
+        entry_points: &["main.rb"],
+        submission_file: "main.rb",
+        toolchain: "ruby",
+        toolchain_probe: &["ruby", "--version"],
+        toolchain_guidance: "Use a Ruby image that provides `ruby`",
+        formatter: None,
+        linter: None,
+    },
+];
+
+/// The spec for a language name or alias, ignoring case
+pub fn language(name: &str) -> Option<&'static LanguageSpec> {
+    let name = name.to_lowercase();
+    LANGUAGES.iter().find(|spec| spec.id == name || spec.aliases.contains(&name.as_str()))
+}
+
+/// Like `language`, but an unknown language is an error
+pub fn supported(name: &str) -> Result<&'static LanguageSpec> {
+    language(name).ok_or_else(|| ShellError::Execution(format!("Unsupported language: {}", name)))
+}
+
+/// What the "new file" picker needs to know about a language
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LanguageInfo {
+    pub id: String,
+    pub display_name: String,
+    pub extensions: Vec<String>,
+    pub default_entry_point: String,
+    pub image: String,
+    /// Whether the image is pulled; when it isn't, the UI offers to prepare
+    /// the language before its first run
+    pub available: bool,
+}
+
+/// Every supported language, marked available when its image is among the
+/// pulled `images`
+pub fn language_infos(images: &[ImagePresence]) -> Vec<LanguageInfo> {
+    LANGUAGES.iter().map(|spec| LanguageInfo {
+        id: spec.id.to_string(),
+        display_name: spec.display_name.to_string(),
+        extensions: spec.extensions.iter().map(|e| e.to_string()).collect(),
+        default_entry_point: spec.entry_points[0].to_string(),
+        image: spec.image.to_string(),
+        available: images.iter().any(|i| i.image == spec.image && i.present),
+    }).collect()
+}
+
+impl LanguageSpec {
+    /// Run command for `entry_point`
+    pub fn run_command(&self, entry_point: &str) -> Vec<String> {
+        self.run_command_template.iter()
+            .map(|part| part.replace("{entry}", entry_point))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_infos_mark_unpulled_images() {
+        let images = vec![
+            ImagePresence { language: "python".into(), image: "python:3.12-slim".into(), present: true },
+            ImagePresence { language: "javascript".into(), image: "node:20-slim".into(), present: false },
+        ];
+        let infos = language_infos(&images);
+        assert_eq!(infos.len(), LANGUAGES.len());
+
+        let python = infos.iter().find(|i| i.id == "python").unwrap();
+        assert_eq!(python.display_name, "Python");
+        assert_eq!(python.extensions, vec!["py"]);
+        assert_eq!(python.default_entry_point, "main.py");
+        assert!(python.available);
+
+        let cpp = infos.iter().find(|i| i.id == "cpp").unwrap();
+        assert_eq!(cpp.display_name, "C++");
+        assert_eq!(cpp.default_entry_point, "main.cpp");
+        // Not pulled, and unknown images count as not pulled
+        assert!(!infos.iter().find(|i| i.id == "typescript").unwrap().available);
+        assert!(!cpp.available);
+
+        // Without Docker nothing is available
+        assert!(language_infos(&[]).iter().all(|i| !i.available));
+    }
+}
@@ -0,0 +1,857 @@
+//! Lesson file handling for Shell IDE
+//!
+//! Lessons are read as raw documents, upgraded from older format versions
+//! and validated before deserializing, so authors get errors that point at
+//! the offending field instead of a serde message.
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use crate::commands::lessons::{
+    Difficulty, GradingConfig, IoDiagram, IoExample, Lesson, LessonContent, OutputMatch, RubricItem, TestCase, TestCheck,
+};
+use crate::error::{Result, ShellError};
+
+/// Newest lesson format (major component of `Lesson.version`) this build reads.
+/// Versions `0.x` are upgraded on load.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// How deep recursive lesson discovery descends below the library root
+pub const MAX_DISCOVERY_DEPTH: usize = 8;
+
+/// Extra ignore file honoured by lesson discovery and snapshots, alongside `.gitignore`
+pub const IGNORE_FILE: &str = ".shellignore";
+
+const DIFFICULTIES: &[&str] = &["beginner", "intermediate", "advanced"];
+
+const LESSON_FIELDS: &[&str] = &[
+    "id", "version", "title", "description", "language", "difficulty", "tags", "prerequisites", "content",
+];
+
+const TEST_FIELDS: &[&str] = &["id", "name", "input", "expected_output", "points", "hidden"];
+
+/// A problem in a lesson file, located by field path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationError {
+    /// Dotted path to the field, e.g. `grading.local_tests[0].points`
+    pub path: String,
+    pub message: String,
+}
+
+/// Extensions a lesson file may have
+pub const LESSON_EXTENSIONS: &[&str] = &["yaml", "yml", "json", "toml"];
+
+/// Whether a lesson file is YAML (otherwise JSON or TOML)
+pub fn is_yaml(path: &Path) -> bool {
+    path.extension().map(|e| e == "yaml" || e == "yml").unwrap_or(false)
+}
+
+/// Whether a lesson file is TOML
+pub fn is_toml(path: &Path) -> bool {
+    path.extension().map(|e| e == "toml").unwrap_or(false)
+}
+
+/// Whether a file name looks like a lesson: `lesson.yaml` or `<name>.lesson.json`
+pub fn is_lesson_file(name: &str) -> bool {
+    let Some(stem) = LESSON_EXTENSIONS.iter().find_map(|ext| name.strip_suffix(ext)?.strip_suffix('.')) else {
+        return false;
+    };
+    stem == "lesson" || stem.ends_with(".lesson")
+}
+
+/// Find lesson files under `root`, at most `max_depth` directories down.
+///
+/// Hidden directories and anything matched by `.gitignore`, `.ignore` or
+/// `.shellignore` files are skipped. Results are sorted by path.
+pub fn discover(root: &Path, max_depth: usize) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = ignore::WalkBuilder::new(root)
+        .max_depth(Some(max_depth + 1))
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter(|entry| entry.file_name().to_str().is_some_and(is_lesson_file))
+        .map(|entry| entry.into_path())
+        .collect();
+    found.sort();
+    found
+}
+
+/// Order dotted version strings numerically, e.g. `1.10.0` > `1.9.2`.
+/// Non-numeric parts compare as text.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut left = a.trim_start_matches('v').split('.');
+    let mut right = b.trim_start_matches('v').split('.');
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (l, r) => {
+                let (l, r) = (l.unwrap_or("0"), r.unwrap_or("0"));
+                let order = match (l.parse::<u64>(), r.parse::<u64>()) {
+                    (Ok(l), Ok(r)) => l.cmp(&r),
+                    _ => l.cmp(r),
+                };
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+        }
+    }
+}
+
+/// Parse a lesson file into an untyped document
+pub fn parse_document(path: &Path, content: &str) -> Result<Value> {
+    if is_yaml(path) {
+        serde_yaml::from_str(content).map_err(|e| ShellError::Lesson(format!("Invalid YAML: {}", e)))
+    } else if is_toml(path) {
+        toml::from_str(content).map_err(|e| ShellError::Lesson(format!("Invalid TOML: {}", e)))
+    } else {
+        serde_json::from_str(content).map_err(|e| ShellError::Lesson(format!("Invalid JSON: {}", e)))
+    }
+}
+
+/// Upgrade a lesson document to the current format and deserialize it.
+/// Fields this build doesn't know are ignored, so lessons written for a
+/// newer minor format still load.
+pub fn migrate(doc: Value) -> Result<Lesson> {
+    let (lesson, unknown) = deserialize_lesson(upgrade(doc)?)?;
+    if !unknown.is_empty() {
+        tracing::debug!("Ignoring unknown lesson fields: {}", unknown.join(", "));
+    }
+    Ok(lesson)
+}
+
+/// Like `migrate`, but unknown fields are an error, to catch typos while authoring
+pub fn migrate_strict(doc: Value) -> Result<Lesson> {
+    let (lesson, unknown) = deserialize_lesson(upgrade(doc)?)?;
+    if !unknown.is_empty() {
+        return Err(ShellError::Lesson(format!("Unknown lesson fields: {}", unknown.join(", "))));
+    }
+    Ok(lesson)
+}
+
+/// Fields of an upgraded lesson document that `Lesson` has no place for
+pub fn unknown_fields(doc: &Value) -> Vec<ValidationError> {
+    match deserialize_lesson(doc.clone()) {
+        Ok((_, unknown)) => unknown.iter().map(|path| error(path, "Unknown field")).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Deserialize a current-format document, with the paths of ignored fields
+fn deserialize_lesson(doc: Value) -> Result<(Lesson, Vec<String>)> {
+    let mut unknown = Vec::new();
+    let lesson = serde_ignored::deserialize(doc, |path| unknown.push(field_path(&path)))
+        .map_err(|e| ShellError::Lesson(format!("Invalid lesson: {}", e)))?;
+    Ok((lesson, unknown))
+}
+
+/// `ValidationError`-style path, e.g. `grading.local_tests[0].points`
+fn field_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{}]", field_path(parent), index),
+        Path::Map { parent, key } => match field_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+/// Rewrite a lesson document from an older format into the current one
+pub fn upgrade(mut doc: Value) -> Result<Value> {
+    let version = doc.get("version").and_then(Value::as_str).unwrap_or_default().to_string();
+    let major = version.split('.').next()
+        .and_then(|major| major.trim_start_matches('v').parse::<u64>().ok())
+        .ok_or_else(|| ShellError::Lesson(format!("Unsupported lesson version '{}'", version)))?;
+
+    if major > FORMAT_VERSION {
+        return Err(ShellError::Lesson(format!(
+            "Lesson version {} is newer than this version of Shell supports (up to {}.x). Please update Shell.",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    if major == 0 {
+        upgrade_v0(&mut doc);
+        doc["version"] = Value::from(format!("{}.0.0", FORMAT_VERSION));
+    }
+
+    Ok(doc)
+}
+
+/// 0.x lessons used `grading.tests`, `content.starter` and `output_type`,
+/// allowed capitalized difficulties and let tests omit `hidden`
+fn upgrade_v0(doc: &mut Value) {
+    if let Some(difficulty) = doc.get("difficulty").and_then(Value::as_str) {
+        doc["difficulty"] = Value::from(difficulty.to_lowercase());
+    }
+
+    if let Some(content) = doc.get_mut("content").and_then(Value::as_object_mut) {
+        rename(content, "starter", "starter_code");
+    }
+
+    if let Some(grading) = doc.get_mut("grading").and_then(Value::as_object_mut) {
+        rename(grading, "tests", "local_tests");
+
+        for list in ["local_tests", "hidden_tests"] {
+            let hidden = list == "hidden_tests";
+            let tests = grading.get_mut(list).and_then(Value::as_array_mut);
+            for test in tests.into_iter().flatten().filter_map(Value::as_object_mut) {
+                rename(test, "output_type", "comparison");
+                test.entry("hidden").or_insert(Value::Bool(hidden));
+            }
+        }
+    }
+}
+
+fn rename(object: &mut serde_json::Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = object.remove(from) {
+        object.entry(to).or_insert(value);
+    }
+}
+
+/// Skeleton lesson for authors to fill in, with one example test and a rubric stub
+pub fn template(language: &str, difficulty: Difficulty, with_io_diagram: bool) -> Result<Lesson> {
+    let (language, starter, solution) = starter_code(language)?;
+
+    let io_diagram = with_io_diagram.then(|| IoDiagram {
+        inputs: vec![IoExample {
+            label: "Input".into(),
+            value: "(none)".into(),
+            description: Some("Describe what the program reads".into()),
+        }],
+        outputs: vec![IoExample {
+            label: "Console Output".into(),
+            value: "Hello, World!".into(),
+            description: Some("Describe what the program prints".into()),
+        }],
+    });
+
+    Ok(Lesson {
+        id: format!("{}-new-lesson", language),
+        version: format!("{}.0.0", FORMAT_VERSION),
+        title: "New Lesson".into(),
+        description: "Describe what students will learn".into(),
+        author: None,
+        language: language.into(),
+        entry_point: None,
+        run: None,
+        dependencies: vec![],
+        difficulty,
+        tags: vec![],
+        prerequisites: vec![],
+        content: LessonContent {
+            explanation: "# New Lesson\n\nExplain the concept here, then describe the task.\n".into(),
+            starter_code: Some(starter.into()),
+            solution: Some(solution.into()),
+            io_diagram,
+            hints: vec!["Add a hint that nudges students in the right direction".into()],
+        },
+        constraints: None,
+        grading: Some(GradingConfig {
+            local_tests: vec![TestCase {
+                id: "prints-greeting".into(),
+                name: "Prints the greeting".into(),
+                input: String::new(),
+                expected_output: "Hello, World!".into(),
+                points: 100.0,
+                hidden: false,
+                comparison: OutputMatch::default(),
+                timeout_ms: None,
+                max_memory_bytes: None,
+                command: None,
+                check: TestCheck::default(),
+            }],
+            hidden_tests: None,
+            rubric: Some(vec![RubricItem {
+                id: "readability".into(),
+                name: "Readability".into(),
+                description: "Code is clear and well named".into(),
+                points: 0.0,
+                criteria: vec!["Replace with your own criteria".into()],
+            }]),
+            auto_grade: None,
+            passing_score: None,
+            hint_penalty: None,
+            total_points: None,
+        }),
+    })
+}
+
+/// Canonical language name with starter and solution code for the template
+fn starter_code(language: &str) -> Result<(&'static str, &'static str, &'static str)> {
+    let code = match language.to_lowercase().as_str() {
+        "python" | "py" => ("python", "# Write your code here\n", "print(\"Hello, World!\")\n"),
+        "javascript" | "js" | "node" => ("javascript", "// Write your code here\n", "console.log(\"Hello, World!\");\n"),
+        "typescript" | "ts" => ("typescript", "// Write your code here\n", "console.log(\"Hello, World!\");\n"),
+        "rust" | "rs" => (
+            "rust",
+            "fn main() {\n    // Write your code here\n}\n",
+            "fn main() {\n    println!(\"Hello, World!\");\n}\n",
+        ),
+        "go" | "golang" => (
+            "go",
+            "package main\n\nfunc main() {\n\t// Write your code here\n}\n",
+            "package main\n\nimport \"fmt\"\n\nfunc main() {\n\tfmt.Println(\"Hello, World!\")\n}\n",
+        ),
+        "java" => (
+            "java",
+            "public class Main {\n    public static void main(String[] args) {\n        // Write your code here\n    }\n}\n",
+            "public class Main {\n    public static void main(String[] args) {\n        System.out.println(\"Hello, World!\");\n    }\n}\n",
+        ),
+        "c" => (
+            "c",
+            "#include <stdio.h>\n\nint main(void) {\n    // Write your code here\n    return 0;\n}\n",
+            "#include <stdio.h>\n\nint main(void) {\n    printf(\"Hello, World!\\n\");\n    return 0;\n}\n",
+        ),
+        "cpp" | "c++" => (
+            "cpp",
+            "#include <iostream>\n\nint main() {\n    // Write your code here\n    return 0;\n}\n",
+            "#include <iostream>\n\nint main() {\n    std::cout << \"Hello, World!\" << std::endl;\n    return 0;\n}\n",
+        ),
+        "ruby" | "rb" => ("ruby", "# Write your code here\n", "puts \"Hello, World!\"\n"),
+        _ => return Err(ShellError::Lesson(format!("No lesson template for language: {}", language))),
+    };
+    Ok(code)
+}
+
+/// Strip raw HTML and script-capable links from lesson Markdown.
+///
+/// Fenced code blocks and inline code spans are left untouched, so an
+/// example like `#include <stdio.h>` survives while a `<script>` in prose
+/// does not.
+pub fn sanitize_markdown(md: &str) -> String {
+    let mut out = String::with_capacity(md.len());
+    let mut prose = String::new();
+    let mut fence: Option<&str> = None;
+
+    for line in md.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        match fence {
+            Some(marker) => {
+                out.push_str(line);
+                if trimmed.starts_with(marker) {
+                    fence = None;
+                }
+            }
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                out.push_str(&sanitize_prose(&prose));
+                prose.clear();
+                fence = Some(&trimmed[..3]);
+                out.push_str(line);
+            }
+            None => prose.push_str(line),
+        }
+    }
+
+    out.push_str(&sanitize_prose(&prose));
+    out
+}
+
+/// Sanitize the learner-facing text of a lesson in place
+pub fn sanitize_lesson(lesson: &mut Lesson) {
+    lesson.title = sanitize_markdown(&lesson.title);
+    lesson.description = sanitize_markdown(&lesson.description);
+    lesson.content.explanation = sanitize_markdown(&lesson.content.explanation);
+    for hint in &mut lesson.content.hints {
+        *hint = sanitize_markdown(hint);
+    }
+    if let Some(diagram) = &mut lesson.content.io_diagram {
+        for example in diagram.inputs.iter_mut().chain(diagram.outputs.iter_mut()) {
+            example.label = sanitize_markdown(&example.label);
+            example.value = sanitize_markdown(&example.value);
+            example.description = example.description.as_deref().map(sanitize_markdown);
+        }
+    }
+}
+
+/// Where first-party lessons ship, under the app's resource directory
+pub const BUILT_IN_LESSONS_DIR: &str = "lessons";
+
+/// Whether `path` is a first-party lesson shipped in `resource_dir`, whose
+/// Markdown is trusted. Anything else, including imported bundles, isn't.
+pub fn is_built_in(path: &Path, resource_dir: &Path) -> bool {
+    let Ok(built_in) = resource_dir.join(BUILT_IN_LESSONS_DIR).canonicalize() else {
+        return false;
+    };
+    path.canonicalize().is_ok_and(|path| path.starts_with(built_in))
+}
+
+/// Sanitize text outside fenced blocks, skipping inline code spans.
+///
+/// A run of N backticks only opens a span if a run of exactly N backticks
+/// closes it; otherwise the backticks are literal, as in CommonMark.
+fn sanitize_prose(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut pending = 0;
+    let mut pos = 0;
+
+    while let Some(offset) = text[pos..].find('`') {
+        let start = pos + offset;
+        let run = backtick_run(&text[start..]);
+        let close = find_run(&text[start + run..], run).map(|i| start + run + i);
+        match close {
+            Some(close) => {
+                out.push_str(&sanitize_html(&text[pending..start]));
+                out.push_str(&text[start..close + run]);
+                pending = close + run;
+                pos = pending;
+            }
+            None => pos = start + run,
+        }
+    }
+
+    out.push_str(&sanitize_html(&text[pending..]));
+    out
+}
+
+fn backtick_run(text: &str) -> usize {
+    text.bytes().take_while(|&b| b == b'`').count()
+}
+
+/// Offset of the next backtick run of exactly `len`
+fn find_run(text: &str, len: usize) -> Option<usize> {
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find('`') {
+        let start = pos + offset;
+        let run = backtick_run(&text[start..]);
+        if run == len {
+            return Some(start);
+        }
+        pos = start + run;
+    }
+    None
+}
+
+fn sanitize_html(text: &str) -> String {
+    static BLOCK_RE: OnceLock<Regex> = OnceLock::new();
+    static COMMENT_RE: OnceLock<Regex> = OnceLock::new();
+    static TAG_RE: OnceLock<Regex> = OnceLock::new();
+    static AUTOLINK_RE: OnceLock<Regex> = OnceLock::new();
+    static LINK_RE: OnceLock<Regex> = OnceLock::new();
+    static REFERENCE_RE: OnceLock<Regex> = OnceLock::new();
+
+    // Elements whose content is never meant to be shown as text
+    let block = BLOCK_RE.get_or_init(|| {
+        Regex::new(r"(?is)<(?:script|style|iframe|object|embed|noscript|template)\b.*?</(?:script|style|iframe|object|embed|noscript|template)\s*>")
+            .expect("valid block regex")
+    });
+    let comment = COMMENT_RE.get_or_init(|| Regex::new(r"(?s)<!--.*?(?:-->|$)").expect("valid comment regex"));
+    // Autolinks like `<https://…>` have no whitespace after the scheme, so they don't match
+    let tag = TAG_RE.get_or_init(|| {
+        Regex::new(r"(?s)</?[A-Za-z][A-Za-z0-9-]*(?:[\s/][^>]*)?>").expect("valid tag regex")
+    });
+    let autolink = AUTOLINK_RE.get_or_init(|| Regex::new(r"<([^<>\s]+)>").expect("valid autolink regex"));
+    let link = LINK_RE.get_or_init(|| Regex::new(r"(\]\(\s*)(<[^>]*>|[^)\s]*)").expect("valid link regex"));
+    let reference = REFERENCE_RE.get_or_init(|| {
+        Regex::new(r"(?m)^(\s{0,3}\[[^\]]+\]:\s*)(<[^>]*>|\S+)").expect("valid reference regex")
+    });
+
+    let text = block.replace_all(text, "");
+    let text = comment.replace_all(&text, "");
+    let text = tag.replace_all(&text, "");
+    let text = autolink.replace_all(&text, |caps: &Captures| {
+        if is_dangerous_url(&caps[1]) { String::new() } else { caps[0].to_string() }
+    });
+    let text = link.replace_all(&text, |caps: &Captures| {
+        if is_dangerous_url(&caps[2]) { format!("{}#", &caps[1]) } else { caps[0].to_string() }
+    });
+    let text = reference.replace_all(&text, |caps: &Captures| {
+        if is_dangerous_url(&caps[2]) { format!("{}#", &caps[1]) } else { caps[0].to_string() }
+    });
+    text.into_owned()
+}
+
+/// Whether a link target uses a scheme that can run script.
+///
+/// Renderers decode entities and ignore embedded whitespace in URLs, so the
+/// scheme is checked after doing the same.
+fn is_dangerous_url(url: &str) -> bool {
+    let url = decode_entities(url.trim_start_matches('<').trim_end_matches('>'));
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_lowercase();
+
+    if url.starts_with("data:") {
+        let raster = ["data:image/png", "data:image/jpeg", "data:image/gif", "data:image/webp"];
+        return !raster.iter().any(|prefix| url.starts_with(prefix));
+    }
+    url.starts_with("javascript:") || url.starts_with("vbscript:")
+}
+
+fn decode_entities(text: &str) -> String {
+    static ENTITY_RE: OnceLock<Regex> = OnceLock::new();
+    let entity = ENTITY_RE.get_or_init(|| {
+        Regex::new(r"(?i)&#x([0-9a-f]+);?|&#([0-9]+);?|&(colon|tab|newline);").expect("valid entity regex")
+    });
+
+    entity
+        .replace_all(text, |caps: &Captures| {
+            let code = if let Some(hex) = caps.get(1) {
+                u32::from_str_radix(hex.as_str(), 16).ok()
+            } else if let Some(dec) = caps.get(2) {
+                dec.as_str().parse().ok()
+            } else {
+                match caps[3].to_lowercase().as_str() {
+                    "colon" => Some(':' as u32),
+                    _ => Some(' ' as u32),
+                }
+            };
+            code.and_then(char::from_u32).map(String::from).unwrap_or_default()
+        })
+        .into_owned()
+}
+
+/// Check a lesson document against the lesson format
+pub fn validate_document(doc: &Value) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    let Some(lesson) = doc.as_object() else {
+        errors.push(error("", "lesson must be an object"));
+        return errors;
+    };
+
+    require_fields(doc, "", LESSON_FIELDS, &mut errors);
+    non_empty_string(doc, "id", &mut errors);
+    non_empty_string(doc, "title", &mut errors);
+
+    if let Some(difficulty) = lesson.get("difficulty").and_then(Value::as_str) {
+        if !DIFFICULTIES.contains(&difficulty) {
+            let message = if DIFFICULTIES.contains(&difficulty.to_lowercase().as_str()) {
+                format!("must be lowercase (got '{}')", difficulty)
+            } else {
+                format!("must be one of {} (got '{}')", DIFFICULTIES.join(", "), difficulty)
+            };
+            errors.push(error("difficulty", &message));
+        }
+    }
+
+    if let Some(content) = lesson.get("content") {
+        require_fields(content, "content", &["explanation", "hints"], &mut errors);
+    }
+
+    if let Some(grading) = lesson.get("grading").filter(|g| !g.is_null()) {
+        require_fields(grading, "grading", &["local_tests"], &mut errors);
+
+        let mut ids = HashSet::new();
+        for list in ["local_tests", "hidden_tests"] {
+            for (i, test) in array(grading, list).iter().enumerate() {
+                let path = format!("grading.{}[{}]", list, i);
+                require_fields(test, &path, TEST_FIELDS, &mut errors);
+                non_empty_string(test, &format!("{}.id", path), &mut errors);
+                non_negative(test, &format!("{}.points", path), &mut errors);
+
+                if let Some(id) = test.get("id").and_then(Value::as_str).filter(|id| !id.is_empty()) {
+                    if !ids.insert(id) {
+                        errors.push(error(&format!("{}.id", path), &format!("duplicate test id '{}'", id)));
+                    }
+                }
+            }
+        }
+
+        for (i, item) in array(grading, "rubric").iter().enumerate() {
+            non_negative(item, &format!("grading.rubric[{}].points", i), &mut errors);
+        }
+    }
+
+    errors
+}
+
+fn error(path: &str, message: &str) -> ValidationError {
+    ValidationError { path: path.to_string(), message: message.to_string() }
+}
+
+/// Last segment of a dotted path, used to look the field up on its parent
+fn field_name(path: &str) -> &str {
+    path.rsplit('.').next().unwrap_or(path)
+}
+
+fn array<'a>(value: &'a Value, field: &str) -> &'a [Value] {
+    value.get(field).and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn require_fields(value: &Value, path: &str, fields: &[&str], errors: &mut Vec<ValidationError>) {
+    for field in fields {
+        if !matches!(value.get(field), Some(v) if !v.is_null()) {
+            let full = if path.is_empty() { field.to_string() } else { format!("{}.{}", path, field) };
+            errors.push(error(&full, "is required"));
+        }
+    }
+}
+
+fn non_empty_string(parent: &Value, path: &str, errors: &mut Vec<ValidationError>) {
+    if let Some(text) = parent.get(field_name(path)).and_then(Value::as_str) {
+        if text.trim().is_empty() {
+            errors.push(error(path, "must not be empty"));
+        }
+    }
+}
+
+fn non_negative(parent: &Value, path: &str, errors: &mut Vec<ValidationError>) {
+    if let Some(number) = parent.get(field_name(path)).and_then(Value::as_f64) {
+        if number < 0.0 {
+            errors.push(error(path, &format!("must not be negative (got {})", number)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LESSON: &str = r#"
+id: hello
+version: "1.0.0"
+title: Hello
+description: ""
+language: python
+difficulty: beginner
+tags: []
+prerequisites: []
+content:
+  explanation: Print a greeting
+  hints: []
+grading:
+  local_tests:
+    - { id: greet, name: Greet, input: "", expected_output: "hi", points: 10, hidden: false }
+  hidden_tests:
+    - { id: secret, name: Secret, input: "", expected_output: "hi", points: 5, hidden: true }
+  rubric:
+    - { id: style, name: Style, description: "", points: 5, criteria: [] }
+"#;
+
+    fn validate(edit: impl FnOnce(&mut Value)) -> Vec<ValidationError> {
+        let mut doc: Value = serde_yaml::from_str(LESSON).unwrap();
+        edit(&mut doc);
+        validate_document(&doc)
+    }
+
+    fn only(errors: Vec<ValidationError>) -> (String, String) {
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        let e = errors.into_iter().next().unwrap();
+        (e.path, e.message)
+    }
+
+    #[test]
+    fn test_valid_lesson_has_no_errors() {
+        assert!(validate(|_| {}).is_empty());
+    }
+
+    #[test]
+    fn test_missing_required_fields() {
+        let errors = validate(|doc| {
+            doc.as_object_mut().unwrap().remove("language");
+            doc["content"].as_object_mut().unwrap().remove("explanation");
+        });
+        assert_eq!(errors, vec![error("language", "is required"), error("content.explanation", "is required")]);
+    }
+
+    #[test]
+    fn test_difficulty_enum() {
+        let (path, message) = only(validate(|doc| doc["difficulty"] = "Beginner".into()));
+        assert_eq!((path.as_str(), message.as_str()), ("difficulty", "must be lowercase (got 'Beginner')"));
+
+        let (_, message) = only(validate(|doc| doc["difficulty"] = "expert".into()));
+        assert_eq!(message, "must be one of beginner, intermediate, advanced (got 'expert')");
+    }
+
+    #[test]
+    fn test_empty_title_and_test_id() {
+        let (path, message) = only(validate(|doc| doc["title"] = " ".into()));
+        assert_eq!((path.as_str(), message.as_str()), ("title", "must not be empty"));
+
+        let (path, message) = only(validate(|doc| doc["grading"]["local_tests"][0]["id"] = "".into()));
+        assert_eq!((path.as_str(), message.as_str()), ("grading.local_tests[0].id", "must not be empty"));
+    }
+
+    #[test]
+    fn test_negative_points() {
+        let (path, message) = only(validate(|doc| doc["grading"]["hidden_tests"][0]["points"] = (-5).into()));
+        assert_eq!((path.as_str(), message.as_str()), ("grading.hidden_tests[0].points", "must not be negative (got -5)"));
+
+        let (path, _) = only(validate(|doc| doc["grading"]["rubric"][0]["points"] = (-1.5).into()));
+        assert_eq!(path, "grading.rubric[0].points");
+    }
+
+    #[test]
+    fn test_migrate_v0_lesson() {
+        let doc: Value = serde_yaml::from_str(include_str!("../tests/fixtures/lesson-v0.yaml")).unwrap();
+
+        let upgraded = upgrade(doc.clone()).unwrap();
+        assert!(validate_document(&upgraded).is_empty());
+
+        let lesson = migrate(doc).unwrap();
+        assert_eq!(lesson.version, "1.0.0");
+        assert_eq!(lesson.content.starter_code.as_deref(), Some("# your code here\n"));
+        let grading = lesson.grading.unwrap();
+        assert_eq!(grading.local_tests.len(), 2);
+        assert_eq!(grading.local_tests[0].comparison, crate::commands::lessons::OutputMatch::Exact);
+        assert!(!grading.local_tests[1].hidden);
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let doc = lesson_doc(|doc| doc["version"] = "2.1.0".into());
+        let err = migrate(doc).unwrap_err().to_string();
+        assert!(err.contains("newer than this version of Shell supports"), "{}", err);
+
+        let doc = lesson_doc(|doc| doc["version"] = "latest".into());
+        assert!(migrate(doc).unwrap_err().to_string().contains("Unsupported lesson version 'latest'"));
+    }
+
+    #[test]
+    fn test_unknown_fields_by_mode() {
+        let doc = lesson_doc(|doc| {
+            doc["estimated_minutes"] = 20.into();
+            doc["grading"]["local_tests"][0]["pionts"] = 3.into();
+        });
+
+        // Lenient loading keeps working for lessons from newer authoring tools
+        assert_eq!(migrate(doc.clone()).unwrap().id, "hello");
+
+        let err = migrate_strict(doc.clone()).unwrap_err().to_string();
+        assert!(err.contains("estimated_minutes, grading.local_tests[0].pionts"), "{}", err);
+        let errors = unknown_fields(&doc);
+        assert_eq!(errors, vec![
+            error("estimated_minutes", "Unknown field"),
+            error("grading.local_tests[0].pionts", "Unknown field"),
+        ]);
+
+        assert!(migrate_strict(lesson_doc(|_| {})).is_ok());
+        assert!(unknown_fields(&lesson_doc(|_| {})).is_empty());
+    }
+
+    fn lesson_doc(edit: impl FnOnce(&mut Value)) -> Value {
+        let mut doc: Value = serde_yaml::from_str(LESSON).unwrap();
+        edit(&mut doc);
+        doc
+    }
+
+    #[test]
+    fn test_templates_are_valid() {
+        for language in ["python", "js", "typescript", "rust", "go", "java", "c", "cpp", "ruby"] {
+            let lesson = template(language, Difficulty::Beginner, true).unwrap();
+            let yaml = serde_yaml::to_string(&lesson).unwrap();
+            let doc: Value = serde_yaml::from_str(&yaml).unwrap();
+
+            assert!(validate_document(&doc).is_empty(), "{}: {:?}", language, validate_document(&doc));
+            assert!(migrate(doc).unwrap().content.io_diagram.is_some());
+        }
+
+        assert_eq!(template("js", Difficulty::Advanced, false).unwrap().language, "javascript");
+        assert!(template("cobol", Difficulty::Beginner, false).is_err());
+    }
+
+    #[test]
+    fn test_discover_nested_lessons() {
+        let root = std::env::temp_dir().join(format!("shell-discover-{}", uuid::Uuid::new_v4()));
+        for dir in ["unit1/topic", "unit2", "drafts", ".hidden", "a/b/c/d/e/f/g/h/i"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "lesson.yaml",
+            "unit1/topic/lesson.yaml",
+            "unit2/loops.lesson.json",
+            "unit2/notes.yaml",
+            "drafts/lesson.yaml",
+            ".hidden/lesson.yaml",
+            "a/b/c/d/e/f/g/h/i/lesson.yaml",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        std::fs::write(root.join(".shellignore"), "drafts/\n").unwrap();
+
+        let found: Vec<_> = discover(&root, MAX_DISCOVERY_DEPTH).iter()
+            .map(|p| p.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect();
+
+        assert_eq!(found, vec!["lesson.yaml", "unit1/topic/lesson.yaml", "unit2/loops.lesson.json"]);
+        assert_eq!(discover(&root, 0).len(), 1);
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.9.0", "v1.0.0"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_duplicate_test_ids() {
+        let (path, message) = only(validate(|doc| doc["grading"]["hidden_tests"][0]["id"] = "greet".into()));
+        assert_eq!((path.as_str(), message.as_str()), ("grading.hidden_tests[0].id", "duplicate test id 'greet'"));
+    }
+
+    #[test]
+    fn test_sanitize_removes_script() {
+        let md = "# Intro\n\nHello <script>alert('x')</script>world <img src=x onerror=alert(1)>\n";
+        assert_eq!(sanitize_markdown(md), "# Intro\n\nHello world \n");
+    }
+
+    #[test]
+    fn test_sanitize_neutralizes_script_links() {
+        let md = "[a](javascript:alert(1)) [b](JaVa&#x53;cript:alert(1)) <javascript:alert(1)>\n\n[c]: vbscript:msgbox\n";
+        let clean = sanitize_markdown(md);
+        assert!(!clean.to_lowercase().contains("script:"), "{}", clean);
+        assert!(clean.starts_with("[a](#)"));
+        assert!(clean.contains("[c]: #"));
+    }
+
+    #[test]
+    fn test_sanitize_keeps_safe_markdown_and_code() {
+        let md = "Use **bold** and [docs](https://example.com) or <https://example.com>.\n\n\
+                  Compare `a < b` and ``<b>``.\n\n```c\n#include <stdio.h>\n<script>kept</script>\n```\n";
+        assert_eq!(sanitize_markdown(md), md);
+    }
+
+    #[test]
+    fn test_sanitize_unclosed_code_span() {
+        assert_eq!(sanitize_markdown("`<script>x</script>``"), "```");
+    }
+
+    #[test]
+    fn test_sanitize_lesson_covers_all_text() {
+        let script = "<script>alert(1)</script>";
+        let mut lesson = template("python", Difficulty::Beginner, true).unwrap();
+        lesson.title = format!("Loops{}", script);
+        lesson.description = format!("Count{}", script);
+        let diagram = lesson.content.io_diagram.as_mut().unwrap();
+        diagram.inputs[0].label = format!("n{}", script);
+        diagram.outputs[0].description = Some(format!("total{}", script));
+
+        sanitize_lesson(&mut lesson);
+        let diagram = lesson.content.io_diagram.as_ref().unwrap();
+        assert_eq!((lesson.title.as_str(), lesson.description.as_str()), ("Loops", "Count"));
+        assert_eq!(diagram.inputs[0].label, "n");
+        assert_eq!(diagram.outputs[0].description.as_deref(), Some("total"));
+    }
+
+    #[test]
+    fn test_only_shipped_lessons_are_built_in() {
+        let root = std::env::temp_dir().join(format!("shell-resources-{}", uuid::Uuid::new_v4()));
+        let shipped = root.join(BUILT_IN_LESSONS_DIR).join("intro");
+        let imported = root.join("imported").join("intro");
+        for dir in [&shipped, &imported] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("lesson.yaml"), "").unwrap();
+        }
+
+        assert!(is_built_in(&shipped.join("lesson.yaml"), &root));
+        assert!(!is_built_in(&imported.join("lesson.yaml"), &root));
+        // Escaping the lessons directory doesn't count
+        assert!(!is_built_in(&shipped.join("../../imported/intro/lesson.yaml"), &root));
+        assert!(!is_built_in(&shipped.join("lesson.yaml"), &root.join("missing")));
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,326 @@
+//! Lint-on-demand
+//!
+//! Runs a language's linter over a project in the sandbox and normalizes
+//! its report into `Diagnostic`s. Linters exit non-zero when they find
+//! problems, so the exit code alone doesn't mean the run failed.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use crate::commands::execution::get_language_image;
+use crate::docker::{ExecutionBackend, ExecutionRequest, ExecutionResult};
+use crate::error::{Result, ShellError};
+
+/// Exit code the lint script uses when the linter isn't in the image
+const NOT_INSTALLED: i64 = 127;
+
+/// Linters compile (clippy) or walk whole projects, so allow longer than a run
+const LINT_TIMEOUT_SECS: u64 = 120;
+
+/// Where the project is mounted in the container
+const WORKSPACE: &str = "/workspace";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// One problem reported by a linter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Path relative to the project
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub severity: Severity,
+    /// Rule or lint name, e.g. `F401` or `clippy::needless_return`
+    pub code: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ruff,
+    Eslint,
+    Clippy,
+    GoVet,
+}
+
+/// A linter and how to run it in a language's image
+#[derive(Debug, Clone, PartialEq)]
+struct Linter {
+    name: &'static str,
+    /// Shell command that succeeds when the linter is available
+    probe: &'static str,
+    command: &'static str,
+    format: Format,
+    installation_instructions: &'static str,
+}
+
+fn linter_for(language: &str) -> Result<Linter> {
+    Ok(match language.to_lowercase().as_str() {
+        "python" | "py" => Linter {
+            name: "ruff",
+            probe: "ruff --version",
+            // The project is mounted read-only, so no cache
+            command: "ruff check --no-cache --output-format=json .",
+            format: Format::Ruff,
+            installation_instructions: "Add ruff to the Python sandbox image (pip install ruff)",
+        },
+        "javascript" | "js" | "typescript" | "ts" => Linter {
+            name: "ESLint",
+            probe: "npx --no-install eslint --version",
+            command: "npx --no-install eslint --format json .",
+            format: Format::Eslint,
+            installation_instructions: "Add ESLint to the project (npm install --save-dev eslint)",
+        },
+        "rust" | "rs" => Linter {
+            name: "Clippy",
+            probe: "cargo clippy --version",
+            command: "cargo clippy --offline --quiet --message-format=json",
+            format: Format::Clippy,
+            installation_instructions: "Add Clippy to the Rust sandbox image (rustup component add clippy)",
+        },
+        "go" | "golang" => Linter {
+            name: "go vet",
+            probe: "go version",
+            command: "go vet ./...",
+            format: Format::GoVet,
+            installation_instructions: "Use a Go sandbox image that includes the Go toolchain",
+        },
+        _ => return Err(ShellError::Service(format!("No linter for: {}", language))),
+    })
+}
+
+/// Lint the project at `project_path`
+pub async fn lint(language: &str, project_path: &str, backend: &dyn ExecutionBackend) -> Result<Vec<Diagnostic>> {
+    let linter = linter_for(language)?;
+    let script = format!("{} >/dev/null 2>&1 || exit {}; exec {}", linter.probe, NOT_INSTALLED, linter.command);
+    let request = ExecutionRequest {
+        id: uuid::Uuid::new_v4().to_string(),
+        image: get_language_image(language)?,
+        command: vec!["sh".into(), "-c".into(), script],
+        working_dir: WORKSPACE.to_string(),
+        source_path: project_path.to_string(),
+        // Build output can't go in the read-only project
+        env: HashMap::from([("CARGO_TARGET_DIR".to_string(), "/tmp/target".to_string())]),
+        memory_limit: None,
+        cpu_quota: None,
+        timeout: Some(LINT_TIMEOUT_SECS),
+        step_mode: false,
+        trace_io: false,
+        stdin: None,
+        compile_command: None,
+        raw_output: false,
+        workspace_writable: false,
+        scratch_workspace: false,
+    };
+    let result = backend.execute(request).await?;
+    diagnostics(&linter, &result)
+}
+
+/// Diagnostics from a finished lint run. A non-zero exit with findings is
+/// a successful lint; without findings it's a linter failure.
+fn diagnostics(linter: &Linter, result: &ExecutionResult) -> Result<Vec<Diagnostic>> {
+    if result.exit_code == NOT_INSTALLED {
+        return Err(ShellError::Service(format!(
+            "Linter '{}' not installed. {}", linter.name, linter.installation_instructions
+        )));
+    }
+    if result.timed_out {
+        return Err(ShellError::Service(format!("{} timed out", linter.name)));
+    }
+
+    let diagnostics = match linter.format {
+        Format::Ruff => parse_ruff(&result.stdout),
+        Format::Eslint => parse_eslint(&result.stdout),
+        Format::Clippy => parse_clippy(&result.stdout),
+        Format::GoVet => parse_go_vet(&result.stderr),
+    };
+    if diagnostics.is_empty() && result.exit_code != 0 {
+        return Err(ShellError::Service(format!("{} failed: {}", linter.name, result.stderr.trim())));
+    }
+    Ok(diagnostics)
+}
+
+/// Project-relative form of a path the linter printed
+fn relative_path(path: &str) -> String {
+    let path = path.strip_prefix(WORKSPACE).unwrap_or(path);
+    path.trim_start_matches('/').trim_start_matches("./").to_string()
+}
+
+fn as_u32(value: &Value) -> u32 {
+    value.as_u64().unwrap_or(0) as u32
+}
+
+/// `ruff check --output-format=json`: an array of findings
+fn parse_ruff(output: &str) -> Vec<Diagnostic> {
+    let findings: Vec<Value> = serde_json::from_str(output).unwrap_or_default();
+    findings.iter().map(|finding| {
+        let code = finding["code"].as_str().map(str::to_string);
+        // Syntax errors have no rule code; E9xx rules are also fatal
+        let severity = match &code {
+            None => Severity::Error,
+            Some(code) if code.starts_with("E9") => Severity::Error,
+            Some(_) => Severity::Warning,
+        };
+        Diagnostic {
+            file: relative_path(finding["filename"].as_str().unwrap_or_default()),
+            line: as_u32(&finding["location"]["row"]),
+            column: as_u32(&finding["location"]["column"]),
+            severity,
+            code,
+            message: finding["message"].as_str().unwrap_or_default().to_string(),
+        }
+    }).collect()
+}
+
+/// `eslint --format json`: per-file results holding messages
+fn parse_eslint(output: &str) -> Vec<Diagnostic> {
+    let files: Vec<Value> = serde_json::from_str(output).unwrap_or_default();
+    files.iter().flat_map(|file| {
+        let path = relative_path(file["filePath"].as_str().unwrap_or_default());
+        file["messages"].as_array().cloned().unwrap_or_default().into_iter().map(move |message| Diagnostic {
+            file: path.clone(),
+            line: as_u32(&message["line"]),
+            column: as_u32(&message["column"]),
+            severity: if message["severity"].as_u64() == Some(2) { Severity::Error } else { Severity::Warning },
+            code: message["ruleId"].as_str().map(str::to_string),
+            message: message["message"].as_str().unwrap_or_default().to_string(),
+        })
+    }).collect()
+}
+
+/// `cargo clippy --message-format=json`: one JSON object per line
+fn parse_clippy(output: &str) -> Vec<Diagnostic> {
+    output.lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|record| record["reason"] == "compiler-message")
+        .filter_map(|record| {
+            let message = &record["message"];
+            let span = message["spans"].as_array()?.iter().find(|s| s["is_primary"] == true)?;
+            let severity = match message["level"].as_str()? {
+                "error" | "error: internal compiler error" => Severity::Error,
+                "warning" => Severity::Warning,
+                _ => Severity::Info,
+            };
+            Some(Diagnostic {
+                file: relative_path(span["file_name"].as_str()?),
+                line: as_u32(&span["line_start"]),
+                column: as_u32(&span["column_start"]),
+                severity,
+                code: message["code"]["code"].as_str().map(str::to_string),
+                message: message["message"].as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `go vet`: `file:line:col: message` lines on stderr, between `# package` headers
+fn parse_go_vet(output: &str) -> Vec<Diagnostic> {
+    output.lines().filter_map(|line| {
+        let mut parts = line.splitn(4, ':');
+        let (file, line_no, column, message) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        Some(Diagnostic {
+            file: relative_path(file),
+            line: line_no.trim().parse().ok()?,
+            column: column.trim().parse().ok()?,
+            severity: Severity::Warning,
+            code: None,
+            message: message.trim().to_string(),
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::ExecutionPhase;
+
+    const RUFF_OUTPUT: &str = r#"[
+        {"code": "F401", "filename": "/workspace/app/main.py", "location": {"row": 1, "column": 8},
+         "end_location": {"row": 1, "column": 10}, "fix": null, "message": "`os` imported but unused", "noqa_row": 1,
+         "url": "https://docs.astral.sh/ruff/rules/unused-import"},
+        {"code": null, "filename": "/workspace/broken.py", "location": {"row": 3, "column": 5},
+         "end_location": {"row": 3, "column": 6}, "fix": null, "message": "SyntaxError: Expected ':'", "noqa_row": null,
+         "url": null}
+    ]"#;
+
+    #[test]
+    fn test_parse_ruff() {
+        assert_eq!(parse_ruff(RUFF_OUTPUT), vec![
+            Diagnostic {
+                file: "app/main.py".into(), line: 1, column: 8, severity: Severity::Warning,
+                code: Some("F401".into()), message: "`os` imported but unused".into(),
+            },
+            Diagnostic {
+                file: "broken.py".into(), line: 3, column: 5, severity: Severity::Error,
+                code: None, message: "SyntaxError: Expected ':'".into(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_clippy_and_go_vet() {
+        let clippy = concat!(
+            r#"{"reason":"compiler-artifact","target":{"name":"demo"}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"message":"unneeded `return` statement","code":{"code":"clippy::needless_return"},"level":"warning","#,
+            r#""spans":[{"file_name":"src/main.rs","line_start":3,"column_start":5,"is_primary":true}]}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"message":"1 warning emitted","code":null,"level":"warning","spans":[]}}"#,
+        );
+        assert_eq!(parse_clippy(clippy), vec![Diagnostic {
+            file: "src/main.rs".into(), line: 3, column: 5, severity: Severity::Warning,
+            code: Some("clippy::needless_return".into()), message: "unneeded `return` statement".into(),
+        }]);
+
+        let vet = "# example.com/demo\n./main.go:7:2: fmt.Printf format %d has arg s of wrong type string\n";
+        assert_eq!(parse_go_vet(vet)[0].file, "main.go");
+        assert_eq!((parse_go_vet(vet)[0].line, parse_go_vet(vet)[0].column), (7, 2));
+    }
+
+    #[test]
+    fn test_linters_match_language_registry() {
+        for spec in crate::languages::LANGUAGES {
+            assert_eq!(linter_for(spec.id).ok().map(|l| l.name), spec.linter, "{}", spec.id);
+        }
+    }
+
+    /// Replies with a canned lint run
+    struct LintBackend(i64, &'static str);
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for LintBackend {
+        async fn execute(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+            Ok(ExecutionResult {
+                id: request.id,
+                exit_code: self.0,
+                stdout: self.1.to_string(),
+                stderr: "ruff: error".into(),
+                duration_ms: 1,
+                timed_out: false,
+                trace: None,
+                phase: ExecutionPhase::Run,
+                compile_output: None,
+                tests_passed: None,
+                stdout_base64: None,
+                stderr_base64: None,
+                log_collection_timed_out: false,
+                output_truncated: false,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exit_codes() {
+        // Findings make ruff exit 1; that's still a successful lint
+        assert_eq!(lint("python", "/tmp", &LintBackend(1, RUFF_OUTPUT)).await.unwrap().len(), 2);
+        assert!(lint("python", "/tmp", &LintBackend(0, "[]")).await.unwrap().is_empty());
+
+        let missing = lint("python", "/tmp", &LintBackend(NOT_INSTALLED, "")).await.unwrap_err();
+        assert!(missing.to_string().contains("pip install ruff"), "{}", missing);
+        assert!(lint("python", "/tmp", &LintBackend(2, "")).await.is_err());
+    }
+}
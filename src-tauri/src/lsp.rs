@@ -0,0 +1,205 @@
+//! Language Server Protocol bridge
+//!
+//! A language server runs as a `ServiceManager` process speaking JSON-RPC
+//! over stdio, framed the same way as DAP. The UI drives the protocol; the
+//! bridge only adds the configured initialization options to the first
+//! `initialize` request and pushes settings changes with
+//! `workspace/didChangeConfiguration`.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufReader, Write};
+use std::process::ChildStdin;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use crate::dap::{encode_message, read_message};
+use crate::error::{Result, ShellError};
+use crate::services::{ServiceConfig, ServiceManager};
+
+/// Something a language server produced
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspEvent {
+    /// A response, notification or server request
+    Message(Value),
+    /// The server exited
+    Closed,
+}
+
+/// Receives a server's service id and what it produced
+pub type LspListener = Arc<dyn Fn(&str, LspEvent) + Send + Sync>;
+
+/// A running server's stdin and the options its `initialize` still needs
+struct Connection {
+    stdin: ChildStdin,
+    initialize_options: Option<Value>,
+}
+
+/// Add `options` to an `initialize` request as `initializationOptions`,
+/// unless the UI set its own. Returns whether `message` was an `initialize`.
+pub fn with_initialize_options(message: &mut Value, options: &Value) -> bool {
+    if message.get("method").and_then(Value::as_str) != Some("initialize") {
+        return false;
+    }
+    if let Some(params) = message.get_mut("params").and_then(Value::as_object_mut) {
+        let current = params.entry("initializationOptions").or_insert(Value::Null);
+        if current.is_null() {
+            *current = options.clone();
+        }
+    }
+    true
+}
+
+/// `workspace/didChangeConfiguration` notification carrying `settings`
+pub fn did_change_configuration(settings: Value) -> Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "workspace/didChangeConfiguration",
+        "params": { "settings": settings },
+    })
+}
+
+/// Running language servers, keyed by service id
+#[derive(Default)]
+pub struct LspSessions {
+    connections: Mutex<HashMap<String, Connection>>,
+}
+
+impl LspSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start the server in `config` and relay its messages to `listener`.
+    /// `initialize_options` go into the first `initialize` the UI sends.
+    /// Does nothing if the server is already running.
+    pub async fn start(
+        &self,
+        services: &ServiceManager,
+        config: ServiceConfig,
+        initialize_options: Option<Value>,
+        listener: LspListener,
+    ) -> Result<()> {
+        let service_id = config.id.clone();
+        let mut connections = self.connections.lock().await;
+        if connections.contains_key(&service_id) && services.is_running(&service_id).await {
+            return Ok(());
+        }
+
+        services.register(config).await;
+        services.start(&service_id).await?;
+        let (stdin, stdout) = services.take_stdio(&service_id).await
+            .ok_or_else(|| ShellError::Service("Language server has no stdio".into()))?;
+
+        let id = service_id.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut reader = BufReader::new(stdout);
+            loop {
+                match read_message(&mut reader) {
+                    Ok(Some(message)) => listener(&id, LspEvent::Message(message)),
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::warn!("Dropping language server {}: {}", id, e);
+                        break;
+                    }
+                }
+            }
+            listener(&id, LspEvent::Closed);
+        });
+
+        connections.insert(service_id, Connection { stdin, initialize_options });
+        Ok(())
+    }
+
+    /// Send a JSON-RPC message to a server
+    pub async fn send(&self, service_id: &str, mut message: Value) -> Result<()> {
+        let mut connections = self.connections.lock().await;
+        let connection = connections.get_mut(service_id)
+            .ok_or_else(|| ShellError::Service(format!("Language server {} is not running", service_id)))?;
+
+        // Only the first initialize gets the options
+        if let Some(options) = &connection.initialize_options {
+            if with_initialize_options(&mut message, options) {
+                connection.initialize_options = None;
+            }
+        }
+        connection.stdin.write_all(&encode_message(&message))?;
+        connection.stdin.flush()?;
+        Ok(())
+    }
+
+    /// Push new workspace settings to a server
+    pub async fn change_configuration(&self, service_id: &str, settings: Value) -> Result<()> {
+        self.send(service_id, did_change_configuration(settings)).await
+    }
+
+    /// Stop a server
+    pub async fn stop(&self, services: &ServiceManager, service_id: &str) -> Result<()> {
+        self.connections.lock().await.remove(service_id);
+        services.stop(service_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ServiceType;
+    use serde_json::json;
+
+    #[test]
+    fn test_initialize_options_fill_only_missing() {
+        let options = json!({"cargo": {"features": ["serde"]}});
+
+        let mut initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"rootUri": "file:///p"}});
+        assert!(with_initialize_options(&mut initialize, &options));
+        assert_eq!(initialize["params"]["initializationOptions"], options);
+
+        let own = json!({"cargo": {"features": "all"}});
+        let mut initialize = json!({"id": 1, "method": "initialize", "params": {"initializationOptions": own}});
+        with_initialize_options(&mut initialize, &options);
+        assert_eq!(initialize["params"]["initializationOptions"], own);
+
+        let mut hover = json!({"id": 2, "method": "textDocument/hover", "params": {}});
+        assert!(!with_initialize_options(&mut hover, &options));
+        assert!(hover["params"].get("initializationOptions").is_none());
+    }
+
+    /// `cat` echoes every framed message, standing in for a server
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_options_reach_outgoing_initialize() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let listener: LspListener = Arc::new(move |_: &str, event| {
+            let _ = sender.send(event);
+        });
+        let services = ServiceManager::new();
+        let sessions = LspSessions::new();
+        let config = ServiceConfig {
+            id: "lsp-python".into(),
+            service_type: ServiceType::Lsp("python".into()),
+            command: "cat".into(),
+            args: vec![],
+            working_dir: Some(std::env::temp_dir()),
+            env: HashMap::new(),
+        };
+        let options = json!({"python": {"analysis": {"typeCheckingMode": "strict"}}});
+        sessions.start(&services, config, Some(options.clone()), listener).await.unwrap();
+
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}});
+        sessions.send("lsp-python", initialize.clone()).await.unwrap();
+        let Some(LspEvent::Message(sent)) = receiver.recv().await else { panic!("no initialize echoed") };
+        assert_eq!(sent["params"]["initializationOptions"], options);
+        assert_eq!(sent["params"]["capabilities"], json!({}));
+
+        // A second initialize goes through untouched
+        sessions.send("lsp-python", initialize.clone()).await.unwrap();
+        assert_eq!(receiver.recv().await, Some(LspEvent::Message(initialize)));
+
+        let settings = json!({"python": {"analysis": {"typeCheckingMode": "basic"}}});
+        sessions.change_configuration("lsp-python", settings.clone()).await.unwrap();
+        assert_eq!(receiver.recv().await, Some(LspEvent::Message(did_change_configuration(settings))));
+
+        sessions.stop(&services, "lsp-python").await.unwrap();
+        assert_eq!(receiver.recv().await, Some(LspEvent::Closed));
+        assert!(sessions.send("lsp-python", json!({})).await.is_err());
+    }
+}
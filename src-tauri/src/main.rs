@@ -10,17 +10,43 @@
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod analytics;
+mod autosave;
+mod bundle;
+mod classroom;
+mod cloud;
+mod coalesce;
 mod commands;
+mod coverage;
+mod dap;
 mod db;
+mod dependencies;
 mod docker;
 mod error;
 mod features;
+mod file_index;
+mod format;
 mod fs;
+mod git;
+mod grading;
+mod languages;
+mod lessons;
+mod lint;
+mod lsp;
+mod operations;
+mod plagiarism;
+mod projects;
+mod search;
+mod seats;
 mod security;
 mod services;
+mod session;
+mod shutdown;
+mod snapshot;
+mod terminal;
 
 use std::sync::{Arc, RwLock};
-use tauri::Manager;
+use tauri::{Emitter, Manager, RunEvent};
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -58,54 +84,233 @@ fn main() {
             // Initialize feature flags with RwLock for thread-safe read/write
             let features = features::FeatureFlags::load(&app_data);
             app.manage(RwLock::new(features));
+            // One license change at a time, including the periodic seat check
+            app.manage(commands::features::LicenseLock::default());
 
-            // Initialize shared security policy (cached, not recreated per request)
-            let security_policy = Arc::new(security::SecurityPolicy::default());
+            // Re-check license expiry and seat while the app stays open
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(features::LICENSE_CHECK_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Ok(mut flags) = handle.state::<RwLock<features::FeatureFlags>>().write() {
+                        flags.enforce_expiry_at(chrono::Utc::now());
+                    }
+                    if let Err(e) = commands::features::refresh_seat(&handle).await {
+                        tracing::warn!("Seat check failed: {}", e);
+                    }
+                }
+            });
+
+            // Initialize shared security policy (cached, not recreated per request),
+            // keeping the app's own data out of containers
+            let mut security_policy = security::SecurityPolicy::default();
+            security_policy.protected_paths.push(app_data.clone());
+            let security_policy = Arc::new(security_policy);
+            // An exam in progress survives restarts
+            if let Err(e) = commands::exam::resume(&app.state::<db::Database>(), &security_policy) {
+                tracing::warn!("Failed to resume exam: {}", e);
+            }
             app.manage(security_policy);
 
-            // Initialize Docker manager (lazy connection)
-            let docker = docker::DockerManager::new();
+            // File contents shared across reads, invalidated on change
+            app.manage(Arc::new(fs::FileCache::default()));
+
+            // Debounced auto-saves, one pending write per file
+            app.manage(Arc::new(autosave::AutoSaver::new()));
+
+            // Initialize Docker manager (lazy connection), forwarding run
+            // lifecycle to the UI and checking every mount against the policy
+            let handle = app.handle().clone();
+            let policy = Arc::clone(app.state::<Arc<security::SecurityPolicy>>().inner());
+            let docker = docker::DockerManager::new()
+                .with_listener(Arc::new(move |event| {
+                    let _ = handle.emit(&event.name(), event);
+                }))
+                .with_policy(policy);
             app.manage(docker);
 
             // Initialize services manager
             let services = services::ServiceManager::new();
             app.manage(services);
 
+            // Interactive terminal sessions
+            app.manage(terminal::TerminalManager::new());
+
+            // Debug adapter sessions
+            app.manage(dap::DapSessions::new());
+
+            // Language server connections
+            app.manage(lsp::LspSessions::new());
+
+            // Buffered analytics events, stored only with the user's opt-in
+            app.manage(analytics::AnalyticsSink::new());
+
+            // Long-running commands the UI can cancel
+            app.manage(operations::OperationRegistry::new());
+
+            // Identical keyed runs share the container already in flight
+            app.manage(coalesce::RunCoalescer::new());
+
+            // Quick-open file indexes, built per project on demand
+            app.manage(file_index::FileIndexes::new());
+
             info!("Shell IDE initialized successfully");
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // Filesystem commands
             commands::fs::read_project,
+            commands::fs::register_project,
+            commands::fs::list_projects,
+            commands::session::save_session,
+            commands::session::load_session,
+            commands::fs::read_file,
+            commands::fs::read_file_range,
             commands::fs::write_file,
+            commands::fs::schedule_autosave,
             commands::fs::create_file,
             commands::fs::delete_file,
+            commands::fs::rename_file,
             commands::fs::list_directory,
+            commands::fs::get_file_info,
             commands::fs::watch_directory,
+            commands::fs::unwatch_directory,
+            commands::fs::list_watched_directories,
             commands::fs::create_directory,
+            commands::search::search_project,
+            commands::search::read_tree,
+            commands::search::build_file_index,
+            commands::search::query_file_index,
+            commands::search::cancel_operation,
+            commands::git::git_status,
+            commands::git::git_diff,
+            commands::git::git_stage,
+            commands::git::git_unstage,
+            commands::git::git_commit,
+            commands::git::git_init,
+            // Snapshots
+            commands::snapshot::create_snapshot,
+            commands::snapshot::list_snapshots,
+            commands::snapshot::restore_snapshot,
             // Lesson commands
             commands::lessons::load_lesson,
             commands::lessons::save_lesson,
             commands::lessons::list_lessons,
+            commands::lessons::get_lesson_summary,
             commands::lessons::validate_lesson,
+            commands::lessons::export_lesson,
+            commands::lessons::import_lesson,
+            commands::lessons::create_lesson_template,
+            commands::lessons::check_prerequisites,
+            commands::lessons::get_lesson_progress,
+            commands::lessons::list_lesson_progress,
+            commands::lessons::reveal_hint,
             // Execution commands
             commands::execution::run_code,
+            commands::execution::validate_run_request,
+            commands::execution::check_toolchain,
+            commands::execution::list_supported_languages,
+            commands::execution::get_supported_languages,
+            commands::execution::benchmark_code,
+            commands::execution::run_with_coverage,
+            commands::execution::prepare_language,
+            commands::execution::prepare_languages,
             commands::execution::stop_execution,
             commands::execution::get_execution_status,
+            commands::execution::get_execution_output,
+            commands::execution::docker_info,
+            // Exam mode
+            commands::exam::enter_exam_mode,
+            commands::exam::exit_exam_mode,
+            commands::exam::get_exam_session,
             // LSP commands
             commands::lsp::start_language_server,
             commands::lsp::stop_language_server,
+            commands::lsp::lsp_send,
+            commands::lsp::lsp_change_configuration,
             commands::lsp::get_available_servers,
+            commands::lsp::set_lsp_config,
+            commands::lsp::get_lsp_config,
+            commands::lsp::list_lsp_configs,
+            // Formatting and linting
+            commands::format::format_code,
+            commands::lint::lint_code,
+            commands::dependencies::install_dependencies,
             // Grading commands
             commands::grading::run_local_tests,
             commands::grading::submit_for_grading,
+            commands::grading::apply_rubric,
+            commands::grading::compare_submissions_diff,
+            // Plagiarism detection
+            commands::plagiarism::compare_submissions,
+            commands::plagiarism::detect_similar,
             // Feature flags
             commands::features::get_feature_flags,
             commands::features::is_teacher_mode,
+            commands::features::activate_license,
+            commands::features::deactivate_license,
+            commands::features::release_seat,
             // Settings
             commands::settings::get_settings,
             commands::settings::update_settings,
+            commands::settings::reset_settings,
+            commands::settings::reset_settings_field,
+            commands::settings::export_settings,
+            commands::settings::import_settings,
+            commands::settings::get_keybindings,
+            commands::settings::update_keybindings,
+            commands::settings::reset_keybindings,
+            commands::settings::get_effective_settings,
+            commands::settings::update_project_settings,
+            // Terminal
+            commands::terminal::terminal_open,
+            commands::terminal::terminal_write,
+            commands::terminal::terminal_resize,
+            commands::terminal::terminal_close,
+            // Debugger
+            commands::dap::dap_start,
+            commands::dap::dap_send,
+            commands::dap::dap_stop,
+            // Classrooms
+            commands::classroom::create_classroom,
+            commands::classroom::list_classrooms,
+            commands::classroom::add_student,
+            commands::classroom::remove_student,
+            commands::classroom::list_students,
+            commands::classroom::assign_submission,
+            commands::classroom::classroom_progress,
+            // Analytics
+            commands::analytics::get_local_analytics,
+            commands::analytics::get_analytics_opt_in,
+            commands::analytics::set_analytics_opt_in,
+            commands::analytics::purge_analytics,
+            commands::analytics::get_telemetry_status,
+            commands::analytics::set_telemetry_enabled,
+            commands::analytics::export_telemetry_log,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running shell ide");
+        .build(tauri::generate_context!())
+        .expect("error while building shell ide")
+        .run(|app, event| {
+            // Don't leave terminal containers running after exit
+            if let RunEvent::Exit = event {
+                let terminals = app.state::<terminal::TerminalManager>();
+                let docker = app.state::<docker::DockerManager>();
+                tauri::async_runtime::block_on(terminals.close_all(&docker));
+
+                // Nor runs and services, so student code doesn't outlive the IDE
+                let services = app.state::<services::ServiceManager>();
+                let report = tauri::async_runtime::block_on(
+                    shutdown::shutdown(&*docker, &services, shutdown::SHUTDOWN_TIMEOUT),
+                );
+                if !report.timed_out {
+                    info!("Stopped {} running execution(s) on exit", report.executions_stopped.len());
+                }
+
+                // Keep events still in the buffer
+                if let Err(e) = app.state::<analytics::AnalyticsSink>().flush(&app.state::<db::Database>()) {
+                    tracing::warn!("Failed to save analytics events: {}", e);
+                }
+            }
+        });
 }
@@ -0,0 +1,119 @@
+//! Cancellable operations
+//!
+//! Long-running commands take an `operation_id` from the UI and register a
+//! token under it; `cancel_operation` trips the token and the command's loop
+//! stops at its next check.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use crate::error::{Result, ShellError};
+
+/// Shared flag an operation polls to see whether it should stop
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Error with `cancelled` once the token is tripped
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(ShellError::Execution("cancelled".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Tokens of running operations by id
+#[derive(Default)]
+pub struct OperationRegistry {
+    tokens: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new operation. Its token is dropped from the registry
+    /// when the returned guard goes out of scope.
+    pub fn register(&self, operation_id: Option<String>) -> Result<OperationGuard<'_>> {
+        let token = CancellationToken::new();
+        if let Some(id) = &operation_id {
+            let mut tokens = self.lock();
+            if tokens.contains_key(id) {
+                return Err(ShellError::Execution(format!("Operation {} is already running", id)));
+            }
+            tokens.insert(id.clone(), token.clone());
+        }
+        Ok(OperationGuard { registry: self, operation_id, token })
+    }
+
+    /// Trip the token of a running operation. Returns false if none is
+    /// registered under `operation_id`, e.g. because it already finished.
+    pub fn cancel(&self, operation_id: &str) -> bool {
+        match self.lock().get(operation_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, CancellationToken>> {
+        self.tokens.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A registered operation's token, unregistered on drop
+pub struct OperationGuard<'a> {
+    registry: &'a OperationRegistry,
+    operation_id: Option<String>,
+    token: CancellationToken,
+}
+
+impl OperationGuard<'_> {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = &self.operation_id {
+            self.registry.lock().remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_trips_registered_token_until_finished() {
+        let registry = OperationRegistry::new();
+        let guard = registry.register(Some("search-1".into())).unwrap();
+        assert!(guard.token().check().is_ok());
+        assert!(registry.register(Some("search-1".into())).is_err());
+
+        assert!(registry.cancel("search-1"));
+        assert_eq!(guard.token().check().unwrap_err().to_string(), "Execution error: cancelled");
+
+        drop(guard);
+        assert!(!registry.cancel("search-1"));
+        assert!(registry.register(Some("search-1".into())).is_ok());
+    }
+}
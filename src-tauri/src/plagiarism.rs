@@ -0,0 +1,307 @@
+//! Similarity between submissions
+//!
+//! Code is reduced to a token stream with comments and whitespace dropped
+//! and identifiers, literals and numbers normalized, so renaming variables
+//! or reformatting doesn't hide copying. Token k-grams are hashed and
+//! winnowed into fingerprints (Schleimer et al., 2003); any shared run of
+//! at least `K + WINDOW - 1` tokens is guaranteed to share a fingerprint.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Tokens per k-gram
+const K: usize = 5;
+/// K-grams per winnowing window
+const WINDOW: usize = 4;
+
+/// Kept as-is; every other identifier becomes `ID`
+const KEYWORDS: &[&str] = &[
+    "and", "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def", "default",
+    "do", "elif", "else", "enum", "except", "false", "False", "finally", "fn", "for", "func", "function",
+    "if", "impl", "import", "in", "is", "let", "loop", "match", "mut", "new", "not", "null", "None", "or",
+    "pub", "return", "self", "static", "struct", "switch", "this", "throw", "true", "True", "try", "var",
+    "void", "while", "with", "yield",
+];
+
+/// Inclusive, 1-based line span
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A stretch of code that appears in both submissions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchedFragment {
+    pub a: LineRange,
+    pub b: LineRange,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimilarityReport {
+    /// Share of fingerprints in common, from 0 to 1
+    pub similarity: f64,
+    pub matches: Vec<MatchedFragment>,
+}
+
+/// Two stored submissions whose similarity reached the threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimilarPair {
+    pub first: String,
+    pub second: String,
+    pub report: SimilarityReport,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: String,
+    line: usize,
+}
+
+/// Normalized tokens of `source`. `//`, `#` and `/* */` comments are all
+/// dropped, which also drops C preprocessor lines.
+fn tokenize(source: &str) -> Vec<Token> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start_line = line;
+
+        if c.is_whitespace() {
+            line += usize::from(c == '\n');
+            i += 1;
+        } else if c == '#' || (c == '/' && next == Some('/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                line += usize::from(chars[i] == '\n');
+                i += 1;
+            }
+            i += 2;
+        } else if matches!(c, '"' | '\'' | '`') {
+            let triple = next == Some(c) && chars.get(i + 2) == Some(&c);
+            let quote_len = if triple { 3 } else { 1 };
+            i += quote_len;
+            while i < chars.len() {
+                if chars[i] == '\\' {
+                    i += 2;
+                    continue;
+                }
+                if (0..quote_len).all(|k| chars.get(i + k) == Some(&c)) {
+                    i += quote_len;
+                    break;
+                }
+                if chars[i] == '\n' && !triple && c != '`' {
+                    break;
+                }
+                line += usize::from(chars[i] == '\n');
+                i += 1;
+            }
+            tokens.push(Token { kind: "STR".into(), line: start_line });
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let kind = if KEYWORDS.contains(&word.as_str()) { word } else { "ID".into() };
+            tokens.push(Token { kind, line: start_line });
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token { kind: "NUM".into(), line: start_line });
+        } else {
+            tokens.push(Token { kind: c.to_string(), line: start_line });
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// Winnowed k-gram hashes with the token index each k-gram starts at
+fn fingerprints(tokens: &[Token]) -> Vec<(u64, usize)> {
+    let hashes: Vec<u64> = tokens.windows(K).map(|gram| {
+        let mut hasher = DefaultHasher::new();
+        for token in gram {
+            token.kind.hash(&mut hasher);
+        }
+        hasher.finish()
+    }).collect();
+
+    // Short inputs get a single, shorter window
+    let window_count = if hashes.len() < WINDOW { hashes.len().min(1) } else { hashes.len() - WINDOW + 1 };
+    let mut selected: Vec<(u64, usize)> = Vec::new();
+    for start in 0..window_count {
+        let window = &hashes[start..(start + WINDOW).min(hashes.len())];
+        // Rightmost minimum, so runs of equal hashes select one fingerprint
+        let (offset, &hash) = window.iter().enumerate().rev().min_by_key(|(_, &h)| h).expect("non-empty window");
+        if selected.last().map(|&(_, pos)| pos) != Some(start + offset) {
+            selected.push((hash, start + offset));
+        }
+    }
+    selected
+}
+
+/// A submission prepared for comparison
+struct Fingerprinted {
+    tokens: Vec<Token>,
+    prints: Vec<(u64, usize)>,
+}
+
+impl Fingerprinted {
+    fn new(source: &str) -> Self {
+        let tokens = tokenize(source);
+        let prints = fingerprints(&tokens);
+        Self { tokens, prints }
+    }
+
+    fn lines(&self, start: usize, end: usize) -> LineRange {
+        LineRange { start: self.tokens[start].line, end: self.tokens[end - 1].line }
+    }
+}
+
+fn compare_fingerprinted(a: &Fingerprinted, b: &Fingerprinted) -> SimilarityReport {
+    let hashes_a: HashSet<u64> = a.prints.iter().map(|&(h, _)| h).collect();
+    let mut first_in_b: HashMap<u64, usize> = HashMap::new();
+    for &(hash, pos) in &b.prints {
+        first_in_b.entry(hash).or_insert(pos);
+    }
+
+    let total = hashes_a.len() + first_in_b.len();
+    let shared = hashes_a.iter().filter(|h| first_in_b.contains_key(h)).count();
+    let similarity = if total == 0 { 0.0 } else { 2.0 * shared as f64 / total as f64 };
+
+    // Merge overlapping k-grams into fragments, as token ranges [start, end)
+    let mut fragments: Vec<((usize, usize), (usize, usize))> = Vec::new();
+    for &(hash, pos_a) in &a.prints {
+        let Some(&pos_b) = first_in_b.get(&hash) else { continue };
+        match fragments.last_mut() {
+            Some(((_, end_a), (start_b, end_b))) if pos_a <= *end_a && pos_b >= *start_b && pos_b <= *end_b => {
+                *end_a = (*end_a).max(pos_a + K);
+                *end_b = (*end_b).max(pos_b + K);
+            }
+            _ => fragments.push(((pos_a, pos_a + K), (pos_b, pos_b + K))),
+        }
+    }
+
+    let matches = fragments.into_iter()
+        .map(|((start_a, end_a), (start_b, end_b))| MatchedFragment {
+            a: a.lines(start_a, end_a),
+            b: b.lines(start_b, end_b),
+        })
+        .collect();
+    SimilarityReport { similarity, matches }
+}
+
+/// How similar two pieces of code are, with the fragments they share
+pub fn compare(a: &str, b: &str) -> SimilarityReport {
+    compare_fingerprinted(&Fingerprinted::new(a), &Fingerprinted::new(b))
+}
+
+/// Every pair among `submissions` (id, code) at or above `threshold`,
+/// most similar first
+pub fn detect_similar(submissions: &[(String, String)], threshold: f64) -> Vec<SimilarPair> {
+    let prepared: Vec<Fingerprinted> = submissions.iter().map(|(_, code)| Fingerprinted::new(code)).collect();
+    let mut pairs = Vec::new();
+    for i in 0..prepared.len() {
+        for j in i + 1..prepared.len() {
+            let report = compare_fingerprinted(&prepared[i], &prepared[j]);
+            if report.similarity >= threshold {
+                pairs.push(SimilarPair {
+                    first: submissions[i].0.clone(),
+                    second: submissions[j].0.clone(),
+                    report,
+                });
+            }
+        }
+    }
+    pairs.sort_by(|x, y| y.report.similarity.total_cmp(&x.report.similarity));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = r#"def average(numbers):
+    # Sum everything up
+    total = 0
+    for n in numbers:
+        total = total + n
+    return total / len(numbers)
+
+print(average([1, 2, 3]))
+"#;
+
+    /// Renamed, recommented and reformatted
+    const PARAPHRASED: &str = r#"def mean(values):
+    """Mean of a list"""
+    acc = 0
+
+    for v in values:   # each value
+        acc = acc + v
+    return acc / len(values)
+
+
+print(mean([4, 5, 6, 7]))
+"#;
+
+    const UNRELATED: &str = r#"class Stack:
+    def __init__(self):
+        self.items = []
+
+    def push(self, item):
+        self.items.append(item)
+
+    def pop(self):
+        if not self.items:
+            raise IndexError("empty")
+        return self.items.pop()
+"#;
+
+    #[test]
+    fn test_identical_pair() {
+        let report = compare(ORIGINAL, ORIGINAL);
+        assert_eq!(report.similarity, 1.0);
+        assert_eq!(report.matches, vec![MatchedFragment {
+            a: LineRange { start: 1, end: 8 },
+            b: LineRange { start: 1, end: 8 },
+        }]);
+    }
+
+    #[test]
+    fn test_paraphrased_pair() {
+        let report = compare(ORIGINAL, PARAPHRASED);
+        assert!(report.similarity > 0.8, "{}", report.similarity);
+        // The docstring breaks up the header; everything after it matches
+        assert_eq!(report.matches, vec![MatchedFragment {
+            a: LineRange { start: 3, end: 8 },
+            b: LineRange { start: 3, end: 10 },
+        }]);
+    }
+
+    #[test]
+    fn test_unrelated_pair() {
+        let report = compare(ORIGINAL, UNRELATED);
+        assert!(report.similarity < 0.2, "{}", report.similarity);
+    }
+
+    #[test]
+    fn test_detect_similar_filters_by_threshold() {
+        let submissions = [("s1", ORIGINAL), ("s2", UNRELATED), ("s3", PARAPHRASED)]
+            .map(|(id, code)| (id.to_string(), code.to_string()));
+        let pairs = detect_similar(&submissions, 0.8);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].first.as_str(), pairs[0].second.as_str()), ("s1", "s3"));
+    }
+}
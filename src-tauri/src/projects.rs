@@ -0,0 +1,49 @@
+//! Projects the user has opened
+//!
+//! Each project directory is registered once, keyed by its canonical path,
+//! so per-project settings and history attach to a stable id.
+
+use std::path::Path;
+use crate::db::{Database, Project};
+use crate::error::{Result, ShellError};
+use crate::fs::ProjectInfo;
+use crate::security::generate_secure_id;
+
+/// Register the project at `path`, or refresh it if it's already known.
+/// Returns the stored row.
+pub fn register(db: &Database, path: &Path) -> Result<Project> {
+    let canonical = path.canonicalize()?;
+    let info = ProjectInfo::detect(&canonical)?;
+    let canonical = canonical.to_string_lossy().into_owned();
+
+    let id = match db.get_project_by_path(&canonical)? {
+        Some(existing) => existing.id,
+        None => generate_secure_id(),
+    };
+    db.register_project(&id, &info.name, &canonical, info.language.as_deref())?;
+    db.get_project_by_path(&canonical)?
+        .ok_or_else(|| ShellError::Configuration(format!("Project {} was not saved", canonical)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reopening_a_project_keeps_one_row() {
+        let root = std::env::temp_dir().join(format!("shell-project-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        let db = Database::in_memory().unwrap();
+
+        let first = register(&db, &root).unwrap();
+        assert_eq!(first.language.as_deref(), Some("rust"));
+        // Another spelling of the same directory
+        let second = register(&db, &root.join("src").join("..")).unwrap();
+
+        assert_eq!(first.id, second.id);
+        assert_eq!(first.created_at, second.created_at);
+        assert_eq!(db.list_projects().unwrap().len(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+//! Project search and tree walks
+//!
+//! Both walk the project the way snapshots and lesson discovery do, skipping
+//! hidden files and anything ignored, and stop as soon as their cancellation
+//! token is tripped.
+
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::error::{Result, ShellError};
+use crate::lessons::IGNORE_FILE;
+use crate::operations::CancellationToken;
+
+/// Most matches returned when the caller doesn't say
+const DEFAULT_MAX_RESULTS: usize = 1000;
+/// Files larger than this aren't searched
+const MAX_SEARCHED_FILE_SIZE: u64 = 1024 * 1024;
+/// Most entries a tree walk returns
+const MAX_TREE_ENTRIES: usize = 10_000;
+/// Longest line text included with a match
+const MAX_LINE_LENGTH: usize = 300;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchOptions {
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Treat the query as a regular expression rather than literal text
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchMatch {
+    /// Relative to the project root
+    pub path: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub matches: Vec<SearchMatch>,
+    pub files_searched: usize,
+    /// Whether the search stopped at `max_results`
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeEntry {
+    /// Relative to the project root
+    pub path: String,
+    pub is_directory: bool,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileTree {
+    /// Sorted by path
+    pub entries: Vec<TreeEntry>,
+    /// Whether the walk stopped at the entry limit
+    pub truncated: bool,
+}
+
+/// Ignore-aware walk of `root` in file name order
+pub fn walker(root: &Path) -> ignore::Walk {
+    ignore::WalkBuilder::new(root)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+}
+
+/// `path` relative to `root`, with forward slashes
+pub fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+/// Find `query` in the text files under `root`
+pub fn search(root: &Path, query: &str, options: &SearchOptions, token: &CancellationToken) -> Result<SearchResults> {
+    if query.is_empty() {
+        return Err(ShellError::Execution("Search query is empty".into()));
+    }
+    let pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+    let matcher = RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| ShellError::Execution(format!("Invalid search pattern: {}", e)))?;
+    let max_results = options.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+
+    let mut results = SearchResults { matches: Vec::new(), files_searched: 0, truncated: false };
+    for entry in walker(root) {
+        token.check()?;
+        let Ok(entry) = entry else { continue };
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        if entry.metadata().map_or(true, |m| m.len() > MAX_SEARCHED_FILE_SIZE) {
+            continue;
+        }
+        // Binary and non-UTF-8 files are skipped
+        let Ok(content) = std::fs::read_to_string(entry.path()) else { continue };
+        results.files_searched += 1;
+
+        for (index, line) in content.lines().enumerate() {
+            let Some(found) = matcher.find(line) else { continue };
+            if results.matches.len() == max_results {
+                results.truncated = true;
+                return Ok(results);
+            }
+            results.matches.push(SearchMatch {
+                path: relative(root, entry.path()),
+                line: index + 1,
+                column: line[..found.start()].chars().count() + 1,
+                text: line.chars().take(MAX_LINE_LENGTH).collect(),
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// Every file and directory under `root`
+pub fn read_tree(root: &Path, token: &CancellationToken) -> Result<FileTree> {
+    let mut tree = FileTree { entries: Vec::new(), truncated: false };
+    for entry in walker(root) {
+        token.check()?;
+        let Ok(entry) = entry else { continue };
+        if entry.depth() == 0 {
+            continue;
+        }
+        if tree.entries.len() == MAX_TREE_ENTRIES {
+            tree.truncated = true;
+            break;
+        }
+        let is_directory = entry.file_type().is_some_and(|t| t.is_dir());
+        tree.entries.push(TreeEntry {
+            path: relative(root, entry.path()),
+            is_directory,
+            size: if is_directory { 0 } else { entry.metadata().map_or(0, |m| m.len()) },
+        });
+    }
+    tree.entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    fn project(files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("shell-search-{}", uuid::Uuid::new_v4()));
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_search_finds_matches_and_skips_ignored() {
+        let root = project(&[
+            ("main.py", "import os\nprint(Total)\n"),
+            ("src/util.py", "total = 0\n    total += 1\n"),
+            ("build/out.py", "total\n"),
+            (".gitignore", "build/\n"),
+        ]);
+        let token = CancellationToken::new();
+
+        let found = search(&root, "total", &SearchOptions::default(), &token).unwrap();
+        let locations: Vec<_> = found.matches.iter().map(|m| (m.path.as_str(), m.line, m.column)).collect();
+        assert_eq!(locations, vec![("main.py", 2, 7), ("src/util.py", 1, 1), ("src/util.py", 2, 5)]);
+
+        let options = SearchOptions { case_sensitive: true, max_results: Some(1), ..Default::default() };
+        let found = search(&root, "total", &options, &token).unwrap();
+        assert_eq!(found.matches.len(), 1);
+        assert!(found.truncated);
+
+        let options = SearchOptions { regex: true, ..Default::default() };
+        assert_eq!(search(&root, r"total \+=", &options, &token).unwrap().matches.len(), 1);
+        assert!(search(&root, "(", &options, &token).is_err());
+
+        let paths: Vec<_> = read_tree(&root, &token).unwrap().entries.into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["main.py", "src", "src/util.py"]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_cancelled_search_returns_promptly() {
+        let files: Vec<(String, String)> = (0..2000)
+            .map(|i| (format!("dir{}/file{}.txt", i / 50, i), "needle\n".repeat(50)))
+            .collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let root = project(&files);
+        let token = CancellationToken::new();
+
+        let walk = {
+            let (root, token) = (root.clone(), token.clone());
+            let options = SearchOptions { max_results: Some(usize::MAX), ..Default::default() };
+            std::thread::spawn(move || search(&root, "needle", &options, &token))
+        };
+        std::thread::sleep(Duration::from_millis(2));
+        let cancelled_at = Instant::now();
+        token.cancel();
+
+        let err = walk.join().unwrap().unwrap_err();
+        assert!(cancelled_at.elapsed() < Duration::from_secs(1));
+        assert_eq!(err.to_string(), "Execution error: cancelled");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Seat tracking for multi-seat licenses
+//!
+//! Each install registers against one of a license's seats. The license
+//! server is the authority when it can be reached; the last known seat list
+//! is cached in `seats.json` so registered installs keep working offline
+//! for a grace period.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::cloud::CloudClient;
+use crate::error::{Result, ShellError};
+use crate::features::License;
+
+/// Days a seat keeps working without reaching the license server
+pub const SEAT_GRACE_DAYS: i64 = 14;
+
+const LEDGER_FILE: &str = "seats.json";
+
+/// An install registered against a license seat
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeatClaim {
+    pub license_id: String,
+    pub install_id: String,
+    /// Last time the claim was confirmed
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Locally cached seat registrations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatLedger {
+    /// Stable id of this install
+    pub install_id: String,
+    #[serde(default)]
+    pub claims: Vec<SeatClaim>,
+}
+
+impl SeatLedger {
+    pub fn new() -> Self {
+        Self { install_id: uuid::Uuid::new_v4().to_string(), claims: Vec::new() }
+    }
+
+    /// Load the ledger from app data, creating an install id on first use
+    pub fn load(app_data: &Path) -> Self {
+        std::fs::read_to_string(app_data.join(LEDGER_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, app_data: &Path) -> Result<()> {
+        std::fs::write(app_data.join(LEDGER_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Installs currently holding a seat on the license
+    pub fn installs(&self, license_id: &str) -> Vec<&str> {
+        self.claims.iter()
+            .filter(|c| c.license_id == license_id)
+            .map(|c| c.install_id.as_str())
+            .collect()
+    }
+
+    /// Whether this install holds a seat confirmed within the grace period
+    pub fn holds_seat_at(&self, license_id: &str, now: DateTime<Utc>) -> bool {
+        self.claims.iter().any(|c| {
+            c.license_id == license_id
+                && c.install_id == self.install_id
+                && now <= c.checked_at + Duration::days(SEAT_GRACE_DAYS)
+        })
+    }
+
+    /// Register this install without a license server
+    pub fn claim_local(&mut self, license_id: &str, seats: u32, now: DateTime<Utc>) -> Result<()> {
+        let install_id = self.install_id.clone();
+        if let Some(claim) = self.claims.iter_mut()
+            .find(|c| c.license_id == license_id && c.install_id == install_id)
+        {
+            claim.checked_at = now;
+            return Ok(());
+        }
+
+        if self.installs(license_id).len() >= seats as usize {
+            return Err(no_seats(seats));
+        }
+        self.claims.push(SeatClaim { license_id: license_id.to_string(), install_id, checked_at: now });
+        Ok(())
+    }
+
+    /// Replace the cached seat list with the server's
+    pub fn sync(&mut self, license_id: &str, installs: Vec<String>, now: DateTime<Utc>) {
+        self.claims.retain(|c| c.license_id != license_id);
+        self.claims.extend(installs.into_iter().map(|install_id| SeatClaim {
+            license_id: license_id.to_string(),
+            install_id,
+            checked_at: now,
+        }));
+    }
+
+    /// Drop an install's seat. Returns whether it held one.
+    pub fn release(&mut self, license_id: &str, install_id: &str) -> bool {
+        let before = self.claims.len();
+        self.claims.retain(|c| !(c.license_id == license_id && c.install_id == install_id));
+        self.claims.len() != before
+    }
+}
+
+impl Default for SeatLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Register this install against a seat of `license`.
+///
+/// Uses the license server when `endpoint` is set and falls back to the
+/// cached claim while it is unreachable.
+pub async fn claim(ledger: &mut SeatLedger, license: &License, endpoint: Option<&str>, now: DateTime<Utc>) -> Result<()> {
+    let Some(seats) = license.seats else {
+        return Ok(());
+    };
+    let license_id = license.id();
+
+    let (Some(endpoint), Some(token)) = (endpoint, license.token.as_deref()) else {
+        return ledger.claim_local(&license_id, seats, now);
+    };
+
+    match CloudClient::new(endpoint, token)?.claim_seat(&license_id, &ledger.install_id).await {
+        Ok(installs) => {
+            ledger.sync(&license_id, installs, now);
+            Ok(())
+        }
+        Err(ShellError::Network(reason)) if ledger.holds_seat_at(&license_id, now) => {
+            tracing::warn!("License server unreachable, using cached seat: {}", reason);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Give up an install's seat so it can be reassigned
+pub async fn release(ledger: &mut SeatLedger, license: &License, install_id: &str, endpoint: Option<&str>) -> Result<()> {
+    let license_id = license.id();
+    if let (Some(endpoint), Some(token)) = (endpoint, license.token.as_deref()) {
+        CloudClient::new(endpoint, token)?.release_seat(&license_id, install_id).await?;
+    }
+    ledger.release(&license_id, install_id);
+    Ok(())
+}
+
+fn no_seats(seats: u32) -> ShellError {
+    ShellError::FeatureNotAvailable(format!(
+        "All {} seats on this license are in use. Release a seat on another install first.", seats
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::LicenseType;
+
+    fn license() -> License {
+        License {
+            license_type: LicenseType::TeacherInstitution,
+            valid_until: None,
+            seats: Some(2),
+            grace_days: None,
+            token: Some("secret-token".into()),
+            signature: None,
+        }
+    }
+
+    fn ledger(install_id: &str) -> SeatLedger {
+        SeatLedger { install_id: install_id.into(), claims: Vec::new() }
+    }
+
+    #[test]
+    fn test_seat_exhaustion_and_release() {
+        let now = Utc::now();
+        let mut ledger = ledger("c");
+        ledger.sync("lic", vec!["a".into(), "b".into()], now);
+
+        assert!(matches!(ledger.claim_local("lic", 2, now), Err(ShellError::FeatureNotAvailable(_))));
+
+        assert!(ledger.release("lic", "a"));
+        ledger.claim_local("lic", 2, now).unwrap();
+        assert_eq!(ledger.installs("lic"), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_cached_seat_expires_after_grace() {
+        let now = Utc::now();
+        let mut ledger = ledger("a");
+        ledger.claim_local("lic", 1, now).unwrap();
+
+        assert!(ledger.holds_seat_at("lic", now + Duration::days(SEAT_GRACE_DAYS)));
+        assert!(!ledger.holds_seat_at("lic", now + Duration::days(SEAT_GRACE_DAYS + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_server_refuses_when_seats_exhausted() {
+        let mut server = mockito::Server::new_async().await;
+        let license = license();
+        let path = format!("/api/v1/licenses/{}/seats", license.id());
+        server.mock("POST", path.as_str()).with_status(409).create_async().await;
+
+        let err = claim(&mut ledger("c"), &license, Some(&server.url()), Utc::now()).await.unwrap_err();
+        assert!(matches!(err, ShellError::FeatureNotAvailable(_)));
+    }
+
+    #[tokio::test]
+    async fn test_offline_keeps_cached_seat() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let endpoint = format!("http://127.0.0.1:{}", port);
+        let license = license();
+        let now = Utc::now();
+
+        let mut registered = ledger("a");
+        registered.sync(&license.id(), vec!["a".into()], now - Duration::days(1));
+        claim(&mut registered, &license, Some(&endpoint), now).await.unwrap();
+
+        let mut fresh = ledger("b");
+        assert!(matches!(
+            claim(&mut fresh, &license, Some(&endpoint), now).await,
+            Err(ShellError::Network(_))
+        ));
+    }
+}
@@ -7,8 +7,9 @@
 //! - Plugin sandboxing
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 use crate::error::{Result, ShellError};
 
 /// Security policy for Shell IDE
@@ -34,6 +35,131 @@ pub struct SecurityPolicy {
     
     /// Plugin execution policy
     pub plugin_policy: PluginPolicy,
+
+    /// Compiler flags lessons and students may pass. A trailing `*` allows
+    /// any plain suffix (letters, digits, `_=+.-`), e.g. `-std=*`.
+    #[serde(default = "default_compile_flags")]
+    pub allowed_compile_flags: Vec<String>,
+
+    /// Environment variables runs may set
+    #[serde(default)]
+    pub env_policy: EnvPolicy,
+
+    /// Images code may run in. `*` matches any run of characters, e.g. `python:*`.
+    #[serde(default = "default_allowed_images")]
+    pub allowed_images: Vec<String>,
+
+    /// Programs a lesson's custom test command may start, matched like
+    /// `allowed_images`, e.g. `./*` for drivers shipped in the workspace
+    #[serde(default = "default_test_programs")]
+    pub allowed_test_programs: Vec<String>,
+
+    /// The app's own data (database, feature flags, licenses), which is
+    /// never mounted into a container
+    #[serde(default)]
+    pub protected_paths: Vec<PathBuf>,
+
+    /// Exam lockdown, while one is in progress
+    #[serde(skip)]
+    exam: ExamLock,
+}
+
+/// An exam in progress. While active, file access and runs are confined to
+/// `project` and nothing runs on the host outside a container.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExamSession {
+    pub session_id: String,
+    /// Canonical project directory
+    pub project: PathBuf,
+    pub started_at: String,
+}
+
+/// Shared so a policy behind an `Arc` can enter and leave exam mode;
+/// clones take a snapshot
+#[derive(Debug, Default)]
+struct ExamLock(RwLock<Option<ExamSession>>);
+
+impl Clone for ExamLock {
+    fn clone(&self) -> Self {
+        Self(RwLock::new(self.0.read().ok().and_then(|exam| exam.clone())))
+    }
+}
+
+fn default_compile_flags() -> Vec<String> {
+    [
+        "-std=*", "-O0", "-O1", "-O2", "-O3", "-Os", "-g",
+        "-Wall", "-Wextra", "-Werror", "-Wpedantic", "-pedantic", "-Wshadow", "-Wconversion", "-Wno-*",
+        "-D*", "-lm", "-lpthread", "-pthread", "-fsanitize=address", "-fsanitize=undefined",
+    ]
+    .iter().map(|s| s.to_string()).collect()
+}
+
+/// Any tag of the built-in language images, plus installed dependency images
+fn default_allowed_images() -> Vec<String> {
+    ["python:*", "node:*", "rust:*", "golang:*", "eclipse-temurin:*", "gcc:*", "ruby:*", "shell-deps:*"]
+        .iter().map(|s| s.to_string()).collect()
+}
+
+/// The language runtimes, compiled programs and scripts in the workspace;
+/// not shells, which would run anything
+fn default_test_programs() -> Vec<String> {
+    ["python", "python3", "node", "npx", "cargo", "go", "java", "ruby", "/tmp/a.out", "./*"]
+        .iter().map(|s| s.to_string()).collect()
+}
+
+/// Files that mark a directory as app data, wherever it lives
+const APP_DATA_MARKERS: [&str; 2] = ["shell.db", "features.json"];
+
+/// Whether `path` is, names or directly holds the app's data files
+pub fn holds_app_data(path: &Path) -> bool {
+    APP_DATA_MARKERS.iter().any(|marker| {
+        path.iter().any(|component| component == *marker) || path.join(marker).exists()
+    })
+}
+
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => text.strip_prefix(prefix).is_some_and(|text| {
+            (0..=text.len()).filter(|&i| text.is_char_boundary(i)).any(|i| glob_matches(rest, &text[i..]))
+        }),
+    }
+}
+
+/// Names may end in `*` to match a prefix, e.g. `LD_*`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvPolicy {
+    /// Only these variables are passed through; empty allows any not denied
+    #[serde(default)]
+    pub allowed: Vec<String>,
+
+    /// Variables always stripped (takes precedence)
+    #[serde(default = "default_denied_env")]
+    pub denied: Vec<String>,
+}
+
+impl Default for EnvPolicy {
+    fn default() -> Self {
+        Self { allowed: Vec::new(), denied: default_denied_env() }
+    }
+}
+
+/// Loader, shell and interpreter hooks that can run code before the program
+fn default_denied_env() -> Vec<String> {
+    [
+        "PATH", "LD_*", "DYLD_*", "GCONV_PATH", "MALLOC_*", "BASH_ENV", "ENV", "IFS", "SHELLOPTS", "PS4",
+        "PYTHON*", "NODE_OPTIONS", "NODE_PATH", "RUBYOPT", "RUBYLIB", "PERL5OPT", "PERL5LIB",
+        "JAVA_TOOL_OPTIONS", "_JAVA_OPTIONS", "JDK_JAVA_OPTIONS", "CLASSPATH",
+        "RUSTC_WRAPPER", "RUSTFLAGS", "CARGO_*", "GOFLAGS", "GOPATH", "GOROOT",
+    ]
+    .iter().map(|s| s.to_string()).collect()
+}
+
+fn env_matches(pattern: &str, key: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => key == pattern,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -93,11 +219,132 @@ impl Default for SecurityPolicy {
                 require_signatures: false, // Relaxed for development
                 trusted_publishers: vec!["shell.dev".to_string()],
             },
+            allowed_compile_flags: default_compile_flags(),
+            env_policy: EnvPolicy::default(),
+            allowed_images: default_allowed_images(),
+            allowed_test_programs: default_test_programs(),
+            protected_paths: Vec::new(),
+            exam: ExamLock::default(),
         }
     }
 }
 
 impl SecurityPolicy {
+    /// Reject compiler flags outside the allowlist, such as `-o` or `-Wl,…`
+    /// that could write outside the scratch directory
+    pub fn validate_compile_flags(&self, flags: &[String]) -> Result<()> {
+        for flag in flags {
+            let allowed = self.allowed_compile_flags.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => flag.strip_prefix(prefix).is_some_and(|rest| {
+                    !rest.is_empty() && rest.chars().all(|c| c.is_ascii_alphanumeric() || "_=+.-".contains(c))
+                }),
+                None => flag == pattern,
+            });
+            if !allowed {
+                return Err(ShellError::Security(format!("Compiler flag '{}' is not allowed", flag)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject images outside the allowlist, e.g. one a lesson names that
+    /// could exfiltrate the mounted workspace
+    pub fn validate_image(&self, image: &str) -> Result<()> {
+        if !self.allowed_images.iter().any(|pattern| glob_matches(pattern, image)) {
+            tracing::warn!(target: "audit", image = %image, "Denied image");
+            return Err(ShellError::Security(format!("Image '{}' is not allowed", image)));
+        }
+        Ok(())
+    }
+
+    /// Reject a lesson's custom test command unless it starts an allowed
+    /// program; lesson bundles are untrusted
+    pub fn validate_test_command(&self, command: &[String]) -> Result<()> {
+        let program = command.first().map(String::as_str).unwrap_or_default();
+        let allowed = self.allowed_test_programs.iter().any(|pattern| glob_matches(pattern, program))
+            && !program.contains("..")
+            && command.iter().all(|arg| !arg.contains('\0'));
+        if !allowed {
+            tracing::warn!(target: "audit", program = %program, "Denied test command");
+            return Err(ShellError::Security(format!("Test command '{}' is not allowed", command.join(" "))));
+        }
+        Ok(())
+    }
+
+    /// Drop variables the env policy denies and reject malformed names.
+    /// Stripped names are recorded in the audit log; values are not.
+    pub fn sanitize_env(&self, env: HashMap<String, String>) -> Result<HashMap<String, String>> {
+        let policy = &self.env_policy;
+        let mut sanitized = HashMap::with_capacity(env.len());
+        for (key, value) in env {
+            let valid = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid || value.contains('\0') {
+                return Err(ShellError::Security(format!("Invalid environment variable '{}'", key)));
+            }
+
+            let denied = policy.denied.iter().any(|p| env_matches(p, &key))
+                || (!policy.allowed.is_empty() && !policy.allowed.iter().any(|p| env_matches(p, &key)));
+            if denied {
+                tracing::warn!(target: "audit", variable = %key, "Stripped environment variable from run");
+                continue;
+            }
+            sanitized.insert(key, value);
+        }
+        Ok(sanitized)
+    }
+
+    /// Start an exam confined to `project`, which must already be allowed.
+    /// The session is recorded in the audit log.
+    pub fn enter_exam(&self, project: &Path) -> Result<ExamSession> {
+        self.validate_path(project)?;
+        if !project.is_dir() {
+            return Err(ShellError::Security(format!("Exam project is not a directory: {}", project.display())));
+        }
+        let session = ExamSession {
+            session_id: uuid::Uuid::new_v4().to_string(),
+            project: project.canonicalize()?,
+            started_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        let mut exam = self.exam.0.write().map_err(|_| ShellError::Security("Exam state is unavailable".into()))?;
+        if exam.is_some() {
+            return Err(ShellError::Security("An exam is already in progress".into()));
+        }
+        tracing::warn!(target: "audit", exam = %session.session_id, project = %session.project.display(), "Exam started");
+        *exam = Some(session.clone());
+        Ok(session)
+    }
+
+    /// Resume an exam that was in progress when the app last closed
+    pub fn resume_exam(&self, session: ExamSession) {
+        tracing::warn!(target: "audit", exam = %session.session_id, "Exam resumed");
+        if let Ok(mut exam) = self.exam.0.write() {
+            *exam = Some(session);
+        }
+    }
+
+    /// End the exam in progress, if any. Callers check the teacher's token.
+    pub fn exit_exam(&self) -> Option<ExamSession> {
+        let session = self.exam.0.write().ok()?.take()?;
+        tracing::warn!(target: "audit", exam = %session.session_id, "Exam ended");
+        Some(session)
+    }
+
+    /// The exam in progress
+    pub fn exam(&self) -> Option<ExamSession> {
+        self.exam.0.read().ok().and_then(|exam| exam.clone())
+    }
+
+    /// Reject running processes on the host (local terminals, debuggees)
+    /// during an exam; they'd escape the project and have network access
+    pub fn check_host_execution(&self) -> Result<()> {
+        match self.exam() {
+            Some(_) => Err(ShellError::Security("Running programs outside the sandbox is disabled during an exam".into())),
+            None => Ok(()),
+        }
+    }
+
     /// Check if a path is allowed for file operations
     pub fn is_path_allowed(&self, path: &Path) -> bool {
         let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
@@ -108,6 +355,11 @@ impl SecurityPolicy {
                 return false;
             }
         }
+
+        // An exam narrows the allowed paths to its project
+        if let Some(exam) = self.exam() {
+            return path.starts_with(&exam.project);
+        }
         
         // Check allowed paths
         for allowed in &self.allowed_paths {
@@ -122,6 +374,9 @@ impl SecurityPolicy {
     /// Validate a path and return an error if not allowed
     pub fn validate_path(&self, path: &Path) -> Result<()> {
         if !self.is_path_allowed(path) {
+            if let Some(exam) = self.exam() {
+                tracing::warn!(target: "audit", exam = %exam.session_id, path = %path.display(), "Denied access during exam");
+            }
             return Err(ShellError::Security(format!(
                 "Access denied: {}",
                 path.display()
@@ -130,6 +385,26 @@ impl SecurityPolicy {
         Ok(())
     }
     
+    /// Validate a directory about to be bind-mounted into a container.
+    /// Besides being allowed, it must not hold, sit inside or contain the
+    /// app's data, which a program in the container could read or rewrite.
+    pub fn validate_mount(&self, path: &Path) -> Result<()> {
+        self.validate_path(path)?;
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let overlaps = self.protected_paths.iter().any(|protected| {
+            let protected = protected.canonicalize().unwrap_or_else(|_| protected.clone());
+            canonical.starts_with(&protected) || protected.starts_with(&canonical)
+        });
+        if overlaps || holds_app_data(&canonical) {
+            tracing::warn!(target: "audit", path = %canonical.display(), "Denied mounting app data");
+            return Err(ShellError::Security(format!(
+                "Access denied: {} contains Shell's app data and can't be mounted",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
     /// Check if a file can be executed
     pub fn can_execute(&self, path: &Path) -> bool {
         if !self.is_path_allowed(path) {
@@ -180,6 +455,29 @@ impl PathSanitizer {
         
         Ok(canonical)
     }
+
+    /// Validate a relative path for a file that does not exist yet.
+    ///
+    /// Rejects absolute paths and any `..` or root component, so joining the
+    /// result onto a base directory can never escape it.
+    pub fn relative(relative: &str) -> Result<PathBuf> {
+        use std::path::Component;
+
+        let path = Path::new(relative);
+        let mut clean = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(part) => clean.push(part),
+                Component::CurDir => {}
+                _ => return Err(ShellError::Security(format!("Invalid relative path: {}", relative))),
+            }
+        }
+
+        if clean.as_os_str().is_empty() {
+            return Err(ShellError::Security("Empty file path".into()));
+        }
+        Ok(clean)
+    }
 }
 
 /// Secure random string generation
@@ -197,6 +495,63 @@ pub fn generate_secure_id() -> String {
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_compile_flag_allowlist() {
+        let policy = SecurityPolicy::default();
+        let flags = |f: &[&str]| f.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(policy.validate_compile_flags(&flags(&["-std=c++20", "-O2", "-Wall", "-DDEBUG=1"])).is_ok());
+        for denied in ["-o", "-Wl,-o,/workspace/x", "-std=../x", "-I/etc", "@args.txt", "-D"] {
+            assert!(policy.validate_compile_flags(&flags(&[denied])).is_err(), "{}", denied);
+        }
+    }
+
+    #[test]
+    fn test_image_allowlist() {
+        let mut policy = SecurityPolicy::default();
+        for allowed in ["python:3.12-slim", "python:3.11", "gcc:13", "shell-deps:0123abcd"] {
+            assert!(policy.validate_image(allowed).is_ok(), "{}", allowed);
+        }
+        for denied in ["attacker/exfiltrate:latest", "python", "pythonx:3.12", "docker.io/evil/python:3.12"] {
+            assert!(policy.validate_image(denied).is_err(), "{}", denied);
+        }
+
+        policy.allowed_images.push("jupyter/*-notebook:2024-*".into());
+        assert!(policy.validate_image("jupyter/scipy-notebook:2024-05-01").is_ok());
+        assert!(policy.validate_image("jupyter/scipy-notebook:latest").is_err());
+    }
+
+    #[test]
+    fn test_test_command_allowlist() {
+        let policy = SecurityPolicy::default();
+        let command = |c: &[&str]| c.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        for allowed in [&["python", "driver.py"][..], &["./run_tests.sh"], &["/tmp/a.out", "--check"]] {
+            assert!(policy.validate_test_command(&command(allowed)).is_ok(), "{:?}", allowed);
+        }
+        for denied in [&[][..], &["sh", "-c", "curl x"], &["/bin/bash"], &["./../escape"], &["python", "a\0b"]] {
+            assert!(policy.validate_test_command(&command(denied)).is_err(), "{:?}", denied);
+        }
+    }
+
+    #[test]
+    fn test_env_sanitizing() {
+        let env = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>();
+        let mut policy = SecurityPolicy::default();
+
+        let sanitized = policy.sanitize_env(env(&[
+            ("LD_PRELOAD", "/tmp/x.so"), ("PYTHONSTARTUP", "x.py"), ("PATH", "/tmp"), ("DEBUG", "1"),
+        ])).unwrap();
+        assert_eq!(sanitized, env(&[("DEBUG", "1")]));
+
+        for key in ["", "1ABC", "A-B", "A=B", "A B"] {
+            assert!(policy.sanitize_env(env(&[(key, "1")])).is_err(), "{:?}", key);
+        }
+
+        policy.env_policy.allowed = vec!["APP_*".into()];
+        assert_eq!(policy.sanitize_env(env(&[("APP_MODE", "a"), ("DEBUG", "1")])).unwrap(), env(&[("APP_MODE", "a")]));
+    }
+
     #[test]
     fn test_path_security() {
         let policy = SecurityPolicy::default();
@@ -209,4 +564,52 @@ mod tests {
         assert!(policy.executable_extensions.contains("py"));
         assert!(policy.executable_extensions.contains("js"));
     }
+
+    #[test]
+    fn test_app_data_is_never_mounted() {
+        let root = std::env::temp_dir().canonicalize().unwrap().join(format!("shell-mount-{}", uuid::Uuid::new_v4()));
+        let app_data = root.join("app-data");
+        let project = root.join("project");
+        let stray = root.join("copied");
+        for dir in [&app_data, &project, &stray] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(stray.join("features.json"), "{}").unwrap();
+
+        let mut policy = SecurityPolicy::default();
+        policy.allowed_paths = vec![root.clone()];
+        policy.protected_paths = vec![app_data.clone()];
+        assert!(policy.validate_mount(&project).is_ok());
+        // The app-data dir, anything inside it and anything holding it
+        for denied in [&app_data, &app_data.join("licenses"), &root, &stray] {
+            let err = policy.validate_mount(denied).unwrap_err();
+            assert!(matches!(err, ShellError::Security(_)), "{}", denied.display());
+        }
+        assert!(policy.validate_mount(&project.join("shell.db")).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_exam_confines_paths_while_active() {
+        let root = std::env::temp_dir().canonicalize().unwrap().join(format!("shell-exam-{}", uuid::Uuid::new_v4()));
+        let project = root.join("exam");
+        let notes = root.join("notes.txt");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(&notes, "answers").unwrap();
+        std::fs::write(project.join("main.py"), "").unwrap();
+
+        let policy = SecurityPolicy { allowed_paths: vec![root.clone()], ..SecurityPolicy::default() };
+        assert!(policy.validate_path(&notes).is_ok());
+        assert!(policy.check_host_execution().is_ok());
+
+        let session = policy.enter_exam(&project).unwrap();
+        assert!(policy.validate_path(&notes).is_err());
+        assert!(policy.validate_path(&project.join("main.py")).is_ok());
+        assert!(policy.check_host_execution().is_err());
+        assert!(policy.enter_exam(&project).is_err());
+
+        assert_eq!(policy.exit_exam(), Some(session));
+        assert!(policy.validate_path(&notes).is_ok());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -10,7 +10,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use crate::error::{Result, ShellError};
@@ -49,6 +49,8 @@ pub enum ServiceType {
     TestRunner,
     /// Local grader
     Grader,
+    /// Debug Adapter Protocol server
+    Debugger(String), // Language name
     /// Custom service
     Custom(String),
 }
@@ -133,6 +135,14 @@ impl ServiceManager {
         Ok(())
     }
 
+    /// Take a running service's stdin and stdout, for proxying its
+    /// protocol. `None` if it is not running or they were already taken.
+    pub async fn take_stdio(&self, id: &str) -> Option<(ChildStdin, ChildStdout)> {
+        let mut processes = self.processes.lock().await;
+        let child = &mut processes.get_mut(id)?.child;
+        Some((child.stdin.take()?, child.stdout.take()?))
+    }
+
     /// Get status of all services
     pub async fn status(&self) -> Vec<ServiceStatus> {
         let configs = self.configs.lock().await;
@@ -0,0 +1,123 @@
+//! Editor sessions
+//!
+//! Which files a project had open, the active one and cursor positions,
+//! saved with the project so reopening it picks up where the user left off.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::db::Database;
+use crate::error::{Result, ShellError};
+use crate::security::PathSanitizer;
+
+/// 1-based cursor location in a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorPosition {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Paths are relative to the project root
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    /// In tab order
+    #[serde(default)]
+    pub open_files: Vec<String>,
+    #[serde(default)]
+    pub active_file: Option<String>,
+    /// Cursor of each open file
+    #[serde(default)]
+    pub cursors: BTreeMap<String, CursorPosition>,
+}
+
+/// Save the session of a registered project
+pub fn save(db: &Database, project_id: &str, state: &SessionState) -> Result<()> {
+    for file in state.open_files.iter().chain(&state.active_file) {
+        PathSanitizer::relative(file)?;
+    }
+    if let Some(active) = &state.active_file {
+        if !state.open_files.contains(active) {
+            return Err(ShellError::Configuration(format!("Active file {} is not open", active)));
+        }
+    }
+    db.set_project_session(project_id, &serde_json::to_string(state)?)
+}
+
+/// The project's saved session without files that no longer exist, or
+/// `None` if nothing was saved
+pub fn load(db: &Database, project_id: &str) -> Result<Option<SessionState>> {
+    let Some(json) = db.get_project_session(project_id)? else { return Ok(None) };
+    let project = db.get_project(project_id)?
+        .ok_or_else(|| ShellError::Configuration(format!("Unknown project: {}", project_id)))?;
+    let mut state: SessionState = serde_json::from_str(&json)?;
+    prune(&mut state, Path::new(&project.path));
+    Ok(Some(state))
+}
+
+/// Drop files missing from `root`; a missing active file gives way to the
+/// first file still open
+fn prune(state: &mut SessionState, root: &Path) {
+    let exists = |file: &String| PathSanitizer::relative(file).is_ok_and(|path| root.join(path).is_file());
+    state.open_files.retain(exists);
+    let SessionState { open_files, active_file, cursors } = state;
+    if active_file.as_ref().is_some_and(|active| !open_files.contains(active)) {
+        *active_file = open_files.first().cloned();
+    }
+    cursors.retain(|file, _| open_files.contains(file));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(db: &Database, files: &[&str]) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("shell-session-{}", uuid::Uuid::new_v4()));
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        db.register_project("p1", "Project", &root.to_string_lossy(), None).unwrap();
+        root
+    }
+
+    fn state(open: &[&str], active: &str) -> SessionState {
+        SessionState {
+            open_files: open.iter().map(|f| f.to_string()).collect(),
+            active_file: Some(active.to_string()),
+            cursors: open.iter().enumerate()
+                .map(|(i, f)| (f.to_string(), CursorPosition { line: i as u32 + 1, column: 4 }))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_session_round_trips() {
+        let db = Database::in_memory().unwrap();
+        let root = project(&db, &["main.py", "src/util.py"]);
+        assert_eq!(load(&db, "p1").unwrap(), None);
+
+        let saved = state(&["main.py", "src/util.py"], "src/util.py");
+        save(&db, "p1", &saved).unwrap();
+        assert_eq!(load(&db, "p1").unwrap(), Some(saved));
+
+        assert!(save(&db, "p1", &state(&["../secrets.txt"], "../secrets.txt")).is_err());
+        assert!(save(&db, "p1", &state(&["main.py"], "other.py")).is_err());
+        assert!(save(&db, "missing", &SessionState::default()).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_deleted_files_are_pruned_on_load() {
+        let db = Database::in_memory().unwrap();
+        let root = project(&db, &["main.py", "old.py"]);
+        save(&db, "p1", &state(&["main.py", "old.py"], "old.py")).unwrap();
+        std::fs::remove_file(root.join("old.py")).unwrap();
+
+        let restored = load(&db, "p1").unwrap().unwrap();
+        assert_eq!(restored.open_files, vec!["main.py"]);
+        assert_eq!(restored.active_file.as_deref(), Some("main.py"));
+        assert_eq!(restored.cursors.keys().collect::<Vec<_>>(), vec!["main.py"]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
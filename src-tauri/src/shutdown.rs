@@ -0,0 +1,83 @@
+//! Coordinated teardown on exit
+//!
+//! Runs still going in containers and services started from the IDE are
+//! stopped before the app exits, so student code doesn't keep running after
+//! the IDE closes. Teardown is bounded so a hung daemon can't block exit.
+
+use std::time::Duration;
+use crate::docker::ExecutionBackend;
+use crate::services::ServiceManager;
+
+/// Longest the app waits on teardown before exiting anyway
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What teardown managed before exit
+#[derive(Debug, Default, PartialEq)]
+pub struct ShutdownReport {
+    pub executions_stopped: Vec<String>,
+    /// `timeout` passed before teardown finished
+    pub timed_out: bool,
+}
+
+/// Stop running executions and services, giving up after `timeout`
+pub async fn shutdown(executions: &dyn ExecutionBackend, services: &ServiceManager, timeout: Duration) -> ShutdownReport {
+    let teardown = async {
+        let (stopped, services) = tokio::join!(executions.stop_all(), services.stop_all());
+        if let Err(e) = services {
+            tracing::warn!("Failed to stop services: {}", e);
+        }
+        stopped
+    };
+    match tokio::time::timeout(timeout, teardown).await {
+        Ok(executions_stopped) => ShutdownReport { executions_stopped, timed_out: false },
+        Err(_) => {
+            tracing::warn!("Shutdown did not finish within {:?}; leftover containers are cleaned up on next launch", timeout);
+            ShutdownReport { executions_stopped: Vec::new(), timed_out: true }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::docker::{ExecutionRequest, ExecutionResult};
+    use crate::error::{Result, ShellError};
+
+    /// Tracks "running" executions; stopping them can hang
+    struct Tracked {
+        running: Mutex<Vec<String>>,
+        hang: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl ExecutionBackend for Tracked {
+        async fn execute(&self, _request: ExecutionRequest) -> Result<ExecutionResult> {
+            Err(ShellError::Execution("not used".into()))
+        }
+
+        async fn stop_all(&self) -> Vec<String> {
+            if self.hang {
+                std::future::pending::<()>().await;
+            }
+            self.running.lock().unwrap().drain(..).collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_tracked_executions() {
+        let services = ServiceManager::new();
+        let backend = Tracked { running: Mutex::new(vec!["run-1".into(), "run-2".into()]), hang: false };
+
+        let report = shutdown(&backend, &services, SHUTDOWN_TIMEOUT).await;
+        assert_eq!(report, ShutdownReport { executions_stopped: vec!["run-1".into(), "run-2".into()], timed_out: false });
+        assert!(backend.running.lock().unwrap().is_empty());
+
+        // A hung daemon doesn't hold up exit
+        let hung = Tracked { running: Mutex::new(vec!["run-3".into()]), hang: true };
+        let started = std::time::Instant::now();
+        let report = shutdown(&hung, &services, Duration::from_millis(50)).await;
+        assert!(report.timed_out);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
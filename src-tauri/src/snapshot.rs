@@ -0,0 +1,290 @@
+//! Project snapshots
+//!
+//! A snapshot is a zip of a project's files, kept under app data so it
+//! doesn't need git. Files matched by `.gitignore`, `.ignore` or
+//! `.shellignore`, the `.git` directory and symlinks are left out; a restore
+//! keeps the project's current copies of those.
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use crate::db::SnapshotRecord;
+use crate::error::{Result, ShellError};
+use crate::lessons::IGNORE_FILE;
+use crate::security::PathSanitizer;
+
+/// Largest total size of the files in a snapshot
+pub const MAX_SNAPSHOT_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Where a snapshot's archive lives
+pub fn archive_path(snapshots_dir: &Path, id: &str) -> PathBuf {
+    snapshots_dir.join(format!("{}.zip", id))
+}
+
+/// Files and directories a snapshot covers, relative to `project`
+fn included(project: &Path) -> Vec<(PathBuf, bool)> {
+    ignore::WalkBuilder::new(project)
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.depth() > 0)
+        .filter_map(|entry| {
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                return None;
+            }
+            let relative = entry.path().strip_prefix(project).ok()?.to_path_buf();
+            Some((relative, file_type.is_dir()))
+        })
+        .collect()
+}
+
+/// Archive `project` into `snapshots_dir`
+pub fn create(project: &Path, label: &str, snapshots_dir: &Path) -> Result<SnapshotRecord> {
+    if !project.is_dir() {
+        return Err(ShellError::Filesystem(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Project not found: {}", project.display()),
+        )));
+    }
+
+    let mut files = Vec::new();
+    let mut size_bytes = 0;
+    for (relative, is_dir) in included(project) {
+        if is_dir {
+            continue;
+        }
+        size_bytes += std::fs::metadata(project.join(&relative))?.len();
+        if size_bytes > MAX_SNAPSHOT_SIZE {
+            return Err(ShellError::Security(format!(
+                "Project is too large to snapshot (max {} bytes)", MAX_SNAPSHOT_SIZE
+            )));
+        }
+        files.push(archive_name(&relative)?);
+    }
+    files.sort();
+
+    let id = uuid::Uuid::new_v4().to_string();
+    std::fs::create_dir_all(snapshots_dir)?;
+    let archive = archive_path(snapshots_dir, &id);
+    // Written under a temporary name so a failed snapshot leaves no archive
+    let partial = archive.with_extension("zip.partial");
+    let written = write_archive(project, &files, &partial).and_then(|_| Ok(std::fs::rename(&partial, &archive)?));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    Ok(SnapshotRecord {
+        id,
+        project_path: project.to_string_lossy().into_owned(),
+        label: label.to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        file_count: files.len() as u64,
+        size_bytes,
+    })
+}
+
+fn write_archive(project: &Path, files: &[String], out: &Path) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(out)?);
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    for name in files {
+        zip.start_file(name.as_str(), options).map_err(zip_error)?;
+        zip.write_all(&std::fs::read(project.join(name))?)?;
+    }
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+/// Portable `/`-separated form of a relative path
+fn archive_name(relative: &Path) -> Result<String> {
+    let parts: Vec<_> = relative.components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<_>>()
+        .ok_or_else(|| ShellError::Filesystem(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Non UTF-8 file name: {}", relative.display()),
+        )))?;
+    Ok(parts.join("/"))
+}
+
+/// Replace `project`'s files with the snapshot's.
+///
+/// The snapshot is extracted to a staging directory next to the project,
+/// the project's excluded files are moved into it, and the two directories
+/// are swapped, so a failure at any point leaves the project as it was.
+pub fn restore(project: &Path, archive: &Path) -> Result<()> {
+    let name = project.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| ShellError::Security(format!("Invalid project path: {}", project.display())))?;
+    let parent = project.parent()
+        .ok_or_else(|| ShellError::Security(format!("Invalid project path: {}", project.display())))?;
+    let suffix = uuid::Uuid::new_v4();
+    let staging = parent.join(format!(".{}.restore-{}", name, suffix));
+    let previous = parent.join(format!(".{}.previous-{}", name, suffix));
+
+    let restored = extract(archive, &staging)
+        .and_then(|_| carry_over(project, &staging))
+        .and_then(|moved| swap(project, &staging, &previous).map_err(|e| {
+            // Hand the excluded files back before the staging copy goes
+            for relative in moved.iter().rev() {
+                let _ = std::fs::rename(staging.join(relative), project.join(relative));
+            }
+            e
+        }));
+    if staging.exists() {
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+    restored?;
+
+    if let Err(e) = std::fs::remove_dir_all(&previous) {
+        tracing::warn!("Failed to remove pre-restore copy {}: {}", previous.display(), e);
+    }
+    Ok(())
+}
+
+fn extract(archive: &Path, dest: &Path) -> Result<()> {
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(archive)?).map_err(zip_error)?;
+    std::fs::create_dir_all(dest)?;
+
+    let mut remaining = MAX_SNAPSHOT_SIZE;
+    for i in 0..zip.len() {
+        let entry = zip.by_index(i).map_err(zip_error)?;
+        let relative = entry.enclosed_name()
+            .and_then(|p| p.to_str())
+            .and_then(|p| PathSanitizer::relative(p).ok())
+            .ok_or_else(|| ShellError::Security(format!("Snapshot entry escapes project: {}", entry.name())))?;
+
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::File::create(&target)?;
+        let written = std::io::copy(&mut entry.take(remaining + 1), &mut file)?;
+        if written > remaining {
+            return Err(ShellError::Security(format!("Snapshot is too large (max {} bytes)", MAX_SNAPSHOT_SIZE)));
+        }
+        remaining -= written;
+    }
+    Ok(())
+}
+
+/// Move what snapshots leave out from `project` into `staging`, returning
+/// the moved paths. The snapshot's copy wins if both have a path. On error
+/// everything moved so far is put back.
+fn carry_over(project: &Path, staging: &Path) -> Result<Vec<PathBuf>> {
+    let included: HashSet<PathBuf> = included(project).into_iter().map(|(path, _)| path).collect();
+    let mut excluded = Vec::new();
+    find_excluded(project, Path::new(""), &included, &mut excluded)?;
+
+    let mut moved = Vec::new();
+    for relative in excluded {
+        let target = staging.join(&relative);
+        if target.symlink_metadata().is_ok() {
+            continue;
+        }
+        let result = target.parent().map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::rename(project.join(&relative), &target));
+        if let Err(e) = result {
+            for relative in moved.iter().rev() {
+                let _ = std::fs::rename(staging.join(relative), project.join(relative));
+            }
+            return Err(e.into());
+        }
+        moved.push(relative);
+    }
+    Ok(moved)
+}
+
+/// Topmost entries under `dir` that aren't in `included`
+fn find_excluded(project: &Path, dir: &Path, included: &HashSet<PathBuf>, excluded: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(project.join(dir))? {
+        let entry = entry?;
+        let relative = dir.join(entry.file_name());
+        if !included.contains(&relative) {
+            excluded.push(relative);
+        } else if entry.file_type()?.is_dir() {
+            find_excluded(project, &relative, included, excluded)?;
+        }
+    }
+    Ok(())
+}
+
+/// Put `staging` in `project`'s place, keeping the old project at `previous`
+fn swap(project: &Path, staging: &Path, previous: &Path) -> Result<()> {
+    std::fs::rename(project, previous)?;
+    if let Err(e) = std::fs::rename(staging, project) {
+        std::fs::rename(previous, project)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn zip_error(e: zip::result::ZipError) -> ShellError {
+    ShellError::Filesystem(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Snapshot archive error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_create_and_restore_round_trip() {
+        let root = std::env::temp_dir().join(format!("shell-snapshot-{}", uuid::Uuid::new_v4()));
+        let project = root.join("project");
+        let snapshots = root.join("snapshots");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::create_dir_all(project.join(".git")).unwrap();
+        std::fs::create_dir_all(project.join("build")).unwrap();
+        std::fs::write(project.join(".gitignore"), "build/\n").unwrap();
+        std::fs::write(project.join("main.py"), "print('v1')\n").unwrap();
+        std::fs::write(project.join("src/util.py"), "X = 1\n").unwrap();
+        std::fs::write(project.join("build/out.bin"), "old build").unwrap();
+        std::fs::write(project.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        let snapshot = create(&project, "before refactor", &snapshots).unwrap();
+        assert_eq!(snapshot.file_count, 3);
+        assert!(archive_path(&snapshots, &snapshot.id).is_file());
+
+        std::fs::write(project.join("main.py"), "print('v2')\n").unwrap();
+        std::fs::remove_file(project.join("src/util.py")).unwrap();
+        std::fs::write(project.join("scratch.py"), "").unwrap();
+        std::fs::write(project.join("build/out.bin"), "new build").unwrap();
+
+        restore(&project, &archive_path(&snapshots, &snapshot.id)).unwrap();
+        assert_eq!(read(&project.join("main.py")), "print('v1')\n");
+        assert_eq!(read(&project.join("src/util.py")), "X = 1\n");
+        assert!(!project.join("scratch.py").exists());
+        // Excluded files are the project's current ones
+        assert_eq!(read(&project.join("build/out.bin")), "new build");
+        assert_eq!(read(&project.join(".git/HEAD")), "ref: refs/heads/main\n");
+        // Nothing is left behind next to the project
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_failed_restore_leaves_project_untouched() {
+        let root = std::env::temp_dir().join(format!("shell-snapshot-{}", uuid::Uuid::new_v4()));
+        let project = root.join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(project.join("main.py"), "print('current')\n").unwrap();
+        let corrupt = root.join("corrupt.zip");
+        std::fs::write(&corrupt, "not a zip").unwrap();
+
+        assert!(restore(&project, &corrupt).is_err());
+        assert_eq!(read(&project.join("main.py")), "print('current')\n");
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,330 @@
+//! Interactive terminal sessions
+//!
+//! Unlike batch runs, a session keeps a PTY open so students can use a
+//! shell or REPL. Sessions normally run in a container; without Docker a
+//! local shell is used instead, with only a restricted environment and a
+//! policy-checked working directory between it and the host. Output is
+//! pushed to a listener as it arrives; closing a session tears it down.
+
+use bollard::container::{
+    AttachContainerOptions, Config, CreateContainerOptions, RemoveContainerOptions, ResizeContainerTtyOptions,
+    StartContainerOptions,
+};
+use futures_util::StreamExt;
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use crate::commands::execution::get_language_image;
+use crate::docker::DockerManager;
+use crate::error::{Result, ShellError};
+
+/// Open sessions allowed at once; each holds a container
+const MAX_TERMINAL_SESSIONS: usize = 4;
+
+/// Something a session produced
+#[derive(Debug, Clone, PartialEq)]
+pub enum TerminalEvent {
+    Data(String),
+    /// The shell exited or the session was closed
+    Closed,
+}
+
+/// Receives a session's id and what it produced
+pub type TerminalListener = Arc<dyn Fn(&str, TerminalEvent) + Send + Sync>;
+
+/// Where a session's shell runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalBackend {
+    /// Docker when available, otherwise local
+    #[default]
+    Auto,
+    /// Sandboxed container
+    Docker,
+    /// Shell on the host, with reduced isolation
+    Local,
+}
+
+/// Result of opening a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalOpened {
+    pub session_id: String,
+    /// `Docker` or `Local`, so the UI can warn about reduced isolation
+    pub backend: TerminalBackend,
+}
+
+enum TerminalSession {
+    Container {
+        container_id: String,
+        input: Pin<Box<dyn AsyncWrite + Send>>,
+        reader: JoinHandle<()>,
+    },
+    Local {
+        master: Box<dyn MasterPty + Send>,
+        writer: Box<dyn Write + Send>,
+        child: Box<dyn Child + Send + Sync>,
+    },
+}
+
+pub struct TerminalManager {
+    sessions: Mutex<HashMap<String, TerminalSession>>,
+}
+
+/// PATH for local shells: system locations only, nothing user-writable
+const LOCAL_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// Interactive program for a language: its REPL, or a shell
+pub fn repl_command(language: &str) -> Vec<String> {
+    let command: &[&str] = match language.to_lowercase().as_str() {
+        "python" | "py" => &["python", "-q"],
+        "javascript" | "js" | "node" | "typescript" | "ts" => &["node"],
+        "ruby" | "rb" => &["irb"],
+        _ => &["sh"],
+    };
+    command.iter().map(|s| s.to_string()).collect()
+}
+
+impl TerminalManager {
+    pub fn new() -> Self {
+        Self { sessions: Mutex::new(HashMap::new()) }
+    }
+
+    async fn check_capacity(&self) -> Result<()> {
+        if self.sessions.lock().await.len() >= MAX_TERMINAL_SESSIONS {
+            return Err(ShellError::Execution(format!(
+                "At most {} terminals can be open at once", MAX_TERMINAL_SESSIONS
+            )));
+        }
+        Ok(())
+    }
+
+    /// Start a session in a fresh container and return its id
+    pub async fn open_container(&self, docker: &DockerManager, language: &str, listener: TerminalListener) -> Result<String> {
+        self.check_capacity().await?;
+
+        let image = get_language_image(language)?;
+        let client = docker.client().await?;
+        let session_id = uuid::Uuid::new_v4().to_string();
+
+        let config = Config {
+            image: Some(image),
+            cmd: Some(repl_command(language)),
+            working_dir: Some("/tmp".to_string()),
+            env: Some(vec!["TERM=xterm-256color".to_string()]),
+            tty: Some(true),
+            open_stdin: Some(true),
+            attach_stdin: Some(true),
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            host_config: Some(DockerManager::host_config(None, None)),
+            labels: Some(docker.labels("terminal")),
+            ..Default::default()
+        };
+        let container = client.create_container(
+            Some(CreateContainerOptions { name: format!("shell-term-{}", &session_id[..8]), platform: None }),
+            config,
+        ).await
+            .map_err(|e| ShellError::Docker(format!("Failed to create terminal container: {}", e)))?;
+
+        let attach = AttachContainerOptions::<String> {
+            stdin: Some(true),
+            stdout: Some(true),
+            stderr: Some(true),
+            stream: Some(true),
+            ..Default::default()
+        };
+        let started = async {
+            let attached = client.attach_container(&container.id, Some(attach)).await?;
+            client.start_container(&container.id, None::<StartContainerOptions<String>>).await?;
+            Ok::<_, bollard::errors::Error>(attached)
+        }.await;
+        let attached = match started {
+            Ok(attached) => attached,
+            Err(e) => {
+                let _ = client.remove_container(&container.id, Some(RemoveContainerOptions { force: true, ..Default::default() })).await;
+                return Err(ShellError::Docker(format!("Failed to start terminal: {}", e)));
+            }
+        };
+
+        let mut output = attached.output;
+        let id = session_id.clone();
+        let reader = tokio::spawn(async move {
+            while let Some(Ok(chunk)) = output.next().await {
+                listener(&id, TerminalEvent::Data(String::from_utf8_lossy(&chunk.into_bytes()).into_owned()));
+            }
+            listener(&id, TerminalEvent::Closed);
+        });
+
+        self.sessions.lock().await.insert(session_id.clone(), TerminalSession::Container {
+            container_id: container.id,
+            input: attached.input,
+            reader,
+        });
+        Ok(session_id)
+    }
+
+    /// Start a shell on the host in `cwd`, which the caller must have
+    /// checked against the security policy. The shell gets a minimal
+    /// environment rather than the app's.
+    pub async fn open_local(&self, cwd: &Path, listener: TerminalListener) -> Result<String> {
+        self.check_capacity().await?;
+        let pty_error = |e: anyhow::Error| ShellError::Service(format!("Failed to open local terminal: {}", e));
+
+        let pair = native_pty_system().openpty(PtySize { rows: 24, cols: 80, pixel_width: 0, pixel_height: 0 })
+            .map_err(pty_error)?;
+        let mut command = CommandBuilder::new(if cfg!(windows) { "cmd.exe" } else { "/bin/sh" });
+        command.cwd(cwd);
+        command.env_clear();
+        if !cfg!(windows) {
+            command.env("PATH", LOCAL_PATH);
+        }
+        command.env("HOME", cwd);
+        command.env("TERM", "xterm-256color");
+        command.env("LANG", "C.UTF-8");
+
+        let child = pair.slave.spawn_command(command).map_err(pty_error)?;
+        // Only the child should hold the slave end, so EOF reaches the reader
+        drop(pair.slave);
+        let mut reader = pair.master.try_clone_reader().map_err(pty_error)?;
+        let writer = pair.master.take_writer().map_err(pty_error)?;
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let id = session_id.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(n @ 1..) = reader.read(&mut buf) {
+                listener(&id, TerminalEvent::Data(String::from_utf8_lossy(&buf[..n]).into_owned()));
+            }
+            listener(&id, TerminalEvent::Closed);
+        });
+
+        self.sessions.lock().await.insert(session_id.clone(), TerminalSession::Local {
+            master: pair.master,
+            writer,
+            child,
+        });
+        Ok(session_id)
+    }
+
+    /// Send keystrokes to a session
+    pub async fn write(&self, session_id: &str, data: &str) -> Result<()> {
+        let mut sessions = self.sessions.lock().await;
+        match sessions.get_mut(session_id).ok_or_else(|| unknown_session(session_id))? {
+            TerminalSession::Container { input, .. } => {
+                input.write_all(data.as_bytes()).await?;
+                input.flush().await?;
+            }
+            TerminalSession::Local { writer, .. } => {
+                writer.write_all(data.as_bytes())?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn resize(&self, docker: &DockerManager, session_id: &str, rows: u16, cols: u16) -> Result<()> {
+        let container_id = {
+            let sessions = self.sessions.lock().await;
+            match sessions.get(session_id).ok_or_else(|| unknown_session(session_id))? {
+                TerminalSession::Container { container_id, .. } => container_id.clone(),
+                TerminalSession::Local { master, .. } => {
+                    return master.resize(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 })
+                        .map_err(|e| ShellError::Service(format!("Failed to resize terminal: {}", e)));
+                }
+            }
+        };
+        docker.client().await?
+            .resize_container_tty(&container_id, ResizeContainerTtyOptions { height: rows, width: cols }).await
+            .map_err(|e| ShellError::Docker(format!("Failed to resize terminal: {}", e)))
+    }
+
+    /// End a session, stopping its reader and removing its container or
+    /// killing its shell
+    pub async fn close(&self, docker: &DockerManager, session_id: &str) -> Result<()> {
+        let session = self.sessions.lock().await.remove(session_id)
+            .ok_or_else(|| unknown_session(session_id))?;
+        match session {
+            TerminalSession::Container { container_id, reader, .. } => {
+                reader.abort();
+                docker.client().await?
+                    .remove_container(&container_id, Some(RemoveContainerOptions { force: true, ..Default::default() })).await
+                    .map_err(|e| ShellError::Docker(format!("Failed to remove terminal container: {}", e)))
+            }
+            // Killing the shell closes the PTY, which ends the reader
+            TerminalSession::Local { mut child, .. } => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Ok(())
+            }
+        }
+    }
+
+    /// Close every session, e.g. on shutdown
+    pub async fn close_all(&self, docker: &DockerManager) {
+        let ids: Vec<String> = self.sessions.lock().await.keys().cloned().collect();
+        for id in ids {
+            if let Err(e) = self.close(docker, &id).await {
+                tracing::warn!("Failed to close terminal {}: {}", id, e);
+            }
+        }
+    }
+}
+
+impl Default for TerminalManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn unknown_session(session_id: &str) -> ShellError {
+    ShellError::Execution(format!("No terminal session {}", session_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl_per_language() {
+        assert_eq!(repl_command("python"), vec!["python", "-q"]);
+        assert_eq!(repl_command("ts"), vec!["node"]);
+        assert_eq!(repl_command("go"), vec!["sh"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_local_session_round_trip() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let listener: TerminalListener = Arc::new(move |_: &str, event| {
+            let _ = sender.send(event);
+        });
+        let terminals = TerminalManager::new();
+        let dir = std::env::temp_dir();
+        let id = terminals.open_local(&dir, listener).await.unwrap();
+
+        terminals.write(&id, "echo \"$PWD:${LD_PRELOAD:-clean}\"; exit\n").await.unwrap();
+        let mut output = String::new();
+        let expected = format!("{}:clean", dir.canonicalize().unwrap().display());
+        while let Some(TerminalEvent::Data(data)) = receiver.recv().await {
+            output.push_str(&data);
+        }
+        assert!(output.contains(&expected), "{}", output);
+
+        terminals.close(&DockerManager::new(), &id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unknown_session_is_rejected() {
+        let terminals = TerminalManager::new();
+        let docker = DockerManager::new();
+        assert!(terminals.write("missing", "ls\n").await.is_err());
+        assert!(terminals.close(&docker, "missing").await.is_err());
+    }
+}
@@ -11,6 +11,7 @@ import { useState, useEffect } from "react";
 import { open } from "@tauri-apps/plugin-dialog";
 import { invoke } from "@tauri-apps/api/core";
 import { homeDir, join } from "@tauri-apps/api/path";
+import { errorMessage } from "@/lib/api";
 import {
   X,
   Folder,
@@ -739,14 +740,14 @@ export function NewProjectModal({
       handleClose();
     } catch (err) {
       console.error("Failed to create project:", err);
-      const errorMessage = err instanceof Error ? err.message : String(err);
-      const lowerError = errorMessage.toLowerCase();
+      const message = errorMessage(err);
+      const lowerError = message.toLowerCase();
       if (lowerError.includes("permission denied") || lowerError.includes("access denied")) {
         setError("Permission denied. Please choose a different folder or check folder permissions.");
       } else if (lowerError.includes("already exists") || lowerError.includes("file exists")) {
         setError("A project with this name already exists. Please choose a different name.");
       } else {
-        setError(`Failed to create project: ${errorMessage}`);
+        setError(`Failed to create project: ${message}`);
       }
     } finally {
       setIsCreating(false);
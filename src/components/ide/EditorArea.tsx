@@ -163,6 +163,11 @@ export const EditorArea = memo(function EditorArea() {
         entry_point: activeFileData.name,
       });
       
+      if (result.phase === "Compile") {
+        appendOutput("Build failed:\n" + (result.compile_output || ""));
+        return;
+      }
+
       const output = (result.stdout || "") + (result.stderr ? "\n" + result.stderr : "");
       appendOutput(output || "Program completed with no output.");
       
@@ -170,7 +175,8 @@ export const EditorArea = memo(function EditorArea() {
         appendOutput(`\nProcess exited with code ${result.exit_code}`);
       }
     } catch (err) {
-      const errorMsg = "\nError: " + (err instanceof Error ? err.message : String(err));
+      const { errorMessage } = await import("@/lib/api");
+      const errorMsg = "\nError: " + errorMessage(err);
       appendOutput(errorMsg);
       
       // Provide helpful message if Docker is not running
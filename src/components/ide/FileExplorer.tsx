@@ -1,7 +1,7 @@
 import { useState, useEffect, useCallback, useMemo, memo, DragEvent } from "react";
 import { useAppStore } from "@/stores/appStore";
 import { useEditorStore } from "@/stores/editorStore";
-import { listDirectory, createFile, deleteFile, writeFile } from "@/lib/api";
+import { listDirectory, createFile, deleteFile, writeFile, errorMessage } from "@/lib/api";
 import { readTextFile, mkdir, remove, rename, copyFile } from "@tauri-apps/plugin-fs";
 import { open as shellOpen } from "@tauri-apps/plugin-shell";
 import { getLanguageFromExtension, getFileExtension, cn } from "@/lib/utils";
@@ -530,13 +530,13 @@ function FileTreeItem({
     } catch (error) {
       console.error("Failed to open file:", file.path, error);
       // Show an alert for now - can be replaced with toast later
-      const errorMessage = error instanceof Error ? error.message : String(error);
-      if (errorMessage.includes("permission") || errorMessage.includes("denied")) {
+      const message = errorMessage(error);
+      if (message.includes("permission") || message.includes("denied")) {
         alert(`Cannot open file: Permission denied.\n\nPath: ${file.path}`);
-      } else if (errorMessage.includes("binary") || errorMessage.includes("valid UTF-8")) {
+      } else if (message.includes("binary") || message.includes("valid UTF-8")) {
         alert(`Cannot open file: This appears to be a binary file.\n\n${file.name}`);
       } else {
-        alert(`Failed to open file: ${errorMessage}`);
+        alert(`Failed to open file: ${message}`);
       }
     }
   }, [file.path, file.name, openFile]);
@@ -5,32 +5,147 @@
  */
 
 import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import type {
+  AutoSaveEvent,
   ProjectInfo,
+  Project,
+  SessionState,
   DirectoryContents,
+  FileInfo,
+  WatchedDirectory,
+  FileContents,
   Lesson,
   LessonSummary,
+  Difficulty,
   ValidationResult,
+  BundleManifest,
+  PrereqStatus,
+  LessonProgress,
   RunCodeRequest,
+  RunValidation,
+  ToolchainStatus,
+  LanguageSpec,
+  LanguageInfo,
   ExecutionResult,
+  ExecutionOutput,
+  BenchmarkResult,
+  PullProgress,
+  PreparedLanguage,
   ExecutionStatus,
+  DockerInfo,
+  LifecycleEvent,
   LspServerInfo,
+  LspConfig,
   GradingResult,
+  GradingProgress,
   CloudGradingRequest,
+  ProjectFile,
+  RubricScore,
   FeatureFlags,
   Settings,
+  SettingsOverride,
+  EffectiveSettings,
+  SettingsImport,
+  Keybinding,
+  GitStatus,
+  GitAuthor,
+  TerminalOpened,
+  DapSession,
+  SimilarityReport,
+  ExamSession,
+  SnapshotRecord,
+  Diagnostic,
+  InstallResult,
+  AnalyticsRange,
+  SubmissionDiff,
+  Classroom,
+  NewStudent,
+  Student,
+  StudentProgress,
+  LocalAnalytics,
+  TelemetryStatus,
+  CoverageReport,
+  SimilarPair,
+  SearchOptions,
+  SearchResults,
+  FileTree,
+  FileIndex,
+  FileMatch,
+  ShellError,
 } from "@/types/ipc";
 
+// ============================================
+// Errors
+// ============================================
+
+export function isShellError(err: unknown): err is ShellError {
+  return typeof err === "object" && err !== null && "code" in err && "message" in err;
+}
+
+/** Display message for anything a command or plugin call rejects with */
+export function errorMessage(err: unknown): string {
+  if (isShellError(err) || err instanceof Error) return err.message;
+  return String(err);
+}
+
 // ============================================
 // Filesystem Commands
 // ============================================
 
+/** Also registers the project, so it shows up in listProjects */
 export async function readProject(path: string): Promise<ProjectInfo> {
   return invoke("read_project", { path });
 }
 
-export async function writeFile(path: string, content: string): Promise<void> {
-  return invoke("write_file", { path, content });
+export async function registerProject(path: string): Promise<Project> {
+  return invoke("register_project", { path });
+}
+
+/** Most recently opened first */
+export async function listProjects(): Promise<Project[]> {
+  return invoke("list_projects");
+}
+
+export async function saveSession(projectId: string, state: SessionState): Promise<void> {
+  return invoke("save_session", { projectId, state });
+}
+
+/** Files deleted since the session was saved are left out */
+export async function loadSession(projectId: string): Promise<SessionState | null> {
+  return invoke("load_session", { projectId });
+}
+
+export async function readFile(path: string): Promise<FileContents> {
+  return invoke("read_file", { path });
+}
+
+/** Reads up to `len` bytes from `start`, for paging through large files */
+export async function readFileRange(path: string, start: number, len: number): Promise<FileContents> {
+  return invoke("read_file_range", { path, start, len });
+}
+
+/** Pass the `encoding` the file was read with to keep it; defaults to UTF-8 */
+export async function writeFile(path: string, content: string, encoding?: string): Promise<void> {
+  return invoke("write_file", { path, content, encoding });
+}
+
+/**
+ * Saves after edits pause for the auto-save delay; only the last content
+ * scheduled within the delay is written. Resolves false when auto-save is off.
+ */
+export async function scheduleAutosave(path: string, content: string, encoding?: string): Promise<boolean> {
+  return invoke("schedule_autosave", { path, content, encoding });
+}
+
+/** Listens for finished auto-saves, successful or not */
+export async function onAutoSave(handler: (event: AutoSaveEvent) => void): Promise<UnlistenFn> {
+  const unlistenSaved = await listen<AutoSaveEvent>("fs://saved", (e) => handler(e.payload));
+  const unlistenFailed = await listen<AutoSaveEvent>("fs://save-failed", (e) => handler(e.payload));
+  return () => {
+    unlistenSaved();
+    unlistenFailed();
+  };
 }
 
 export async function createFile(
@@ -44,34 +159,145 @@ export async function deleteFile(path: string): Promise<void> {
   return invoke("delete_file", { path });
 }
 
+export async function renameFile(from: string, to: string): Promise<void> {
+  return invoke("rename_file", { from, to });
+}
+
 export async function listDirectory(path: string): Promise<DirectoryContents> {
   return invoke("list_directory", { path });
 }
 
+/** With `withStats`, small text files also get line and character counts */
+export async function getFileInfo(path: string, withStats?: boolean): Promise<FileInfo> {
+  return invoke("get_file_info", { path, withStats });
+}
+
 export async function watchDirectory(path: string): Promise<void> {
   return invoke("watch_directory", { path });
 }
 
+/** Drops one watchDirectory subscription; false if the path wasn't watched */
+export async function unwatchDirectory(path: string): Promise<boolean> {
+  return invoke("unwatch_directory", { path });
+}
+
+export async function listWatchedDirectories(): Promise<WatchedDirectory[]> {
+  return invoke("list_watched_directories");
+}
+
+// ============================================
+// Search Commands
+// ============================================
+
+/** Pass an operationId to be able to cancel with cancelOperation; cancelled searches reject with "cancelled" */
+export async function searchProject(
+  root: string,
+  query: string,
+  options?: SearchOptions,
+  operationId?: string
+): Promise<SearchResults> {
+  return invoke("search_project", { root, query, options, operationId });
+}
+
+export async function readTree(root: string, operationId?: string): Promise<FileTree> {
+  return invoke("read_tree", { root, operationId });
+}
+
+/** (Re)builds the quick-open index; it then follows file changes on its own */
+export async function buildFileIndex(projectPath: string): Promise<FileIndex> {
+  return invoke("build_file_index", { projectPath });
+}
+
+/** Fuzzy-ranked files for a "go to file" palette, best first */
+export async function queryFileIndex(
+  projectPath: string,
+  query: string,
+  limit?: number
+): Promise<FileMatch[]> {
+  return invoke("query_file_index", { projectPath, query, limit });
+}
+
+/** Resolves false if the operation already finished */
+export async function cancelOperation(operationId: string): Promise<boolean> {
+  return invoke("cancel_operation", { operationId });
+}
+
 // ============================================
 // Lesson Commands
 // ============================================
 
-export async function loadLesson(path: string): Promise<Lesson> {
-  return invoke("load_lesson", { path });
+/** Text is sanitized unless the lesson ships with the app; unknown fields are ignored unless `strict` is set */
+export async function loadLesson(path: string, strict?: boolean): Promise<Lesson> {
+  return invoke("load_lesson", { path, strict });
 }
 
 export async function saveLesson(path: string, lesson: Lesson): Promise<void> {
   return invoke("save_lesson", { path, lesson });
 }
 
-export async function listLessons(directory: string): Promise<LessonSummary[]> {
-  return invoke("list_lessons", { directory });
+export async function listLessons(
+  directory: string,
+  recursive?: boolean
+): Promise<LessonSummary[]> {
+  return invoke("list_lessons", { directory, recursive });
 }
 
+/** Looks up one known lesson by id; null when it's unknown or its file is gone */
+export async function getLessonSummary(lessonId: string): Promise<LessonSummary | null> {
+  return invoke("get_lesson_summary", { lessonId });
+}
+
+/** Strict: unknown fields are reported as errors */
 export async function validateLesson(path: string): Promise<ValidationResult> {
   return invoke("validate_lesson", { path });
 }
 
+export async function exportLesson(
+  lessonPath: string,
+  outZip: string
+): Promise<BundleManifest> {
+  return invoke("export_lesson", { lessonPath, outZip });
+}
+
+export async function importLesson(
+  bundlePath: string,
+  destDir: string
+): Promise<LessonSummary> {
+  return invoke("import_lesson", { bundlePath, destDir });
+}
+
+export async function createLessonTemplate(
+  language: string,
+  difficulty: Difficulty,
+  dest: string,
+  withIoDiagram?: boolean
+): Promise<string> {
+  return invoke("create_lesson_template", {
+    language,
+    difficulty,
+    dest,
+    withIoDiagram,
+  });
+}
+
+export async function revealHint(lessonId: string, index: number): Promise<string> {
+  return invoke("reveal_hint", { lessonId, index });
+}
+
+export async function checkPrerequisites(lessonId: string): Promise<PrereqStatus> {
+  return invoke("check_prerequisites", { lessonId });
+}
+
+/** Best score and completion of a lesson; null if never attempted */
+export async function getLessonProgress(lessonId: string): Promise<LessonProgress | null> {
+  return invoke("get_lesson_progress", { lessonId });
+}
+
+/** Progress on every attempted lesson, most recently submitted first */
+export async function listLessonProgress(): Promise<LessonProgress[]> {
+  return invoke("list_lesson_progress");
+}
+
 // ============================================
 // Execution Commands
 // ============================================
@@ -82,6 +308,85 @@ export async function runCode(
   return invoke("run_code", { request });
 }
 
+/** Checks a request the way runCode would, without running the program */
+export async function validateRunRequest(request: RunCodeRequest): Promise<RunValidation> {
+  return invoke("validate_run_request", { request });
+}
+
+/** Probes a language's image for the tool its runs need */
+export async function checkToolchain(language: string): Promise<ToolchainStatus> {
+  return invoke("check_toolchain", { language });
+}
+
+/** Lists every supported language and how it runs */
+export async function listSupportedLanguages(): Promise<LanguageSpec[]> {
+  return invoke("list_supported_languages");
+}
+
+/** Lists languages for the new-file picker, marking those not yet prepared */
+export async function getSupportedLanguages(): Promise<LanguageInfo[]> {
+  return invoke("get_supported_languages");
+}
+
+/** Runs a program repeatedly (at most 50 timed runs) and summarizes its timings */
+export async function benchmarkCode(
+  request: RunCodeRequest,
+  runs: number,
+  warmup?: number
+): Promise<BenchmarkResult> {
+  return invoke("benchmark_code", { request, runs, warmup });
+}
+
+/** Runs under coverage.py, nyc, cargo-llvm-cov or go test -cover; Go needs run_mode "tests" */
+export async function runWithCoverage(request: RunCodeRequest): Promise<CoverageReport> {
+  return invoke("run_with_coverage", { request });
+}
+
+/** Characters event names can't contain (like ".") become "_" */
+function pullEventName(image: string): string {
+  return `image://${image.replace(/[^\p{L}\p{N}\-/:_]/gu, "_")}/progress`;
+}
+
+/** Pulls the language's image if it isn't local yet; progress goes to onPullProgress */
+export async function prepareLanguage(language: string): Promise<PreparedLanguage> {
+  return invoke("prepare_language", { language });
+}
+
+/** Warms the given languages (all supported ones by default) for a setup flow */
+export async function prepareLanguages(languages?: string[]): Promise<PreparedLanguage[]> {
+  return invoke("prepare_languages", { languages });
+}
+
+/** Subscribe to pull progress of an image, e.g. during prepareLanguages */
+export async function onPullProgress(
+  image: string,
+  handler: (progress: PullProgress) => void
+): Promise<UnlistenFn> {
+  return listen<PullProgress>(pullEventName(image), (event) => handler(event.payload));
+}
+
+/** Installs packages from the project lesson's allowlist; later runs of the project can import them */
+export async function installDependencies(
+  language: string,
+  projectPath: string,
+  packages: string[]
+): Promise<InstallResult> {
+  return invoke("install_dependencies", { language, projectPath, packages });
+}
+
+/** Follow a run's queued/started/finished transitions */
+export async function onExecutionLifecycle(
+  executionId: string,
+  handler: (event: LifecycleEvent) => void
+): Promise<UnlistenFn> {
+  const unlisteners = await Promise.all(
+    (["queued", "started", "finished"] as const).map((stage) =>
+      listen<LifecycleEvent>(`execution://${executionId}/${stage}`, (e) => handler(e.payload))
+    )
+  );
+  return () => unlisteners.forEach((unlisten) => unlisten());
+}
+
 export async function stopExecution(executionId: string): Promise<void> {
   return invoke("stop_execution", { executionId });
 }
@@ -90,15 +395,166 @@ export async function getExecutionStatus(): Promise<ExecutionStatus> {
   return invoke("get_execution_status");
 }
 
+/** Output a running or just-finished run has printed so far, for catching up after a reconnect */
+export async function getExecutionOutput(executionId: string): Promise<ExecutionOutput> {
+  return invoke("get_execution_output", { executionId });
+}
+
+/** Cached for a few seconds; resolves with available: false rather than rejecting */
+export async function dockerInfo(): Promise<DockerInfo> {
+  return invoke("docker_info");
+}
+
+// ============================================
+// Terminal Commands
+// ============================================
+
+/** Opens a REPL or shell, in a container when Docker is available */
+export async function terminalOpen(language: string, cwd?: string): Promise<TerminalOpened> {
+  return invoke("terminal_open", { language, cwd });
+}
+
+export async function terminalWrite(sessionId: string, data: string): Promise<void> {
+  return invoke("terminal_write", { sessionId, data });
+}
+
+export async function terminalResize(sessionId: string, rows: number, cols: number): Promise<void> {
+  return invoke("terminal_resize", { sessionId, rows, cols });
+}
+
+export async function terminalClose(sessionId: string): Promise<void> {
+  return invoke("terminal_close", { sessionId });
+}
+
+/** Follow a session's output; `onExit` fires when the shell ends */
+export async function onTerminalOutput(
+  sessionId: string,
+  onData: (data: string) => void,
+  onExit?: () => void
+): Promise<UnlistenFn> {
+  const unlistenData = await listen<string>(`terminal://${sessionId}/data`, (e) => onData(e.payload));
+  const unlistenExit = await listen(`terminal://${sessionId}/exit`, () => onExit?.());
+  return () => {
+    unlistenData();
+    unlistenExit();
+  };
+}
+
+// ============================================
+// Debugger Commands
+// ============================================
+
+/** Starts a debug adapter; the UI then drives it with DAP requests */
+export async function dapStart(language: string, program: string, projectPath: string): Promise<DapSession> {
+  return invoke("dap_start", { language, program, projectPath });
+}
+
+export async function dapSend(sessionId: string, message: Record<string, unknown>): Promise<void> {
+  return invoke("dap_send", { sessionId, message });
+}
+
+export async function dapStop(sessionId: string): Promise<void> {
+  return invoke("dap_stop", { sessionId });
+}
+
+/** Follow a session's DAP messages; `onExit` fires when the adapter stops */
+export async function onDapMessage(
+  sessionId: string,
+  onMessage: (message: Record<string, unknown>) => void,
+  onExit?: () => void
+): Promise<UnlistenFn> {
+  const unlistenMessage = await listen<Record<string, unknown>>(`dap://${sessionId}/message`, (e) => onMessage(e.payload));
+  const unlistenExit = await listen(`dap://${sessionId}/exit`, () => onExit?.());
+  return () => {
+    unlistenMessage();
+    unlistenExit();
+  };
+}
+
+// ============================================
+// Git Commands
+// ============================================
+
+export async function gitStatus(projectPath: string): Promise<GitStatus> {
+  return invoke("git_status", { projectPath });
+}
+
+/** Unified diff of one file; empty when unchanged */
+export async function gitDiff(projectPath: string, filePath: string, staged: boolean): Promise<string> {
+  return invoke("git_diff", { projectPath, filePath, staged });
+}
+
+export async function gitStage(projectPath: string, paths: string[]): Promise<void> {
+  return invoke("git_stage", { projectPath, paths });
+}
+
+export async function gitUnstage(projectPath: string, paths: string[]): Promise<void> {
+  return invoke("git_unstage", { projectPath, paths });
+}
+
+/** Returns the initial commit id when `initialCommit` is set */
+export async function gitInit(projectPath: string, initialCommit: boolean): Promise<string | null> {
+  return invoke("git_init", { projectPath, initialCommit });
+}
+
+/** Returns the new commit id */
+export async function gitCommit(projectPath: string, message: string, author?: GitAuthor): Promise<string> {
+  return invoke("git_commit", { projectPath, message, author });
+}
+
+// ============================================
+// Snapshot Commands
+// ============================================
+
+/** Archives the project's files, skipping ignored ones; returns the snapshot id */
+export async function createSnapshot(projectPath: string, label: string): Promise<string> {
+  return invoke("create_snapshot", { projectPath, label });
+}
+
+/** Newest first */
+export async function listSnapshots(projectPath: string): Promise<SnapshotRecord[]> {
+  return invoke("list_snapshots", { projectPath });
+}
+
+/** Replaces the project's files with the snapshot's; ignored files are kept */
+export async function restoreSnapshot(snapshotId: string): Promise<void> {
+  return invoke("restore_snapshot", { snapshotId });
+}
+
 // ============================================
 // LSP Commands
 // ============================================
 
+/** `initializeOptions` go into the first `initialize` sent; defaults to the stored config's settings */
 export async function startLanguageServer(
   language: string,
-  projectPath: string
+  projectPath: string,
+  initializeOptions?: unknown
 ): Promise<string> {
-  return invoke("start_language_server", { language, projectPath });
+  return invoke("start_language_server", { language, projectPath, initializeOptions });
+}
+
+export async function lspSend(language: string, message: Record<string, unknown>): Promise<void> {
+  return invoke("lsp_send", { language, message });
+}
+
+/** Sends `workspace/didChangeConfiguration` with the new settings */
+export async function lspChangeConfiguration(language: string, settings: unknown): Promise<void> {
+  return invoke("lsp_change_configuration", { language, settings });
+}
+
+/** Follow a server's JSON-RPC messages; `serviceId` is what startLanguageServer returned */
+export async function onLspMessage(
+  serviceId: string,
+  onMessage: (message: Record<string, unknown>) => void,
+  onExit?: () => void
+): Promise<UnlistenFn> {
+  const unlistenMessage = await listen<Record<string, unknown>>(`lsp://${serviceId}/message`, (e) => onMessage(e.payload));
+  const unlistenExit = await listen(`lsp://${serviceId}/exit`, () => onExit?.());
+  return () => {
+    unlistenMessage();
+    unlistenExit();
+  };
 }
 
 export async function stopLanguageServer(language: string): Promise<void> {
@@ -109,30 +565,116 @@ export async function getAvailableServers(): Promise<LspServerInfo[]> {
   return invoke("get_available_servers");
 }
 
+/** `serverPath` must be an absolute path to an executable */
+export async function setLspConfig(
+  language: string,
+  serverPath: string,
+  args?: string[],
+  settings?: unknown
+): Promise<LspConfig> {
+  return invoke("set_lsp_config", { language, serverPath, args, settings });
+}
+
+export async function getLspConfig(language: string): Promise<LspConfig | null> {
+  return invoke("get_lsp_config", { language });
+}
+
+export async function listLspConfigs(): Promise<LspConfig[]> {
+  return invoke("list_lsp_configs");
+}
+
+// ============================================
+// Formatting and Lint Commands
+// ============================================
+
+/** Formats with black, prettier, rustfmt, gofmt or clang-format; rejects rather than returning partial output */
+export async function formatCode(language: string, code: string): Promise<string> {
+  return invoke("format_code", { language, code });
+}
+
+/** Lints the project in a container; findings are not an error */
+export async function lintCode(language: string, projectPath: string): Promise<Diagnostic[]> {
+  return invoke("lint_code", { language, projectPath });
+}
+
 // ============================================
 // Grading Commands
 // ============================================
 
 export async function runLocalTests(
   lessonPath: string,
-  projectPath: string,
   language: string,
-  code: string
+  code: string,
+  projectFiles?: ProjectFile[],
+  submissionId?: string
 ): Promise<GradingResult> {
   return invoke("run_local_tests", {
     lessonPath,
-    projectPath,
     language,
     code,
+    projectFiles,
+    submissionId,
   });
 }
 
+/** Follow tests as they finish; pass the same UUID to runLocalTests */
+export async function onGradingProgress(
+  submissionId: string,
+  handler: (progress: GradingProgress) => void
+): Promise<UnlistenFn> {
+  return listen<GradingProgress>(`grading://${submissionId}/progress`, (e) => handler(e.payload));
+}
+
 export async function submitForGrading(
   request: CloudGradingRequest
 ): Promise<GradingResult> {
   return invoke("submit_for_grading", { request });
 }
 
+export async function applyRubric(
+  submissionId: string,
+  scores: RubricScore[]
+): Promise<GradingResult> {
+  return invoke("apply_rubric", { submissionId, scores });
+}
+
+/** Rejects submissions for different lessons */
+export async function compareSubmissionsDiff(submissionA: string, submissionB: string): Promise<SubmissionDiff> {
+  return invoke("compare_submissions_diff", { submissionA, submissionB });
+}
+
+// ============================================
+// Exam Commands
+// ============================================
+
+/** Requires the `exam_mode` feature */
+export async function enterExamMode(projectPath: string): Promise<ExamSession> {
+  return invoke("enter_exam_mode", { projectPath });
+}
+
+/** `token` is the teacher's license token */
+export async function exitExamMode(token: string): Promise<void> {
+  return invoke("exit_exam_mode", { token });
+}
+
+export async function getExamSession(): Promise<ExamSession | null> {
+  return invoke("get_exam_session");
+}
+
+// ============================================
+// Plagiarism Commands
+// ============================================
+
+/** Requires the `plagiarism_detection` feature */
+export async function compareSubmissions(a: string, b: string): Promise<SimilarityReport> {
+  return invoke("compare_submissions", { a, b });
+}
+
+/** Pairs of a lesson's submissions at or above `threshold` (0 to 1), most similar first */
+export async function detectSimilar(lessonId: string, threshold: number): Promise<SimilarPair[]> {
+  return invoke("detect_similar", { lessonId, threshold });
+}
+
 // ============================================
 // Feature Flags Commands
 // ============================================
@@ -141,10 +683,97 @@ export async function getFeatureFlags(): Promise<FeatureFlags> {
   return invoke("get_feature_flags");
 }
 
+/** Activate a license as issued (JSON or base64) */
+export async function activateLicense(licenseBlob: string): Promise<FeatureFlags> {
+  return invoke("activate_license", { licenseBlob });
+}
+
+export async function deactivateLicense(): Promise<FeatureFlags> {
+  return invoke("deactivate_license");
+}
+
+/** Release a license seat; defaults to this install */
+export async function releaseSeat(installId?: string): Promise<FeatureFlags> {
+  return invoke("release_seat", { installId });
+}
+
 export async function isEducatorMode(): Promise<boolean> {
   return invoke("is_educator_mode");
 }
 
+// ============================================
+// Classroom Commands (require the classrooms feature)
+// ============================================
+
+export async function createClassroom(name: string): Promise<Classroom> {
+  return invoke("create_classroom", { name });
+}
+
+export async function listClassrooms(): Promise<Classroom[]> {
+  return invoke("list_classrooms");
+}
+
+export async function addStudent(classroomId: string, student: NewStudent): Promise<Student> {
+  return invoke("add_student", { classroomId, student });
+}
+
+export async function removeStudent(classroomId: string, studentId: string): Promise<void> {
+  return invoke("remove_student", { classroomId, studentId });
+}
+
+export async function listStudents(classroomId: string): Promise<Student[]> {
+  return invoke("list_students", { classroomId });
+}
+
+/** Attributes a stored submission to a student in a classroom */
+export async function assignSubmission(
+  submissionId: string,
+  classroomId: string,
+  studentId: string
+): Promise<void> {
+  return invoke("assign_submission", { submissionId, classroomId, studentId });
+}
+
+/** One entry per student, including students with no submissions */
+export async function classroomProgress(classroomId: string, lessonId: string): Promise<StudentProgress[]> {
+  return invoke("classroom_progress", { classroomId, lessonId });
+}
+
+// ============================================
+// Analytics Commands
+// ============================================
+
+export async function getLocalAnalytics(range?: AnalyticsRange): Promise<LocalAnalytics> {
+  return invoke("get_local_analytics", { range });
+}
+
+export async function getAnalyticsOptIn(): Promise<boolean> {
+  return invoke("get_analytics_opt_in");
+}
+
+/** Nothing is recorded until the user opts in; opting out drops unsaved events */
+export async function setAnalyticsOptIn(enabled: boolean): Promise<void> {
+  return invoke("set_analytics_opt_in", { enabled });
+}
+
+/** Deletes every locally recorded event */
+export async function purgeAnalytics(): Promise<void> {
+  return invoke("purge_analytics");
+}
+
+export async function getTelemetryStatus(): Promise<TelemetryStatus> {
+  return invoke("get_telemetry_status");
+}
+
+export async function setTelemetryEnabled(enabled: boolean): Promise<void> {
+  return invoke("set_telemetry_enabled", { enabled });
+}
+
+/** Writes every upload telemetry has sent, verbatim, as a JSON array */
+export async function exportTelemetryLog(path: string): Promise<void> {
+  return invoke("export_telemetry_log", { path });
+}
+
 // ============================================
 // Settings Commands
 // ============================================
@@ -156,3 +785,43 @@ export async function getSettings(): Promise<Settings> {
 export async function updateSettings(settings: Settings): Promise<void> {
   return invoke("update_settings", { settings });
 }
+
+export async function resetSettings(): Promise<Settings> {
+  return invoke("reset_settings");
+}
+
+export async function resetSettingsField(fieldName: keyof Settings): Promise<Settings> {
+  return invoke("reset_settings_field", { fieldName });
+}
+
+export async function getKeybindings(): Promise<Keybinding[]> {
+  return invoke("get_keybindings");
+}
+
+export async function updateKeybindings(keybindings: Keybinding[]): Promise<Keybinding[]> {
+  return invoke("update_keybindings", { keybindings });
+}
+
+export async function resetKeybindings(): Promise<Keybinding[]> {
+  return invoke("reset_keybindings");
+}
+
+export async function exportSettings(path: string, includeProjects?: boolean): Promise<void> {
+  return invoke("export_settings", { path, includeProjects });
+}
+
+/** `merge` keeps current values the file omits; otherwise they reset to defaults */
+export async function importSettings(path: string, merge: boolean): Promise<SettingsImport> {
+  return invoke("import_settings", { path, merge });
+}
+
+export async function getEffectiveSettings(projectId: string): Promise<EffectiveSettings> {
+  return invoke("get_effective_settings", { projectId });
+}
+
+export async function updateProjectSettings(
+  projectId: string,
+  partial: SettingsOverride
+): Promise<EffectiveSettings> {
+  return invoke("update_project_settings", { projectId, partial });
+}
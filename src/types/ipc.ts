@@ -5,6 +5,41 @@
  * Any changes here must be synchronized with src-tauri/src/commands/*.rs
  */
 
+// ============================================
+// Error Types
+// ============================================
+
+export type ErrorCode =
+  | "filesystem"
+  | "database"
+  | "docker"
+  | "image_not_found"
+  | "serialization"
+  | "lesson"
+  | "execution"
+  | "security"
+  | "service"
+  | "feature_not_available"
+  | "network"
+  | "cloud_required"
+  | "configuration"
+  | "git";
+
+/** Where an error happened, when known */
+export interface ErrorContext {
+  language?: string;
+  /** File being run, relative to the project */
+  file?: string;
+  container_id?: string;
+}
+
+/** Error returned by a rejected command */
+export interface ShellError {
+  code: ErrorCode;
+  message: string;
+  context?: ErrorContext;
+}
+
 // ============================================
 // Filesystem Types
 // ============================================
@@ -16,6 +51,9 @@ export interface FileInfo {
   size: number;
   modified: string | null;
   extension: string | null;
+  /** Only from getFileInfo with stats, for small text files */
+  line_count?: number;
+  char_count?: number;
 }
 
 export interface DirectoryContents {
@@ -23,10 +61,34 @@ export interface DirectoryContents {
   entries: FileInfo[];
 }
 
+/** Outcome of a scheduled auto-save; `error` is set only on `fs://save-failed` */
+export interface AutoSaveEvent {
+  path: string;
+  error?: string;
+}
+
 export interface FileContents {
   path: string;
   content: string;
+  /** Encoding the file was decoded from, e.g. "utf-8" or "utf-16le" */
   encoding: string;
+  /** Only for readFileRange */
+  range?: FileRange;
+}
+
+/** Byte window of a partially read file, trimmed to whole characters */
+export interface FileRange {
+  start: number;
+  /** Exclusive */
+  end: number;
+  total_size: number;
+}
+
+/** An active directory watch */
+export interface WatchedDirectory {
+  path: string;
+  /** watchDirectory calls not yet matched by unwatchDirectory */
+  subscribers: number;
 }
 
 export interface ProjectInfo {
@@ -38,6 +100,82 @@ export interface ProjectInfo {
   files: string[];
 }
 
+/** A registered project, keyed by its canonical path */
+export interface Project {
+  id: string;
+  name: string;
+  path: string;
+  language: string | null;
+  created_at: string;
+  updated_at: string;
+}
+
+/** 1-based */
+export interface CursorPosition {
+  line: number;
+  column: number;
+}
+
+/** Open editors of a project; paths are relative to the project root */
+export interface SessionState {
+  /** In tab order */
+  open_files: string[];
+  active_file: string | null;
+  cursors: Record<string, CursorPosition>;
+}
+
+// ============================================
+// Search Types
+// ============================================
+
+export interface SearchOptions {
+  case_sensitive?: boolean;
+  /** Treat the query as a regular expression rather than literal text */
+  regex?: boolean;
+  max_results?: number | null;
+}
+
+export interface SearchMatch {
+  /** Relative to the project root */
+  path: string;
+  /** 1-based */
+  line: number;
+  /** 1-based, in characters */
+  column: number;
+  text: string;
+}
+
+export interface SearchResults {
+  matches: SearchMatch[];
+  files_searched: number;
+  truncated: boolean;
+}
+
+export interface TreeEntry {
+  path: string;
+  is_directory: boolean;
+  size: number;
+}
+
+export interface FileTree {
+  entries: TreeEntry[];
+  truncated: boolean;
+}
+
+export interface FileIndex {
+  project_path: string;
+  /** Relative paths, sorted */
+  files: string[];
+  truncated: boolean;
+}
+
+export interface FileMatch {
+  path: string;
+  score: number;
+  /** Character positions in path that matched, for highlighting */
+  positions: number[];
+}
+
 // ============================================
 // Lesson Types
 // ============================================
@@ -78,6 +216,16 @@ export interface Constraints {
   max_lines?: number;
 }
 
+export type OutputMatch =
+  | "exact"
+  | "trim_trailing_whitespace"
+  | "ignore_all_whitespace"
+  | "regex"
+  | { numeric: { tolerance: number } };
+
+/** What a test checks to pass; all also require exit code 0 */
+export type TestCheck = "stdout_equals" | "exit_code_zero" | "contains_substring";
+
 export interface TestCase {
   id: string;
   name: string;
@@ -85,6 +233,12 @@ export interface TestCase {
   expected_output: string;
   points: number;
   hidden: boolean;
+  comparison?: OutputMatch;
+  timeout_ms?: number;
+  max_memory_bytes?: number;
+  /** Runs instead of the entry point, e.g. a provided driver */
+  command?: string[];
+  check?: TestCheck;
 }
 
 export interface RubricItem {
@@ -106,6 +260,17 @@ export interface GradingConfig {
   hidden_tests?: TestCase[];
   rubric?: RubricItem[];
   auto_grade?: AutoGradeConfig;
+  /** Minimum percentage to pass (defaults to 70) */
+  passing_score?: number;
+  /** Points deducted per revealed hint */
+  hint_penalty?: number;
+  /** Intended sum of the test points; validation warns when they differ */
+  total_points?: number;
+}
+
+export interface RunOptions {
+  compile_flags?: string[];
+  run_args?: string[];
 }
 
 export interface Lesson {
@@ -115,6 +280,12 @@ export interface Lesson {
   description: string;
   author?: Author;
   language: string;
+  /** File the submission runs from, relative to the project root */
+  entry_point?: string;
+  /** Compile flags and program arguments used when grading */
+  run?: RunOptions;
+  /** Packages install_dependencies may add to the run image */
+  dependencies?: string[];
   difficulty: Difficulty;
   tags: string[];
   prerequisites: string[];
@@ -129,12 +300,56 @@ export interface LessonSummary {
   description: string;
   language: string;
   difficulty: Difficulty;
+  version: string;
+  path: string;
+  /** Path relative to the listed directory */
+  relative_path: string;
+}
+
+export type PrereqState = "satisfied" | "failed" | "not_attempted" | "unknown";
+
+export interface Prerequisite {
+  lesson_id: string;
+  state: PrereqState;
+  best_score?: number;
+}
+
+export interface PrereqStatus {
+  lesson_id: string;
+  satisfied: boolean;
+  prerequisites: Prerequisite[];
+}
+
+export interface LessonProgress {
+  lesson_id: string;
+  /** Best percentage scored */
+  best_score: number;
+  attempts: number;
+  passing_score: number;
+  completed: boolean;
+  last_submitted_at: string;
+}
+
+/** Contents of a `.shelllesson` bundle */
+export interface BundleManifest {
+  format: number;
+  lesson_id: string;
+  lesson_version: string;
+  title: string;
+  lesson_file: string;
+  files: string[];
+  created_at: string;
+}
+
+export interface ValidationError {
+  /** Dotted path to the field, e.g. `grading.local_tests[0].points` */
   path: string;
+  message: string;
 }
 
 export interface ValidationResult {
   valid: boolean;
-  errors: string[];
+  errors: ValidationError[];
   warnings: string[];
 }
 
@@ -142,16 +357,92 @@ export interface ValidationResult {
 // Execution Types
 // ============================================
 
+/** `tests` runs the project's test suite instead of the entry point */
+export type RunMode = "main" | "tests";
+
 export interface RunCodeRequest {
+  /** UUID for lifecycle events; subscribe before calling `runCode` */
+  execution_id?: string;
   language: string;
   code: string;
   project_path: string;
   entry_point?: string;
+  run_mode?: RunMode;
   stdin?: string;
+  /** Extra compiler flags, checked against the security policy */
+  compile_flags?: string[];
+  run_args?: string[];
   env?: Record<string, string>;
   step_mode?: boolean;
   trace_io?: boolean;
   timeout?: number;
+  /** Also return base64 of streams that aren't valid UTF-8 */
+  raw_output?: boolean;
+  /** Let the program write into the project directory; never used for grading */
+  workspace_writable?: boolean;
+  /** Share the result of an identical run already in flight under this key */
+  dedupe_key?: string;
+}
+
+/** Something that would stop a run request from running */
+export type RunProblem =
+  | { kind: "unsupported_language"; language: string }
+  | { kind: "missing_entry_point"; entry_point: string | null; candidates: string[] }
+  | { kind: "invalid_options"; message: string }
+  | { kind: "denied"; message: string }
+  | { kind: "docker_unavailable" }
+  | { kind: "missing_toolchain"; tool: string; guidance: string };
+
+export interface RunValidation {
+  valid: boolean;
+  image: string | null;
+  entry_point: string | null;
+  /** What the container would execute */
+  command: string[] | null;
+  problems: RunProblem[];
+}
+
+export interface ToolchainStatus {
+  language: string;
+  image: string;
+  tool: string;
+  available: boolean;
+  version: string | null;
+  /** How to get the tool, when it's missing */
+  guidance: string | null;
+}
+
+export interface LanguageSpec {
+  id: string;
+  display_name: string;
+  aliases: string[];
+  image: string;
+  /** Run command; `{entry}` stands for the entry point */
+  run_command_template: string[];
+  compiler: string | null;
+  test_command: string[] | null;
+  /** Framework the tests need that an image may lack */
+  test_framework: string | null;
+  test_framework_probe: string[];
+  test_framework_guidance: string;
+  extensions: string[];
+  entry_points: string[];
+  submission_file: string;
+  toolchain: string;
+  toolchain_probe: string[];
+  toolchain_guidance: string;
+  formatter: string | null;
+  linter: string | null;
+}
+
+export interface LanguageInfo {
+  id: string;
+  display_name: string;
+  extensions: string[];
+  default_entry_point: string;
+  image: string;
+  /** False until the language's image is pulled */
+  available: boolean;
 }
 
 export interface IoEvent {
@@ -171,6 +462,9 @@ export interface ExecutionTrace {
   io_events: IoEvent[];
 }
 
+/** `Compile` means the build failed and the program never ran */
+export type ExecutionPhase = "Compile" | "Run";
+
 export interface ExecutionResult {
   id: string;
   exit_code: number;
@@ -179,6 +473,86 @@ export interface ExecutionResult {
   duration_ms: number;
   timed_out: boolean;
   trace?: ExecutionTrace;
+  phase: ExecutionPhase;
+  /** Compiler diagnostics and warnings, for compiled languages */
+  compile_output?: string;
+  /** Test framework verdict, only for `tests` runs */
+  tests_passed?: boolean;
+  /** Exact bytes of a stream that isn't valid UTF-8, when `raw_output` was set */
+  stdout_base64?: string;
+  stderr_base64?: string;
+  /** The log stream outlived its time budget; output may be cut short */
+  log_collection_timed_out: boolean;
+  /** Output passed the per-stream or per-line limit and was cut */
+  output_truncated: boolean;
+}
+
+/** Timing summary of a benchmark's timed runs, in milliseconds */
+export interface BenchmarkStats {
+  min_ms: number;
+  median_ms: number;
+  mean_ms: number;
+  max_ms: number;
+  stddev_ms: number;
+}
+
+export interface BenchmarkResult {
+  runs: number;
+  /** Runs discarded before timing started */
+  warmup: number;
+  durations_ms: number[];
+  stats: BenchmarkStats;
+  /** The final run, for its output */
+  last: ExecutionResult;
+}
+
+/** Payload of `image://{image}/progress` events */
+export interface PullProgress {
+  image: string;
+  /** Layer the message is about; null for image-wide status */
+  layer: string | null;
+  /** e.g. "Downloading", "Extracting", "Pull complete" */
+  status: string;
+  current: number | null;
+  total: number | null;
+}
+
+export interface PreparedLanguage {
+  language: string;
+  image: string;
+  /** False when the image was already present */
+  pulled: boolean;
+}
+
+export interface FileCoverage {
+  /** Path relative to the project */
+  file: string;
+  covered_lines: number;
+  /** Lines the tool considers executable */
+  total_lines: number;
+  percent: number;
+  /** Runs of executable lines that never ran */
+  uncovered: LineRange[];
+}
+
+export interface CoverageReport {
+  /** Sorted by path */
+  files: FileCoverage[];
+  covered_lines: number;
+  total_lines: number;
+  percent: number;
+  /** The run itself, without the coverage report in its output */
+  run: ExecutionResult;
+}
+
+export interface InstallResult {
+  /** Image later runs of the project use */
+  image: string;
+  packages: string[];
+  /** Whether an earlier install had already built the image */
+  cached: boolean;
+  /** Package manager output; empty when cached */
+  log: string;
 }
 
 export type ContainerStatus =
@@ -188,11 +562,28 @@ export type ContainerStatus =
   | "TimedOut"
   | "Failed";
 
+export interface ResourceLimits {
+  /** Bytes */
+  memory_limit: number;
+  /** Microseconds of CPU time per 100ms, so 50000 is half a CPU */
+  cpu_quota: number;
+  timeout_seconds: number;
+}
+
 export interface ContainerInfo {
   id: string;
   execution_id: string;
   started_at: string;
   status: ContainerStatus;
+  /** Limits the container was created with */
+  limits: ResourceLimits;
+}
+
+/** A run's output so far; `complete` once no more will arrive */
+export interface ExecutionOutput {
+  stdout: string;
+  stderr: string;
+  complete: boolean;
 }
 
 export interface ExecutionStatus {
@@ -200,6 +591,121 @@ export interface ExecutionStatus {
   containers: ContainerInfo[];
 }
 
+export interface ImagePresence {
+  language: string;
+  image: string;
+  present: boolean;
+}
+
+/** When Docker can't be reached, available is false and reason says why */
+export interface DockerInfo {
+  available: boolean;
+  reason: string | null;
+  version: string | null;
+  os: string | null;
+  architecture: string | null;
+  cpus: number | null;
+  /** Bytes of the daemon's host (or VM) */
+  memory_total: number | null;
+  /** memory_total less the memory limits of running containers */
+  memory_available: number | null;
+  storage_driver: string | null;
+  images: ImagePresence[];
+}
+
+export type LifecycleStage = "queued" | "started" | "finished";
+
+/** Emitted as `execution://{execution_id}/{stage}` */
+export interface LifecycleEvent {
+  execution_id: string;
+  stage: LifecycleStage;
+  status: ContainerStatus;
+  elapsed_ms: number;
+  exit_code?: number;
+}
+
+// ============================================
+// Terminal Types
+// ============================================
+
+/** `auto` uses Docker when available, otherwise a local shell */
+export type TerminalBackend = "auto" | "docker" | "local";
+
+export interface TerminalOpened {
+  session_id: string;
+  /** `local` has reduced isolation; the UI should say so */
+  backend: Exclude<TerminalBackend, "auto">;
+}
+
+// ============================================
+// Debugger Types
+// ============================================
+
+export interface DapSession {
+  session_id: string;
+  /** Arguments for the `launch` request */
+  launch: Record<string, unknown>;
+}
+
+// ============================================
+// Lint Types
+// ============================================
+
+export type Severity = "error" | "warning" | "info";
+
+/** A linter finding, normalized across ruff, ESLint, Clippy and go vet */
+export interface Diagnostic {
+  /** Relative to the project */
+  file: string;
+  line: number;
+  column: number;
+  severity: Severity;
+  /** Rule name, e.g. `F401` or `clippy::needless_return` */
+  code?: string | null;
+  message: string;
+}
+
+// ============================================
+// Snapshot Types
+// ============================================
+
+export interface SnapshotRecord {
+  id: string;
+  project_path: string;
+  label: string;
+  created_at: string;
+  file_count: number;
+  /** Uncompressed size of the archived files */
+  size_bytes: number;
+}
+
+// ============================================
+// Git Types
+// ============================================
+
+/** A file staged and then edited again has both `staged` and `modified` */
+export type GitFileState = "untracked" | "modified" | "staged" | "deleted";
+
+export interface GitFileStatus {
+  /** Relative to the repository root */
+  path: string;
+  states: GitFileState[];
+}
+
+export interface GitAuthor {
+  name: string;
+  email: string;
+}
+
+export interface GitStatus {
+  /** False when the project is not inside a git repository */
+  is_repository: boolean;
+  /** Absent on a detached HEAD */
+  branch?: string;
+  root?: string;
+  files: GitFileStatus[];
+}
+
 // ============================================
 // LSP Types
 // ============================================
@@ -213,6 +719,16 @@ export interface LspServerInfo {
   installation_instructions?: string;
 }
 
+/** A user-registered language server, used instead of the well-known one */
+export interface LspConfig {
+  language: string;
+  server_path: string;
+  /** JSON array of arguments */
+  args?: string;
+  /** JSON initialization options */
+  settings?: string;
+}
+
 // ============================================
 // Grading Types
 // ============================================
@@ -221,16 +737,51 @@ export interface TestResult {
   id: string;
   name: string;
   passed: boolean;
+  /** Stdout followed by stderr */
   actual_output: string;
+  actual_stdout: string;
+  actual_stderr: string;
   expected_output: string;
   points_earned: number;
   points_possible: number;
   execution_time_ms: number;
   error?: string;
+  diff?: DiffLine[];
+}
+
+export interface DiffLine {
+  kind: "added" | "removed" | "unchanged";
+  content: string;
 }
 
 export type GradingSource = "Local" | "Cloud";
 
+export type TestChange =
+  | "newly_passing"
+  | "newly_failing"
+  | "still_passing"
+  | "still_failing"
+  | "added"
+  | "removed";
+
+export interface TestDelta {
+  id: string;
+  name: string;
+  change: TestChange;
+}
+
+/** Comparison from `submission_a` to `submission_b` */
+export interface SubmissionDiff {
+  lesson_id: string;
+  submission_a: string;
+  submission_b: string;
+  score_a?: number;
+  score_b?: number;
+  /** `added` lines are only in `submission_b` */
+  code: DiffLine[];
+  tests: TestDelta[];
+}
+
 export interface GradingResult {
   submission_id: string;
   lesson_id: string;
@@ -241,13 +792,96 @@ export interface GradingResult {
   feedback?: string;
   graded_at: string;
   graded_by: GradingSource;
+  constraint_violations: ConstraintViolation[];
+  rubric_scores: RubricAward[];
+  /** Hidden tests that only run on cloud submission */
+  hidden_test_count: number;
+  hidden_points: number;
+  /** Points deducted for revealed hints */
+  hint_penalty: number;
+}
+
+/** Emitted as `grading://{submission_id}/progress` when a test finishes */
+export interface GradingProgress {
+  submission_id: string;
+  test: TestResult;
+  completed: number;
+  total: number;
+  points_earned: number;
+  max_points: number;
+}
+
+export interface RubricScore {
+  item_id: string;
+  points: number;
+  comment?: string;
+}
+
+export interface RubricAward {
+  item_id: string;
+  name: string;
+  points_awarded: number;
+  points_possible: number;
+  comment?: string;
+}
+
+export type ConstraintViolation =
+  | { kind: "disallowed_import"; module: string; line: number }
+  | { kind: "import_not_allowed"; module: string; line: number }
+  | { kind: "missing_symbol"; symbol: string }
+  | { kind: "too_many_lines"; lines: number; max: number };
+
+export interface ProjectFile {
+  path: string;
+  content: string;
 }
 
 export interface CloudGradingRequest {
   lesson_id: string;
   code: string;
   language: string;
-  project_files: { path: string; content: string }[];
+  project_files: ProjectFile[];
+  /** Local lesson file used to grade offline when the service is unreachable */
+  lesson_path?: string;
+}
+
+// ============================================
+// Exam Types
+// ============================================
+
+/** An exam in progress; file access and runs are confined to `project` */
+export interface ExamSession {
+  session_id: string;
+  project: string;
+  started_at: string;
+}
+
+// ============================================
+// Plagiarism Types
+// ============================================
+
+/** Inclusive, 1-based line span */
+export interface LineRange {
+  start: number;
+  end: number;
+}
+
+/** Code that appears in both submissions */
+export interface MatchedFragment {
+  a: LineRange;
+  b: LineRange;
+}
+
+export interface SimilarityReport {
+  /** From 0 to 1 */
+  similarity: number;
+  matches: MatchedFragment[];
+}
+
+export interface SimilarPair {
+  first: string;
+  second: string;
+  report: SimilarityReport;
 }
 
 // ============================================
@@ -264,6 +898,11 @@ export interface License {
   license_type: LicenseType;
   valid_until?: string;
   seats?: number;
+  /** Days the license keeps working after `valid_until` */
+  grace_days?: number;
+  token?: string;
+  /** Base64 Ed25519 signature over the other fields */
+  signature?: string;
 }
 
 export interface FeatureFlags {
@@ -276,6 +915,81 @@ export interface FeatureFlags {
   plagiarism_detection: boolean;
   marketplace: boolean;
   license?: License;
+  /** License is past `valid_until`; features are removed once the grace period ends */
+  license_expired: boolean;
+}
+
+// ============================================
+// Classroom Types
+// ============================================
+
+export interface Classroom {
+  id: string;
+  name: string;
+  created_at: string;
+}
+
+export interface Student {
+  id: string;
+  name: string;
+  email?: string;
+  added_at: string;
+}
+
+export interface NewStudent {
+  name: string;
+  /** Unique within a classroom */
+  email?: string;
+}
+
+export interface StudentProgress {
+  student: Student;
+  submissions: number;
+  best_score?: number;
+  /** Status of the latest submission, e.g. `graded` */
+  last_status?: string;
+  last_submitted_at?: string;
+}
+
+// ============================================
+// Analytics Types
+// ============================================
+
+export type AnalyticsEvent =
+  | { type: "lesson_started"; lesson_id: string }
+  | { type: "run_executed"; language: string; exit_code: number; duration_ms: number; timed_out: boolean }
+  | { type: "test_passed"; lesson_id: string; test_id: string }
+  | { type: "test_failed"; lesson_id: string; test_id: string }
+  | { type: "hint_revealed"; lesson_id: string; index: number };
+
+export interface RecordedEvent {
+  /** RFC 3339 timestamp */
+  recorded_at: string;
+  event: AnalyticsEvent;
+}
+
+/** Open ends are unbounded; `until` is exclusive */
+export interface AnalyticsRange {
+  since?: string;
+  until?: string;
+}
+
+export interface LocalAnalytics {
+  /** Oldest first */
+  events: RecordedEvent[];
+  /** Events per `type` */
+  counts: Record<string, number>;
+}
+
+/** Telemetry (uploading) is off unless turned on, separately from local recording */
+export interface TelemetryStatus {
+  enabled: boolean;
+  /** Where uploads would go, if configured */
+  endpoint: string | null;
+  /** Recorded events not uploaded yet */
+  pending_events: number;
+  sent_uploads: number;
+  last_sent_at: string | null;
 }
 
 // ============================================
@@ -297,5 +1011,47 @@ export interface Settings {
   format_on_save: boolean;
   default_language?: string;
   docker_enabled: boolean;
+  /** Default run timeout in seconds, clamped to 1-600 */
   execution_timeout: number;
+  /** Default memory limit of a run in MB, clamped to 64-16384 */
+  execution_memory_mb: number;
+  /** Default CPU share of a run as a percentage of one CPU, clamped to 10-400 */
+  execution_cpu_percent: number;
+  cloud_grading_url?: string;
+  /** Where opted-in analytics are uploaded; kept local when unset */
+  analytics_url?: string;
+  keybindings: Keybinding[];
+  /** Author for commits made in the IDE; falls back to git config */
+  git_author?: GitAuthor;
+  /** Where terminal sessions run */
+  terminal_backend: TerminalBackend;
+}
+
+export interface Keybinding {
+  /** Command id, e.g. `file.save` */
+  command: string;
+  /** Modifiers and key joined by `+`, e.g. `CmdOrCtrl+Shift+N` */
+  key: string;
+  /** Context the binding is limited to */
+  when?: string;
+}
+
+/** Fields set to `null` revert to the global value */
+export type SettingsOverride = { [K in keyof Settings]?: Settings[K] | null };
+
+export interface SkippedSetting {
+  /** Prefixed with `projects.<id>.` for project overrides */
+  field: string;
+  reason: string;
+}
+
+export interface SettingsImport {
+  settings: Settings;
+  skipped: SkippedSetting[];
+}
+
+export interface EffectiveSettings {
+  settings: Settings;
+  /** Fields the project overrides */
+  overridden: (keyof Settings)[];
 }